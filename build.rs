@@ -0,0 +1,32 @@
+//! Generates the messages and the service of `proto/searchspot.proto`
+//! when Searchspot is built with the `grpc` feature.
+
+#[cfg(feature = "grpc")]
+extern crate protoc_grpcio;
+
+#[cfg(feature = "grpc")]
+fn main() {
+    use std::env;
+    use std::fs;
+    use std::path::Path;
+
+    println!("cargo:rerun-if-changed=proto/searchspot.proto");
+
+    let out_dir = env::var("OUT_DIR").unwrap();
+    protoc_grpcio::compile_grpc_protos(&["searchspot.proto"], &["proto"], &out_dir, None)
+        .expect("Cannot compile proto/searchspot.proto");
+
+    // the generated files are `include!`d, where inner attributes aren't allowed
+    for file in &["searchspot.rs", "searchspot_grpc.rs"] {
+        let path = Path::new(&out_dir).join(file);
+        let source = fs::read_to_string(&path).unwrap();
+        let source: Vec<&str> = source
+            .lines()
+            .filter(|line| !line.starts_with("#!"))
+            .collect();
+        fs::write(&path, source.join("\n")).unwrap();
+    }
+}
+
+#[cfg(not(feature = "grpc"))]
+fn main() {}
@@ -0,0 +1,243 @@
+//! Saved talent searches run on a schedule: the talents that start matching
+//! one of them are posted to its webhook, so that the interested parties
+//! are notified without polling the searches themselves.
+//!
+//! The first run of an alert only records the talents already matching it.
+//! Alerts are only run by the instances that write, not by the read-only
+//! replicas, so that every new talent is notified once.
+
+use params::{Map, Value};
+use reqwest::{self, Url};
+use serde_json;
+
+use backends::{self, SearchBackend};
+use config::{Alert, Config};
+use resource::{IndexSet, Resource};
+use resources::{Talent, TalentId, TalentSearchParams};
+use scheduler;
+
+use std::collections::HashMap;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// How many talents an alert looks at, unless its query says otherwise.
+const MAX_MATCHES: u64 = 100;
+
+/// How long an alert remembers a talent that stopped matching it (30 days), so
+/// that what it has seen stays bounded by the talents matching in that time.
+const SEEN_TTL: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// The body posted to the webhook of an alert.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AlertEvent {
+    pub alert: String,
+    pub talent_ids: Vec<TalentId>,
+}
+
+/// Turn the query string of an alert into the params of a talent search.
+pub fn parse_query(query: &str) -> Result<Map, String> {
+    let url = Url::parse(&format!("http://searchspot/talents?{}", query))
+        .map_err(|error| error.to_string())?;
+
+    let mut params = Map::new();
+    for (key, value) in url.query_pairs() {
+        params
+            .assign(&key, Value::String(value.into_owned()))
+            .map_err(|error| format!("{:?}", error))?;
+    }
+
+    if !params.contains_key("per_page") {
+        let _ = params.assign("per_page", Value::U64(MAX_MATCHES));
+    }
+
+    // alerts are configured, so only their syntax is checked
+    TalentSearchParams::from_map(&params)?;
+    Ok(params)
+}
+
+/// Run the search of an alert at `now` and return the talents that weren't in `seen`,
+/// which keeps when every talent matched last, so that a talent leaving the results
+/// and coming back isn't notified twice. The talents that didn't match for `SEEN_TTL`
+/// are forgotten, and notified again if they come back.
+/// Nothing is returned while `seen` is still `None`, nor when the search
+/// fails, in which case `seen` is left untouched.
+pub fn run(
+    es: &mut dyn SearchBackend,
+    indexes: &IndexSet,
+    params: &Map,
+    seen: &mut Option<HashMap<TalentId, Instant>>,
+    now: Instant,
+) -> Vec<TalentId> {
+    let matching: Vec<TalentId> = match Talent::search_in(es, indexes, params) {
+        Ok(results) => results
+            .talents
+            .iter()
+            .map(|result| result.talent.id())
+            .collect(),
+        Err(error) => {
+            error!("{}", error);
+            return vec![];
+        }
+    };
+
+    match *seen {
+        Some(ref mut seen) => {
+            seen.retain(|_, matched_at| now.saturating_duration_since(*matched_at) < SEEN_TTL);
+            matching
+                .into_iter()
+                .filter(|id| seen.insert(id.clone(), now).is_none())
+                .collect()
+        }
+        None => {
+            *seen = Some(matching.into_iter().map(|id| (id, now)).collect());
+            vec![]
+        }
+    }
+}
+
+fn notify(alert: &Alert, talent_ids: Vec<TalentId>) -> Result<(), String> {
+    let event = AlertEvent {
+        alert: alert.name.to_owned(),
+        talent_ids: talent_ids,
+    };
+    let body = serde_json::to_string(&event).map_err(|error| error.to_string())?;
+
+    let response = reqwest::blocking::Client::new()
+        .post(&alert.webhook)
+        .header("Content-Type", "application/json")
+        .body(body)
+        .send()
+        .map_err(|error| error.to_string())?;
+
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err(response.status().to_string())
+    }
+}
+
+/// Run every alert of `config` every `interval` seconds, each with a client
+/// of its own. Alerts with an invalid query are not started.
+pub fn schedule(config: &Config) -> Vec<JoinHandle<()>> {
+    let mut handles = vec![];
+
+    for alert in config.alerts.iter().cloned() {
+        let params = match parse_query(&alert.query) {
+            Ok(params) => params,
+            Err(error) => {
+                error!("Alert {} not started: {}", alert.name, error);
+                continue;
+            }
+        };
+
+        let mut client = match backends::from_config(&config.es) {
+            Ok(client) => client,
+            Err(error) => {
+                error!("Alert {} not started: {}", alert.name, error);
+                continue;
+            }
+        };
+
+        let indexes = IndexSet::from_config(&config.es);
+        let interval = Duration::from_secs(alert.interval);
+        let mut seen = None;
+
+        handles.push(scheduler::every("alert", interval, move || {
+            let new_matches = run(&mut *client, &indexes, &params, &mut seen, Instant::now());
+            if new_matches.is_empty() {
+                return;
+            }
+
+            let count = new_matches.len();
+            match notify(&alert, new_matches) {
+                Ok(()) => info!("Alert {}: {} new talents notified.", alert.name, count),
+                Err(error) => error!("Alert {} not delivered: {}", alert.name, error),
+            }
+        }));
+    }
+
+    handles
+}
+
+#[cfg(test)]
+mod tests {
+    use alerts::{self, SEEN_TTL};
+    use backends::{Memory, SearchBackend};
+    use resource::{IndexSet, Resource};
+    use resources::{Talent, TalentId};
+    use test_support;
+
+    use params::Value;
+
+    use std::time::{Duration, Instant};
+
+    const INDEX: &'static str = "memory_alerts";
+
+    fn talent(id: u32, location: &str) -> Talent {
+        test_support::talent(
+            id,
+            json!({ "work_locations": [location], "current_location": location }),
+        )
+    }
+
+    #[test]
+    fn test_parse_query() {
+        let params = alerts::parse_query("work_locations[]=Berlin&work_locations[]=Hamburg").unwrap();
+        assert_eq!(
+            params.get("work_locations"),
+            Some(&Value::Array(vec![
+                Value::String("Berlin".to_owned()),
+                Value::String("Hamburg".to_owned()),
+            ]))
+        );
+        assert_eq!(params.get("per_page"), Some(&Value::U64(100)));
+
+        assert!(alerts::parse_query("per_page=many").is_err());
+    }
+
+    #[test]
+    fn test_run() {
+        let mut memory: Box<dyn SearchBackend> = Box::new(Memory::new());
+        Talent::reset_index(&mut *memory, INDEX, None).unwrap();
+        Talent::index(&mut *memory, INDEX, vec![talent(1, "Berlin"), talent(2, "Munich")]).unwrap();
+
+        let params = alerts::parse_query(
+            "epoch=2018-01-01T00:00:00Z&work_locations[]=Berlin",
+        ).unwrap();
+        let indexes = IndexSet::new(INDEX);
+        let mut seen = None;
+        let now = Instant::now();
+
+        // the first run records the talents already matching
+        assert!(alerts::run(&mut *memory, &indexes, &params, &mut seen, now).is_empty());
+
+        Talent::index(&mut *memory, INDEX, vec![talent(3, "Berlin"), talent(4, "Munich")]).unwrap();
+        assert_eq!(alerts::run(&mut *memory, &indexes, &params, &mut seen, now), vec![3]);
+        assert!(alerts::run(&mut *memory, &indexes, &params, &mut seen, now).is_empty());
+
+        // a talent leaving the results and coming back isn't notified again
+        Talent::delete(&mut *memory, &TalentId::from(1), INDEX).unwrap();
+        assert!(alerts::run(&mut *memory, &indexes, &params, &mut seen, now).is_empty());
+        Talent::index(&mut *memory, INDEX, vec![talent(1, "Berlin")]).unwrap();
+        assert!(alerts::run(&mut *memory, &indexes, &params, &mut seen, now).is_empty());
+
+        // a failing search leaves what was seen untouched
+        let before = seen.clone();
+        let missing = IndexSet::new("memory_alerts_missing");
+        assert!(alerts::run(&mut *memory, &missing, &params, &mut seen, now).is_empty());
+        assert_eq!(seen, before);
+
+        Talent::index(&mut *memory, INDEX, vec![talent(5, "Berlin")]).unwrap();
+        assert_eq!(alerts::run(&mut *memory, &indexes, &params, &mut seen, now), vec![5]);
+
+        // the talents that stopped matching long ago are forgotten
+        Talent::delete(&mut *memory, &TalentId::from(1), INDEX).unwrap();
+        let halfway = now + SEEN_TTL / 2;
+        assert!(alerts::run(&mut *memory, &indexes, &params, &mut seen, halfway).is_empty());
+        let later = now + SEEN_TTL + Duration::from_secs(1);
+        assert!(alerts::run(&mut *memory, &indexes, &params, &mut seen, later).is_empty());
+        assert_eq!(seen.as_ref().map(|seen| seen.len()), Some(2));
+        Talent::index(&mut *memory, INDEX, vec![talent(1, "Berlin")]).unwrap();
+        assert_eq!(alerts::run(&mut *memory, &indexes, &params, &mut seen, later), vec![1]);
+    }
+}
@@ -0,0 +1,1513 @@
+//! An evaluator of the ElasticSearch query DSL over plain JSON documents.
+//!
+//! Backends that don't understand the DSL natively use it to run the searches
+//! built by the resources. It covers the subset of the DSL Searchspot sends
+//! (`bool`, `term`, `terms`, `range`, `exists`, `nested`, `match`,
+//! `query_string` and `function_score` with `field_value_factor` and `gauss`)
+//! and approximates the scoring: a full-text query scores the share of its
+//! terms found in the document, every other query scores 1.
+//! Aggregations are limited to `terms`, `stats`, `histogram`, `cardinality`,
+//! `filter` and `nested`, highlighting is not supported.
+
+use chrono::DateTime;
+use serde_json::Value;
+
+use resource::{EsError, SearchHitsHitsResult, SearchHitsResult, SearchResult, ShardsResult,
+               TotalRelation};
+
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+
+/// A stored document, as seen by the evaluator.
+#[derive(Debug, Clone)]
+pub struct Document {
+    pub index: String,
+    /// The type the document was stored with, if the backend keeps it.
+    pub doc_type: Option<String>,
+    pub id: String,
+    pub source: Value,
+}
+
+/// Run the search described by `body` over `documents`, honouring `query`,
+/// `min_score`, `sort` (by the fields, `_score` or `_id`), `aggs`, `post_filter`,
+/// `collapse`, `search_after`, `from`, `size`, `_source` and `track_total_hits`,
+/// which caps the `total` when given as a number.
+pub fn search(documents: Vec<Document>, body: &Value) -> Result<SearchResult<Value>, EsError> {
+    let match_all = json!({ "match_all": {} });
+    let query = body.get("query").unwrap_or(&match_all);
+    let min_score = body.get("min_score").and_then(Value::as_f64);
+
+    let mut hits = vec![];
+    for document in documents.into_iter() {
+        if let Some(score) = score(query, &document.source)? {
+            if min_score.map_or(true, |min_score| score >= min_score) {
+                hits.push((score, document));
+            }
+        }
+    }
+
+    match body.get("sort") {
+        Some(sort) => {
+            let criteria = sort_criteria(sort);
+            hits.sort_by(|a, b| compare_hits(&criteria, a, b));
+        }
+        None => hits.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal)),
+    }
+
+    // the aggregations see the hits removed by `post_filter`
+    let aggregations = match body.get("aggs").or_else(|| body.get("aggregations")) {
+        Some(aggs) => Some(aggregate(aggs, hits.iter().map(|hit| &hit.1))?),
+        None => None,
+    };
+
+    if let Some(post_filter) = body.get("post_filter") {
+        let mut visible = vec![];
+        for hit in hits.into_iter() {
+            if score(post_filter, &hit.1.source)?.is_some() {
+                visible.push(hit);
+            }
+        }
+        hits = visible;
+    }
+
+    let mut total = hits.len() as u64;
+    let mut total_relation = TotalRelation::Eq;
+    if let Some(cap) = body.get("track_total_hits").and_then(Value::as_u64) {
+        if total > cap {
+            total = cap;
+            total_relation = TotalRelation::Gte;
+        }
+    }
+    let max_score = hits
+        .iter()
+        .map(|hit| hit.0)
+        .fold(None, |max: Option<f64>, score| {
+            Some(max.map_or(score, |max| max.max(score)))
+        });
+
+    // the first hit of every value of the `collapse` field stands for the others,
+    // which are still counted in the total
+    if let Some(field) = body.pointer("/collapse/field").and_then(Value::as_str) {
+        let mut collapsed = HashSet::new();
+        hits.retain(|hit| {
+            let value = values_at(&hit.1.source, field).first().map(|value| value.to_string());
+            collapsed.insert(value)
+        });
+    }
+
+    // like `from`, `search_after` pages through the hits without changing the total
+    if let (Some(sort), Some(search_after)) = (
+        body.get("sort"),
+        body.get("search_after").and_then(Value::as_array),
+    ) {
+        let criteria = sort_criteria(sort);
+        hits.retain(|hit| is_after(&criteria, hit, search_after));
+    }
+
+    let from = body.get("from").and_then(Value::as_u64).unwrap_or(0) as usize;
+    let size = body.get("size").and_then(Value::as_u64).unwrap_or(10) as usize;
+    let includes = source_includes(body.get("_source"));
+
+    let hits = hits
+        .into_iter()
+        .skip(from)
+        .take(size)
+        .map(|(score, document)| SearchHitsHitsResult {
+            index: document.index,
+            doc_type: document.doc_type,
+            id: document.id,
+            score: Some(score),
+            source: Some(Box::new(filter_source(document.source, &includes))),
+            highlight: None,
+        })
+        .collect();
+
+    Ok(SearchResult {
+        took: 0,
+        timed_out: false,
+        shards: ShardsResult {
+            total: 1,
+            successful: 1,
+            failed: 0,
+            failures: vec![],
+        },
+        hits: SearchHitsResult {
+            total: total,
+            total_relation: total_relation,
+            max_score: max_score,
+            hits: hits,
+        },
+        aggregations: aggregations,
+        scroll_id: None,
+    })
+}
+
+/// Return the top-level fields listed by `_source`, given either as
+/// an array or as `{ "includes": [...] }`, or `None` to keep them all.
+fn source_includes(filter: Option<&Value>) -> Option<Vec<String>> {
+    let includes = match filter {
+        Some(&Value::Object(ref filter)) => filter.get("includes"),
+        filter => filter,
+    };
+
+    includes.and_then(Value::as_array).map(|fields| {
+        fields
+            .iter()
+            .filter_map(Value::as_str)
+            .map(str::to_owned)
+            .collect()
+    })
+}
+
+fn filter_source(source: Value, includes: &Option<Vec<String>>) -> Value {
+    match (source, includes) {
+        (Value::Object(fields), &Some(ref includes)) => Value::Object(
+            fields
+                .into_iter()
+                .filter(|&(ref field, _)| includes.contains(field))
+                .collect(),
+        ),
+        (source, _) => source,
+    }
+}
+
+/// Merge the fields of `doc` into `source`, as the partial updates do:
+/// the objects are merged field by field, any other value is replaced.
+pub fn merge(source: &mut Value, doc: Value) {
+    match (source, doc) {
+        (&mut Value::Object(ref mut source), Value::Object(doc)) => {
+            for (field, value) in doc.into_iter() {
+                merge(source.entry(field).or_insert(Value::Null), value);
+            }
+        }
+        (source, doc) => *source = doc,
+    }
+}
+
+/// The error of a partial update of the missing document `id`.
+pub fn document_missing(doc_type: &str, id: &str) -> Value {
+    json!({
+        "type":   "document_missing_exception",
+        "reason": format!("[{}][{}]: document missing", doc_type, id),
+    })
+}
+
+/// The error of a create-only action whose id is taken already.
+pub fn version_conflict(id: &str) -> Value {
+    json!({
+        "type":   "version_conflict_engine_exception",
+        "reason": format!("[{}]: version conflict, document already exists", id),
+    })
+}
+
+/// The error of an action whose external version isn't newer than the stored one.
+pub fn stale_version(id: &str, version: u64, current: u64) -> Value {
+    json!({
+        "type":   "version_conflict_engine_exception",
+        "reason": format!(
+            "[{}]: version conflict, current version [{}] is higher or equal to the one \
+             provided [{}]",
+            id, current, version
+        ),
+    })
+}
+
+/// Return whether `index` matches `pattern`, where `*` stands for any sequence of characters.
+pub fn index_matches(pattern: &str, index: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or("");
+    if !index.starts_with(first) {
+        return false;
+    }
+
+    let mut rest = &index[first.len()..];
+    let parts: Vec<&str> = parts.collect();
+
+    for (i, part) in parts.iter().enumerate() {
+        if i == parts.len() - 1 {
+            return rest.ends_with(part);
+        }
+
+        match rest.find(part) {
+            Some(position) => rest = &rest[position + part.len()..],
+            None => return false,
+        }
+    }
+
+    // no wildcards at all
+    rest.is_empty()
+}
+
+/// Compute the `terms`, `stats`, `histogram`, `cardinality`, `filter` and `nested`
+/// aggregations described by `aggs` over `documents`. `_index` and `_id` can be used as `terms`
+/// fields as well, while `terms`, `filter` and `nested` aggregations can contain
+/// other aggregations.
+fn aggregate<'a, I>(aggs: &Value, documents: I) -> Result<Value, EsError>
+where
+    I: Iterator<Item = &'a Document> + Clone,
+{
+    let mut results = json!({});
+
+    for (name, aggregation) in aggs.as_object().into_iter().flat_map(|aggs| aggs.iter()) {
+        results[name] = if let Some(terms) = aggregation.get("terms") {
+            aggregate_terms(aggregation, terms, documents.clone())?
+        } else if let Some(stats) = aggregation.get("stats") {
+            aggregate_stats(&numbers_of(stats, documents.clone()))
+        } else if let Some(histogram) = aggregation.get("histogram") {
+            let interval = histogram
+                .get("interval")
+                .and_then(Value::as_f64)
+                .filter(|interval| *interval > 0.0)
+                .ok_or_else(|| {
+                    EsError::Unsupported(format!("Invalid histogram interval: {}", histogram))
+                })?;
+            aggregate_histogram(&numbers_of(histogram, documents.clone()), interval)
+        } else if let Some(cardinality) = aggregation.get("cardinality") {
+            // exact, while ElasticSearch approximates it
+            let field = cardinality.get("field").and_then(Value::as_str).unwrap_or("");
+            let values: HashSet<String> = documents
+                .clone()
+                .flat_map(|document| values_at(&document.source, field).into_iter().map(text_of))
+                .collect();
+            json!({ "value": values.len() })
+        } else if let Some(filter) = aggregation.get("filter") {
+            let mut matching = vec![];
+            for document in documents.clone() {
+                if score(filter, &document.source)?.is_some() {
+                    matching.push(document);
+                }
+            }
+
+            let mut result = sub_aggregate(aggregation, matching.iter().cloned())?;
+            result["doc_count"] = json!(matching.len());
+            result
+        } else if let Some(nested) = aggregation.get("nested") {
+            // every nested object becomes a document of its own
+            let path = nested.get("path").and_then(Value::as_str).unwrap_or("");
+            let objects: Vec<Document> = documents
+                .clone()
+                .flat_map(|document| {
+                    values_at(&document.source, path)
+                        .into_iter()
+                        .map(move |object| Document {
+                            index: document.index.to_owned(),
+                            doc_type: document.doc_type.to_owned(),
+                            id: document.id.to_owned(),
+                            source: nest(path, object.to_owned()),
+                        })
+                })
+                .collect();
+
+            let mut result = sub_aggregate(aggregation, objects.iter())?;
+            result["doc_count"] = json!(objects.len());
+            result
+        } else {
+            return Err(EsError::Unsupported(format!(
+                "Unsupported aggregation: {}",
+                aggregation
+            )));
+        };
+    }
+
+    Ok(results)
+}
+
+/// Compute the aggregations nested in `aggregation`, if any, over `documents`.
+fn sub_aggregate<'a, I>(aggregation: &Value, documents: I) -> Result<Value, EsError>
+where
+    I: Iterator<Item = &'a Document> + Clone,
+{
+    match aggregation.get("aggs").or_else(|| aggregation.get("aggregations")) {
+        Some(aggs) => aggregate(aggs, documents),
+        None => Ok(json!({})),
+    }
+}
+
+/// Return `value` placed at the dotted `path` of an otherwise empty object.
+fn nest(path: &str, value: Value) -> Value {
+    path.rsplit('.').fold(value, |value, segment| {
+        let mut object = ::serde_json::Map::new();
+        object.insert(segment.to_owned(), value);
+        Value::Object(object)
+    })
+}
+
+fn aggregate_terms<'a, I>(
+    aggregation: &Value,
+    terms: &Value,
+    documents: I,
+) -> Result<Value, EsError>
+where
+    I: Iterator<Item = &'a Document>,
+{
+    let field = terms.get("field").and_then(Value::as_str).unwrap_or("");
+    let size = terms.get("size").and_then(Value::as_u64).unwrap_or(10) as usize;
+
+    let mut groups: HashMap<String, Vec<&'a Document>> = HashMap::new();
+    for document in documents {
+        let keys = match field {
+            "_index" => vec![document.index.to_owned()],
+            "_id" => vec![document.id.to_owned()],
+            _ => values_at(&document.source, field)
+                .into_iter()
+                .map(text_of)
+                .collect(),
+        };
+
+        for key in keys.into_iter() {
+            groups.entry(key).or_insert_with(Vec::new).push(document);
+        }
+    }
+
+    let mut groups: Vec<(String, Vec<&Document>)> = groups.into_iter().collect();
+    groups.sort_by(|a, b| b.1.len().cmp(&a.1.len()).then_with(|| a.0.cmp(&b.0)));
+
+    let mut buckets = vec![];
+    for (key, documents) in groups.into_iter().take(size) {
+        let mut bucket = sub_aggregate(aggregation, documents.iter().cloned())?;
+        bucket["key"] = json!(key);
+        bucket["doc_count"] = json!(documents.len());
+        buckets.push(bucket);
+    }
+
+    Ok(json!({ "buckets": buckets }))
+}
+
+/// Return the numeric values of the `field` of `options` found in `documents`.
+fn numbers_of<'a, I>(options: &Value, documents: I) -> Vec<f64>
+where
+    I: Iterator<Item = &'a Document>,
+{
+    let field = options.get("field").and_then(Value::as_str).unwrap_or("");
+
+    documents
+        .flat_map(|document| {
+            values_at(&document.source, field)
+                .into_iter()
+                .filter_map(as_number)
+                .collect::<Vec<f64>>()
+        })
+        .collect()
+}
+
+fn aggregate_stats(values: &[f64]) -> Value {
+    if values.is_empty() {
+        return json!({ "count": 0, "min": null, "max": null, "avg": null, "sum": 0.0 });
+    }
+
+    let sum: f64 = values.iter().sum();
+    let min = values.iter().cloned().fold(f64::INFINITY, f64::min);
+    let max = values.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+
+    json!({
+        "count": values.len(),
+        "min":   min,
+        "max":   max,
+        "avg":   sum / values.len() as f64,
+        "sum":   sum,
+    })
+}
+
+/// Count `values` in buckets `interval` wide, including the empty ones
+/// between the lowest and the highest bucket, as ElasticSearch does.
+fn aggregate_histogram(values: &[f64], interval: f64) -> Value {
+    let slots: Vec<i64> = values
+        .iter()
+        .map(|value| (value / interval).floor() as i64)
+        .collect();
+
+    let buckets: Vec<Value> = match (slots.iter().min(), slots.iter().max()) {
+        (Some(&first), Some(&last)) => (first..last + 1)
+            .map(|slot| {
+                let count = slots.iter().filter(|s| **s == slot).count();
+                json!({ "key": slot as f64 * interval, "doc_count": count })
+            })
+            .collect(),
+        _ => vec![],
+    };
+
+    json!({ "buckets": buckets })
+}
+
+/// Return the score of `source` for `query`, or `None` if it doesn't match.
+pub fn score(query: &Value, source: &Value) -> Result<Option<f64>, EsError> {
+    let (kind, options) = match query.as_object() {
+        Some(query) if query.len() == 1 => query.iter().next().unwrap(),
+        _ => return Err(unsupported(query)),
+    };
+
+    match &**kind {
+        "match_all" => Ok(Some(1.0)),
+        "bool" => score_bool(options, source),
+        "term" => {
+            let (field, value) = field_and_options(options, query)?;
+            let value = value.get("value").unwrap_or(value);
+            Ok(matched(
+                values_at(source, field).iter().any(|v| equals(v, value)),
+            ))
+        }
+        "terms" => {
+            let (field, values) = field_and_options(options, query)?;
+            let values = values.as_array().ok_or_else(|| unsupported(query))?;
+            Ok(matched(
+                values_at(source, field)
+                    .iter()
+                    .any(|v| values.iter().any(|value| equals(v, value))),
+            ))
+        }
+        "range" => {
+            let (field, bounds) = field_and_options(options, query)?;
+            Ok(matched(
+                values_at(source, field).iter().any(|v| in_range(v, bounds)),
+            ))
+        }
+        "exists" => {
+            let field = options.get("field").and_then(Value::as_str).unwrap_or("");
+            Ok(matched(!values_at(source, field).is_empty()))
+        }
+        "nested" => score_nested(options, source, query),
+        "match" => score_match(options, source, query),
+        "query_string" => score_query_string(options, source, query),
+        "function_score" => score_function_score(options, source, query),
+        _ => Err(unsupported(query)),
+    }
+}
+
+fn unsupported(query: &Value) -> EsError {
+    EsError::Unsupported(format!("Unsupported query: {}", query))
+}
+
+fn matched(condition: bool) -> Option<f64> {
+    if condition {
+        Some(1.0)
+    } else {
+        None
+    }
+}
+
+/// Split `{ "field": options }` into its parts.
+fn field_and_options<'a>(
+    options: &'a Value,
+    query: &Value,
+) -> Result<(&'a str, &'a Value), EsError> {
+    match options.as_object() {
+        Some(options) => options
+            .iter()
+            .find(|&(key, _)| !key.starts_with('_') && key != "boost")
+            .map(|(field, options)| (&**field, options))
+            .ok_or_else(|| unsupported(query)),
+        None => Err(unsupported(query)),
+    }
+}
+
+fn clauses<'a>(options: &'a Value, clause: &str) -> Vec<&'a Value> {
+    match options.get(clause) {
+        Some(&Value::Array(ref queries)) => queries.iter().collect(),
+        Some(query) => vec![query],
+        None => vec![],
+    }
+}
+
+fn score_bool(options: &Value, source: &Value) -> Result<Option<f64>, EsError> {
+    let must = clauses(options, "must");
+    let filter = clauses(options, "filter");
+    let should = clauses(options, "should");
+    let must_not = clauses(options, "must_not");
+
+    let mut total = 0.0;
+
+    for query in must.iter() {
+        match score(query, source)? {
+            Some(score) => total += score,
+            None => return Ok(None),
+        }
+    }
+
+    for query in filter.iter() {
+        if score(query, source)?.is_none() {
+            return Ok(None);
+        }
+    }
+
+    for query in must_not.iter() {
+        if score(query, source)?.is_some() {
+            return Ok(None);
+        }
+    }
+
+    let mut should_matches = 0;
+    for query in should.iter() {
+        if let Some(score) = score(query, source)? {
+            should_matches += 1;
+            total += score;
+        }
+    }
+
+    // without `must` and `filter` at least one `should` is required
+    if must.is_empty() && filter.is_empty() && !should.is_empty() && should_matches == 0 {
+        return Ok(None);
+    }
+
+    if must.is_empty() && should.is_empty() {
+        Ok(Some(1.0))
+    } else {
+        Ok(Some(total))
+    }
+}
+
+fn score_nested(options: &Value, source: &Value, query: &Value) -> Result<Option<f64>, EsError> {
+    let path = options.get("path").and_then(Value::as_str);
+    let inner = options.get("query");
+
+    let (path, inner) = match (path, inner) {
+        (Some(path), Some(inner)) => (path, inner),
+        _ => return Err(unsupported(query)),
+    };
+
+    // every nested object is evaluated alone, keeping its full path
+    let mut best = None;
+    for object in values_at(source, path) {
+        let mut scoped = object.to_owned();
+        for segment in path.rsplit('.') {
+            let mut parent = json!({});
+            parent[segment] = scoped;
+            scoped = parent;
+        }
+
+        if let Some(score) = score(inner, &scoped)? {
+            best = Some(best.map_or(score, |best: f64| best.max(score)));
+        }
+    }
+
+    Ok(best)
+}
+
+fn score_function_score(
+    options: &Value,
+    source: &Value,
+    query: &Value,
+) -> Result<Option<f64>, EsError> {
+    let match_all = json!({ "match_all": {} });
+    let score = match score(options.get("query").unwrap_or(&match_all), source)? {
+        Some(score) => score,
+        None => return Ok(None),
+    };
+
+    let functions = options.get("functions").and_then(Value::as_array);
+    let mut scores = vec![];
+    for function in functions.into_iter().flat_map(|functions| functions.iter()) {
+        scores.push(function_value(function, source).ok_or_else(|| unsupported(query))?);
+    }
+
+    let combined: f64 = match options.get("score_mode").and_then(Value::as_str) {
+        _ if scores.is_empty() => 1.0,
+        None | Some("multiply") => scores.iter().product(),
+        Some("sum") => scores.iter().sum(),
+        Some("max") => scores.iter().cloned().fold(::std::f64::MIN, f64::max),
+        Some(_) => return Err(unsupported(query)),
+    };
+
+    match options.get("boost_mode").and_then(Value::as_str) {
+        None | Some("multiply") => Ok(Some(score * combined)),
+        Some("sum") => Ok(Some(score + combined)),
+        Some("replace") => Ok(Some(combined)),
+        Some(_) => Err(unsupported(query)),
+    }
+}
+
+/// Return the score given to `source` by one of the `functions` of a `function_score`
+/// (a `field_value_factor` or a `gauss` decay), or `None` if it isn't supported.
+fn function_value(function: &Value, source: &Value) -> Option<f64> {
+    if let Some(options) = function.get("field_value_factor") {
+        let field = options.get("field").and_then(Value::as_str)?;
+        let value = match values_at(source, field).into_iter().next() {
+            Some(value) => as_number(value)?,
+            None => options.get("missing").and_then(as_number).unwrap_or(0.0),
+        };
+        let value = value * options.get("factor").and_then(Value::as_f64).unwrap_or(1.0);
+
+        return match options.get("modifier").and_then(Value::as_str) {
+            None | Some("none") => Some(value),
+            Some("log1p") => Some((1.0 + value).log10()),
+            Some("ln1p") => Some(value.ln_1p()),
+            Some("sqrt") => Some(value.sqrt()),
+            Some(_) => None,
+        };
+    }
+
+    let (field, options) = function.get("gauss")?.as_object()?.iter().next()?;
+    let origin = options.get("origin")?;
+    let decay = options.get("decay").and_then(Value::as_f64).unwrap_or(0.5);
+
+    // the documents missing the field aren't decayed
+    let value = match values_at(source, field).into_iter().next() {
+        Some(value) => value,
+        None => return Some(1.0),
+    };
+
+    let (distance, scale) = match (origin, options.get("scale")?) {
+        (&Value::String(ref origin), &Value::String(ref scale)) => {
+            let origin = DateTime::parse_from_rfc3339(origin).ok()?;
+            let date = DateTime::parse_from_rfc3339(value.as_str()?).ok()?;
+            let distance = (date.timestamp_millis() - origin.timestamp_millis()).abs();
+            (distance as f64, duration_millis(scale)?)
+        }
+        (origin, scale) => ((as_number(value)? - as_number(origin)?).abs(), as_number(scale)?),
+    };
+
+    // `decay` at `scale` from the origin
+    Some(decay.powf((distance / scale).powi(2)))
+}
+
+/// Return the milliseconds of a duration of the ElasticSearch date math
+/// (i.e. `7d`, `12h`, `500ms`), or `None` if it isn't a positive one.
+pub fn duration_millis(duration: &str) -> Option<f64> {
+    const UNITS: &'static [(&'static str, f64)] = &[
+        ("ms", 1.0),
+        ("s", 1000.0),
+        ("m", 60.0 * 1000.0),
+        ("h", 60.0 * 60.0 * 1000.0),
+        ("d", 24.0 * 60.0 * 60.0 * 1000.0),
+        ("w", 7.0 * 24.0 * 60.0 * 60.0 * 1000.0),
+    ];
+
+    let &(unit, millis) = UNITS.iter().find(|&&(unit, _)| duration.ends_with(unit))?;
+    duration[..duration.len() - unit.len()]
+        .parse::<u64>()
+        .ok()
+        .filter(|amount| *amount > 0)
+        .map(|amount| amount as f64 * millis)
+}
+
+fn score_match(options: &Value, source: &Value, query: &Value) -> Result<Option<f64>, EsError> {
+    let (field, options) = field_and_options(options, query)?;
+    let text = match options.get("query") {
+        Some(text) => text,
+        None => options,
+    };
+    let text = text_of(text);
+
+    let haystack = values_at(source, field)
+        .into_iter()
+        .map(text_of)
+        .collect::<Vec<String>>()
+        .join(" ");
+
+    match options.get("type").and_then(Value::as_str) {
+        Some("phrase") | Some("phrase_prefix") => Ok(matched(contains_phrase(&haystack, &text))),
+        _ => Ok(share_of_terms(&tokenize(&text), &tokenize(&haystack))),
+    }
+}
+
+fn score_query_string(
+    options: &Value,
+    source: &Value,
+    query: &Value,
+) -> Result<Option<f64>, EsError> {
+    let text = options
+        .get("query")
+        .and_then(Value::as_str)
+        .ok_or_else(|| unsupported(query))?;
+
+    let haystack = match options.get("fields").and_then(Value::as_array) {
+        Some(fields) => fields
+            .iter()
+            .filter_map(Value::as_str)
+            .flat_map(|field| values_at(source, field.split('^').next().unwrap_or(field)))
+            .map(text_of)
+            .collect::<Vec<String>>(),
+        None => vec![text_of(source)],
+    }
+    .join(" ");
+
+    let default_and = options.get("default_operator").and_then(Value::as_str) == Some("AND");
+    let terms = QueryStringTerms::parse(text, default_and);
+    let tokens = tokenize(&haystack);
+
+    let found = |term: &String| {
+        if term.contains(' ') {
+            contains_phrase(&haystack, term)
+        } else {
+            tokens.contains(term)
+        }
+    };
+
+    if terms.excluded.iter().any(&found) || !terms.required.iter().all(&found) {
+        return Ok(None);
+    }
+
+    let wanted = terms.required.len() + terms.optional.len();
+    if wanted == 0 {
+        return Ok(Some(1.0));
+    }
+
+    let optional_found = terms.optional.iter().filter(|term| found(*term)).count();
+    if terms.required.is_empty() && optional_found == 0 {
+        return Ok(None);
+    }
+
+    Ok(Some(
+        (terms.required.len() + optional_found) as f64 / wanted as f64,
+    ))
+}
+
+/// The terms of a query string, split by the operators applying to them.
+#[derive(Debug, Default, PartialEq)]
+struct QueryStringTerms {
+    required: Vec<String>,
+    optional: Vec<String>,
+    excluded: Vec<String>,
+}
+
+impl QueryStringTerms {
+    fn parse(text: &str, default_and: bool) -> QueryStringTerms {
+        let mut words = vec![];
+        let mut phrase: Option<String> = None;
+
+        for word in text.split_whitespace() {
+            match phrase.take() {
+                Some(mut open) => {
+                    open.push(' ');
+                    open.push_str(word);
+                    if word.ends_with('"') {
+                        words.push(open);
+                    } else {
+                        phrase = Some(open);
+                    }
+                }
+                None => {
+                    let unquoted = word.trim_start_matches(|c| c == '+' || c == '-');
+                    if unquoted.starts_with('"')
+                        && (unquoted.len() == 1 || !unquoted.ends_with('"'))
+                    {
+                        phrase = Some(word.to_owned());
+                    } else {
+                        words.push(word.to_owned());
+                    }
+                }
+            }
+        }
+        words.extend(phrase);
+
+        let all_required = default_and || words.iter().any(|word| word == "AND");
+        let mut terms = QueryStringTerms::default();
+        let mut negate_next = false;
+
+        for word in words.into_iter() {
+            match &*word {
+                "AND" | "OR" | "&&" | "||" => continue,
+                "NOT" | "!" => {
+                    negate_next = true;
+                    continue;
+                }
+                _ => {}
+            }
+
+            let (bucket, word) = if negate_next || word.starts_with('-') {
+                (&mut terms.excluded, word.trim_start_matches('-'))
+            } else if all_required || word.starts_with('+') {
+                (&mut terms.required, word.trim_start_matches('+'))
+            } else {
+                (&mut terms.optional, &*word)
+            };
+            negate_next = false;
+
+            let term = tokenize(word).join(" ");
+            if !term.is_empty() {
+                bucket.push(term);
+            }
+        }
+
+        terms
+    }
+}
+
+/// Return the values found at the dotted `path` of `source`, flattening the arrays.
+/// A path going past a scalar value (i.e. `skills.raw`) refers to one of its
+/// multi-fields, which share the value of the field itself.
+pub fn values_at<'a>(source: &'a Value, path: &str) -> Vec<&'a Value> {
+    let mut current = vec![source];
+
+    for segment in path.split('.').filter(|segment| !segment.is_empty()) {
+        let mut next = vec![];
+        for value in current.into_iter() {
+            match *value {
+                Value::Object(ref object) => {
+                    if let Some(child) = object.get(segment) {
+                        next.push(child);
+                    }
+                }
+                Value::Array(_) | Value::Null => {}
+                _ => next.push(value),
+            }
+        }
+
+        current = next.into_iter().flat_map(flatten).collect();
+    }
+
+    current
+        .into_iter()
+        .filter(|value| !value.is_null())
+        .collect()
+}
+
+fn flatten(value: &Value) -> Vec<&Value> {
+    match *value {
+        Value::Array(ref values) => values.iter().flat_map(flatten).collect(),
+        _ => vec![value],
+    }
+}
+
+fn text_of(value: &Value) -> String {
+    match *value {
+        Value::String(ref text) => text.to_owned(),
+        Value::Array(ref values) => values.iter().map(text_of).collect::<Vec<_>>().join(" "),
+        Value::Object(ref object) => object.values().map(text_of).collect::<Vec<_>>().join(" "),
+        Value::Null => String::new(),
+        ref value => value.to_string(),
+    }
+}
+
+/// Split `text` into lowercase words, keeping the symbols of names like C++ and C#.
+/// Replace the accented latin letters of `word` with their ASCII
+/// counterpart, like the `asciifolding` filter of the talent analyzers.
+fn fold(word: &str) -> String {
+    let mut folded = String::with_capacity(word.len());
+    for character in word.chars() {
+        let replacement = match character {
+            'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ą' => "a",
+            'æ' => "ae",
+            'ç' | 'ć' | 'č' => "c",
+            'ď' | 'đ' => "d",
+            'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ę' | 'ě' => "e",
+            'ğ' => "g",
+            'ì' | 'í' | 'î' | 'ï' | 'ī' | 'ı' => "i",
+            'ł' => "l",
+            'ñ' | 'ń' | 'ň' => "n",
+            'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ő' => "o",
+            'œ' => "oe",
+            'ř' => "r",
+            'ś' | 'š' | 'ş' => "s",
+            'ß' => "ss",
+            'ť' | 'ţ' => "t",
+            'ù' | 'ú' | 'û' | 'ü' | 'ū' | 'ů' | 'ű' => "u",
+            'ý' | 'ÿ' => "y",
+            'ź' | 'ż' | 'ž' => "z",
+            _ => {
+                folded.push(character);
+                continue;
+            }
+        };
+        folded.push_str(replacement);
+    }
+    folded
+}
+
+fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !(c.is_alphanumeric() || c == '+' || c == '#'))
+        .filter(|word| !word.is_empty())
+        .map(|word| fold(&word.to_lowercase()))
+        .collect()
+}
+
+fn contains_phrase(haystack: &str, phrase: &str) -> bool {
+    let phrase = tokenize(phrase);
+    if phrase.is_empty() {
+        return false;
+    }
+
+    tokenize(haystack)
+        .windows(phrase.len())
+        .any(|window| window == &phrase[..])
+}
+
+fn share_of_terms(terms: &[String], tokens: &[String]) -> Option<f64> {
+    let found = terms.iter().filter(|term| tokens.contains(term)).count();
+    if found == 0 {
+        None
+    } else {
+        Some(found as f64 / terms.len() as f64)
+    }
+}
+
+fn equals(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (&Value::Number(_), &Value::Number(_)) => a.as_f64() == b.as_f64(),
+        _ => a == b || text_of(a) == text_of(b),
+    }
+}
+
+/// Compare two values numerically, as dates or as strings, in this order.
+fn compare(a: &Value, b: &Value) -> Option<Ordering> {
+    if let (Some(a), Some(b)) = (as_number(a), as_number(b)) {
+        return a.partial_cmp(&b);
+    }
+
+    match (a.as_str(), b.as_str()) {
+        (Some(a), Some(b)) => match (
+            DateTime::parse_from_rfc3339(a),
+            DateTime::parse_from_rfc3339(b),
+        ) {
+            (Ok(a), Ok(b)) => Some(a.cmp(&b)),
+            _ => Some(a.cmp(b)),
+        },
+        _ => None,
+    }
+}
+
+fn as_number(value: &Value) -> Option<f64> {
+    match *value {
+        Value::Number(_) => value.as_f64(),
+        Value::String(ref number) => number.parse().ok(),
+        _ => None,
+    }
+}
+
+fn in_range(value: &Value, bounds: &Value) -> bool {
+    let check = |bound: &str, accepted: &[Ordering]| match bounds.get(bound) {
+        Some(limit) => compare(value, limit).map_or(false, |ord| accepted.contains(&ord)),
+        None => true,
+    };
+
+    check("gt", &[Ordering::Greater])
+        && check("gte", &[Ordering::Greater, Ordering::Equal])
+        && check("lt", &[Ordering::Less])
+        && check("lte", &[Ordering::Less, Ordering::Equal])
+}
+
+/// A field to sort by, or the score when `None`, whether the order is descending
+/// and whether the documents with many values are sorted by the lowest one
+/// (`"mode": "min"`, the default of the ascending orders) or by the highest one.
+type SortCriterion = (Option<String>, bool, bool);
+
+fn sort_criteria(sort: &Value) -> Vec<SortCriterion> {
+    let criterion = |field: &str, options: Option<&Value>| {
+        let order = options.and_then(|options| match *options {
+            Value::String(ref order) => Some(&**order),
+            _ => options.get("order").and_then(Value::as_str),
+        });
+
+        if field == "_score" {
+            return (None, order != Some("asc"), false);
+        }
+
+        let descending = order == Some("desc");
+        let mode = options.and_then(|options| options.get("mode")).and_then(Value::as_str);
+        (Some(field.to_owned()), descending, mode.map_or(!descending, |mode| mode == "min"))
+    };
+
+    let sort: Vec<&Value> = match *sort {
+        Value::Array(ref sort) => sort.iter().collect(),
+        ref sort => vec![sort],
+    };
+
+    sort.into_iter()
+        .flat_map(|item| match *item {
+            Value::String(ref field) => vec![criterion(field, None)],
+            Value::Object(ref fields) => fields
+                .iter()
+                .map(|(field, options)| criterion(field, Some(options)))
+                .collect(),
+            _ => vec![],
+        })
+        .collect()
+}
+
+/// Return the lowest of `values` when `lowest`, the highest one otherwise.
+fn sort_value(values: Vec<&Value>, lowest: bool) -> Option<&Value> {
+    let wanted = if lowest {
+        Ordering::Less
+    } else {
+        Ordering::Greater
+    };
+
+    values.into_iter().fold(None, |picked, value| match picked {
+        Some(picked) if compare(value, picked) != Some(wanted) => Some(picked),
+        _ => Some(value),
+    })
+}
+
+/// Return the value `hit` is sorted by through `criterion`: its score, its `_id`
+/// or the lowest (or the highest) value of the field, `None` when it's missing.
+fn sort_key(criterion: &SortCriterion, hit: &(f64, Document)) -> Option<Value> {
+    match criterion.0 {
+        None => Some(json!(hit.0)),
+        Some(ref field) if field == "_id" => Some(json!(hit.1.id)),
+        Some(ref field) => sort_value(values_at(&hit.1.source, field), criterion.2).cloned(),
+    }
+}
+
+/// Compare the keys of two hits by `criterion`: the missing ones always come last.
+fn compare_keys(criterion: &SortCriterion, a: Option<&Value>, b: Option<&Value>) -> Ordering {
+    let ordering = match (a, b) {
+        (Some(_), None) => return Ordering::Less,
+        (None, Some(_)) => return Ordering::Greater,
+        (None, None) => Ordering::Equal,
+        (Some(a), Some(b)) => compare(a, b).unwrap_or(Ordering::Equal),
+    };
+
+    if criterion.1 {
+        ordering.reverse()
+    } else {
+        ordering
+    }
+}
+
+fn compare_hits(criteria: &[SortCriterion], a: &(f64, Document), b: &(f64, Document)) -> Ordering {
+    for criterion in criteria.iter() {
+        let (key_a, key_b) = (sort_key(criterion, a), sort_key(criterion, b));
+        let ordering = compare_keys(criterion, key_a.as_ref(), key_b.as_ref());
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+
+    Ordering::Equal
+}
+
+/// Whether `hit` is sorted after the sort values given in `search_after`.
+fn is_after(criteria: &[SortCriterion], hit: &(f64, Document), search_after: &[Value]) -> bool {
+    for (criterion, after) in criteria.iter().zip(search_after.iter()) {
+        match compare_keys(criterion, sort_key(criterion, hit).as_ref(), Some(after)) {
+            Ordering::Greater => return true,
+            Ordering::Less => return false,
+            Ordering::Equal => {}
+        }
+    }
+
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn talent() -> Value {
+        json!({
+            "id":        1,
+            "accepted":  true,
+            "languages": ["English", "German"],
+            "skills":    ["Rust", "C++", "Ember.js"],
+            "summary":   "Backend engineer writing services in Rust",
+            "batch_starts_at": "2018-01-10T10:00:00+01:00",
+            "salary_expectations": [
+                { "minimum": 40000, "city": "Berlin" },
+                { "minimum": 60000, "city": "Munich" }
+            ]
+        })
+    }
+
+    fn documents() -> Vec<Document> {
+        (1..6)
+            .map(|id| Document {
+                index: "talents".to_owned(),
+                doc_type: None,
+                id: id.to_string(),
+                source: json!({ "id": id, "weight": id % 3 }),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_term_and_terms() {
+        let talent = talent();
+        assert!(score(&json!({ "term": { "accepted": true } }), &talent)
+            .unwrap()
+            .is_some());
+        assert!(
+            score(&json!({ "term": { "languages": "German" } }), &talent)
+                .unwrap()
+                .is_some()
+        );
+        assert!(
+            score(&json!({ "term": { "languages": "French" } }), &talent)
+                .unwrap()
+                .is_none()
+        );
+        assert!(score(&json!({ "terms": { "id": [3, 1] } }), &talent)
+            .unwrap()
+            .is_some());
+        assert!(score(&json!({ "terms": { "id": [] } }), &talent)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_range() {
+        let talent = talent();
+        let query = json!({ "range": { "batch_starts_at": { "lte": "2018-01-10T09:30:00Z" } } });
+        assert!(score(&query, &talent).unwrap().is_some());
+
+        let query = json!({ "range": { "batch_starts_at": { "gt": "2018-01-10T09:30:00Z" } } });
+        assert!(score(&query, &talent).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_bool() {
+        let talent = talent();
+        let query = json!({
+            "bool": {
+                "must":     [{ "term": { "accepted": true } }],
+                "must_not": [{ "terms": { "id": [1] } }]
+            }
+        });
+        assert!(score(&query, &talent).unwrap().is_none());
+
+        let query = json!({
+            "bool": {
+                "should": [{ "term": { "id": 2 } }, { "term": { "id": 1 } }]
+            }
+        });
+        assert!(score(&query, &talent).unwrap().is_some());
+
+        // empty bools match everything
+        assert!(score(&json!({ "bool": {} }), &talent).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_nested() {
+        let talent = talent();
+        let query = json!({
+            "nested": {
+                "path": "salary_expectations",
+                "query": {
+                    "bool": {
+                        "must": [
+                            { "range": { "salary_expectations.minimum": { "lte": 50000 } } },
+                            { "term": { "salary_expectations.city": "Munich" } }
+                        ]
+                    }
+                }
+            }
+        });
+
+        // the conditions must hold on the same nested object
+        assert!(score(&query, &talent).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_query_string() {
+        let talent = talent();
+        let query = |text: &str| json!({ "query_string": { "query": text, "fields": ["skills.raw", "summary^2"] } });
+
+        assert_eq!(score(&query("rust"), &talent).unwrap(), Some(1.0));
+        assert_eq!(score(&query("rust java"), &talent).unwrap(), Some(0.5));
+        assert!(score(&query("rust AND java"), &talent).unwrap().is_none());
+        assert!(score(&query("c++ -backend"), &talent).unwrap().is_none());
+        assert!(score(&query("\"writing services\""), &talent)
+            .unwrap()
+            .is_some());
+        assert!(score(&query("\"services writing\""), &talent)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_accents_are_folded() {
+        let talent = json!({ "current_location": "Zürich", "summary": "José, Straßenbau" });
+
+        let query = json!({ "match": { "current_location.folded": "Zurich" } });
+        assert!(score(&query, &talent).unwrap().is_some());
+
+        let query = json!({ "query_string": { "query": "jose strassenbau", "fields": ["summary"] } });
+        assert_eq!(score(&query, &talent).unwrap(), Some(1.0));
+    }
+
+    #[test]
+    fn test_search_sorts_and_paginates() {
+        let body = json!({
+            "query": { "bool": { "must_not": [{ "term": { "id": 5 } }] } },
+            "sort":  [{ "weight": { "order": "desc" } }, { "id": { "order": "asc" } }],
+            "from":  1,
+            "size":  2,
+        });
+
+        let result = search(documents(), &body).unwrap();
+        assert_eq!(result.hits.total, 4);
+
+        let ids: Vec<String> = result.hits.hits.into_iter().map(|hit| hit.id).collect();
+        assert_eq!(ids, vec!["1".to_owned(), "4".to_owned()]);
+    }
+
+    #[test]
+    fn test_search_after() {
+        let body = json!({
+            "sort":         [{ "_id": { "order": "asc" } }],
+            "search_after": ["2"],
+            "size":         2,
+        });
+
+        let result = search(documents(), &body).unwrap();
+        assert_eq!(result.hits.total, 5);
+
+        let ids: Vec<String> = result.hits.hits.into_iter().map(|hit| hit.id).collect();
+        assert_eq!(ids, vec!["3".to_owned(), "4".to_owned()]);
+
+        let body = json!({
+            "sort":         [{ "weight": { "order": "desc" } }, { "_id": "asc" }],
+            "search_after": [1, "1"],
+        });
+        let ids: Vec<String> = search(documents(), &body)
+            .unwrap()
+            .hits
+            .hits
+            .into_iter()
+            .map(|hit| hit.id)
+            .collect();
+        assert_eq!(ids, vec!["4".to_owned(), "3".to_owned()]);
+    }
+
+    #[test]
+    fn test_function_score() {
+        let talent = json!({ "weight": 9, "added_to_batch_at": "2018-01-08T00:00:00Z" });
+        let query = |score_mode: &str, boost_mode: &str| {
+            json!({
+                "function_score": {
+                    "query":      { "term": { "weight": 9 } },
+                    "functions":  [
+                        {
+                            "gauss": {
+                                "added_to_batch_at": {
+                                    "origin": "2018-01-01T00:00:00Z",
+                                    "scale":  "7d",
+                                    "decay":  0.5,
+                                }
+                            }
+                        },
+                        {
+                            "field_value_factor": {
+                                "field":    "weight",
+                                "factor":   1,
+                                "modifier": "log1p",
+                            }
+                        },
+                    ],
+                    "score_mode": score_mode,
+                    "boost_mode": boost_mode,
+                }
+            })
+        };
+
+        assert_eq!(score(&query("sum", "replace"), &talent).unwrap(), Some(1.5));
+        assert_eq!(score(&query("multiply", "sum"), &talent).unwrap(), Some(1.5));
+        assert!(score(&query("avg", "replace"), &talent).is_err());
+
+        let other = json!({ "weight": 1 });
+        assert_eq!(score(&query("sum", "replace"), &other).unwrap(), None);
+    }
+
+    #[test]
+    fn test_duration_millis() {
+        assert_eq!(duration_millis("500ms"), Some(500.0));
+        assert_eq!(duration_millis("30s"), Some(30000.0));
+        assert_eq!(duration_millis("2m"), Some(120000.0));
+        assert_eq!(duration_millis("7d"), Some(604800000.0));
+        assert_eq!(duration_millis("0d"), None);
+        assert_eq!(duration_millis("7"), None);
+        assert_eq!(duration_millis("a week"), None);
+    }
+
+    #[test]
+    fn test_sort_mode() {
+        let documents: Vec<Document> = vec![vec![50000, 20000], vec![40000], vec![]]
+            .into_iter()
+            .enumerate()
+            .map(|(id, minimums)| {
+                let salaries: Vec<Value> = minimums
+                    .into_iter()
+                    .map(|minimum| json!({ "minimum": minimum }))
+                    .collect();
+                Document {
+                    index: "talents".to_owned(),
+                    doc_type: None,
+                    id: id.to_string(),
+                    source: json!({ "salary_expectations": salaries }),
+                }
+            })
+            .collect();
+
+        let ids = |order: &str, mode: &str| -> Vec<String> {
+            let body = json!({
+                "sort": [{
+                    "salary_expectations.minimum": {
+                        "order":       order,
+                        "mode":        mode,
+                        "nested_path": "salary_expectations",
+                    }
+                }]
+            });
+            let result = search(documents.to_owned(), &body).unwrap();
+            result.hits.hits.into_iter().map(|hit| hit.id).collect()
+        };
+
+        assert_eq!(ids("asc", "min"), vec!["0", "1", "2"]);
+        assert_eq!(ids("desc", "min"), vec!["1", "0", "2"]);
+        assert_eq!(ids("desc", "max"), vec!["0", "1", "2"]);
+    }
+
+    #[test]
+    fn test_source_filtering() {
+        let body = json!({ "_source": { "includes": ["id"] }, "size": 1 });
+        let result = search(documents(), &body).unwrap();
+        assert_eq!(*result.hits.hits[0].source.as_ref().unwrap().as_ref(), json!({ "id": 1 }));
+
+        let body = json!({ "_source": ["weight"], "size": 1 });
+        let result = search(documents(), &body).unwrap();
+        assert_eq!(*result.hits.hits[0].source.as_ref().unwrap().as_ref(), json!({ "weight": 1 }));
+    }
+
+    #[test]
+    fn test_terms_aggregation() {
+        let mut documents = documents();
+        documents[0].index = "talents_nl".to_owned();
+
+        let body = json!({ "aggs": { "indexes": { "terms": { "field": "_index" } } } });
+        let result = search(documents, &body).unwrap();
+
+        assert_eq!(
+            result.aggregations.unwrap()["indexes"]["buckets"],
+            json!([
+                { "key": "talents",    "doc_count": 4 },
+                { "key": "talents_nl", "doc_count": 1 }
+            ])
+        );
+    }
+
+    #[test]
+    fn test_stats_and_histogram_aggregations() {
+        let body = json!({
+            "aggs": {
+                "weights":   { "stats": { "field": "weight" } },
+                "histogram": { "histogram": { "field": "weight", "interval": 2 } },
+            }
+        });
+        let aggregations = search(documents(), &body).unwrap().aggregations.unwrap();
+
+        assert_eq!(
+            aggregations["weights"],
+            json!({ "count": 5, "min": 0.0, "max": 2.0, "avg": 1.2, "sum": 6.0 })
+        );
+        assert_eq!(
+            aggregations["histogram"]["buckets"],
+            json!([
+                { "key": 0.0, "doc_count": 3 },
+                { "key": 2.0, "doc_count": 2 }
+            ])
+        );
+
+        let body = json!({ "aggs": { "histogram": { "histogram": { "field": "weight" } } } });
+        assert!(search(documents(), &body).is_err());
+    }
+
+    #[test]
+    fn test_post_filter_and_filter_aggregations() {
+        let body = json!({
+            "post_filter": { "bool": { "must_not": [{ "terms": { "weight": [0] } }] } },
+            "aggs": {
+                "hidden":  { "filter": { "term": { "weight": 0 } } },
+                "visible": {
+                    "filter": { "bool": { "must_not": [{ "term": { "weight": 0 } }] } },
+                    "aggs":   { "indexes": { "terms": { "field": "_index" } } }
+                }
+            }
+        });
+        let result = search(documents(), &body).unwrap();
+
+        // the talent 3 is hidden, but still counted by the aggregations
+        assert_eq!(result.hits.total, 4);
+        let aggregations = result.aggregations.unwrap();
+        assert_eq!(aggregations["hidden"]["doc_count"], json!(1));
+        assert_eq!(aggregations["visible"]["doc_count"], json!(4));
+        assert_eq!(aggregations["visible"]["indexes"]["buckets"][0]["doc_count"], json!(4));
+    }
+
+    #[test]
+    fn test_nested_aggregations() {
+        let documents = vec![
+            Document {
+                index: "talents".to_owned(),
+                doc_type: None,
+                id: "1".to_owned(),
+                source: json!({ "salaries": [
+                    { "city": "Berlin", "minimum": 40000 },
+                    { "city": "Munich", "minimum": 50000 }
+                ] }),
+            },
+            Document {
+                index: "talents".to_owned(),
+                doc_type: None,
+                id: "2".to_owned(),
+                source: json!({ "salaries": [{ "city": "Berlin", "minimum": 60000 }] }),
+            },
+        ];
+
+        let body = json!({
+            "aggs": {
+                "salaries": {
+                    "nested": { "path": "salaries" },
+                    "aggs": {
+                        "cities": {
+                            "terms": { "field": "salaries.city" },
+                            "aggs":  { "minimum": { "stats": { "field": "salaries.minimum" } } }
+                        }
+                    }
+                }
+            }
+        });
+        let aggregations = search(documents, &body).unwrap().aggregations.unwrap();
+
+        // every salary expectation is counted on its own
+        assert_eq!(aggregations["salaries"]["doc_count"], json!(3));
+
+        let berlin = &aggregations["salaries"]["cities"]["buckets"][0];
+        assert_eq!(berlin["key"], json!("Berlin"));
+        assert_eq!(berlin["doc_count"], json!(2));
+        assert_eq!(berlin["minimum"]["min"], json!(40000.0));
+        assert_eq!(berlin["minimum"]["max"], json!(60000.0));
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut source = json!({ "id": 1, "skills": ["Rust"], "salary": { "minimum": 1 } });
+        merge(&mut source, json!({ "skills": ["Go"], "salary": { "currency": "EUR" } }));
+
+        assert_eq!(
+            source,
+            json!({ "id": 1, "skills": ["Go"], "salary": { "minimum": 1, "currency": "EUR" } })
+        );
+    }
+
+    #[test]
+    fn test_cardinality_aggregation() {
+        let body = json!({ "aggs": { "weights": { "cardinality": { "field": "weight" } } } });
+        let result = search(documents(), &body).unwrap();
+
+        assert_eq!(result.aggregations.unwrap()["weights"]["value"], json!(3));
+    }
+
+    #[test]
+    fn test_track_total_hits() {
+        let result = search(documents(), &json!({ "track_total_hits": 3, "size": 1 })).unwrap();
+        assert_eq!(result.hits.total, 3);
+        assert_eq!(result.hits.total_relation, TotalRelation::Gte);
+
+        let result = search(documents(), &json!({ "track_total_hits": true })).unwrap();
+        assert_eq!(result.hits.total, 5);
+        assert_eq!(result.hits.total_relation, TotalRelation::Eq);
+    }
+
+    #[test]
+    fn test_index_matches() {
+        assert!(index_matches("talents", "talents"));
+        assert!(!index_matches("talents", "talents_de"));
+        assert!(index_matches("talents_*", "talents_de"));
+        assert!(index_matches("*_de", "talents_de"));
+        assert!(index_matches("t*s_*", "talents_de"));
+        assert!(!index_matches("talents_*", "scores"));
+    }
+
+    #[test]
+    fn test_unsupported_query() {
+        assert!(score(&json!({ "fuzzy": { "skills": "rusd" } }), &talent()).is_err());
+    }
+}
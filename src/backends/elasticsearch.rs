@@ -0,0 +1,605 @@
+use serde::de::DeserializeOwned;
+use serde_json::{self, Map, Value};
+
+use elasticsearch::auth::Credentials;
+use elasticsearch::cert::{Certificate, CertificateValidation};
+use elasticsearch::http::headers::{HeaderMap, HeaderValue, CONTENT_TYPE};
+use elasticsearch::http::transport::{SingleNodeConnectionPool, Transport, TransportBuilder};
+use elasticsearch::http::{Method, StatusCode, Url};
+use elasticsearch::Elasticsearch;
+
+use tokio::runtime::{Builder, Runtime};
+
+use opentelemetry::KeyValue;
+
+use backends::{Refresh, SearchBackend};
+use config::ES;
+use telemetry;
+use resource::{
+    Action, BulkResult, DeleteByQueryResult, DeleteResult, EsError, MappingResult, MgetResult,
+    SearchResult, Settings,
+};
+
+use std::cmp;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read};
+
+/// A blocking ElasticSearch client built on top of the official one.
+///
+/// The official client is asynchronous, while Iron handlers are not:
+/// every request is driven to completion on a runtime owned by the client.
+///
+/// When more nodes are given, the requests are spread across them in turn,
+/// and a request that can't reach its node, times out or is answered with
+/// `502`, `503` or `504` is retried on the following ones.
+///
+/// The bulk requests are split into chunks of `bulk_chunk_size` actions,
+/// up to `bulk_concurrency` of which are sent at the same time, unless some
+/// document is acted upon by more chunks: they're sent one by one then.
+pub struct ElasticSearch {
+    nodes: Vec<Elasticsearch>,
+    next_node: usize,
+    runtime: Runtime,
+    refresh: Refresh,
+    bulk_chunk_size: usize,
+    bulk_concurrency: usize,
+}
+
+impl ElasticSearch {
+    pub fn new(url: &str) -> Result<ElasticSearch, EsError> {
+        ElasticSearch::with_transports(vec![Transport::single_node(url)?])
+    }
+
+    /// Connect to the nodes of `config` using the credentials and
+    /// the certificate authority given in `config`, if any.
+    pub fn from_config(config: &ES) -> Result<ElasticSearch, EsError> {
+        let certificate = match config.ca_certificate {
+            Some(ref path) => Some(ElasticSearch::certificate(path)?),
+            None => None,
+        };
+
+        let mut transports = vec![];
+        for node in config.nodes().iter() {
+            let url = Url::parse(node).map_err(::elasticsearch::Error::from)?;
+            let mut builder = TransportBuilder::new(SingleNodeConnectionPool::new(url));
+
+            if let Some(credentials) = ElasticSearch::credentials(config) {
+                builder = builder.auth(credentials);
+            }
+
+            if let Some(ref certificate) = certificate {
+                builder = builder.cert_validation(CertificateValidation::Full(certificate.clone()));
+            }
+
+            transports.push(builder.build().map_err(::elasticsearch::Error::from)?);
+        }
+
+        let mut client = ElasticSearch::with_transports(transports)?;
+        client.bulk_chunk_size = cmp::max(config.bulk_chunk_size, 1);
+        client.bulk_concurrency = cmp::max(config.bulk_concurrency, 1);
+        Ok(client)
+    }
+
+    fn with_transports(transports: Vec<Transport>) -> Result<ElasticSearch, EsError> {
+        if transports.is_empty() {
+            return Err(EsError::Unsupported("No ElasticSearch node given".to_owned()));
+        }
+
+        let runtime = Builder::new_current_thread().enable_all().build()?;
+
+        Ok(ElasticSearch {
+            nodes: transports.into_iter().map(Elasticsearch::new).collect(),
+            next_node: 0,
+            runtime: runtime,
+            refresh: Refresh::default(),
+            bulk_chunk_size: 1000,
+            bulk_concurrency: 4,
+        })
+    }
+
+    /// API keys take precedence over username and password.
+    fn credentials(config: &ES) -> Option<Credentials> {
+        if let (&Some(ref id), &Some(ref key)) = (&config.api_key_id, &config.api_key) {
+            return Some(Credentials::ApiKey(id.to_owned(), key.to_owned()));
+        }
+
+        match (&config.username, &config.password) {
+            (&Some(ref username), password) => Some(Credentials::Basic(
+                username.to_owned(),
+                password.to_owned().unwrap_or_default(),
+            )),
+            _ => None,
+        }
+    }
+
+    fn certificate(path: &str) -> Result<Certificate, EsError> {
+        let mut pem = vec![];
+        File::open(path)?.read_to_end(&mut pem)?;
+
+        Certificate::from_pem(&pem).map_err(|err| {
+            EsError::IoError(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid certificate {}: {}", path, err),
+            ))
+        })
+    }
+
+    /// Return the query string of the writes, telling when they become searchable.
+    fn refresh_query(refresh: Refresh) -> String {
+        match refresh {
+            Refresh::Lazy => String::new(),
+            refresh => format!("?refresh={}", refresh.as_param()),
+        }
+    }
+
+    /// Send the request to the next node and deserialize its response into `T`,
+    /// turning error statuses into `EsError::EsServerError`.
+    /// Nodes that can't be reached, time out or are unavailable (see `is_unavailable`)
+    /// are skipped in favour of the following ones.
+    fn execute<T: DeserializeOwned>(
+        &mut self,
+        method: Method,
+        path: &str,
+        content_type: &'static str,
+        body: Option<&[u8]>,
+    ) -> Result<T, EsError> {
+        let attributes = vec![
+            KeyValue::new("db.system", "elasticsearch"),
+            KeyValue::new("db.operation", format!("{:?} {}", method, path)),
+        ];
+
+        telemetry::in_span("elasticsearch.request", attributes, || {
+            self.send_to_nodes(method, path, content_type, body)
+        })
+    }
+
+    fn send_to_nodes<T: DeserializeOwned>(
+        &mut self,
+        method: Method,
+        path: &str,
+        content_type: &'static str,
+        body: Option<&[u8]>,
+    ) -> Result<T, EsError> {
+        let first_node = self.next_node;
+        self.next_node = (self.next_node + 1) % self.nodes.len();
+
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+
+        let mut last_error = None;
+        for attempt in 0..self.nodes.len() {
+            let node = &self.nodes[(first_node + attempt) % self.nodes.len()];
+            let request = node.send(
+                method,
+                path,
+                headers.clone(),
+                Option::<&Value>::None,
+                body,
+                None,
+            );
+
+            let response = match self.runtime.block_on(request) {
+                Ok(response) => response,
+                Err(error) => {
+                    warn!("ElasticSearch node unreachable: {}", error);
+                    last_error = Some(error.into());
+                    continue;
+                }
+            };
+
+            let status = response.status_code();
+            if ElasticSearch::is_unavailable(status) {
+                let content = self.runtime.block_on(response.text()).unwrap_or_default();
+                warn!("ElasticSearch node unavailable: {} - {}", status, content);
+                last_error = Some(EsError::EsServerError(format!("{} - {}", status, content)));
+                continue;
+            }
+
+            let content: Value = match self.runtime.block_on(response.json()) {
+                Ok(content) => content,
+                Err(error) => {
+                    if !error.is_timeout() {
+                        return Err(error.into());
+                    }
+
+                    warn!("ElasticSearch node timed out: {}", error);
+                    last_error = Some(error.into());
+                    continue;
+                }
+            };
+
+            if !status.is_success() {
+                return Err(EsError::EsServerError(format!("{} - {}", status, content)));
+            }
+
+            return Ok(serde_json::from_value(content)?);
+        }
+
+        Err(last_error.unwrap())
+    }
+
+    /// Tell whether `status` comes from a node (or the proxy in front of it)
+    /// that can't serve the request right now, so that another one should.
+    fn is_unavailable(status: StatusCode) -> bool {
+        match status.as_u16() {
+            502 | 503 | 504 => true,
+            _ => false,
+        }
+    }
+
+    /// Percent-encode `segment` to be used as a single segment of a path,
+    /// so that ids holding e.g. `/`, `?` or `#` don't change the endpoint.
+    fn path_segment(segment: &str) -> String {
+        let mut encoded = String::with_capacity(segment.len());
+
+        for byte in segment.bytes() {
+            match byte {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                    encoded.push(byte as char)
+                }
+                byte => encoded.push_str(&format!("%{:02X}", byte)),
+            }
+        }
+
+        encoded
+    }
+
+    /// Send every body of `bodies` at the same time, each to the next node, and
+    /// deserialize their responses in the same order. The bodies whose node can't
+    /// be reached or is unavailable are sent again one by one, trying the following nodes.
+    fn execute_concurrently<T: DeserializeOwned>(
+        &mut self,
+        method: Method,
+        path: &str,
+        content_type: &'static str,
+        bodies: &[Vec<u8>],
+    ) -> Result<Vec<T>, EsError> {
+        let attributes = vec![
+            KeyValue::new("db.system", "elasticsearch"),
+            KeyValue::new("db.operation", format!("{:?} {}", method, path)),
+            KeyValue::new("db.requests", bodies.len() as i64),
+        ];
+
+        telemetry::in_span("elasticsearch.request", attributes, || {
+            let mut headers = HeaderMap::new();
+            headers.insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+
+            let mut requests = Vec::with_capacity(bodies.len());
+            for body in bodies.iter() {
+                let node = self.nodes[self.next_node].clone();
+                self.next_node = (self.next_node + 1) % self.nodes.len();
+
+                let headers = headers.clone();
+                let path = path.to_owned();
+                let body = body.to_owned();
+                requests.push(self.runtime.spawn(async move {
+                    let response = node
+                        .send(method, &path, headers, Option::<&Value>::None, Some(&body[..]), None)
+                        .await?;
+                    let status = response.status_code();
+                    // the proxies in front of the unavailable nodes don't answer with JSON
+                    let content = response.text().await?;
+                    Ok::<_, ::elasticsearch::Error>((status, content))
+                }));
+            }
+
+            // the runtime serves every request while waiting for the first ones
+            let mut results = Vec::with_capacity(bodies.len());
+            for (body, request) in bodies.iter().zip(requests.into_iter()) {
+                let response = self
+                    .runtime
+                    .block_on(request)
+                    .map_err(|error| io::Error::new(io::ErrorKind::Other, error.to_string()))?;
+
+                match response {
+                    Ok((status, content)) if !ElasticSearch::is_unavailable(status) => {
+                        if !status.is_success() {
+                            return Err(EsError::EsServerError(format!("{} - {}", status, content)));
+                        }
+
+                        results.push(serde_json::from_str(&content)?);
+                    }
+                    Ok((status, content)) => {
+                        warn!("ElasticSearch node unavailable: {} - {}", status, content);
+                        let body = Some(&body[..]);
+                        results.push(self.send_to_nodes(method, path, content_type, body)?);
+                    }
+                    Err(error) => {
+                        warn!("ElasticSearch node unreachable: {}", error);
+                        let body = Some(&body[..]);
+                        results.push(self.send_to_nodes(method, path, content_type, body)?);
+                    }
+                }
+            }
+
+            Ok(results)
+        })
+    }
+
+    /// Return the body of a bulk request applying `actions`.
+    fn bulk_body(doc_type: &str, actions: Vec<Action<Value>>) -> Result<Vec<u8>, EsError> {
+        let mut body = vec![];
+
+        for action in actions.into_iter() {
+            let mut header = json!({ "_type": doc_type });
+            if let Some(id) = action.id {
+                header["_id"] = json!(id);
+            }
+            if let Some(version) = action.version {
+                header["version"] = json!(version);
+                header["version_type"] = json!("external");
+            }
+
+            let mut line = Map::new();
+            line.insert(action.operation.to_owned(), header);
+            serde_json::to_writer(&mut body, &line)?;
+            body.push(b'\n');
+
+            if let Some(source) = action.source {
+                serde_json::to_writer(&mut body, &source)?;
+                body.push(b'\n');
+            }
+        }
+
+        Ok(body)
+    }
+
+    /// Return `body` without `track_total_hits`, which ElasticSearch 5.6 rejects:
+    /// it counts every hit anyway, so only the embedded backends cap the count.
+    fn search_body(body: &Value) -> Value {
+        let mut body = body.clone();
+        if let Some(body) = body.as_object_mut() {
+            body.remove("track_total_hits");
+        }
+
+        body
+    }
+
+    /// Tell whether some document is acted upon by more of `chunks`, which could
+    /// then be applied out of order if they were sent at the same time.
+    fn share_ids(chunks: &[Vec<Action<Value>>]) -> bool {
+        let mut chunk_of_id = HashMap::new();
+
+        for (position, chunk) in chunks.iter().enumerate() {
+            for id in chunk.iter().filter_map(|action| action.id.as_ref()) {
+                if *chunk_of_id.entry(id).or_insert(position) != position {
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+
+    fn execute_json<T: DeserializeOwned>(
+        &mut self,
+        method: Method,
+        path: &str,
+        body: Option<&Value>,
+    ) -> Result<T, EsError> {
+        let body = match body {
+            Some(body) => Some(serde_json::to_vec(body)?),
+            None => None,
+        };
+
+        self.execute(method, path, "application/json", body.as_ref().map(|b| &b[..]))
+    }
+}
+
+impl SearchBackend for ElasticSearch {
+    fn search_json(
+        &mut self,
+        indexes: &[&str],
+        body: &Value,
+    ) -> Result<SearchResult<Value>, EsError> {
+        let path = format!("/{}/_search", indexes.join(","));
+        let body = ElasticSearch::search_body(body);
+        let response: Value = self.execute_json(Method::Post, &path, Some(&body))?;
+        SearchResult::from_response(response)
+    }
+
+    fn bulk_json(
+        &mut self,
+        index: &str,
+        doc_type: &str,
+        actions: Vec<Action<Value>>,
+    ) -> Result<BulkResult, EsError> {
+        let mut chunks = vec![];
+        let mut actions = actions.into_iter().peekable();
+        while actions.peek().is_some() {
+            chunks.push(actions.by_ref().take(self.bulk_chunk_size).collect::<Vec<_>>());
+        }
+
+        // the later actions on a document must not overtake the earlier ones
+        let concurrency = if ElasticSearch::share_ids(&chunks) {
+            1
+        } else {
+            self.bulk_concurrency
+        };
+
+        let mut bodies = vec![];
+        for chunk in chunks.into_iter() {
+            bodies.push(ElasticSearch::bulk_body(doc_type, chunk)?);
+        }
+
+        let path = format!("/{}/_bulk{}", index, ElasticSearch::refresh_query(self.refresh));
+        if bodies.len() <= 1 {
+            let body = bodies.pop().unwrap_or_default();
+            return self.execute(Method::Post, &path, "application/x-ndjson", Some(&body[..]));
+        }
+
+        // the items of the chunks are merged in the order of the actions
+        let mut result = BulkResult {
+            took: 0,
+            errors: false,
+            items: vec![],
+        };
+        for bodies in bodies.chunks(concurrency) {
+            let chunks: Vec<BulkResult> =
+                self.execute_concurrently(Method::Post, &path, "application/x-ndjson", bodies)?;
+
+            for chunk in chunks.into_iter() {
+                result.took += chunk.took;
+                result.errors |= chunk.errors;
+                result.items.extend(chunk.items);
+            }
+        }
+
+        Ok(result)
+    }
+
+    fn mget_json(
+        &mut self,
+        index: &str,
+        doc_type: &str,
+        ids: &[String],
+    ) -> Result<MgetResult<Value>, EsError> {
+        let path = format!("/{}/{}/_mget", index, doc_type);
+        self.execute_json(Method::Post, &path, Some(&json!({ "ids": ids })))
+    }
+
+    fn delete(&mut self, index: &str, doc_type: &str, id: &str) -> Result<DeleteResult, EsError> {
+        let refresh = ElasticSearch::refresh_query(self.refresh);
+        let id = ElasticSearch::path_segment(id);
+        let path = format!("/{}/{}/{}{}", index, doc_type, id, refresh);
+
+        // a missing document is answered with `404 Not Found`, along with the usual body
+        let response: Value = match self.execute_json(Method::Delete, &path, None) {
+            Err(EsError::EsServerError(ref message))
+                if message.starts_with("404") && message.contains(r#""result":"not_found""#) =>
+            {
+                let body = message.splitn(2, " - ").nth(1).unwrap_or("");
+                serde_json::from_str(body)?
+            }
+            response => response?,
+        };
+        DeleteResult::from_response(response)
+    }
+
+    fn delete_by_query(
+        &mut self,
+        indexes: &[&str],
+        query: &Value,
+    ) -> Result<DeleteByQueryResult, EsError> {
+        // the deletions by query can't wait for the refresh, but can trigger it
+        let refresh = match self.refresh {
+            Refresh::WaitFor => Refresh::Immediate,
+            refresh => refresh,
+        };
+        let path = format!(
+            "/{}/_delete_by_query{}",
+            indexes.join(","),
+            ElasticSearch::refresh_query(refresh)
+        );
+        self.execute_json(Method::Post, &path, Some(&json!({ "query": query })))
+    }
+
+    fn create_index(
+        &mut self,
+        index: &str,
+        mappings: &Value,
+        settings: &Settings,
+    ) -> Result<MappingResult, EsError> {
+        let body = json!({
+            "mappings": mappings,
+            "settings": settings,
+        });
+
+        self.execute_json(Method::Put, &format!("/{}", index), Some(&body))
+    }
+
+    fn delete_index(&mut self, index: &str) -> Result<MappingResult, EsError> {
+        self.execute_json(Method::Delete, &format!("/{}", index), None)
+    }
+
+    fn refresh(&mut self, indexes: &[&str]) -> Result<(), EsError> {
+        let path = format!("/{}/_refresh", indexes.join(","));
+        self.execute_json::<Value>(Method::Post, &path, None).map(|_| ())
+    }
+
+    fn set_refresh(&mut self, refresh: Refresh) {
+        self.refresh = refresh;
+    }
+
+    fn scroll_json(
+        &mut self,
+        indexes: &[&str],
+        body: &Value,
+        keep_alive: &str,
+    ) -> Result<SearchResult<Value>, EsError> {
+        let path = format!("/{}/_search?scroll={}", indexes.join(","), keep_alive);
+        let body = ElasticSearch::search_body(body);
+        let response: Value = self.execute_json(Method::Post, &path, Some(&body))?;
+        SearchResult::from_response(response)
+    }
+
+    fn next_scroll_json(
+        &mut self,
+        scroll_id: &str,
+        keep_alive: &str,
+    ) -> Result<SearchResult<Value>, EsError> {
+        let body = json!({ "scroll": keep_alive, "scroll_id": scroll_id });
+        let response: Value = self.execute_json(Method::Post, "/_search/scroll", Some(&body))?;
+        SearchResult::from_response(response)
+    }
+
+    fn clear_scroll(&mut self, scroll_id: &str) -> Result<(), EsError> {
+        let body = json!({ "scroll_id": [scroll_id] });
+        self.execute_json::<Value>(Method::Delete, "/_search/scroll", Some(&body)).map(|_| ())
+    }
+
+    fn engine_version(&mut self) -> Result<String, EsError> {
+        let info: Value = self.execute_json(Method::Get, "/", None)?;
+        let version = &info["version"];
+
+        // OpenSearch tells its name through `distribution`
+        Ok(format!(
+            "{} {}",
+            version["distribution"].as_str().unwrap_or("elasticsearch"),
+            version["number"].as_str().unwrap_or("unknown")
+        ))
+    }
+
+    fn index_health(&mut self, index: &str) -> Result<String, EsError> {
+        let path = format!("/_cluster/health/{}", index);
+        let health: Value = self.execute_json(Method::Get, &path, None)?;
+
+        Ok(health["status"].as_str().unwrap_or("unknown").to_owned())
+    }
+
+    fn switch_alias(&mut self, alias: &str, from: &str, to: &str) -> Result<(), EsError> {
+        let body = json!({
+            "actions": [
+                { "remove": { "index": from, "alias": alias } },
+                { "add":    { "index": to,   "alias": alias } },
+            ]
+        });
+        self.execute_json::<Value>(Method::Post, "/_aliases", Some(&body)).map(|_| ())
+    }
+
+    fn alias_targets(&mut self, alias: &str) -> Result<Vec<String>, EsError> {
+        let path = format!("/_alias/{}", alias);
+        match self.execute_json::<Value>(Method::Get, &path, None) {
+            Ok(indexes) => Ok(indexes
+                .as_object()
+                .map_or(vec![], |indexes| indexes.keys().cloned().collect())),
+            Err(EsError::EsServerError(ref error)) if error.starts_with("404") => Ok(vec![]),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// ElasticSearch 5.6 lacks the `remove_index` action of `_aliases` (added in 6.4),
+    /// so `index` is deleted first: the searches fail until the alias is added.
+    fn replace_with_alias(&mut self, index: &str, to: &str) -> Result<(), EsError> {
+        self.execute_json::<Value>(Method::Get, &format!("/{}", to), None)?;
+        self.delete_index(index)?;
+
+        let body = json!({
+            "actions": [
+                { "add": { "index": to, "alias": index } },
+            ]
+        });
+        self.execute_json::<Value>(Method::Post, "/_aliases", Some(&body)).map(|_| ())
+    }
+}
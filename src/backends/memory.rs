@@ -0,0 +1,977 @@
+//! A deterministic backend keeping the documents in memory, so that the
+//! queries built by the resources and the handlers can be tested without
+//! a running ElasticSearch. It's available in tests and with the `memory` feature.
+//!
+//! Documents are searched through `backends::dsl` and are lost on exit.
+
+use serde_json::Value;
+
+use backends::dsl::{self, Document};
+use backends::SearchBackend;
+use resource::{
+    Action, BulkItemResult, BulkResult, DeleteByQueryResult, DeleteResult, EsError, MappingResult,
+    MgetDocResult, MgetResult, SearchResult, Settings,
+};
+
+use std::collections::HashMap;
+
+/// Every index is a list of documents, kept in insertion order.
+/// `aliases` maps every alias to the index it points to, while `versions`
+/// keeps the external versions of the documents by index and id.
+#[derive(Debug, Default)]
+pub struct Memory {
+    indexes: HashMap<String, Vec<Document>>,
+    aliases: HashMap<String, String>,
+    versions: HashMap<(String, String), u64>,
+}
+
+impl Memory {
+    pub fn new() -> Memory {
+        Memory::default()
+    }
+
+    /// Point `alias` to `index`, as done once before the first `switch_alias`.
+    pub fn add_alias(&mut self, alias: &str, index: &str) {
+        self.aliases.insert(alias.to_owned(), index.to_owned());
+    }
+
+    /// Return the index `index` points to, if it's an alias, or `index` itself.
+    fn resolve(&self, index: &str) -> String {
+        self.aliases.get(index).map_or(index, |index| &**index).to_owned()
+    }
+
+    /// Return `index`, failing the same way ElasticSearch does if it's missing.
+    fn index_mut(&mut self, index: &str) -> Result<&mut Vec<Document>, EsError> {
+        let index = self.resolve(index);
+        self.indexes.get_mut(&index).ok_or_else(|| not_found(&index))
+    }
+}
+
+fn not_found(index: &str) -> EsError {
+    EsError::EsServerError(format!(
+        "404 Not Found - index_not_found_exception: no such index [{}]",
+        index
+    ))
+}
+
+impl SearchBackend for Memory {
+    fn search_json(
+        &mut self,
+        indexes: &[&str],
+        body: &Value,
+    ) -> Result<SearchResult<Value>, EsError> {
+        let mut documents = vec![];
+        for pattern in indexes.iter() {
+            if pattern.contains('*') {
+                let mut names: Vec<&String> = self
+                    .indexes
+                    .keys()
+                    .filter(|index| dsl::index_matches(pattern, index))
+                    .collect();
+                names.sort();
+
+                for index in names.into_iter() {
+                    documents.extend(self.indexes[index].iter().cloned());
+                }
+            } else {
+                documents.extend(self.index_mut(pattern)?.iter().cloned());
+            }
+        }
+
+        dsl::search(documents, body)
+    }
+
+    fn bulk_json(
+        &mut self,
+        index: &str,
+        doc_type: &str,
+        actions: Vec<Action<Value>>,
+    ) -> Result<BulkResult, EsError> {
+        // like ElasticSearch, missing indexes are created on the fly
+        let index = &*self.resolve(index);
+        let documents = self
+            .indexes
+            .entry(index.to_owned())
+            .or_insert_with(Vec::new);
+        let mut items = Vec::with_capacity(actions.len());
+        let mut errors = false;
+
+        for action in actions.into_iter() {
+            // documents without an id get the next free position
+            let id = action.id.unwrap_or_else(|| documents.len().to_string());
+            let position = documents.iter().position(|document| document.id == id);
+            let key = (index.to_owned(), id.to_owned());
+            let current = self.versions.get(&key).cloned();
+            let mut status = 200;
+            let mut error = None;
+
+            match (action.operation, position) {
+                // the external versions must be newer than the stored ones
+                _ if action.version.is_some() && current >= action.version => {
+                    let current = current.unwrap_or_default();
+                    status = 409;
+                    error = Some(dsl::stale_version(&id, action.version.unwrap(), current));
+                    errors = true;
+                }
+                ("delete", Some(position)) => {
+                    documents.remove(position);
+                }
+                ("delete", None) => status = 404,
+                ("update", Some(position)) => {
+                    let doc = action.source.map_or(Value::Null, |update| update["doc"].to_owned());
+                    dsl::merge(&mut documents[position].source, doc);
+
+                    // as ElasticSearch does, the updates bump the external versions
+                    if let Some(current) = current {
+                        self.versions.insert(key.to_owned(), current + 1);
+                    }
+                }
+                ("update", None) => {
+                    status = 404;
+                    error = Some(dsl::document_missing(doc_type, &id));
+                    errors = true;
+                }
+                ("create", Some(_)) => {
+                    status = 409;
+                    error = Some(dsl::version_conflict(&id));
+                    errors = true;
+                }
+                (_, position) => {
+                    let document = Document {
+                        index: index.to_owned(),
+                        doc_type: Some(doc_type.to_owned()),
+                        id: id.to_owned(),
+                        source: action.source.unwrap_or(Value::Null),
+                    };
+
+                    match position {
+                        Some(position) => documents[position] = document,
+                        None => {
+                            documents.push(document);
+                            status = 201;
+                        }
+                    }
+                }
+            }
+
+            if let (Some(version), true) = (action.version, status < 300) {
+                self.versions.insert(key, version);
+            }
+
+            let mut item = HashMap::new();
+            item.insert(
+                action.operation.to_owned(),
+                BulkItemResult {
+                    index: index.to_owned(),
+                    id: id,
+                    version: action.version,
+                    status: status,
+                    error: error,
+                },
+            );
+            items.push(item);
+        }
+
+        Ok(BulkResult {
+            took: 0,
+            errors: errors,
+            items: items,
+        })
+    }
+
+    fn mget_json(
+        &mut self,
+        index: &str,
+        _doc_type: &str,
+        ids: &[String],
+    ) -> Result<MgetResult<Value>, EsError> {
+        let index = &*self.resolve(index);
+        let documents = self.index_mut(index)?;
+        let docs = ids
+            .iter()
+            .map(|id| {
+                let document = documents.iter().find(|document| document.id == *id);
+
+                MgetDocResult {
+                    index: index.to_owned(),
+                    id: id.to_owned(),
+                    found: document.is_some(),
+                    source: document.map(|document| Box::new(document.source.to_owned())),
+                }
+            })
+            .collect();
+
+        Ok(MgetResult { docs: docs })
+    }
+
+    fn delete(&mut self, index: &str, _doc_type: &str, id: &str) -> Result<DeleteResult, EsError> {
+        let index = &*self.resolve(index);
+        let documents = self.index_mut(index)?;
+        let before = documents.len();
+        documents.retain(|document| document.id != id);
+
+        Ok(DeleteResult {
+            index: index.to_owned(),
+            id: id.to_owned(),
+            found: documents.len() < before,
+        })
+    }
+
+    fn delete_by_query(
+        &mut self,
+        indexes: &[&str],
+        query: &Value,
+    ) -> Result<DeleteByQueryResult, EsError> {
+        let mut deleted = 0;
+        for index in indexes.iter() {
+            let documents = self.index_mut(index)?;
+            let mut kept = Vec::with_capacity(documents.len());
+
+            for document in documents.drain(..) {
+                if dsl::score(query, &document.source)?.is_some() {
+                    deleted += 1;
+                } else {
+                    kept.push(document);
+                }
+            }
+
+            *documents = kept;
+        }
+
+        Ok(DeleteByQueryResult {
+            took: 0,
+            deleted: deleted,
+        })
+    }
+
+    fn create_index(
+        &mut self,
+        index: &str,
+        _mappings: &Value,
+        _settings: &Settings,
+    ) -> Result<MappingResult, EsError> {
+        if self.indexes.contains_key(index) {
+            return Err(EsError::EsServerError(format!(
+                "400 Bad Request - index_already_exists_exception: [{}]",
+                index
+            )));
+        }
+
+        self.indexes.insert(index.to_owned(), vec![]);
+        Ok(MappingResult { acknowledged: true })
+    }
+
+    fn delete_index(&mut self, index: &str) -> Result<MappingResult, EsError> {
+        self.indexes.remove(index).ok_or_else(|| not_found(index))?;
+        self.aliases.retain(|_, target| target != index);
+        self.versions.retain(|&(ref versioned, _), _| versioned != index);
+
+        Ok(MappingResult { acknowledged: true })
+    }
+
+    /// Documents are searchable as soon as they're indexed.
+    fn refresh(&mut self, indexes: &[&str]) -> Result<(), EsError> {
+        for index in indexes.iter() {
+            self.index_mut(index)?;
+        }
+
+        Ok(())
+    }
+
+    fn switch_alias(&mut self, alias: &str, from: &str, to: &str) -> Result<(), EsError> {
+        if !self.indexes.contains_key(to) {
+            return Err(not_found(to));
+        }
+        if self.aliases.get(alias).map(|index| &**index) != Some(from) {
+            return Err(EsError::EsServerError(format!(
+                "404 Not Found - aliases_not_found_exception: aliases [{}] missing",
+                alias
+            )));
+        }
+
+        self.aliases.insert(alias.to_owned(), to.to_owned());
+        Ok(())
+    }
+
+    fn alias_targets(&mut self, alias: &str) -> Result<Vec<String>, EsError> {
+        Ok(self.aliases.get(alias).cloned().into_iter().collect())
+    }
+
+    fn replace_with_alias(&mut self, index: &str, to: &str) -> Result<(), EsError> {
+        if !self.indexes.contains_key(to) {
+            return Err(not_found(to));
+        }
+
+        self.delete_index(index)?;
+        self.add_alias(index, to);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use backends::memory::Memory;
+    use backends::SearchBackend;
+    use events::{self, Change};
+    use resource::{Hydratable, IndexSet, Resource};
+    use resources::{Talent, TalentId, TalentSearchParams, TalentView};
+    use test_support;
+
+    use params::{Map, Value};
+    use serde_json;
+
+    const INDEX: &'static str = "memory_talents";
+
+    fn talent(id: u32, skills: &[&str], languages: &[&str]) -> Talent {
+        test_support::talent(
+            id,
+            json!({
+                "desired_work_roles":            ["Backend Developer"],
+                "desired_work_roles_experience": ["2..4"],
+                "work_locations":                ["Berlin"],
+                "current_location":              "Berlin",
+                "skills":                        skills,
+                "headline":                      "Developer",
+                "languages":                     languages,
+            }),
+        )
+    }
+
+    fn populated() -> Box<dyn SearchBackend> {
+        let mut memory: Box<dyn SearchBackend> = Box::new(Memory::new());
+        Talent::reset_index(&mut *memory, INDEX, None).unwrap();
+        Talent::index(
+            &mut *memory,
+            INDEX,
+            vec![
+                talent(1, &["Rust", "Go"], &["English"]),
+                talent(2, &["Java"], &["English", "German"]),
+                talent(3, &["Rust"], &["German"]),
+            ],
+        )
+        .unwrap();
+
+        memory
+    }
+
+    fn search(memory: &mut dyn SearchBackend, params: Vec<(&str, &str)>) -> Vec<TalentId> {
+        let mut map = Map::new();
+        map.assign("epoch", Value::String("2018-01-01T00:00:00Z".to_owned()))
+            .unwrap();
+        for (key, value) in params.into_iter() {
+            map.assign(key, Value::String(value.to_owned())).unwrap();
+        }
+
+        Talent::search(memory, INDEX, &map)
+            .talents
+            .into_iter()
+            .map(|result| result.talent.id())
+            .collect()
+    }
+
+    #[test]
+    fn test_index_set() {
+        let mut memory: Box<dyn SearchBackend> = Box::new(Memory::new());
+        let mut indexes = IndexSet::new("memory_routed");
+        indexes.routes.insert("Munich".to_owned(), "memory_routed_de".to_owned());
+        assert_eq!(indexes.to_string(), "memory_routed,memory_routed_de");
+        assert_eq!(indexes.route(Some("munich")), "memory_routed_de");
+        assert_eq!(indexes.route(Some("Paris")), "memory_routed");
+        assert_eq!(indexes.route(None), "memory_routed");
+
+        for index in indexes.writable() {
+            Talent::reset_index(&mut *memory, index, None).unwrap();
+        }
+
+        let mut munich = talent(2, &["Rust"], &["German"]);
+        munich.current_location = "Munich".to_owned();
+        let talents = vec![talent(1, &["Rust"], &["English"]), munich, talent(3, &["Go"], &[])];
+        let result = Talent::index_in(&mut *memory, &indexes, talents).unwrap();
+        assert_eq!(result.items.len(), 3);
+
+        let mut params = Map::new();
+        params.assign("epoch", Value::String("2018-01-01T00:00:00Z".to_owned()))
+            .unwrap();
+        let routed = Talent::search(&mut *memory, "memory_routed_de", &params);
+        assert_eq!(routed.total, 1);
+        assert_eq!(Talent::search_in(&mut *memory, &indexes, &params).unwrap().total, 3);
+
+        // the talents are looked for in the index they're routed to
+        let ids = vec![TalentId::from(3), TalentId::from(2), TalentId::from(1)];
+        let epoch = test_support::parse_query("epoch=2018-01-01T00:00:00Z");
+        let hydrated = Talent::hydrate(&mut *memory, &indexes, &ids, &epoch).unwrap();
+        let hydrated: Vec<TalentId> = hydrated.into_iter().map(|talent| talent.id).collect();
+        assert_eq!(hydrated, ids);
+
+        Talent::soft_delete_in(&mut *memory, &TalentId::from(2), &indexes).unwrap();
+        assert_eq!(Talent::deleted(&mut *memory, &indexes, 0, 10).unwrap().total, 1);
+        assert!(Talent::restore_in(&mut *memory, &indexes, &TalentId::from(2)).unwrap());
+        assert!(!Talent::restore_in(&mut *memory, &indexes, &TalentId::from(4)).unwrap());
+
+        let deleted = Talent::delete_in(&mut *memory, &TalentId::from(2), &indexes).unwrap();
+        assert!(deleted.found);
+        assert_eq!(deleted.index, "memory_routed_de");
+        let missing = Talent::delete_in(&mut *memory, &TalentId::from(2), &indexes).unwrap();
+        assert!(!missing.found);
+        assert_eq!(Talent::search_in(&mut *memory, &indexes, &params).unwrap().total, 2);
+    }
+
+    #[test]
+    fn test_relocated_talent() {
+        let mut memory: Box<dyn SearchBackend> = Box::new(Memory::new());
+        let mut indexes = IndexSet::new("memory_relocated");
+        indexes.routes.insert("Munich".to_owned(), "memory_relocated_de".to_owned());
+        for index in indexes.writable() {
+            Talent::reset_index(&mut *memory, index, None).unwrap();
+        }
+
+        let mut params = Map::new();
+        params.assign("epoch", Value::String("2018-01-01T00:00:00Z".to_owned()))
+            .unwrap();
+
+        let mut munich = talent(2, &["Rust"], &["German"]);
+        munich.current_location = "Munich".to_owned();
+        let talents = vec![talent(1, &["Rust"], &["English"]), munich];
+        Talent::index_in(&mut *memory, &indexes, talents).unwrap();
+        assert_eq!(Talent::search(&mut *memory, "memory_relocated_de", &params).total, 1);
+
+        // moving to Berlin, the talent leaves the routed index
+        let mut berlin = talent(2, &["Rust"], &["German"]);
+        berlin.current_location = "Berlin".to_owned();
+        let result = Talent::index_in(&mut *memory, &indexes, vec![berlin]).unwrap();
+        assert_eq!(result.items.len(), 1);
+        assert_eq!(Talent::search(&mut *memory, "memory_relocated_de", &params).total, 0);
+        assert_eq!(Talent::search(&mut *memory, "memory_relocated", &params).total, 2);
+        assert_eq!(Talent::search_in(&mut *memory, &indexes, &params).unwrap().total, 2);
+
+        // the copies left in more indexes are all deleted
+        Talent::index(&mut *memory, "memory_relocated_de", vec![talent(1, &["Rust"], &[])])
+            .unwrap();
+        let deleted = Talent::delete_in(&mut *memory, &TalentId::from(1), &indexes).unwrap();
+        assert!(deleted.found);
+        assert_eq!(deleted.index, "memory_relocated");
+        assert_eq!(Talent::search_in(&mut *memory, &indexes, &params).unwrap().total, 1);
+    }
+
+    #[test]
+    fn test_talent_search() {
+        let mut memory = populated();
+
+        // sorted by weight when no keywords are given
+        assert_eq!(search(&mut *memory, vec![]), vec![3, 2, 1]);
+        assert_eq!(
+            search(&mut *memory, vec![("languages[]", "German")]),
+            vec![3, 2]
+        );
+        assert_eq!(search(&mut *memory, vec![("keywords", "rust")]).len(), 2);
+        assert_eq!(
+            search(&mut *memory, vec![("ignored_talents", "1,3")]),
+            vec![2]
+        );
+    }
+
+    #[test]
+    fn test_excluded_talents_are_counted() {
+        let mut memory = populated();
+
+        let mut map = Map::new();
+        map.assign("epoch", Value::String("2018-01-01T00:00:00Z".to_owned()))
+            .unwrap();
+        map.assign("ignored_talents", Value::String("1,3".to_owned()))
+            .unwrap();
+        map.assign("contacted_talents", Value::String("3".to_owned()))
+            .unwrap();
+        let results = Talent::search(&mut *memory, INDEX, &map);
+
+        assert_eq!(results.total, 1);
+        assert_eq!(results.excluded_by_ignored, 2);
+        assert_eq!(results.excluded_by_contacted, 1);
+        assert_eq!(results.excluded_by_company, 0);
+    }
+
+    #[test]
+    fn test_salary_distribution() {
+        let mut memory: Box<dyn SearchBackend> = Box::new(Memory::new());
+        Talent::reset_index(&mut *memory, INDEX, None).unwrap();
+        let talents = vec![
+            (1, json!([{ "minimum": 40000, "currency": "EUR", "city": "Berlin" },
+                       { "minimum": 90000, "currency": "CHF", "city": "Zurich" }])),
+            (2, json!([{ "minimum": 55000, "currency": "EUR", "city": "Berlin" }])),
+            (3, json!([{ "minimum": 70000, "currency": "EUR", "city": "Berlin" }])),
+        ];
+        let talents = talents
+            .into_iter()
+            .map(|(id, salaries)| {
+                let mut talent = serde_json::to_value(talent(id, &["Rust"], &[])).unwrap();
+                talent["salary_expectations"] = salaries;
+                serde_json::from_value(talent).unwrap()
+            })
+            .collect();
+        Talent::index(&mut *memory, INDEX, talents).unwrap();
+
+        let mut map = Map::new();
+        map.assign("epoch", Value::String("2018-01-01T00:00:00Z".to_owned()))
+            .unwrap();
+        map.assign("ignored_talents", Value::String("3".to_owned()))
+            .unwrap();
+        let params = TalentSearchParams::from_map(&map).unwrap();
+        let indexes = IndexSet::new(INDEX);
+        let distribution =
+            Talent::salary_distribution(&mut *memory, &indexes, &params, 10000.0).unwrap();
+
+        // the ignored talent 3 is left out
+        assert_eq!(distribution.count, 3);
+        let berlin = &distribution.groups[0];
+        assert_eq!((&*berlin.city, &*berlin.currency, berlin.count), ("Berlin", "EUR", 2));
+        assert_eq!(berlin.min, Some(40000.0));
+        assert_eq!(berlin.max, Some(55000.0));
+        let histogram: Vec<(f64, u64)> = berlin
+            .histogram
+            .iter()
+            .map(|bucket| (bucket.key, bucket.doc_count))
+            .collect();
+        assert_eq!(histogram, vec![(40000.0, 1), (50000.0, 1)]);
+        assert_eq!(distribution.groups[1].city, "Zurich");
+
+        // from 40000 to 90000, one wide buckets are too many
+        let error =
+            Talent::salary_distribution(&mut *memory, &indexes, &params, 1.0).unwrap_err();
+        assert_eq!(error.status(), 422);
+    }
+
+    #[test]
+    fn test_distinct_counts() {
+        let mut memory = populated();
+
+        let counts = Talent::distinct_counts(&mut *memory, INDEX, 2).unwrap();
+        assert_eq!(counts.total, 3);
+        let fields: Vec<(&str, u64)> = counts
+            .fields
+            .iter()
+            .map(|field| (&*field.field, field.count))
+            .collect();
+        assert_eq!(
+            fields,
+            vec![("skills", 3), ("work_locations", 1), ("current_location", 1), ("languages", 2)]
+        );
+
+        let top: Vec<(&str, u64)> = counts.fields[0]
+            .top
+            .iter()
+            .map(|value| (&*value.key, value.doc_count))
+            .collect();
+        assert_eq!(top, vec![("Rust", 2), ("Go", 1)]);
+    }
+
+    #[test]
+    fn test_salary_sort() {
+        let mut memory: Box<dyn SearchBackend> = Box::new(Memory::new());
+        Talent::reset_index(&mut *memory, INDEX, None).unwrap();
+        let talents = vec![
+            (1, json!([{ "minimum": 60000, "currency": "EUR", "city": "Berlin" },
+                       { "minimum": 45000, "currency": "EUR", "city": "Leipzig" }])),
+            (2, json!([{ "minimum": 50000, "currency": "EUR", "city": "Berlin" }])),
+            (3, json!([])),
+            (4, json!([{ "minimum": 50000, "currency": "EUR", "city": "Berlin" }])),
+        ];
+        let talents = talents
+            .into_iter()
+            .map(|(id, salaries)| {
+                let mut talent = serde_json::to_value(talent(id, &["Rust"], &[])).unwrap();
+                talent["salary_expectations"] = salaries;
+                serde_json::from_value(talent).unwrap()
+            })
+            .collect();
+        Talent::index(&mut *memory, INDEX, talents).unwrap();
+
+        // the same salaries keep the order by weight, the talents expecting none come last
+        assert_eq!(search(&mut *memory, vec![("sort", "salary_asc")]), vec![1, 4, 2, 3]);
+        assert_eq!(search(&mut *memory, vec![("sort", "salary_desc")]), vec![4, 2, 1, 3]);
+    }
+
+    #[test]
+    fn test_sort_by() {
+        let mut memory = populated();
+
+        assert_eq!(search(&mut *memory, vec![("sort_by[]", "id:asc")]), vec![1, 2, 3]);
+        let by_batch = vec![("sort_by[]", "batch_starts_at:desc"), ("sort_by[]", "id:asc")];
+        assert_eq!(search(&mut *memory, by_batch), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_weight_decay() {
+        let mut memory: Box<dyn SearchBackend> = Box::new(Memory::new());
+        Talent::reset_index(&mut *memory, INDEX, None).unwrap();
+        let talents = vec![(1, "2018-01-01T00:00:00Z"), (3, "2017-12-18T00:00:00Z")];
+        let talents = talents
+            .into_iter()
+            .map(|(id, added_to_batch_at)| {
+                let mut talent = serde_json::to_value(talent(id, &["Rust"], &[])).unwrap();
+                talent["added_to_batch_at"] = json!(added_to_batch_at);
+                serde_json::from_value(talent).unwrap()
+            })
+            .collect();
+        Talent::index(&mut *memory, INDEX, talents).unwrap();
+
+        assert_eq!(search(&mut *memory, vec![]), vec![3, 1]);
+        // the heavier talent was added two weeks earlier
+        let decayed = vec![("features[]", "weight_decay")];
+        assert_eq!(search(&mut *memory, decayed), vec![1, 3]);
+        let slower = vec![("features[]", "weight_decay"), ("recency_scale", "60d")];
+        assert_eq!(search(&mut *memory, slower), vec![3, 1]);
+    }
+
+    #[test]
+    fn test_filtered_by_min_score() {
+        let mut memory = populated();
+
+        let mut map = Map::new();
+        map.assign("epoch", Value::String("2018-01-01T00:00:00Z".to_owned()))
+            .unwrap();
+        map.assign("keywords", Value::String("rust".to_owned()))
+            .unwrap();
+        assert_eq!(Talent::search(&mut *memory, INDEX, &map).filtered_by_min_score, None);
+
+        // every talent matching the keywords scores high enough here
+        map.assign("track_min_score", Value::String("true".to_owned()))
+            .unwrap();
+        assert_eq!(Talent::search(&mut *memory, INDEX, &map).filtered_by_min_score, Some(0));
+    }
+
+    #[test]
+    fn test_timeout() {
+        let mut memory = populated();
+
+        let mut map = Map::new();
+        map.assign("timeout_ms", Value::String("250".to_owned()))
+            .unwrap();
+        map.assign("debug_es_query", Value::String("true".to_owned()))
+            .unwrap();
+        let results = Talent::search(&mut *memory, INDEX, &map);
+        assert!(results.raw_es_query.unwrap().contains("\"timeout\":\"250ms\""));
+        assert!(!results.timed_out);
+        assert_eq!(results.talents.len(), 3);
+    }
+
+    #[test]
+    fn test_raw_query() {
+        let mut memory = populated();
+
+        let body = json!({ "query": { "term": { "skills": "Rust" } }, "size": 1 });
+        let results = Talent::raw_query(&mut *memory, &IndexSet::new(INDEX), &body).unwrap();
+
+        assert_eq!(results.total, 2);
+        assert_eq!(results.hits.len(), 1);
+
+        // only the fields returned by the searches are given
+        let source = results.hits[0].source.as_ref().unwrap();
+        assert!(source.get("headline").is_some());
+        assert!(source.get("skills").is_none());
+    }
+
+    #[test]
+    fn test_search_across_indexes() {
+        let mut memory = populated();
+        let index = "memory_talents_nl";
+        Talent::index(&mut *memory, index, vec![talent(1, &["Rust"], &["Dutch"])]).unwrap();
+
+        let mut map = Map::new();
+        map.assign("epoch", Value::String("2018-01-01T00:00:00Z".to_owned()))
+            .unwrap();
+        map.assign("index", Value::String("memory_talents*".to_owned()))
+            .unwrap();
+        let results = Talent::search(&mut *memory, INDEX, &map);
+
+        // talent 1 is returned and counted once
+        let ids: Vec<_> = results.talents.iter().map(|result| result.talent.id()).collect();
+        assert_eq!(ids, vec![3, 2, 1]);
+        assert_eq!(results.total, 3);
+        assert_eq!(results.hits_per_index[INDEX], 3);
+        assert_eq!(results.hits_per_index[index], 1);
+
+        // nor does it take a place in the following pages
+        map.assign("offset", Value::String("3".to_owned())).unwrap();
+        let results = Talent::search(&mut *memory, INDEX, &map);
+        assert!(results.talents.is_empty());
+        assert_eq!(results.total, 3);
+    }
+
+    #[test]
+    fn test_export() {
+        let mut memory = populated();
+
+        let mut map = Map::new();
+        map.assign("epoch", Value::String("2018-01-01T00:00:00Z".to_owned()))
+            .unwrap();
+        map.assign("ignored_talents", Value::String("2".to_owned()))
+            .unwrap();
+        let params = TalentSearchParams::from_map(&map).unwrap();
+        let body = Talent::export_body(&params, "2018-01-01T00:00:00Z", 10);
+
+        // every talent comes in a single batch, without a scroll to follow
+        let result = memory.scroll_json(&[INDEX], &body, "1m").unwrap();
+        assert!(result.scroll_id.is_none());
+        assert!(memory.next_scroll_json("scroll", "1m").is_err());
+
+        let ids: Vec<TalentId> = Talent::results_of(result, &params)
+            .unwrap()
+            .into_iter()
+            .map(|result| result.talent.id())
+            .collect();
+        assert_eq!(ids, vec![3, 1]);
+    }
+
+    #[test]
+    fn test_switch_alias() {
+        let mut memory = Memory::new();
+        Talent::index(&mut memory, "talents_blue", vec![talent(1, &["Rust"], &[])]).unwrap();
+        Talent::index(&mut memory, "talents_green", vec![talent(2, &["Go"], &[])]).unwrap();
+        memory.add_alias("talents", "talents_blue");
+
+        let mut memory: Box<dyn SearchBackend> = Box::new(memory);
+        let mut map = Map::new();
+        map.assign("epoch", Value::String("2018-01-01T00:00:00Z".to_owned()))
+            .unwrap();
+        let ids = |memory: &mut dyn SearchBackend| {
+            Talent::search(memory, "talents", &map)
+                .talents
+                .into_iter()
+                .map(|result| result.talent.id())
+                .collect::<Vec<TalentId>>()
+        };
+        assert_eq!(ids(&mut *memory), vec![1]);
+
+        memory.switch_alias("talents", "talents_blue", "talents_green").unwrap();
+        assert_eq!(ids(&mut *memory), vec![2]);
+
+        // the alias doesn't point to the blue index anymore
+        assert!(memory.switch_alias("talents", "talents_blue", "talents_green").is_err());
+        assert!(memory.switch_alias("talents", "talents_green", "talents_red").is_err());
+    }
+
+    #[test]
+    fn test_create() {
+        let mut memory = populated();
+
+        let result = Talent::create(
+            &mut *memory,
+            INDEX,
+            vec![talent(2, &["Go"], &[]), talent(4, &["Go"], &["English"])],
+        )
+        .unwrap();
+        assert!(result.errors);
+
+        let statuses: Vec<u16> = result
+            .items
+            .iter()
+            .flat_map(|item| item.values())
+            .map(|item| item.status)
+            .collect();
+        assert_eq!(statuses, vec![409, 201]);
+
+        // talent 2 is left as it was
+        assert_eq!(search(&mut *memory, vec![("keywords", "java")]), vec![2]);
+        assert_eq!(search(&mut *memory, vec![("keywords", "go")]).len(), 2);
+    }
+
+    #[test]
+    fn test_external_version() {
+        let mut memory = populated();
+        let versioned = |id: u32, skills: &[&str], version: u64| {
+            let mut talent = talent(id, skills, &[]);
+            talent.external_version = Some(version);
+            talent
+        };
+
+        let result = Talent::index(&mut *memory, INDEX, vec![versioned(4, &["Go"], 2)]).unwrap();
+        assert!(!result.errors);
+
+        // a replay of an older message doesn't clobber the newer profile
+        let result = Talent::index(&mut *memory, INDEX, vec![versioned(4, &["Cobol"], 1)]).unwrap();
+        assert!(result.errors);
+        assert!(search(&mut *memory, vec![("keywords", "cobol")]).is_empty());
+
+        let result = Talent::index(&mut *memory, INDEX, vec![versioned(4, &["Cobol"], 3)]).unwrap();
+        assert!(!result.errors);
+        assert_eq!(search(&mut *memory, vec![("keywords", "cobol")]), vec![4]);
+
+        // the soft deletion bumps the version to 4, which the profile must exceed
+        assert!(Talent::soft_delete(&mut *memory, &TalentId::from(4), INDEX).unwrap().found);
+        let result = Talent::index(&mut *memory, INDEX, vec![versioned(4, &["Go"], 4)]).unwrap();
+        assert!(result.errors);
+        let result = Talent::index(&mut *memory, INDEX, vec![versioned(4, &["Go"], 5)]).unwrap();
+        assert!(!result.errors);
+
+        // the creations can't be externally versioned
+        let result = Talent::create(&mut *memory, INDEX, vec![versioned(5, &["Go"], 1)]).unwrap();
+        assert!(!result.errors);
+        assert_eq!(result.items[0]["create"].version, None);
+    }
+
+    #[test]
+    fn test_delete() {
+        let mut memory = populated();
+
+        assert!(Talent::delete(&mut *memory, &TalentId::from(2), INDEX).unwrap().found);
+        assert!(!Talent::delete(&mut *memory, &TalentId::from(2), INDEX).unwrap().found);
+        assert_eq!(search(&mut *memory, vec![]), vec![3, 1]);
+    }
+
+    #[test]
+    fn test_change_events() {
+        let index = "memory_talents_events";
+        let mut memory: Box<dyn SearchBackend> = Box::new(Memory::new());
+        Talent::reset_index(&mut *memory, index, None).unwrap();
+
+        let changes = events::subscribe();
+        Talent::index(&mut *memory, index, vec![talent(1, &["Rust"], &["English"])]).unwrap();
+        Talent::delete(&mut *memory, &TalentId::from(1), index).unwrap();
+        Talent::delete(&mut *memory, &TalentId::from(1), index).unwrap();
+
+        let changes: Vec<Change> = changes.try_iter().filter(|c| c.index == index).collect();
+        assert_eq!(changes, vec![
+            Change::indexed(index, vec!["1".to_owned()]),
+            Change::deleted(index, vec!["1".to_owned()]),
+        ]);
+    }
+
+    #[test]
+    fn test_soft_delete() {
+        let mut memory = populated();
+
+        assert!(Talent::soft_delete(&mut *memory, &TalentId::from(2), INDEX).unwrap().found);
+        assert!(!Talent::soft_delete(&mut *memory, &TalentId::from(4), INDEX).unwrap().found);
+        assert_eq!(search(&mut *memory, vec![]), vec![3, 1]);
+
+        // the document is still there, hidden from the hydration as well
+        let indexes = IndexSet::new(INDEX);
+        assert_eq!(Talent::deleted(&mut *memory, &indexes, 0, 10).unwrap().total, 1);
+        let epoch = test_support::parse_query("epoch=2018-01-01T00:00:00Z");
+        let hydrated = Talent::hydrate(&mut *memory, &indexes, &[TalentId::from(2)], &epoch);
+        assert!(hydrated.unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_restore() {
+        let mut memory = populated();
+
+        let indexes = IndexSet::new(INDEX);
+        Talent::soft_delete(&mut *memory, &TalentId::from(2), INDEX).unwrap();
+        let deleted = Talent::deleted(&mut *memory, &indexes, 0, 10).unwrap();
+        assert_eq!(deleted.total, 1);
+        assert_eq!(deleted.talents[0].id, 2);
+
+        assert!(Talent::restore(&mut *memory, INDEX, &TalentId::from(2)).unwrap());
+        assert!(!Talent::restore(&mut *memory, INDEX, &TalentId::from(4)).unwrap());
+        assert_eq!(search(&mut *memory, vec![]), vec![3, 2, 1]);
+        assert_eq!(Talent::deleted(&mut *memory, &indexes, 0, 10).unwrap().total, 0);
+    }
+
+    #[test]
+    fn test_contact_status() {
+        let mut memory = populated();
+
+        let mut map = Map::new();
+        map.assign("epoch", Value::String("2018-01-01T00:00:00Z".to_owned()))
+            .unwrap();
+        let results = Talent::search(&mut *memory, INDEX, &map);
+        assert!(results.talents[0].talent.found().unwrap().already_contacted.is_none());
+
+        map.assign("company_id", Value::String("7".to_owned())).unwrap();
+        map.assign("presented_talents", Value::String("2".to_owned())).unwrap();
+        let results = Talent::search(&mut *memory, INDEX, &map);
+        let statuses: Vec<(TalentId, Option<bool>, Option<bool>)> = results
+            .talents
+            .iter()
+            .map(|result| {
+                let talent = result.talent.found().unwrap();
+                (talent.id.to_owned(), talent.already_contacted, talent.is_presented)
+            })
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                (TalentId::from(3), Some(false), Some(false)),
+                (TalentId::from(2), Some(false), Some(true)),
+                (TalentId::from(1), Some(false), Some(false)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_full_view() {
+        let mut memory = populated();
+
+        let mut map = Map::new();
+        map.assign("epoch", Value::String("2018-01-01T00:00:00Z".to_owned()))
+            .unwrap();
+        map.assign("view", Value::String("full".to_owned())).unwrap();
+        let results = Talent::search(&mut *memory, INDEX, &map);
+
+        match results.talents[0].talent {
+            TalentView::Full(ref talent) => assert_eq!(talent.skills, vec!["Rust".to_owned()]),
+            ref talent => panic!("Expected a full talent, got {:?}", talent),
+        }
+    }
+
+    #[test]
+    fn test_hydrate() {
+        let mut memory = populated();
+
+        let mut rejected = talent(5, &["Rust"], &["English"]);
+        rejected.accepted = false;
+        Talent::index(&mut *memory, INDEX, vec![rejected]).unwrap();
+
+        let indexes = IndexSet::new(INDEX);
+        let ids = vec![TalentId::from(3), TalentId::from(4), TalentId::from(5), TalentId::from(1)];
+        let epoch = test_support::parse_query("epoch=2018-01-01T00:00:00Z");
+        let talents = Talent::hydrate(&mut *memory, &indexes, &ids, &epoch).unwrap();
+        let found: Vec<_> = talents.iter().map(|talent| talent.id.to_owned()).collect();
+        assert_eq!(found, vec![3, 1]);
+
+        // the batch of the talents is over by now
+        assert!(Talent::hydrate(&mut *memory, &indexes, &ids, &Map::new()).unwrap().is_empty());
+
+        // unless they're presented
+        let presented = test_support::parse_query("presented_talents=3");
+        let talents = Talent::hydrate(&mut *memory, &indexes, &ids, &presented).unwrap();
+        assert_eq!(talents.len(), 1);
+        assert_eq!(talents[0].id, 3);
+    }
+
+    #[test]
+    fn test_uuid_ids() {
+        let mut memory = populated();
+
+        let uuid: TalentId = "0b6c3bb4-5c2a-4f5a-9a3e-3fdc1c9d4e21".parse().unwrap();
+        let mut with_uuid = talent(4, &["Rust"], &["English"]);
+        with_uuid.id = uuid.to_owned();
+        Talent::index(&mut *memory, INDEX, vec![with_uuid]).unwrap();
+
+        let found = search(&mut *memory, vec![]);
+        assert_eq!(found[0], uuid);
+        assert_eq!(found.len(), 4);
+
+        let ignored = format!("1,{}", uuid);
+        assert_eq!(search(&mut *memory, vec![("ignored_talents", &ignored)]), vec![3, 2]);
+
+        let indexes = IndexSet::new(INDEX);
+        let epoch = test_support::parse_query("epoch=2018-01-01T00:00:00Z");
+        let hydrated = Talent::hydrate(&mut *memory, &indexes, &[uuid.to_owned()], &epoch).unwrap();
+        assert_eq!(hydrated[0].id, uuid);
+
+        assert!(Talent::delete(&mut *memory, &uuid, INDEX).unwrap().found);
+        assert_eq!(search(&mut *memory, vec![]), vec![3, 2, 1]);
+    }
+
+    #[test]
+    fn test_delete_many() {
+        let mut memory = populated();
+
+        let ids = vec![TalentId::from(1), TalentId::from(3), TalentId::from(4)];
+        let result = Talent::delete_many(&mut *memory, &ids, INDEX).unwrap();
+        assert_eq!(result.items.len(), 3);
+        assert_eq!(result.items[2]["delete"].status, 404);
+        assert_eq!(search(&mut *memory, vec![]), vec![2]);
+    }
+}
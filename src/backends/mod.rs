@@ -0,0 +1,312 @@
+//! The search engines Searchspot can talk to.
+//!
+//! Resources and handlers only deal with a `SearchBackend`, whose requests and
+//! responses follow the ElasticSearch ones: the backend used is chosen through
+//! the `backend` key of the `[es]` configuration section.
+
+use serde::de::DeserializeOwned;
+use serde::ser::Serialize;
+use serde_json::Value;
+
+use config::ES;
+use metrics;
+use resource::{
+    Action, BulkResult, DeleteByQueryResult, DeleteResult, EsError, MappingResult, MgetResult,
+    SearchResult, Settings,
+};
+
+pub mod dsl;
+pub mod elasticsearch;
+#[cfg(any(test, feature = "memory"))]
+pub mod memory;
+#[cfg(feature = "tantivy")]
+pub mod tantivy;
+
+pub use self::elasticsearch::ElasticSearch;
+#[cfg(any(test, feature = "memory"))]
+pub use self::memory::Memory;
+#[cfg(feature = "tantivy")]
+pub use self::tantivy::Tantivy;
+
+use std::str::FromStr;
+
+/// When the written documents become visible to the searches,
+/// as told by ElasticSearch's `refresh` parameter of the writes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Refresh {
+    /// At the next periodic refresh (`false`), which is the default.
+    Lazy,
+    /// Right away, refreshing the shards written to (`true`).
+    Immediate,
+    /// At the next periodic refresh, which the write waits for (`wait_for`).
+    WaitFor,
+}
+
+impl Refresh {
+    pub fn as_param(&self) -> &'static str {
+        match *self {
+            Refresh::Lazy => "false",
+            Refresh::Immediate => "true",
+            Refresh::WaitFor => "wait_for",
+        }
+    }
+}
+
+impl Default for Refresh {
+    fn default() -> Refresh {
+        Refresh::Lazy
+    }
+}
+
+/// An empty value means `true`, as in ElasticSearch.
+impl FromStr for Refresh {
+    type Err = String;
+
+    fn from_str(refresh: &str) -> Result<Refresh, String> {
+        match refresh {
+            "false" => Ok(Refresh::Lazy),
+            "" | "true" => Ok(Refresh::Immediate),
+            "wait_for" => Ok(Refresh::WaitFor),
+            _ => Err(format!("Invalid refresh: {} (true, wait_for or false)", refresh)),
+        }
+    }
+}
+
+/// A search engine able to store and search the documents of the resources.
+///
+/// Queries, mappings and settings are given in the ElasticSearch DSL:
+/// backends that don't speak it natively are expected to translate them.
+pub trait SearchBackend: Send {
+    /// Run the search described by `body` on `indexes`.
+    fn search_json(
+        &mut self,
+        indexes: &[&str],
+        body: &Value,
+    ) -> Result<SearchResult<Value>, EsError>;
+
+    /// Apply `actions` to `index` as a single bulk request.
+    fn bulk_json(
+        &mut self,
+        index: &str,
+        doc_type: &str,
+        actions: Vec<Action<Value>>,
+    ) -> Result<BulkResult, EsError>;
+
+    /// Fetch the documents of type `doc_type` identified by `ids`, in the same order.
+    fn mget_json(
+        &mut self,
+        index: &str,
+        doc_type: &str,
+        ids: &[String],
+    ) -> Result<MgetResult<Value>, EsError>;
+
+    /// Delete the document of type `doc_type` identified by `id`.
+    fn delete(&mut self, index: &str, doc_type: &str, id: &str) -> Result<DeleteResult, EsError>;
+
+    /// Delete the documents of `indexes` matching `query`.
+    fn delete_by_query(
+        &mut self,
+        indexes: &[&str],
+        query: &Value,
+    ) -> Result<DeleteByQueryResult, EsError>;
+
+    /// Create `index` with given mappings and settings.
+    fn create_index(
+        &mut self,
+        index: &str,
+        mappings: &Value,
+        settings: &Settings,
+    ) -> Result<MappingResult, EsError>;
+
+    /// Delete `index` and all its documents.
+    fn delete_index(&mut self, index: &str) -> Result<MappingResult, EsError>;
+
+    /// Make the changes done to `indexes` visible to the searches.
+    fn refresh(&mut self, indexes: &[&str]) -> Result<(), EsError>;
+
+    /// Make the writes that follow visible to the searches as told by `refresh`, until
+    /// it's set again. Backends whose writes are searchable right away ignore it.
+    fn set_refresh(&mut self, _refresh: Refresh) {}
+
+    /// Like `search_json`, keeping the context of the search alive for `keep_alive`
+    /// (i.e. `1m`) so that the following batches can be read through `next_scroll_json`.
+    /// Backends without search contexts return every batch at once, without `scroll_id`.
+    fn scroll_json(
+        &mut self,
+        indexes: &[&str],
+        body: &Value,
+        _keep_alive: &str,
+    ) -> Result<SearchResult<Value>, EsError> {
+        // with no batch to follow, the first one has to be as large as the whole result
+        let mut body = body.clone();
+        body["size"] = json!(0);
+        body["track_total_hits"] = json!(true);
+        let total = self.search_json(indexes, &body)?.hits.total;
+
+        body["size"] = json!(total);
+        self.search_json(indexes, &body)
+    }
+
+    /// Return the batch following the one that returned `scroll_id`.
+    fn next_scroll_json(
+        &mut self,
+        _scroll_id: &str,
+        _keep_alive: &str,
+    ) -> Result<SearchResult<Value>, EsError> {
+        Err(EsError::Unsupported("Scrolling isn't supported by this backend".to_owned()))
+    }
+
+    /// Release the context of a scroll before it expires.
+    fn clear_scroll(&mut self, _scroll_id: &str) -> Result<(), EsError> {
+        Ok(())
+    }
+
+    /// Return the name and the version of the search engine (i.e. `elasticsearch 7.10.2`).
+    fn engine_version(&mut self) -> Result<String, EsError> {
+        Err(EsError::Unsupported("The version can't be told by this backend".to_owned()))
+    }
+
+    /// Return the health of `index`: `green`, `yellow` or `red`.
+    fn index_health(&mut self, _index: &str) -> Result<String, EsError> {
+        Err(EsError::Unsupported("The health can't be told by this backend".to_owned()))
+    }
+
+    /// Point `alias` to `to` instead of `from` in a single atomic step,
+    /// so that no search sees both indexes or neither of them.
+    fn switch_alias(&mut self, _alias: &str, _from: &str, _to: &str) -> Result<(), EsError> {
+        Err(EsError::Unsupported("Aliases aren't supported by this backend".to_owned()))
+    }
+
+    /// Return the indexes `alias` points to: none if it's an index itself, or if it doesn't exist.
+    fn alias_targets(&mut self, _alias: &str) -> Result<Vec<String>, EsError> {
+        Err(EsError::Unsupported("Aliases aren't supported by this backend".to_owned()))
+    }
+
+    /// Replace the index `index` with an alias of the same name pointing to `to`,
+    /// deleting `index` and then adding the alias.
+    fn replace_with_alias(&mut self, _index: &str, _to: &str) -> Result<(), EsError> {
+        Err(EsError::Unsupported("Aliases aren't supported by this backend".to_owned()))
+    }
+}
+
+impl<'a> dyn SearchBackend + 'a {
+    /// Like `search_json`, deserializing the found documents into `T`.
+    pub fn search<T: DeserializeOwned>(
+        &mut self,
+        indexes: &[&str],
+        body: &Value,
+    ) -> Result<SearchResult<T>, EsError> {
+        self.search_json(indexes, body)?.into_typed()
+    }
+
+    /// Like `mget_json`, deserializing the found documents into `T`.
+    pub fn mget<T: DeserializeOwned>(
+        &mut self,
+        index: &str,
+        doc_type: &str,
+        ids: &[String],
+    ) -> Result<MgetResult<T>, EsError> {
+        self.mget_json(index, doc_type, ids)?.into_typed()
+    }
+
+    /// Like `bulk_json`, serializing the sources of `actions` first. The documents
+    /// conflicting with the mapping are logged and counted by field in
+    /// `searchspot_mapping_conflicts_total`, wherever they come from.
+    pub fn bulk<T: Serialize>(
+        &mut self,
+        index: &str,
+        doc_type: &str,
+        actions: Vec<Action<T>>,
+    ) -> Result<BulkResult, EsError> {
+        let mut json_actions = Vec::with_capacity(actions.len());
+        for action in actions.into_iter() {
+            json_actions.push(action.into_json()?);
+        }
+
+        let result = self.bulk_json(index, doc_type, json_actions)?;
+        for conflict in result.mapping_conflicts() {
+            warn!(
+                "The {} {} doesn't match the mapping of {}: {}",
+                doc_type, conflict.id, index, conflict.reason
+            );
+            let field = conflict.field.as_ref().map_or("unknown", |field| &**field);
+            metrics::increment("searchspot_mapping_conflicts_total", doc_type, field);
+        }
+
+        Ok(result)
+    }
+}
+
+/// Return the backend selected by `config.backend`.
+/// `opensearch` is an alias of `elasticsearch`, as they share the same API,
+/// while `tantivy` and `memory` need Searchspot to be built with the feature
+/// of the same name.
+pub fn from_config(config: &ES) -> Result<Box<dyn SearchBackend>, EsError> {
+    match &*config.backend {
+        "elasticsearch" | "opensearch" => Ok(Box::new(ElasticSearch::from_config(config)?)),
+        #[cfg(feature = "tantivy")]
+        "tantivy" => Ok(Box::new(Tantivy::new(&*config.url)?)),
+        #[cfg(not(feature = "tantivy"))]
+        "tantivy" => Err(EsError::Unsupported(
+            "Searchspot was built without the tantivy feature".to_owned(),
+        )),
+        #[cfg(any(test, feature = "memory"))]
+        "memory" => Ok(Box::new(Memory::new())),
+        #[cfg(not(any(test, feature = "memory")))]
+        "memory" => Err(EsError::Unsupported(
+            "Searchspot was built without the memory feature".to_owned(),
+        )),
+        backend => Err(EsError::Unsupported(format!(
+            "Unknown search backend: {}",
+            backend
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use backends::{self, Refresh};
+    use config::ES;
+
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_refresh() {
+        assert_eq!("true".parse(), Ok(Refresh::Immediate));
+        assert_eq!("".parse(), Ok(Refresh::Immediate));
+        assert_eq!("wait_for".parse(), Ok(Refresh::WaitFor));
+        assert_eq!("false".parse(), Ok(Refresh::Lazy));
+        assert!("later".parse::<Refresh>().is_err());
+        assert_eq!(Refresh::default().as_param(), "false");
+    }
+
+    #[test]
+    fn test_from_config() {
+        let mut config = ES {
+            backend: "opensearch".to_owned(),
+            url: "http://localhost:9200".to_owned(),
+            urls: vec![],
+            index: "searchspot_test".to_owned(),
+            searchable_indexes: vec![],
+            routes: HashMap::new(),
+            max_result_window: 10000,
+            track_total_hits: true,
+            strict_payloads: false,
+            bulk_chunk_size: 1000,
+            bulk_concurrency: 4,
+            ascii_folding: true,
+            languages: vec![],
+            protected_keywords: None,
+            mapping_file: None,
+            username: None,
+            password: None,
+            api_key_id: None,
+            api_key: None,
+            ca_certificate: None,
+        };
+        assert!(backends::from_config(&config).is_ok());
+
+        config.backend = "solr".to_owned();
+        assert!(backends::from_config(&config).is_err());
+    }
+}
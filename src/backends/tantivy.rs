@@ -0,0 +1,449 @@
+//! An embedded backend storing every index as a Tantivy index on disk,
+//! meant for local development: no ElasticSearch instance is needed.
+//!
+//! Documents are stored as JSON and searched through `backends::dsl`,
+//! so mappings and analysis settings are accepted but ignored.
+
+use serde_json::{self, Value};
+
+use tantivy::collector::{Count, DocSetCollector};
+use tantivy::query::{AllQuery, TermQuery};
+use tantivy::schema::{Field, IndexRecordOption, Schema, STORED, STRING};
+use tantivy::{Document as TantivyDocument, Index, IndexWriter, Term};
+
+use backends::dsl::{self, Document};
+use backends::SearchBackend;
+use resource::{
+    Action, BulkItemResult, BulkResult, DeleteByQueryResult, DeleteResult, EsError, MappingResult,
+    MgetDocResult, MgetResult, SearchResult, Settings,
+};
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+/// The memory given to the writer of an index, in bytes.
+const WRITER_HEAP_SIZE: usize = 50_000_000;
+
+/// Stores the indexes in the directory `path`, one sub-directory per index.
+pub struct Tantivy {
+    path: PathBuf,
+}
+
+/// The fields every document is stored with.
+struct Fields {
+    id: Field,
+    doc_type: Field,
+    source: Field,
+}
+
+impl Fields {
+    fn schema() -> Schema {
+        let mut schema = Schema::builder();
+        schema.add_text_field("_id", STRING | STORED);
+        schema.add_text_field("_type", STRING | STORED);
+        schema.add_text_field("_source", STORED);
+        schema.build()
+    }
+
+    fn of(index: &Index) -> Result<Fields, EsError> {
+        let schema = index.schema();
+        let field = |name: &str| schema.get_field(name).ok_or_else(|| corrupted(name));
+
+        Ok(Fields {
+            id: field("_id")?,
+            doc_type: field("_type")?,
+            source: field("_source")?,
+        })
+    }
+}
+
+impl Tantivy {
+    /// `url` is the directory where the indexes are stored,
+    /// with or without the `file://` scheme.
+    pub fn new(url: &str) -> Result<Tantivy, EsError> {
+        let path = PathBuf::from(url.trim_start_matches("file://"));
+        fs::create_dir_all(&path)?;
+
+        Ok(Tantivy { path: path })
+    }
+
+    fn index_path(&self, index: &str) -> PathBuf {
+        self.path.join(index)
+    }
+
+    /// Open `index`, failing the same way ElasticSearch does if it's missing.
+    fn open(&self, index: &str) -> Result<Index, EsError> {
+        let path = self.index_path(index);
+        if !path.is_dir() {
+            return Err(EsError::EsServerError(format!(
+                "404 Not Found - index_not_found_exception: no such index [{}]",
+                index
+            )));
+        }
+
+        Index::open_in_dir(&path).map_err(from_tantivy)
+    }
+
+    /// Return the name of the stored indexes matching `pattern`.
+    fn matching_indexes(&self, pattern: &str) -> Result<Vec<String>, EsError> {
+        let mut indexes = vec![];
+        for entry in fs::read_dir(&self.path)? {
+            let entry = entry?;
+            if !entry.path().is_dir() {
+                continue;
+            }
+
+            if let Some(name) = entry.file_name().to_str() {
+                if dsl::index_matches(pattern, name) {
+                    indexes.push(name.to_owned());
+                }
+            }
+        }
+
+        indexes.sort();
+        Ok(indexes)
+    }
+
+    fn writer(index: &Index) -> Result<IndexWriter, EsError> {
+        index
+            .writer_with_num_threads(1, WRITER_HEAP_SIZE)
+            .map_err(from_tantivy)
+    }
+
+    fn documents(&self, index: &str) -> Result<Vec<Document>, EsError> {
+        let tantivy_index = self.open(index)?;
+        let fields = Fields::of(&tantivy_index)?;
+        let searcher = tantivy_index.reader().map_err(from_tantivy)?.searcher();
+
+        let addresses = searcher
+            .search(&AllQuery, &DocSetCollector)
+            .map_err(from_tantivy)?;
+
+        let mut documents = Vec::with_capacity(addresses.len());
+        for address in addresses.into_iter() {
+            let document = searcher.doc(address).map_err(from_tantivy)?;
+            let source = text(&document, fields.source)?;
+
+            documents.push(Document {
+                index: index.to_owned(),
+                doc_type: None,
+                id: text(&document, fields.id)?.to_owned(),
+                source: serde_json::from_str(source)?,
+            });
+        }
+
+        // keep the results stable across searches
+        documents.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(documents)
+    }
+
+    fn exists(index: &Index, fields: &Fields, id: &str) -> Result<bool, EsError> {
+        let query = TermQuery::new(
+            Term::from_field_text(fields.id, id),
+            IndexRecordOption::Basic,
+        );
+        let searcher = index.reader().map_err(from_tantivy)?.searcher();
+        let count = searcher.search(&query, &Count).map_err(from_tantivy)?;
+
+        Ok(count > 0)
+    }
+}
+
+fn from_tantivy(error: ::tantivy::TantivyError) -> EsError {
+    EsError::EsServerError(error.to_string())
+}
+
+fn corrupted(field: &str) -> EsError {
+    EsError::EsServerError(format!("The index has no {} field", field))
+}
+
+fn text(document: &TantivyDocument, field: Field) -> Result<&str, EsError> {
+    document
+        .get_first(field)
+        .and_then(|value| value.as_text())
+        .ok_or_else(|| corrupted("stored"))
+}
+
+impl SearchBackend for Tantivy {
+    fn search_json(
+        &mut self,
+        indexes: &[&str],
+        body: &Value,
+    ) -> Result<SearchResult<Value>, EsError> {
+        let mut documents = vec![];
+        for pattern in indexes.iter() {
+            if pattern.contains('*') {
+                for index in self.matching_indexes(pattern)?.iter() {
+                    documents.extend(self.documents(index)?);
+                }
+            } else {
+                documents.extend(self.documents(pattern)?);
+            }
+        }
+
+        dsl::search(documents, body)
+    }
+
+    fn bulk_json(
+        &mut self,
+        index: &str,
+        doc_type: &str,
+        actions: Vec<Action<Value>>,
+    ) -> Result<BulkResult, EsError> {
+        // like ElasticSearch, missing indexes are created on the fly
+        if !self.index_path(index).exists() {
+            self.create_index(index, &Value::Null, &Settings::default())?;
+        }
+
+        // the partial updates are applied to the stored documents
+        let updates = actions.iter().any(|action| action.operation == "update");
+        let mut stored: HashMap<String, Value> = if updates {
+            self.documents(index)?
+                .into_iter()
+                .map(|document| (document.id, document.source))
+                .collect()
+        } else {
+            HashMap::new()
+        };
+
+        let tantivy_index = self.open(index)?;
+        let fields = Fields::of(&tantivy_index)?;
+        let mut writer = Tantivy::writer(&tantivy_index)?;
+        let mut items = Vec::with_capacity(actions.len());
+        let mut errors = false;
+
+        for (position, action) in actions.into_iter().enumerate() {
+            // documents without an id get their position in the request
+            let id = action.id.unwrap_or_else(|| position.to_string());
+            let mut status = 200;
+            let mut error = None;
+
+            let source = match action.operation {
+                "delete" => {
+                    if !Tantivy::exists(&tantivy_index, &fields, &id)? {
+                        status = 404;
+                    }
+                    writer.delete_term(Term::from_field_text(fields.id, &id));
+                    stored.remove(&id);
+                    None
+                }
+                "update" => match stored.get_mut(&id) {
+                    Some(source) => {
+                        let doc = action
+                            .source
+                            .map_or(Value::Null, |update| update["doc"].to_owned());
+                        dsl::merge(source, doc);
+                        Some(source.to_owned())
+                    }
+                    None => {
+                        status = 404;
+                        error = Some(dsl::document_missing(doc_type, &id));
+                        errors = true;
+                        None
+                    }
+                },
+                "create" if Tantivy::exists(&tantivy_index, &fields, &id)? => {
+                    status = 409;
+                    error = Some(dsl::version_conflict(&id));
+                    errors = true;
+                    None
+                }
+                _ => {
+                    let source = action.source.unwrap_or(Value::Null);
+                    if updates {
+                        stored.insert(id.to_owned(), source.to_owned());
+                    }
+                    Some(source)
+                }
+            };
+
+            if let Some(source) = source {
+                writer.delete_term(Term::from_field_text(fields.id, &id));
+                writer
+                    .add_document(doc!(
+                        fields.id       => id.to_owned(),
+                        fields.doc_type => doc_type,
+                        fields.source   => source.to_string()
+                    ))
+                    .map_err(from_tantivy)?;
+            }
+
+            let mut item = HashMap::new();
+            item.insert(
+                action.operation.to_owned(),
+                BulkItemResult {
+                    index: index.to_owned(),
+                    id: id,
+                    version: None,
+                    status: status,
+                    error: error,
+                },
+            );
+            items.push(item);
+        }
+
+        writer.commit().map_err(from_tantivy)?;
+
+        Ok(BulkResult {
+            took: 0,
+            errors: errors,
+            items: items,
+        })
+    }
+
+    fn mget_json(
+        &mut self,
+        index: &str,
+        _doc_type: &str,
+        ids: &[String],
+    ) -> Result<MgetResult<Value>, EsError> {
+        let documents: HashMap<String, Value> = self
+            .documents(index)?
+            .into_iter()
+            .map(|document| (document.id, document.source))
+            .collect();
+
+        let docs = ids
+            .iter()
+            .map(|id| {
+                let source = documents.get(id).cloned();
+
+                MgetDocResult {
+                    index: index.to_owned(),
+                    id: id.to_owned(),
+                    found: source.is_some(),
+                    source: source.map(Box::new),
+                }
+            })
+            .collect();
+
+        Ok(MgetResult { docs: docs })
+    }
+
+    fn delete(&mut self, index: &str, _doc_type: &str, id: &str) -> Result<DeleteResult, EsError> {
+        let tantivy_index = self.open(index)?;
+        let fields = Fields::of(&tantivy_index)?;
+        let found = Tantivy::exists(&tantivy_index, &fields, id)?;
+
+        let mut writer = Tantivy::writer(&tantivy_index)?;
+        writer.delete_term(Term::from_field_text(fields.id, id));
+        writer.commit().map_err(from_tantivy)?;
+
+        Ok(DeleteResult {
+            index: index.to_owned(),
+            id: id.to_owned(),
+            found: found,
+        })
+    }
+
+    fn delete_by_query(
+        &mut self,
+        indexes: &[&str],
+        query: &Value,
+    ) -> Result<DeleteByQueryResult, EsError> {
+        let mut deleted = 0;
+        for index in indexes.iter() {
+            let tantivy_index = self.open(index)?;
+            let fields = Fields::of(&tantivy_index)?;
+            let mut writer = Tantivy::writer(&tantivy_index)?;
+
+            for document in self.documents(index)?.iter() {
+                if dsl::score(query, &document.source)?.is_some() {
+                    writer.delete_term(Term::from_field_text(fields.id, &document.id));
+                    deleted += 1;
+                }
+            }
+
+            writer.commit().map_err(from_tantivy)?;
+        }
+
+        Ok(DeleteByQueryResult {
+            took: 0,
+            deleted: deleted,
+        })
+    }
+
+    fn create_index(
+        &mut self,
+        index: &str,
+        _mappings: &Value,
+        _settings: &Settings,
+    ) -> Result<MappingResult, EsError> {
+        let path = self.index_path(index);
+        if path.exists() {
+            return Err(EsError::EsServerError(format!(
+                "400 Bad Request - index_already_exists_exception: [{}]",
+                index
+            )));
+        }
+
+        fs::create_dir_all(&path)?;
+        Index::create_in_dir(&path, Fields::schema()).map_err(from_tantivy)?;
+
+        Ok(MappingResult { acknowledged: true })
+    }
+
+    fn delete_index(&mut self, index: &str) -> Result<MappingResult, EsError> {
+        self.open(index)?;
+        fs::remove_dir_all(self.index_path(index))?;
+
+        Ok(MappingResult { acknowledged: true })
+    }
+
+    /// Every write is committed right away, so there's nothing to refresh.
+    fn refresh(&mut self, indexes: &[&str]) -> Result<(), EsError> {
+        for index in indexes.iter() {
+            self.open(index)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use backends::tantivy::Tantivy;
+    use backends::SearchBackend;
+    use resource::{Action, Settings};
+
+    use serde_json::Value;
+
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn test_index_search_and_delete() {
+        let path = env::temp_dir().join("searchspot_tantivy_test");
+        let _ = fs::remove_dir_all(&path);
+
+        let mut tantivy: Box<dyn SearchBackend> =
+            Box::new(Tantivy::new(path.to_str().unwrap()).unwrap());
+        let index = "talents";
+
+        assert!(tantivy.search::<Value>(&[index], &json!({})).is_err());
+        tantivy
+            .create_index(index, &json!({}), &Settings::default())
+            .unwrap();
+
+        let actions = vec![
+            Action::index(json!({ "id": 1, "skills": ["Rust"] })).with_id("1"),
+            Action::index(json!({ "id": 2, "skills": ["Java"] })).with_id("2"),
+        ];
+        tantivy.bulk(index, "talent", actions).unwrap();
+
+        let query = json!({ "query": { "query_string": { "query": "rust" } } });
+        let result = tantivy.search::<Value>(&[index], &json!({})).unwrap();
+        assert_eq!(result.hits.total, 2);
+        let result = tantivy.search::<Value>(&[index], &query).unwrap();
+        assert_eq!(result.hits.hits[0].id, "1");
+
+        assert!(tantivy.delete(index, "talent", "1").unwrap().found);
+        assert!(!tantivy.delete(index, "talent", "1").unwrap().found);
+        let result = tantivy.search::<Value>(&[index], &query).unwrap();
+        assert_eq!(result.hits.total, 0);
+
+        tantivy.delete_index(index).unwrap();
+        assert!(tantivy.refresh(&[index]).is_err());
+    }
+}
@@ -0,0 +1,154 @@
+//! Fills the index straight from the database, which is much faster than
+//! sending every talent through `POST /talents`.
+//!
+//! The documents are streamed from the configured query and indexed in
+//! bulk requests of `BATCH_SIZE`. Reading from PostgreSQL is only available
+//! when Searchspot is built with the `postgres` feature.
+
+use serde_json;
+
+use backends::SearchBackend;
+use config::{Config, Postgres};
+use resource::{IndexSet, Resource};
+
+/// How many documents are indexed for each bulk request.
+pub const BATCH_SIZE: usize = 500;
+
+/// Parse every row of `rows` (a JSON document of `R`), normalize it as configured
+/// in `config` and index them into `indexes` in batches, each into the index it's routed to. Stop at the first invalid row,
+/// or at the first batch whose documents aren't all stored, returning the error along
+/// with how many rows had been read and documents indexed until then.
+pub fn index_rows<R, I>(
+    es: &mut dyn SearchBackend,
+    indexes: &IndexSet,
+    config: &Config,
+    rows: I,
+) -> Result<u64, String>
+where
+    R: Resource,
+    I: IntoIterator<Item = Result<String, String>>,
+{
+    let mut batch = Vec::with_capacity(BATCH_SIZE);
+    let mut read = 0;
+    let mut indexed = 0;
+
+    for row in rows.into_iter() {
+        read += 1;
+        let mut document: R = serde_json::from_str(&row?).map_err(|error| {
+            format!("Invalid row {} ({} documents indexed): {}", read, indexed, error)
+        })?;
+        document.normalize(config);
+        batch.push(document);
+
+        if batch.len() == BATCH_SIZE {
+            indexed += flush(es, indexes, &mut batch, indexed)?;
+        }
+    }
+
+    if !batch.is_empty() {
+        indexed += flush(es, indexes, &mut batch, indexed)?;
+    }
+
+    Ok(indexed)
+}
+
+/// Index the documents of `batch`, failing with the ids of the ones that weren't stored.
+fn flush<R: Resource>(
+    es: &mut dyn SearchBackend,
+    indexes: &IndexSet,
+    batch: &mut Vec<R>,
+    indexed: u64,
+) -> Result<u64, String> {
+    let documents: Vec<R> = batch.drain(..).collect();
+    let count = documents.len() as u64;
+
+    R::index_in(es, indexes, documents)
+        .and_then(|result| result.ensure_applied())
+        .map(|_| count)
+        .map_err(|error| format!("Cannot index after {} documents: {}", indexed, error))
+}
+
+/// Stream the documents returned by `postgres.query` into `indexes`.
+#[cfg(feature = "postgres")]
+pub fn from_postgres<R: Resource>(
+    es: &mut dyn SearchBackend,
+    indexes: &IndexSet,
+    config: &Config,
+    postgres: &Postgres,
+) -> Result<u64, String> {
+    use postgres::fallible_iterator::FallibleIterator;
+    use postgres::types::ToSql;
+    use postgres::{Client, NoTls};
+
+    use std::iter;
+
+    let mut client = Client::connect(&postgres.url, NoTls).map_err(|error| error.to_string())?;
+    let rows = client
+        .query_raw(&*postgres.query, iter::empty::<&dyn ToSql>())
+        .map_err(|error| error.to_string())?;
+
+    index_rows::<R, _>(
+        es,
+        indexes,
+        config,
+        rows.iterator().map(|row| {
+            row.and_then(|row| row.try_get::<_, String>(0))
+                .map_err(|error| error.to_string())
+        }),
+    )
+}
+
+/// PostgreSQL isn't available without the `postgres` feature.
+#[cfg(not(feature = "postgres"))]
+pub fn from_postgres<R: Resource>(
+    _es: &mut dyn SearchBackend,
+    _indexes: &IndexSet,
+    _config: &Config,
+    _postgres: &Postgres,
+) -> Result<u64, String> {
+    Err("Searchspot has been built without the `postgres` feature.".to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use backends::{Memory, SearchBackend};
+    use backfill::{self, BATCH_SIZE};
+    use config::Config;
+    use resource::{IndexSet, Resource};
+    use resources::Company;
+
+    use params::{Map, Value};
+
+    const INDEX: &'static str = "memory_backfill";
+
+    fn row(id: usize) -> Result<String, String> {
+        Ok(json!({
+            "id":        id,
+            "name":      format!("Company {}", id),
+            "industry":  "Software",
+            "locations": ["Berlin"],
+        })
+        .to_string())
+    }
+
+    #[test]
+    fn test_index_rows() {
+        let mut memory: Box<dyn SearchBackend> = Box::new(Memory::new());
+        let config = Config::from_file("examples/tests.toml".to_owned());
+        let indexes = IndexSet::new(INDEX);
+        let rows = (1..BATCH_SIZE + 3).map(row);
+
+        let indexed =
+            backfill::index_rows::<Company, _>(&mut *memory, &indexes, &config, rows).unwrap();
+        assert_eq!(indexed, BATCH_SIZE as u64 + 2);
+
+        let mut params = Map::new();
+        params.assign("per_page", Value::U64(0)).unwrap();
+        assert_eq!(Company::search(&mut *memory, INDEX, &params).total, indexed);
+
+        let rows = vec![row(1), Ok("{}".to_owned()), row(2)];
+        let error =
+            backfill::index_rows::<Company, _>(&mut *memory, &indexes, &config, rows).unwrap_err();
+        assert!(error.starts_with("Invalid row 2 (0 documents indexed)"));
+    }
+}
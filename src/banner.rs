@@ -0,0 +1,122 @@
+//! The report logged when Searchspot starts: its effective configuration, with
+//! the secrets redacted, the version of the search engine, the health of the
+//! indexes and whether their mappings are up to date with the migrations.
+
+use backends::SearchBackend;
+use config::Config;
+use migrations;
+use resource::{EsError, IndexSet};
+use resources::Talent;
+
+/// The lines of the report, and the problems found, which deserve a warning.
+#[derive(Debug, Default)]
+pub struct Banner {
+    pub lines: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+impl Banner {
+    /// Inspect the search engine of `config` through `es`.
+    pub fn new(config: &Config, es: &mut dyn SearchBackend) -> Banner {
+        let mut banner = Banner::default();
+
+        banner.lines.push(format!("Searchspot v{}", env!("CARGO_PKG_VERSION")));
+        banner.lines.extend(config.to_string().lines().map(str::to_owned));
+
+        match es.engine_version() {
+            Ok(version) => banner.lines.push(format!("Search engine: {}", version)),
+            Err(EsError::Unsupported(_)) => {}
+            Err(error) => banner.warnings.push(format!("Search engine unreachable: {}", error)),
+        }
+
+        // the primary index and the routed ones, which are migrated alike
+        let indexes = IndexSet::from_config(&config.es);
+        for index in indexes.writable() {
+            match es.index_health(index) {
+                Ok(ref health) if health == "red" => {
+                    banner.warnings.push(format!("Index {}: health red", index))
+                }
+                Ok(health) => banner.lines.push(format!("Index {}: health {}", index, health)),
+                Err(EsError::Unsupported(_)) => {}
+                Err(error) => banner
+                    .warnings
+                    .push(format!("Cannot read the health of {}: {}", index, error)),
+            }
+
+            match migrations::pending::<Talent>(es, index) {
+                Ok(ref pending) if pending.is_empty() => banner.lines.push(format!(
+                    "Mapping of {}: up to date (version {})",
+                    index,
+                    migrations::latest_version::<Talent>()
+                )),
+                Ok(pending) => {
+                    let names: Vec<&str> =
+                        pending.iter().map(|migration| migration.name).collect();
+                    banner.warnings.push(format!(
+                        "Mapping of {}: {} migrations pending ({}), run `searchspot reindex`",
+                        index,
+                        pending.len(),
+                        names.join(", ")
+                    ));
+                }
+                Err(error) => banner
+                    .warnings
+                    .push(format!("Cannot read the migrations of {}: {}", index, error)),
+            }
+        }
+
+        banner
+    }
+
+    pub fn log(&self) {
+        for line in self.lines.iter() {
+            info!("{}", line);
+        }
+
+        for warning in self.warnings.iter() {
+            warn!("{}", warning);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use backends::{Memory, SearchBackend};
+    use banner::Banner;
+    use config::Config;
+    use resource::Resource;
+    use resources::Talent;
+
+    fn config(index: &str) -> Config {
+        let mut config = Config::from_file("examples/tests.toml".to_owned());
+        config.es.index = index.to_owned();
+        config
+    }
+
+    #[test]
+    fn test_banner() {
+        let mut memory: Box<dyn SearchBackend> = Box::new(Memory::new());
+        let mut config = config("banner_talents");
+        config.es.routes.insert("Munich".to_owned(), "banner_talents_de".to_owned());
+
+        let banner = Banner::new(&config, &mut *memory);
+        assert!(banner.lines[0].starts_with("Searchspot v"));
+        assert!(banner.lines.iter().any(|line| line.starts_with("Listening on")));
+        assert_eq!(banner.warnings.len(), 2);
+        assert!(banner.warnings[0].starts_with("Mapping of banner_talents: "));
+        assert!(banner.warnings[1].starts_with("Mapping of banner_talents_de: "));
+
+        Talent::reset_index(&mut *memory, "banner_talents", None).unwrap();
+        let banner = Banner::new(&config, &mut *memory);
+        assert_eq!(banner.warnings.len(), 1);
+        assert!(banner.warnings[0].starts_with("Mapping of banner_talents_de: "));
+
+        Talent::reset_index(&mut *memory, "banner_talents_de", None).unwrap();
+        let banner = Banner::new(&config, &mut *memory);
+        assert!(banner.warnings.is_empty());
+        assert!(banner
+            .lines
+            .iter()
+            .any(|line| line.starts_with("Mapping of banner_talents: up to date")));
+    }
+}
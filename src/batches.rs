@@ -0,0 +1,218 @@
+//! The batches of talents, made of the talents sharing the same `batch_starts_at`
+//! and `batch_ends_at`. They can be listed along with the number of their talents,
+//! and deleted or hidden (the talents stop being `accepted`) as a whole.
+
+use serde_json::Value;
+
+use backends::SearchBackend;
+use migrations::Migratable;
+use query::Query;
+use resource::{Action, DeleteByQueryResult, EsError, IndexSet};
+use resources::Talent;
+
+/// How many talents are hidden with every bulk request.
+const HIDING_PAGE: u64 = 500;
+
+/// A batch and the number of its talents, accepted or not.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Batch {
+    pub starts_at: String,
+    pub ends_at: String,
+    pub talents: u64,
+}
+
+/// Return the key of a `terms` bucket of a date field, which ElasticSearch
+/// gives as milliseconds along with the formatted date.
+fn date_key(bucket: &Value) -> String {
+    match bucket["key_as_string"].as_str() {
+        Some(date) => date.to_owned(),
+        None => match bucket["key"] {
+            Value::String(ref date) => date.to_owned(),
+            ref key => key.to_string(),
+        },
+    }
+}
+
+fn buckets(aggregation: &Value) -> Vec<Value> {
+    aggregation["buckets"].as_array().cloned().unwrap_or_default()
+}
+
+/// Return the batches of the talents of `indexes`, the latest first.
+pub fn list(es: &mut dyn SearchBackend, indexes: &IndexSet) -> Result<Vec<Batch>, EsError> {
+    let body = json!({
+        "size": 0,
+        "aggs": {
+            "starts": {
+                "terms": { "field": "batch_starts_at", "size": 1000 },
+                "aggs":  { "ends": { "terms": { "field": "batch_ends_at", "size": 100 } } }
+            }
+        }
+    });
+
+    let result = es.search_json(&indexes.names(), &body)?;
+    let aggregations = result.aggregations.unwrap_or(Value::Null);
+
+    let mut batches = vec![];
+    for start in buckets(&aggregations["starts"]).iter() {
+        for end in buckets(&start["ends"]).iter() {
+            batches.push(Batch {
+                starts_at: date_key(start),
+                ends_at: date_key(end),
+                talents: end["doc_count"].as_u64().unwrap_or(0),
+            });
+        }
+    }
+
+    batches.sort_by(|a, b| {
+        b.starts_at
+            .cmp(&a.starts_at)
+            .then_with(|| b.ends_at.cmp(&a.ends_at))
+    });
+    Ok(batches)
+}
+
+/// The query matching the talents of the batch going from `starts_at` to `ends_at`.
+pub fn query(starts_at: &str, ends_at: &str) -> Query {
+    Query::build_bool()
+        .with_must(vec![
+            Query::build_term("batch_starts_at", starts_at).build(),
+            Query::build_term("batch_ends_at", ends_at).build(),
+        ])
+        .build()
+}
+
+/// Delete every talent of the batch going from `starts_at` to `ends_at` from `indexes`.
+pub fn delete(
+    es: &mut dyn SearchBackend,
+    indexes: &IndexSet,
+    starts_at: &str,
+    ends_at: &str,
+) -> Result<DeleteByQueryResult, EsError> {
+    es.delete_by_query(&indexes.names(), &query(starts_at, ends_at).into())
+}
+
+/// Hide every talent of the batch going from `starts_at` to `ends_at` from the
+/// searches, by storing them again as not `accepted` in the index of `indexes` they were
+/// found in. Return how many were hidden.
+pub fn hide(
+    es: &mut dyn SearchBackend,
+    indexes: &IndexSet,
+    starts_at: &str,
+    ends_at: &str,
+) -> Result<u64, EsError> {
+    let accepted = Query::build_bool()
+        .with_must(vec![
+            query(starts_at, ends_at),
+            Query::build_term("accepted", true).build(),
+        ])
+        .build();
+    let body = json!({ "query": accepted, "size": HIDING_PAGE });
+
+    let mut hidden = 0;
+    loop {
+        let result = es.search_json(&indexes.names(), &body)?;
+        if result.hits.hits.is_empty() {
+            return Ok(hidden);
+        }
+
+        // the talents are stored again where they were found, i.e. in their routed index
+        let mut groups: Vec<(String, Vec<Action<Value>>)> = vec![];
+        for hit in result.hits.hits.into_iter() {
+            let mut source = match hit.source {
+                Some(source) => *source,
+                None => continue,
+            };
+            source["accepted"] = json!(false);
+            let action = Action::index(source).with_id(hit.id);
+            match groups.iter().position(|&(ref index, _)| *index == hit.index) {
+                Some(position) => groups[position].1.push(action),
+                None => groups.push((hit.index, vec![action])),
+            }
+        }
+
+        let talents: u64 = groups.iter().map(|&(_, ref actions)| actions.len() as u64).sum();
+        let mut stored = talents > 0;
+        for (index, actions) in groups.into_iter() {
+            stored &= !es.bulk_json(&index, Talent::doc_type(), actions)?.errors;
+        }
+        if !stored {
+            return Err(EsError::EsServerError(format!(
+                "Hiding the batch stopped after {} talents: some couldn't be stored",
+                hidden
+            )));
+        }
+
+        hidden += talents;
+        es.refresh(&indexes.names())?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use backends::{Memory, SearchBackend};
+    use batches::{self, Batch};
+    use resource::{Action, IndexSet};
+
+    const INDEX: &'static str = "batch_talents";
+
+    fn populated() -> Memory {
+        let mut memory = Memory::new();
+        let talents = vec![
+            (1, "2018-01-01T00:00:00Z", "2018-02-01T00:00:00Z"),
+            (2, "2018-01-01T00:00:00Z", "2018-02-01T00:00:00Z"),
+            (3, "2018-03-01T00:00:00Z", "2018-04-01T00:00:00Z"),
+        ];
+        let actions = talents
+            .into_iter()
+            .map(|(id, starts_at, ends_at)| {
+                Action::index(json!({
+                    "id":              id,
+                    "accepted":        true,
+                    "batch_starts_at": starts_at,
+                    "batch_ends_at":   ends_at,
+                }))
+                .with_id(id.to_string())
+            })
+            .collect();
+        memory.bulk_json(INDEX, "talent", actions).unwrap();
+
+        memory
+    }
+
+    #[test]
+    fn test_list() {
+        let mut memory = populated();
+
+        assert_eq!(
+            batches::list(&mut memory, &IndexSet::new(INDEX)).unwrap(),
+            vec![
+                Batch {
+                    starts_at: "2018-03-01T00:00:00Z".to_owned(),
+                    ends_at: "2018-04-01T00:00:00Z".to_owned(),
+                    talents: 1,
+                },
+                Batch {
+                    starts_at: "2018-01-01T00:00:00Z".to_owned(),
+                    ends_at: "2018-02-01T00:00:00Z".to_owned(),
+                    talents: 2,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_delete_and_hide() {
+        let mut memory = populated();
+        let indexes = IndexSet::new(INDEX);
+
+        let (starts_at, ends_at) = ("2018-01-01T00:00:00Z", "2018-02-01T00:00:00Z");
+        assert_eq!(batches::hide(&mut memory, &indexes, starts_at, ends_at).unwrap(), 2);
+        let accepted = json!({ "query": { "term": { "accepted": true } } });
+        assert_eq!(memory.search_json(&[INDEX], &accepted).unwrap().hits.total, 1);
+
+        let (starts_at, ends_at) = ("2018-03-01T00:00:00Z", "2018-04-01T00:00:00Z");
+        let deleted = batches::delete(&mut memory, &indexes, starts_at, ends_at).unwrap();
+        assert_eq!(deleted.deleted, 1);
+        assert_eq!(batches::list(&mut memory, &indexes).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,105 @@
+//! Removes the documents that can't be found anymore (i.e. the talents
+//! whose batch ended long ago) or aren't useful anymore (i.e. old scores):
+//! they still make the index grow and the searches slower.
+
+use chrono::prelude::*;
+
+use serde_json::Value;
+
+use backends::{self, SearchBackend};
+use config::{Cleanup, Config};
+use resource::{DeleteByQueryResult, EsError, IndexSet, Resource};
+use scheduler;
+
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// A `Resource` whose documents stop being searchable at some point.
+pub trait Expirable: Resource {
+    /// The query matching the documents that expired before `before`.
+    fn expired_query(before: &DateTime<Utc>) -> Value;
+
+    /// How many days the documents are kept after expiring,
+    /// or `None` if they must not be deleted at all.
+    fn retention_days(cleanup: &Cleanup) -> Option<u64> {
+        Some(cleanup.retention_days)
+    }
+}
+
+/// Delete from `indexes` the documents that expired more than `retention_days` ago.
+pub fn delete_expired<R: Expirable>(
+    es: &mut dyn SearchBackend,
+    indexes: &IndexSet,
+    retention_days: u64,
+) -> Result<DeleteByQueryResult, EsError> {
+    let before = Utc::now() - ::chrono::Duration::days(retention_days as i64);
+    es.delete_by_query(&indexes.names(), &R::expired_query(&before))
+}
+
+/// Run `delete_expired` on the configured indexes every `cleanup.interval` seconds,
+/// using a client of its own. Return `None` if no `[cleanup]` is configured
+/// or it doesn't set a retention for `R`.
+pub fn schedule<R: Expirable>(config: &Config) -> Option<JoinHandle<()>> {
+    let cleanup = match config.cleanup {
+        Some(ref cleanup) => cleanup.to_owned(),
+        None => return None,
+    };
+    let retention_days = match R::retention_days(&cleanup) {
+        Some(retention_days) => retention_days,
+        None => return None,
+    };
+    let indexes = IndexSet::from_config(&config.es);
+
+    let mut client = match backends::from_config(&config.es) {
+        Ok(client) => client,
+        Err(error) => {
+            error!("Cleanup not started: {}", error);
+            return None;
+        }
+    };
+
+    let interval = Duration::from_secs(cleanup.interval);
+    Some(scheduler::every("cleanup", interval, move || {
+        match delete_expired::<R>(&mut *client, &indexes, retention_days) {
+            Ok(result) => info!(
+                "Deleted {} expired documents from {}.",
+                result.deleted, indexes
+            ),
+            Err(error) => error!("Cleanup of {} failed: {}", indexes, error),
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use backends::{Memory, SearchBackend};
+    use cleanup::delete_expired;
+    use resource::{Action, IndexSet};
+    use resources::Talent;
+
+    use chrono::prelude::*;
+    use chrono::Duration;
+
+    use serde_json::Value;
+
+    #[test]
+    fn test_delete_expired() {
+        let mut memory: Box<dyn SearchBackend> = Box::new(Memory::new());
+        let index = "cleanup_talents";
+
+        let ended_at = |days: i64| (Utc::now() - Duration::days(days)).to_rfc3339();
+        let actions = vec![
+            Action::index(json!({ "batch_ends_at": ended_at(60) })).with_id("1"),
+            Action::index(json!({ "batch_ends_at": ended_at(10) })).with_id("2"),
+            Action::index(json!({ "batch_ends_at": ended_at(-10) })).with_id("3"),
+        ];
+        memory.bulk(index, "talent", actions).unwrap();
+
+        let result = delete_expired::<Talent>(&mut *memory, &IndexSet::new(index), 30).unwrap();
+        assert_eq!(result.deleted, 1);
+
+        let result = memory.search::<Value>(&[index], &json!({})).unwrap();
+        let ids: Vec<String> = result.hits.hits.into_iter().map(|hit| hit.id).collect();
+        assert_eq!(ids, vec!["2".to_owned(), "3".to_owned()]);
+    }
+}
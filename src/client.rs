@@ -0,0 +1,182 @@
+//! A typed client of the HTTP API for the Rust services, which generates
+//! the tokens of its requests from the secrets instead of leaving it to
+//! the callers. Only available when Searchspot is built with the `client`
+//! feature.
+
+use oath::{totp_raw_now, HashType};
+use reqwest;
+use reqwest::blocking::{RequestBuilder, Response};
+use serde_json;
+
+use config::TokensLifetime;
+use resources::{SearchResults, Talent, TalentId};
+
+use std::error::Error;
+use std::fmt;
+
+/// Why a request failed: it couldn't be sent, Searchspot replied
+/// with an unexpected status or its body couldn't be read.
+#[derive(Debug)]
+pub enum ClientError {
+    Http(reqwest::Error),
+    Status(u16, String),
+    Json(serde_json::Error),
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ClientError::Http(ref error) => write!(f, "Request failed: {}", error),
+            ClientError::Status(status, ref body) => write!(f, "Replied with {}: {}", status, body),
+            ClientError::Json(ref error) => write!(f, "Invalid response: {}", error),
+        }
+    }
+}
+
+impl Error for ClientError {}
+
+impl From<reqwest::Error> for ClientError {
+    fn from(error: reqwest::Error) -> ClientError {
+        ClientError::Http(error)
+    }
+}
+
+impl From<serde_json::Error> for ClientError {
+    fn from(error: serde_json::Error) -> ClientError {
+        ClientError::Json(error)
+    }
+}
+
+/// Return the `Authorization` header granted by `secret` right now.
+pub fn authorization(secret: &str, lifetime: u64) -> String {
+    let token = totp_raw_now(secret.as_bytes(), 6, 0, lifetime, &HashType::SHA1);
+    format!("token {}", token)
+}
+
+/// A client of the Searchspot listening on `url` (i.e. `http://localhost:3000`).
+/// Without secrets, the requests are sent without tokens, as they
+/// are when the authentication is disabled.
+pub struct Client {
+    url: String,
+    read: Option<String>,
+    write: Option<String>,
+    lifetime: TokensLifetime,
+    http: reqwest::blocking::Client,
+}
+
+impl Client {
+    pub fn new(url: &str) -> Client {
+        Client {
+            url: url.trim_end_matches('/').to_owned(),
+            read: None,
+            write: None,
+            lifetime: TokensLifetime::default(),
+            http: reqwest::blocking::Client::new(),
+        }
+    }
+
+    pub fn with_read_secret(mut self, secret: &str) -> Client {
+        self.read = Some(secret.to_owned());
+        self
+    }
+
+    pub fn with_write_secret(mut self, secret: &str) -> Client {
+        self.write = Some(secret.to_owned());
+        self
+    }
+
+    /// Use the lifetimes of the tokens configured on the server
+    /// (see `tokens.lifetime`), if they aren't the default ones.
+    pub fn with_lifetime(mut self, lifetime: TokensLifetime) -> Client {
+        self.lifetime = lifetime;
+        self
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.url, path)
+    }
+
+    fn authorize(
+        request: RequestBuilder,
+        secret: &Option<String>,
+        lifetime: u64,
+    ) -> RequestBuilder {
+        match *secret {
+            Some(ref secret) => request.header("Authorization", authorization(secret, lifetime)),
+            None => request,
+        }
+    }
+
+    fn read(&self, request: RequestBuilder) -> RequestBuilder {
+        Client::authorize(request, &self.read, self.lifetime.read)
+    }
+
+    fn write(&self, request: RequestBuilder) -> RequestBuilder {
+        Client::authorize(request, &self.write, self.lifetime.write)
+    }
+
+    /// Fail unless `response` has the `expected` status.
+    fn expect(response: Response, expected: u16) -> Result<Response, ClientError> {
+        let status = response.status().as_u16();
+        if status == expected {
+            Ok(response)
+        } else {
+            Err(ClientError::Status(status, response.text().unwrap_or_default()))
+        }
+    }
+
+    /// Search the talents through `GET /talents`, whose `params` are the ones
+    /// of the query string (i.e. `[("keywords", "rust"), ("work_locations[]", "Berlin")]`).
+    pub fn search_talents(&self, params: &[(&str, &str)]) -> Result<SearchResults, ClientError> {
+        let request = self.http.get(&self.url("/talents")).query(params);
+        let response = Client::expect(self.read(request).send()?, 200)?;
+
+        Ok(serde_json::from_str(&response.text()?)?)
+    }
+
+    /// Index the `talents` through `POST /talents`.
+    pub fn index_talents(&self, talents: &[Talent]) -> Result<(), ClientError> {
+        let request = self
+            .http
+            .post(&self.url("/talents"))
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_string(talents)?);
+        Client::expect(self.write(request).send()?, 201)?;
+
+        Ok(())
+    }
+
+    /// Delete the talent `id` through `DELETE /talents/:id`,
+    /// hiding it instead when `soft` (see `Resource::soft_delete`).
+    pub fn delete_talent(&self, id: &TalentId, soft: bool) -> Result<(), ClientError> {
+        let mut request = self.http.delete(&self.url(&format!("/talents/{}", id)));
+        if soft {
+            request = request.query(&[("soft", "true")]);
+        }
+        Client::expect(self.write(request).send()?, 204)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use client::{self, Client};
+    use server::is_valid_token;
+
+    #[test]
+    fn test_authorization() {
+        let header = client::authorization("yxxz7oap7rsf67zl", 30);
+        assert!(header.starts_with("token "));
+
+        let token = header["token ".len()..].parse::<u64>().unwrap();
+        assert!(is_valid_token("yxxz7oap7rsf67zl", token, 30));
+        assert!(!is_valid_token("6po2okn3ddwv6ili", token, 30));
+    }
+
+    #[test]
+    fn test_url() {
+        let client = Client::new("http://localhost:3000/");
+        assert_eq!(client.url("/talents"), "http://localhost:3000/talents");
+    }
+}
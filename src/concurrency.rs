@@ -0,0 +1,150 @@
+//! Caps how many requests of every `EndpointClass` are served at the same
+//! time (see `config::Concurrency`), so that a burst of expensive keyword
+//! searches can't take every worker thread while the indexing waits.
+//!
+//! The requests still share a single client of the search backend, locked by
+//! one of them at a time: the permits bound how many queue up for it, not
+//! how many query the backend in parallel.
+
+use config::Concurrency;
+
+use std::sync::Mutex;
+
+/// The kinds of requests limited independently from each other.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EndpointClass {
+    /// The reads: the searches of the talents and of the scores, and the
+    /// other queries of the indexes (i.e. the statistics and the raw queries).
+    Search,
+    /// The indexing, the deletion and the restoration of the documents.
+    Index,
+    /// The resets, the migrations and the switches of the indexes.
+    Reset,
+    /// The streams of the changes, each holding a worker thread while it lasts.
+    Stream,
+}
+
+lazy_static! {
+    static ref SEARCH: Semaphore = Semaphore::new();
+    static ref INDEX: Semaphore = Semaphore::new();
+    static ref RESET: Semaphore = Semaphore::new();
+    static ref STREAM: Semaphore = Semaphore::new();
+}
+
+impl EndpointClass {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            EndpointClass::Search => "search",
+            EndpointClass::Index => "index",
+            EndpointClass::Reset => "reset",
+            EndpointClass::Stream => "stream",
+        }
+    }
+
+    pub fn limit(&self, config: &Concurrency) -> Option<usize> {
+        match *self {
+            EndpointClass::Search => config.search,
+            EndpointClass::Index => config.index,
+            EndpointClass::Reset => config.reset,
+            EndpointClass::Stream => config.stream,
+        }
+    }
+
+    fn semaphore(&self) -> &'static Semaphore {
+        match *self {
+            EndpointClass::Search => &SEARCH,
+            EndpointClass::Index => &INDEX,
+            EndpointClass::Reset => &RESET,
+            EndpointClass::Stream => &STREAM,
+        }
+    }
+
+    /// Return the permit to serve a request of this class,
+    /// unless its limit has been reached already.
+    pub fn try_acquire(&self, config: &Concurrency) -> Option<Permit<'static>> {
+        self.semaphore().try_acquire(self.limit(config))
+    }
+}
+
+/// Counts the requests being served.
+#[derive(Default)]
+pub struct Semaphore {
+    in_flight: Mutex<usize>,
+}
+
+impl Semaphore {
+    pub fn new() -> Semaphore {
+        Semaphore {
+            in_flight: Mutex::new(0),
+        }
+    }
+
+    /// Return a permit, unless `limit` requests are being served already.
+    /// Requests are always counted, even when there's no limit.
+    pub fn try_acquire(&self, limit: Option<usize>) -> Option<Permit> {
+        let mut in_flight = self.in_flight.lock().unwrap();
+        if limit.map_or(false, |limit| *in_flight >= limit) {
+            return None;
+        }
+
+        *in_flight += 1;
+        Some(Permit { semaphore: self })
+    }
+
+    pub fn in_flight(&self) -> usize {
+        *self.in_flight.lock().unwrap()
+    }
+}
+
+/// A request being served, which leaves room for another one when dropped.
+pub struct Permit<'a> {
+    semaphore: &'a Semaphore,
+}
+
+impl<'a> Drop for Permit<'a> {
+    fn drop(&mut self) {
+        *self.semaphore.in_flight.lock().unwrap() -= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use concurrency::{EndpointClass, Semaphore};
+    use config::Concurrency;
+
+    #[test]
+    fn test_try_acquire() {
+        let semaphore = Semaphore::new();
+
+        let first = semaphore.try_acquire(Some(2)).unwrap();
+        let second = semaphore.try_acquire(Some(2)).unwrap();
+        assert!(semaphore.try_acquire(Some(2)).is_none());
+        assert_eq!(semaphore.in_flight(), 2);
+
+        drop(first);
+        let third = semaphore.try_acquire(Some(2)).unwrap();
+        assert_eq!(semaphore.in_flight(), 2);
+
+        // without a limit the requests are only counted
+        let unlimited = semaphore.try_acquire(None).unwrap();
+        assert_eq!(semaphore.in_flight(), 3);
+
+        drop((second, third, unlimited));
+        assert_eq!(semaphore.in_flight(), 0);
+    }
+
+    #[test]
+    fn test_limit() {
+        let config = Concurrency {
+            search: Some(16),
+            index: None,
+            reset: Some(1),
+            stream: Some(8),
+        };
+
+        assert_eq!(EndpointClass::Search.limit(&config), Some(16));
+        assert_eq!(EndpointClass::Index.limit(&config), None);
+        assert_eq!(EndpointClass::Reset.limit(&config), Some(1));
+        assert_eq!(EndpointClass::Stream.limit(&config), Some(8));
+    }
+}
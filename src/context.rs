@@ -0,0 +1,265 @@
+//! The request being handled by the current thread (every Iron request is
+//! handled on a single thread), so that the errors and the panics reported
+//! to the monitor say which request caused them.
+
+use iron::Request;
+use params::{Map, Value};
+
+use std::cell::RefCell;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The header the caller can identify its requests with.
+const REQUEST_ID_HEADER: &'static str = "X-Request-Id";
+
+/// The params whose values are never reported.
+const SENSITIVE_PARAMS: &'static [&'static str] = &["token", "secret", "password", "auth", "key"];
+
+/// Param values longer than this are truncated.
+const MAX_PARAM_LENGTH: usize = 100;
+
+static REQUEST_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    static CURRENT: RefCell<Option<RequestContext>> = RefCell::new(None);
+}
+
+/// What is known about the request being handled.
+/// `payload_hash` identifies the params or the body that were
+/// sent, without reporting them. `anonymized` tells whether the
+/// response must leave out what could identify the talents.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct RequestContext {
+    pub request_id: String,
+    pub endpoint: String,
+    pub index: String,
+    pub params: BTreeMap<String, String>,
+    pub payload_hash: Option<String>,
+    #[serde(default)]
+    pub anonymized: bool,
+}
+
+impl RequestContext {
+    /// Describe `req`, that works on `index` unless told otherwise by its params.
+    /// The id is taken from the `X-Request-Id` header, or generated.
+    pub fn new(req: &Request, index: &str) -> RequestContext {
+        let request_id = req
+            .headers
+            .get_raw(REQUEST_ID_HEADER)
+            .and_then(|values| values.first())
+            .and_then(|value| String::from_utf8(value.to_owned()).ok())
+            .unwrap_or_else(generate_request_id);
+
+        RequestContext {
+            request_id: request_id,
+            endpoint: format!("{} /{}", req.method, req.url.path().join("/")),
+            index: index.to_owned(),
+            params: BTreeMap::new(),
+            payload_hash: None,
+            anonymized: false,
+        }
+    }
+
+    /// Describe a call to `endpoint` served outside of Iron (i.e. over gRPC), that works
+    /// on `index`. The id is the given `request_id`, or generated.
+    pub fn call(endpoint: &str, index: &str, request_id: Option<String>) -> RequestContext {
+        RequestContext {
+            request_id: request_id.unwrap_or_else(generate_request_id),
+            endpoint: endpoint.to_owned(),
+            index: index.to_owned(),
+            ..RequestContext::default()
+        }
+    }
+}
+
+fn generate_request_id() -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_secs())
+        .unwrap_or(0);
+    let counter = REQUEST_COUNTER.fetch_add(1, Ordering::Relaxed);
+
+    format!("{:x}-{:x}", now, counter)
+}
+
+/// Make `context` the one of the current thread until the returned guard is dropped.
+pub fn enter(context: RequestContext) -> RequestGuard {
+    CURRENT.with(|current| *current.borrow_mut() = Some(context));
+    RequestGuard
+}
+
+/// Clear the context of the current thread once the request is handled.
+pub struct RequestGuard;
+
+impl Drop for RequestGuard {
+    fn drop(&mut self) {
+        CURRENT.with(|current| *current.borrow_mut() = None);
+    }
+}
+
+/// Return the context of the request being handled by the current thread, if any.
+pub fn current() -> Option<RequestContext> {
+    CURRENT.with(|current| current.borrow().to_owned())
+}
+
+/// Add the sanitized `params` to the current context.
+/// The `index` param replaces the default index.
+pub fn set_params(params: &Map) {
+    CURRENT.with(|current| {
+        if let Some(ref mut context) = *current.borrow_mut() {
+            context.params = sanitize(params);
+            context.payload_hash = Some(hash(&format!("{:?}", params)));
+
+            if let Some(&Value::String(ref index)) = params.get("index") {
+                context.index = index.to_owned();
+            }
+        }
+    });
+}
+
+/// Add the hash of the body of the request to the current context.
+pub fn set_payload(payload: &str) {
+    CURRENT.with(|current| {
+        if let Some(ref mut context) = *current.borrow_mut() {
+            context.payload_hash = Some(hash(payload));
+        }
+    });
+}
+
+/// Anonymize the response to the current request.
+pub fn set_anonymized() {
+    CURRENT.with(|current| {
+        if let Some(ref mut context) = *current.borrow_mut() {
+            context.anonymized = true;
+        }
+    });
+}
+
+/// Tell whether the response to the current request must be anonymized.
+pub fn is_anonymized() -> bool {
+    CURRENT.with(|current| {
+        current
+            .borrow()
+            .as_ref()
+            .map_or(false, |context| context.anonymized)
+    })
+}
+
+fn hash(payload: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    payload.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+/// Tell whether the values of the param `key` must never be reported.
+pub fn is_sensitive(key: &str) -> bool {
+    let lowercase = key.to_lowercase();
+    SENSITIVE_PARAMS.iter().any(|name| lowercase.contains(name))
+}
+
+/// Render `params` as strings, hiding the sensitive ones and truncating the long ones.
+pub fn sanitize(params: &Map) -> BTreeMap<String, String> {
+    params
+        .iter()
+        .map(|(key, value)| {
+            let value = if is_sensitive(key) {
+                "[REDACTED]".to_owned()
+            } else {
+                let value = render(value);
+                match value.char_indices().nth(MAX_PARAM_LENGTH) {
+                    Some((end, _)) => format!("{}...", &value[..end]),
+                    None => value,
+                }
+            };
+
+            (key.to_owned(), value)
+        })
+        .collect()
+}
+
+fn render(value: &Value) -> String {
+    match *value {
+        Value::String(ref value) => value.to_owned(),
+        Value::Boolean(value) => value.to_string(),
+        Value::I64(value) => value.to_string(),
+        Value::U64(value) => value.to_string(),
+        Value::F64(value) => value.to_string(),
+        Value::Array(ref values) => {
+            let values: Vec<String> = values.iter().map(render).collect();
+            format!("[{}]", values.join(", "))
+        }
+        ref value => format!("{:?}", value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use context::{self, RequestContext};
+
+    use params::{Map, Value};
+
+    #[test]
+    fn test_current() {
+        assert!(context::current().is_none());
+
+        {
+            let _guard = context::enter(RequestContext {
+                request_id: "42".to_owned(),
+                index: "talents".to_owned(),
+                ..RequestContext::default()
+            });
+
+            let mut params = Map::new();
+            params.assign("index", Value::String("talents_de".to_owned())).unwrap();
+            context::set_params(&params);
+
+            let current = context::current().unwrap();
+            assert_eq!(current.request_id, "42");
+            assert_eq!(current.index, "talents_de");
+            assert_eq!(current.params["index"], "talents_de");
+        }
+
+        assert!(context::current().is_none());
+    }
+
+    #[test]
+    fn test_set_payload() {
+        let _guard = context::enter(RequestContext::default());
+        context::set_payload("[{\"id\": 1}]");
+        let first = context::current().unwrap().payload_hash.unwrap();
+
+        context::set_payload("[{\"id\": 2}]");
+        let second = context::current().unwrap().payload_hash.unwrap();
+
+        assert_eq!(first.len(), 16);
+        assert!(first != second);
+    }
+
+    #[test]
+    fn test_anonymized() {
+        context::set_anonymized();
+        assert!(!context::is_anonymized());
+
+        let _guard = context::enter(RequestContext::default());
+        assert!(!context::is_anonymized());
+        context::set_anonymized();
+        assert!(context::is_anonymized());
+    }
+
+    #[test]
+    fn test_sanitize() {
+        let mut params = Map::new();
+        params.assign("keywords", Value::String("x".repeat(150))).unwrap();
+        params.assign("auth_token", Value::String("hunter2".to_owned())).unwrap();
+        params.assign("languages[]", Value::String("German".to_owned())).unwrap();
+        params.assign("languages[]", Value::String("English".to_owned())).unwrap();
+
+        let sanitized = context::sanitize(&params);
+        assert_eq!(sanitized["keywords"], format!("{}...", "x".repeat(100)));
+        assert_eq!(sanitized["auth_token"], "[REDACTED]");
+        assert_eq!(sanitized["languages"], "[German, English]");
+    }
+}
@@ -0,0 +1,107 @@
+//! The errors of the requests served by Searchspot, classified by what the
+//! caller can do about them: the `server` answers each of them with its own
+//! status instead of a generic `422 Unprocessable Entity`.
+
+use resource::{EsError, MappingConflict};
+
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug)]
+pub enum SearchspotError {
+    /// The request can't be served as it is, and must be changed first.
+    BadRequest(String),
+    /// The index, or the document, the request is about doesn't exist.
+    NotFound(String),
+    /// The search backend couldn't be reached or failed: the request can be retried.
+    EsUnavailable(EsError),
+    /// Some documents don't fit the mapping of the index (see `MappingConflict`).
+    MappingConflict(Vec<MappingConflict>),
+    /// The token is missing, expired or not valid for the request.
+    Unauthorized,
+    /// The request is authorized, but some of its params aren't (i.e. unsigned `presented_talents`).
+    Forbidden(String),
+    /// The params are well-formed, but can't be processed (i.e. numbers too large to add up).
+    Unprocessable(String),
+}
+
+impl SearchspotError {
+    /// Return the HTTP status answering the error.
+    pub fn status(&self) -> u16 {
+        match *self {
+            SearchspotError::BadRequest(_) => 400,
+            SearchspotError::NotFound(_) => 404,
+            SearchspotError::EsUnavailable(_) => 503,
+            SearchspotError::MappingConflict(_) => 422,
+            SearchspotError::Unauthorized => 401,
+            SearchspotError::Forbidden(_) => 403,
+            SearchspotError::Unprocessable(_) => 422,
+        }
+    }
+}
+
+impl fmt::Display for SearchspotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SearchspotError::BadRequest(ref message) => write!(f, "{}", message),
+            SearchspotError::NotFound(ref message) => write!(f, "{}", message),
+            SearchspotError::EsUnavailable(ref error) => write!(f, "{}", error),
+            SearchspotError::MappingConflict(ref conflicts) => {
+                write!(f, "{} records don't match the mapping", conflicts.len())
+            }
+            SearchspotError::Unauthorized => write!(f, "Unauthorized"),
+            SearchspotError::Forbidden(ref message) => write!(f, "{}", message),
+            SearchspotError::Unprocessable(ref message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl Error for SearchspotError {}
+
+/// The errors replied by ElasticSearch (`404 Not Found - index_not_found_exception: ...`)
+/// keep their status: a missing index is `NotFound`, a query it refuses is a `BadRequest`.
+/// Anything else means that the backend can't serve the request right now.
+impl From<EsError> for SearchspotError {
+    fn from(error: EsError) -> SearchspotError {
+        let status: Option<u16> = match error {
+            EsError::EsServerError(ref message) => message.get(..3).and_then(|s| s.parse().ok()),
+            EsError::Unsupported(_) => Some(400),
+            _ => None,
+        };
+
+        match status {
+            Some(404) => SearchspotError::NotFound(error.to_string()),
+            Some(400..=499) => SearchspotError::BadRequest(error.to_string()),
+            _ => SearchspotError::EsUnavailable(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use error::SearchspotError;
+    use resource::EsError;
+
+    use std::io;
+
+    #[test]
+    fn test_from_es_error() {
+        let error =
+            |message: &str| SearchspotError::from(EsError::EsServerError(message.to_owned()));
+
+        let missing = error("404 Not Found - index_not_found_exception: no such index [talents]");
+        assert_eq!(missing.status(), 404);
+        assert_eq!(
+            missing.to_string(),
+            "404 Not Found - index_not_found_exception: no such index [talents]"
+        );
+        assert_eq!(error("400 Bad Request - parsing_exception: [bool]").status(), 400);
+        assert_eq!(error("503 Service Unavailable - cluster_block_exception").status(), 503);
+        assert_eq!(error("The index has no weight field").status(), 503);
+
+        let unreachable = io::Error::new(io::ErrorKind::ConnectionRefused, "refused");
+        assert_eq!(SearchspotError::from(EsError::IoError(unreachable)).status(), 503);
+        let unsupported = EsError::Unsupported("No scroll".to_owned());
+        assert_eq!(SearchspotError::from(unsupported).status(), 400);
+    }
+}
@@ -0,0 +1,135 @@
+//! Broadcasts the changes of the indexed talents to the clients following
+//! them through `GET /talents/stream`, which receive them as server-sent
+//! events (`event: indexed`, `event: deleted` or `event: reset`).
+//! The subscribers that fall `SUBSCRIBER_CAPACITY` changes behind are dropped,
+//! rather than letting the changes pile up for the slow clients.
+
+use serde_json;
+
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
+use std::sync::Mutex;
+
+/// How many changes a subscriber can have yet to receive.
+pub const SUBSCRIBER_CAPACITY: usize = 1000;
+
+/// Something that happened to the documents of `index`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Change {
+    #[serde(skip_serializing)]
+    pub event: &'static str,
+    pub index: String,
+    pub ids: Vec<String>,
+}
+
+impl Change {
+    pub fn indexed(index: &str, ids: Vec<String>) -> Change {
+        Change {
+            event: "indexed",
+            index: index.to_owned(),
+            ids: ids,
+        }
+    }
+
+    pub fn deleted(index: &str, ids: Vec<String>) -> Change {
+        Change {
+            event: "deleted",
+            index: index.to_owned(),
+            ids: ids,
+        }
+    }
+
+    pub fn reset(index: &str) -> Change {
+        Change {
+            event: "reset",
+            index: index.to_owned(),
+            ids: vec![],
+        }
+    }
+
+    /// Return the change as a server-sent event.
+    pub fn to_event(&self) -> String {
+        let data = serde_json::to_string(self).unwrap_or_else(|_| "{}".to_owned());
+        format!("event: {}\ndata: {}\n\n", self.event, data)
+    }
+}
+
+lazy_static! {
+    static ref SUBSCRIBERS: Mutex<Vec<SyncSender<Change>>> = Mutex::new(vec![]);
+}
+
+/// Receive the changes published from now on, until falling too far behind them:
+/// the receiver is then disconnected once it has received the pending ones.
+pub fn subscribe() -> Receiver<Change> {
+    let (sender, receiver) = sync_channel(SUBSCRIBER_CAPACITY);
+    SUBSCRIBERS.lock().unwrap().push(sender);
+    receiver
+}
+
+/// Send `change` to every subscriber, forgetting the ones that went away or
+/// that are too far behind. Publishing never waits for the subscribers.
+/// Changes without documents aren't worth an event, unless they're resets.
+pub fn publish(change: Change) {
+    if change.ids.is_empty() && change.event != "reset" {
+        return;
+    }
+
+    SUBSCRIBERS
+        .lock()
+        .unwrap()
+        .retain(|subscriber| match subscriber.try_send(change.clone()) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => {
+                warn!("A subscriber of the changes fell behind and has been dropped");
+                false
+            }
+            Err(TrySendError::Disconnected(_)) => false,
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use events::{self, Change, SUBSCRIBER_CAPACITY};
+
+    use std::sync::mpsc::TryRecvError;
+
+    #[test]
+    fn test_to_event() {
+        let change = Change::indexed("talents", vec!["1".to_owned(), "2".to_owned()]);
+        assert_eq!(
+            change.to_event(),
+            "event: indexed\ndata: {\"index\":\"talents\",\"ids\":[\"1\",\"2\"]}\n\n"
+        );
+
+        let change = Change::reset("talents");
+        assert_eq!(change.to_event(), "event: reset\ndata: {\"index\":\"talents\",\"ids\":[]}\n\n");
+    }
+
+    #[test]
+    fn test_publish() {
+        let receiver = events::subscribe();
+        let dropped = events::subscribe();
+        drop(dropped);
+
+        events::publish(Change::deleted("test_publish", vec![]));
+        events::publish(Change::deleted("test_publish", vec!["1".to_owned()]));
+
+        // Other tests may be publishing concurrently
+        let change = receiver
+            .try_iter()
+            .find(|change| change.index == "test_publish")
+            .unwrap();
+        assert_eq!(change, Change::deleted("test_publish", vec!["1".to_owned()]));
+    }
+
+    #[test]
+    fn test_slow_subscriber() {
+        let receiver = events::subscribe();
+        for id in 0..SUBSCRIBER_CAPACITY + 1 {
+            events::publish(Change::indexed("test_slow_subscriber", vec![id.to_string()]));
+        }
+
+        // the pending changes are still received, then the subscriber is gone
+        assert_eq!(receiver.try_iter().count(), SUBSCRIBER_CAPACITY);
+        assert_eq!(receiver.try_recv(), Err(TryRecvError::Disconnected));
+    }
+}
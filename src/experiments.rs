@@ -0,0 +1,140 @@
+//! Server-side A/B tests of the search features: every configured
+//! experiment enables its `features` for `traffic` percent of the talent
+//! searches, which report the experiment they took part in.
+//!
+//! The searches of a company always fall in the same experiment, while
+//! the ones without a `company_id` are assigned at random.
+
+use params::{FromValue, Map, Value};
+use sha2::{Digest, Sha256};
+
+use config::Experiment;
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+/// Return the bucket (between 0 and 99) of the searches made by `key`.
+pub fn bucket(key: Option<&str>) -> u64 {
+    let hash = match key {
+        Some(key) => {
+            let digest = Sha256::digest(key.as_bytes());
+            digest[..8]
+                .iter()
+                .fold(0u64, |hash, byte| (hash << 8) | *byte as u64)
+        }
+        None => RandomState::new().build_hasher().finish(),
+    };
+
+    hash % 100
+}
+
+/// Return the experiment whose share of the traffic contains `bucket`,
+/// the shares being taken one after the other in the configured order.
+pub fn assign(experiments: &[Experiment], bucket: u64) -> Option<&Experiment> {
+    let mut upper_bound = 0;
+
+    experiments.iter().find(|experiment| {
+        upper_bound += experiment.traffic as u64;
+        bucket < upper_bound
+    })
+}
+
+/// Return `params` with the features of the experiment they fall in, if any,
+/// and its name as `experiment`. The `experiment` given by the client is dropped.
+pub fn apply(experiments: &[Experiment], params: &Map) -> Map {
+    let mut params = params.to_owned();
+    params.remove("experiment");
+
+    let company_id = match params.get("company_id") {
+        Some(&Value::String(ref company_id)) if !company_id.is_empty() => Some(&**company_id),
+        _ => None,
+    };
+
+    let experiment = match assign(experiments, bucket(company_id)) {
+        Some(experiment) => experiment,
+        None => return params,
+    };
+
+    let mut features: Vec<String> = vec_from_params!(params, "features");
+    for feature in experiment.features.iter() {
+        if !features.iter().any(|name| name == feature.name()) {
+            features.push(feature.name().to_owned());
+        }
+    }
+
+    params.insert(
+        "features".to_owned(),
+        Value::Array(features.into_iter().map(Value::String).collect()),
+    );
+    params.insert("experiment".to_owned(), Value::String(experiment.name.to_owned()));
+    params
+}
+
+#[cfg(test)]
+mod tests {
+    use config::Experiment;
+    use experiments::{self, bucket};
+    use features::Feature;
+
+    use params::{FromValue, Map, Value};
+
+    fn configured() -> Vec<Experiment> {
+        vec![
+            Experiment {
+                name: "should".to_owned(),
+                features: vec![Feature::KeywordsShould],
+                traffic: 10,
+            },
+            Experiment {
+                name: "exact".to_owned(),
+                features: vec![Feature::NoFulltextSearch, Feature::KeywordsShould],
+                traffic: 20,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_bucket() {
+        assert_eq!(bucket(Some("7")), bucket(Some("7")));
+        for _ in 0..100 {
+            assert!(bucket(None) < 100);
+        }
+    }
+
+    #[test]
+    fn test_assign() {
+        let experiments = configured();
+        assert_eq!(experiments::assign(&experiments, 0).unwrap().name, "should");
+        assert_eq!(experiments::assign(&experiments, 9).unwrap().name, "should");
+        assert_eq!(experiments::assign(&experiments, 10).unwrap().name, "exact");
+        assert_eq!(experiments::assign(&experiments, 29).unwrap().name, "exact");
+        assert!(experiments::assign(&experiments, 30).is_none());
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut experiments = configured();
+        experiments[1].traffic = 90;
+
+        let mut params = Map::new();
+        params.assign("features[]", Value::String("keywords_should".to_owned())).unwrap();
+        params.assign("experiment", Value::String("made up".to_owned())).unwrap();
+        params.assign("company_id", Value::String("7".to_owned())).unwrap();
+
+        let params = experiments::apply(&experiments, &params);
+        let experiment = experiments::assign(&experiments, bucket(Some("7"))).unwrap();
+        assert_eq!(params.get("experiment"), Some(&Value::String(experiment.name.to_owned())));
+
+        // the features asked by the client are kept
+        let mut features: Vec<String> = vec_from_params!(params, "features");
+        let mut expected: Vec<&str> =
+            experiment.features.iter().map(|feature| feature.name()).collect();
+        features.sort();
+        expected.sort();
+        assert_eq!(features, expected);
+
+        // without experiments, nothing changes but the `experiment`
+        let params = experiments::apply(&[], &params);
+        assert!(params.get("experiment").is_none());
+    }
+}
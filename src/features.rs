@@ -0,0 +1,116 @@
+//! The search features (`features[]=...`) that change how talents are
+//! searched. Unknown features are rejected, and the supported ones are
+//! listed at `GET /features` so that the clients can discover them.
+
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Feature {
+    /// Match the keywords against the whole fields, without full-text search.
+    NoFulltextSearch,
+    /// Make the keywords boost the talents matching them instead of filtering.
+    KeywordsShould,
+    /// Send the keywords to ElasticSearch without sanitizing them.
+    RawQuerySyntax,
+    /// Let the `weight` of the talents nudge their ranking within a batch,
+    /// along with how recently they were added to it, instead of sorting by it first.
+    WeightDecay,
+}
+
+impl Feature {
+    /// Every feature, in the order they're listed.
+    pub const ALL: &'static [Feature] = &[
+        Feature::NoFulltextSearch,
+        Feature::KeywordsShould,
+        Feature::RawQuerySyntax,
+        Feature::WeightDecay,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Feature::NoFulltextSearch => "no_fulltext_search",
+            Feature::KeywordsShould => "keywords_should",
+            Feature::RawQuerySyntax => "raw_query_syntax",
+            Feature::WeightDecay => "weight_decay",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match *self {
+            Feature::NoFulltextSearch => {
+                "Match the keywords against the whole fields instead of their words."
+            }
+            Feature::KeywordsShould => {
+                "Rank the talents matching the keywords first instead of returning only them."
+            }
+            Feature::RawQuerySyntax => {
+                "Send the keywords as they are, with the whole query_string syntax."
+            }
+            Feature::WeightDecay => {
+                "Rank the talents of a batch by recency, nudged by weight, instead of by weight."
+            }
+        }
+    }
+}
+
+impl fmt::Display for Feature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Feature {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Feature, String> {
+        Feature::ALL
+            .iter()
+            .cloned()
+            .find(|feature| feature.name() == name)
+            .ok_or_else(|| format!("Unknown feature: {}", name))
+    }
+}
+
+/// A feature as described by `GET /features`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FeatureDescription {
+    pub name: String,
+    pub description: String,
+}
+
+/// Describe every supported feature.
+pub fn describe() -> Vec<FeatureDescription> {
+    Feature::ALL
+        .iter()
+        .map(|feature| FeatureDescription {
+            name: feature.name().to_owned(),
+            description: feature.description().to_owned(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use features::{self, Feature};
+
+    #[test]
+    fn test_from_str() {
+        for feature in Feature::ALL {
+            assert_eq!(feature.name().parse::<Feature>(), Ok(*feature));
+        }
+
+        assert_eq!(
+            "keywords_must".parse::<Feature>(),
+            Err("Unknown feature: keywords_must".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_describe() {
+        let descriptions = features::describe();
+        assert_eq!(descriptions.len(), Feature::ALL.len());
+        assert_eq!(descriptions[0].name, "no_fulltext_search");
+    }
+}
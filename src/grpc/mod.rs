@@ -0,0 +1,97 @@
+//! Serves the core operations on the talents (search, index, delete and
+//! reset) over gRPC next to the HTTP API, for the internal services that
+//! prefer typed clients. The schema is `proto/searchspot.proto`, whose
+//! messages mirror `Talent` and `SearchResults`. The calls are checked as
+//! their HTTP routes are, and the reset isn't served when `http.admin` is
+//! configured, since only the admin listener serves it then.
+//!
+//! The interface is only available when Searchspot is built with the
+//! `grpc` feature, which needs `protoc` to generate the messages.
+
+use config::Config;
+use maintenance::Maintenance;
+
+use std::sync::{Arc, RwLock};
+use std::thread::JoinHandle;
+
+#[cfg(feature = "grpc")]
+mod service;
+#[cfg(feature = "grpc")]
+pub use self::service::SearchspotService;
+
+/// The messages of `proto/searchspot.proto`.
+#[cfg(feature = "grpc")]
+#[allow(bad_style, dead_code, unused_imports, clippy::all)]
+pub mod searchspot {
+    include!(concat!(env!("OUT_DIR"), "/searchspot.rs"));
+}
+
+/// The service and the client of `proto/searchspot.proto`.
+#[cfg(feature = "grpc")]
+#[allow(bad_style, dead_code, unused_imports, clippy::all)]
+pub mod searchspot_grpc {
+    include!(concat!(env!("OUT_DIR"), "/searchspot_grpc.rs"));
+}
+
+/// Start serving gRPC in a thread of its own, using a client of its own,
+/// under the `maintenance` mode of the HTTP server.
+/// Return `None` if no `[grpc]` is configured.
+#[cfg(feature = "grpc")]
+pub fn start(config: &Config, maintenance: Arc<RwLock<Maintenance>>) -> Option<JoinHandle<()>> {
+    use backends;
+    use grpcio::{Environment, ServerBuilder};
+
+    use std::thread;
+
+    let grpc = match config.grpc {
+        Some(ref grpc) => grpc.to_owned(),
+        None => return None,
+    };
+
+    let client = match backends::from_config(&config.es) {
+        Ok(client) => client,
+        Err(error) => {
+            error!("gRPC not started: {}", error);
+            return None;
+        }
+    };
+    let service = SearchspotService::new(config.to_owned(), client, maintenance);
+
+    let handle = thread::Builder::new()
+        .name("grpc".to_owned())
+        .spawn(move || {
+            let environment = Arc::new(Environment::new(grpc.threads));
+            let server = ServerBuilder::new(environment)
+                .register_service(searchspot_grpc::create_searchspot(service))
+                .bind(grpc.host.to_owned(), grpc.port)
+                .build();
+
+            let mut server = match server {
+                Ok(server) => server,
+                Err(error) => {
+                    error!("gRPC not started: {}", error);
+                    return;
+                }
+            };
+            server.start();
+            info!("{}", grpc);
+
+            // the server shuts down as soon as it's dropped
+            loop {
+                thread::park();
+            }
+        })
+        .unwrap();
+
+    Some(handle)
+}
+
+/// gRPC isn't available without the `grpc` feature, so nothing is started.
+#[cfg(not(feature = "grpc"))]
+pub fn start(config: &Config, _: Arc<RwLock<Maintenance>>) -> Option<JoinHandle<()>> {
+    if config.grpc.is_some() {
+        warn!("gRPC not started: Searchspot has been built without the `grpc` feature.");
+    }
+
+    None
+}
@@ -0,0 +1,653 @@
+use futures::FutureExt;
+use grpcio::{RpcContext, RpcStatus, RpcStatusCode, UnarySink};
+use params::{Map, Value};
+use protobuf::well_known_types::{BoolValue, UInt64Value};
+use protobuf::RepeatedField;
+
+use backends::SearchBackend;
+use concurrency::{EndpointClass, Permit};
+use config::Config;
+use context::{self, RequestContext, RequestGuard};
+use error::SearchspotError;
+use maintenance::Maintenance;
+use resource::{EsError, IndexSet, MappingResult, Resource, TotalRelation};
+use resources::{is_shown_highlight, shown_avatar_url, FoundTalent, RolesExperience,
+                SalaryExpectations, SearchResult, SearchResults, Talent, TalentId, TalentView};
+use server::{is_valid_token, search_params};
+
+use super::searchspot as proto;
+use super::searchspot_grpc::Searchspot;
+
+use std::str;
+use std::sync::{Arc, Mutex, RwLock};
+
+/// The `Searchspot` service, whose calls share a single client.
+#[derive(Clone)]
+pub struct SearchspotService {
+    config: Config,
+    client: Arc<Mutex<Box<dyn SearchBackend>>>,
+    maintenance: Arc<RwLock<Maintenance>>,
+}
+
+impl SearchspotService {
+    pub fn new(
+        config: Config,
+        client: Box<dyn SearchBackend>,
+        maintenance: Arc<RwLock<Maintenance>>,
+    ) -> SearchspotService {
+        SearchspotService {
+            config: config,
+            client: Arc::new(Mutex::new(client)),
+            maintenance: maintenance,
+        }
+    }
+
+    /// Make the call the request of the current thread until the returned guard is dropped,
+    /// identified by its `x-request-id` metadata as the HTTP requests are by their header.
+    fn enter(&self, ctx: &RpcContext) -> RequestGuard {
+        let request_id = ctx
+            .request_headers()
+            .iter()
+            .find(|&(key, _)| key.eq_ignore_ascii_case("x-request-id"))
+            .and_then(|(_, value)| str::from_utf8(value).ok())
+            .map(str::to_owned);
+        let endpoint = format!("gRPC {}", String::from_utf8_lossy(ctx.method()));
+
+        context::enter(RequestContext::call(&endpoint, &self.config.es.index, request_id))
+    }
+
+    /// Fail unless the `authorization` metadata of the call carries a valid token
+    /// (i.e. `token 123456`) of `secret`, or of `anonymized_secret`, whose calls
+    /// are answered anonymized as the HTTP requests authorized by it are.
+    fn authorize(
+        &self,
+        ctx: &RpcContext,
+        secret: &str,
+        anonymized_secret: Option<&String>,
+        lifetime: u64,
+    ) -> Result<(), RpcStatus> {
+        if self.config.auth.anonymized {
+            context::set_anonymized();
+        }
+
+        if !self.config.auth.enabled {
+            return Ok(());
+        }
+
+        let tokens: Vec<u64> = ctx
+            .request_headers()
+            .iter()
+            .filter(|&(key, _)| key.eq_ignore_ascii_case("authorization"))
+            .filter_map(|(_, value)| str::from_utf8(value).ok())
+            .filter_map(|value| value.split("token ").last())
+            .filter_map(|token| token.trim().parse::<u64>().ok())
+            .collect();
+
+        if tokens.iter().any(|&token| is_valid_token(secret, token, lifetime)) {
+            return Ok(());
+        }
+
+        match anonymized_secret {
+            Some(secret) if tokens.iter().any(|&token| is_valid_token(secret, token, lifetime)) => {
+                context::set_anonymized();
+                Ok(())
+            }
+            _ => Err(RpcStatus::with_message(
+                RpcStatusCode::UNAUTHENTICATED,
+                "Invalid or missing token".to_owned(),
+            )),
+        }
+    }
+
+    /// Fail on the read-only replicas, whose HTTP server doesn't route the writes either.
+    fn writable(&self) -> Result<(), RpcStatus> {
+        if !self.config.read_only {
+            return Ok(());
+        }
+
+        Err(RpcStatus::with_message(
+            RpcStatusCode::UNIMPLEMENTED,
+            "The writes aren't served by this read-only replica".to_owned(),
+        ))
+    }
+
+    /// Fail when the maintenance mode rejects the endpoints of `class`,
+    /// as it does for the HTTP counterparts of the call.
+    fn available(&self, class: EndpointClass) -> Result<(), RpcStatus> {
+        let maintenance = self.maintenance.read().unwrap();
+        if !maintenance.rejects_class(class) {
+            return Ok(());
+        }
+
+        let message = match maintenance.reason {
+            Some(ref reason) => format!("{} {}", maintenance, reason),
+            None => maintenance.to_string(),
+        };
+        Err(RpcStatus::with_message(RpcStatusCode::UNAVAILABLE, message))
+    }
+
+    /// Fail when the reset is served by the admin listener, which the HTTP API
+    /// doesn't expose publicly either.
+    fn resettable(&self) -> Result<(), RpcStatus> {
+        if self.config.http.admin.is_none() {
+            return Ok(());
+        }
+
+        Err(RpcStatus::with_message(
+            RpcStatusCode::PERMISSION_DENIED,
+            "The reset is only served by the admin listener".to_owned(),
+        ))
+    }
+
+    /// Fail when the calls of `class` being served already reached their limit,
+    /// as the HTTP routes of the same class do.
+    fn acquire(&self, class: EndpointClass) -> Result<Permit<'static>, RpcStatus> {
+        class.try_acquire(&self.config.concurrency).ok_or_else(|| {
+            RpcStatus::with_message(
+                RpcStatusCode::RESOURCE_EXHAUSTED,
+                format!("Too many concurrent {} requests", class.name()),
+            )
+        })
+    }
+
+    fn search_talents(
+        &self,
+        request: &proto::SearchRequest,
+    ) -> Result<proto::SearchResults, RpcStatus> {
+        let _permit = self.acquire(EndpointClass::Search)?;
+        let params = params_from_proto(request.get_params());
+        let params = search_params::<Talent>(&self.config, &params).map_err(failure)?;
+        let mut client = self.client.lock().unwrap();
+        let indexes = IndexSet::from_config(&self.config.es);
+        let results = Talent::search_in(&mut **client, &indexes, &params).map_err(failure)?;
+
+        Ok(search_results_to_proto(results))
+    }
+
+    fn index_talents(
+        &self,
+        mut request: proto::IndexRequest,
+    ) -> Result<proto::IndexResponse, RpcStatus> {
+        let mut talents = request
+            .take_talents()
+            .into_iter()
+            .map(talent_from_proto)
+            .collect::<Result<Vec<Talent>, String>>()
+            .map_err(invalid_argument)?;
+        for talent in talents.iter_mut() {
+            talent.normalize(&self.config);
+        }
+
+        let _permit = self.acquire(EndpointClass::Index)?;
+        let mut client = self.client.lock().unwrap();
+        let indexes = IndexSet::from_config(&self.config.es);
+        let result = Talent::index_in(&mut **client, &indexes, talents).map_err(backend_failure)?;
+
+        // the other talents are stored anyway, as over HTTP
+        let mapping_conflicts = result.mapping_conflicts();
+        if !mapping_conflicts.is_empty() {
+            return Err(failure(SearchspotError::MappingConflict(mapping_conflicts)));
+        }
+
+        // the stale versions are skipped rather than failing the call
+        let indexed = result
+            .items
+            .iter()
+            .flat_map(|item| item.values())
+            .filter(|item| item.status < 300)
+            .count();
+
+        let mut response = proto::IndexResponse::new();
+        response.set_indexed(indexed as u32);
+        Ok(response)
+    }
+
+    fn delete_talents(
+        &self,
+        request: &proto::DeleteRequest,
+    ) -> Result<proto::DeleteResponse, RpcStatus> {
+        let ids = request
+            .get_ids()
+            .iter()
+            .map(|id| id.parse::<TalentId>())
+            .collect::<Result<Vec<TalentId>, String>>()
+            .map_err(invalid_argument)?;
+
+        let _permit = self.acquire(EndpointClass::Index)?;
+        let mut client = self.client.lock().unwrap();
+        let indexes = IndexSet::from_config(&self.config.es);
+        Talent::delete_many_in(&mut **client, &ids, &indexes).map_err(backend_failure)?;
+
+        Ok(proto::DeleteResponse::new())
+    }
+
+    fn reset_index(&self) -> Result<proto::ResetResponse, RpcStatus> {
+        let definition = self.config.es.index_definition().map_err(internal)?;
+
+        let _permit = self.acquire(EndpointClass::Reset)?;
+        let mut client = self.client.lock().unwrap();
+
+        // the routed indexes are created along with the primary one, as over HTTP
+        let indexes = IndexSet::from_config(&self.config.es);
+        let reset: Result<Vec<MappingResult>, EsError> = indexes
+            .writable()
+            .into_iter()
+            .map(|index| Talent::reset_index(&mut **client, index, definition.as_ref()))
+            .collect();
+        reset.map_err(backend_failure)?;
+
+        Ok(proto::ResetResponse::new())
+    }
+}
+
+impl Searchspot for SearchspotService {
+    fn search(
+        &mut self,
+        ctx: RpcContext,
+        request: proto::SearchRequest,
+        sink: UnarySink<proto::SearchResults>,
+    ) {
+        let _request = self.enter(&ctx);
+        let lifetime = self.config.tokens.lifetime.read;
+        let analytics = self.config.auth.analytics.as_ref();
+        let response = self
+            .authorize(&ctx, &self.config.auth.read, analytics, lifetime)
+            .and_then(|_| self.available(EndpointClass::Search))
+            .and_then(|_| self.search_talents(&request));
+        reply(&ctx, sink, response);
+    }
+
+    fn index(
+        &mut self,
+        ctx: RpcContext,
+        request: proto::IndexRequest,
+        sink: UnarySink<proto::IndexResponse>,
+    ) {
+        let _request = self.enter(&ctx);
+        let lifetime = self.config.tokens.lifetime.write;
+        let response = self
+            .authorize(&ctx, &self.config.auth.write, None, lifetime)
+            .and_then(|_| self.writable())
+            .and_then(|_| self.available(EndpointClass::Index))
+            .and_then(|_| self.index_talents(request));
+        reply(&ctx, sink, response);
+    }
+
+    fn delete(
+        &mut self,
+        ctx: RpcContext,
+        request: proto::DeleteRequest,
+        sink: UnarySink<proto::DeleteResponse>,
+    ) {
+        let _request = self.enter(&ctx);
+        let lifetime = self.config.tokens.lifetime.write;
+        let response = self
+            .authorize(&ctx, &self.config.auth.write, None, lifetime)
+            .and_then(|_| self.writable())
+            .and_then(|_| self.available(EndpointClass::Index))
+            .and_then(|_| self.delete_talents(&request));
+        reply(&ctx, sink, response);
+    }
+
+    fn reset(
+        &mut self,
+        ctx: RpcContext,
+        _: proto::ResetRequest,
+        sink: UnarySink<proto::ResetResponse>,
+    ) {
+        let _request = self.enter(&ctx);
+        let lifetime = self.config.tokens.lifetime.write;
+        let response = self
+            .authorize(&ctx, &self.config.auth.write, None, lifetime)
+            .and_then(|_| self.writable())
+            .and_then(|_| self.resettable())
+            .and_then(|_| self.available(EndpointClass::Reset))
+            .and_then(|_| self.reset_index());
+        reply(&ctx, sink, response);
+    }
+}
+
+/// Send `response` (or its error) through `sink`.
+fn reply<T>(ctx: &RpcContext, sink: UnarySink<T>, response: Result<T, RpcStatus>) {
+    let sent = match response {
+        Ok(response) => sink.success(response),
+        Err(status) => sink.fail(status),
+    };
+
+    ctx.spawn(sent.map(|result| {
+        if let Err(error) = result {
+            error!("gRPC response not sent: {}", error);
+        }
+    }));
+}
+
+fn invalid_argument<E: ToString>(error: E) -> RpcStatus {
+    RpcStatus::with_message(RpcStatusCode::INVALID_ARGUMENT, error.to_string())
+}
+
+fn internal<E: ToString>(error: E) -> RpcStatus {
+    let message = error.to_string();
+    error!("{}", message);
+
+    RpcStatus::with_message(RpcStatusCode::INTERNAL, message)
+}
+
+/// Like `failure`, classifying the errors of the search backend as the HTTP routes do.
+fn backend_failure(error: EsError) -> RpcStatus {
+    failure(SearchspotError::from(error))
+}
+
+/// The gRPC counterpart of the HTTP status of `error`.
+fn failure(error: SearchspotError) -> RpcStatus {
+    let code = match error {
+        SearchspotError::BadRequest(_)
+        | SearchspotError::MappingConflict(_)
+        | SearchspotError::Unprocessable(_) => RpcStatusCode::INVALID_ARGUMENT,
+        SearchspotError::NotFound(_) => RpcStatusCode::NOT_FOUND,
+        SearchspotError::EsUnavailable(_) => RpcStatusCode::UNAVAILABLE,
+        SearchspotError::Unauthorized => RpcStatusCode::UNAUTHENTICATED,
+        SearchspotError::Forbidden(_) => RpcStatusCode::PERMISSION_DENIED,
+    };
+    error!("{}", error);
+
+    RpcStatus::with_message(code, error.to_string())
+}
+
+/// Gather `params` like the query string of `GET /talents` would be
+/// (i.e. `work_locations[]` given once per location).
+fn params_from_proto(params: &[proto::Param]) -> Map {
+    let mut map = Map::new();
+
+    for param in params {
+        let value = Value::String(param.get_value().to_owned());
+        if let Err(error) = map.assign(param.get_name(), value) {
+            error!("Invalid gRPC param {}: {}", param.get_name(), error);
+        }
+    }
+
+    map
+}
+
+/// Convert a `proto::Talent` into a `Talent`, failing if its id is invalid.
+pub fn talent_from_proto(mut talent: proto::Talent) -> Result<Talent, String> {
+    Ok(Talent {
+        id: talent.get_id().parse()?,
+        accepted: talent.get_accepted(),
+        desired_work_roles: talent.take_desired_work_roles().into_vec(),
+        desired_work_roles_experience: talent.take_desired_work_roles_experience().into_vec(),
+        desired_roles: talent
+            .take_desired_roles()
+            .into_iter()
+            .map(roles_experience_from_proto)
+            .collect(),
+        professional_experience: talent.take_professional_experience(),
+        work_locations: talent.take_work_locations().into_vec(),
+        current_location: talent.take_current_location(),
+        work_authorization: talent.take_work_authorization(),
+        skills: talent.take_skills().into_vec(),
+        summary: talent.take_summary(),
+        headline: talent.take_headline(),
+        contacted_company_ids: talent.take_contacted_company_ids(),
+        batch_starts_at: talent.take_batch_starts_at(),
+        batch_ends_at: talent.take_batch_ends_at(),
+        added_to_batch_at: talent.take_added_to_batch_at(),
+        weight: talent.get_weight(),
+        blocked_companies: talent.take_blocked_companies(),
+        work_experiences: talent.take_work_experiences().into_vec(),
+        avatar_url: talent.take_avatar_url(),
+        salary_expectations: talent
+            .take_salary_expectations()
+            .into_iter()
+            .map(salary_expectations_from_proto)
+            .collect(),
+        latest_position: talent.take_latest_position(),
+        languages: talent.take_languages().into_vec(),
+        educations: talent.take_educations().into_vec(),
+        external_version: match talent.get_external_version() {
+            0 => None,
+            version => Some(version),
+        },
+    })
+}
+
+/// Convert the results of `Talent::search`, whose complete documents
+/// (if they were requested) are reduced to `FoundTalent`s.
+pub fn search_results_to_proto(results: SearchResults) -> proto::SearchResults {
+    let total_relation = match results.total_relation {
+        TotalRelation::Eq => "eq",
+        TotalRelation::Gte => "gte",
+    };
+
+    let mut message = proto::SearchResults::new();
+    message.set_total(results.total);
+    message.set_total_relation(total_relation.to_owned());
+    message.set_timed_out(results.timed_out);
+    message.set_partial(results.partial);
+    message.set_shard_failures(RepeatedField::from_vec(results.shard_failures));
+    message.set_talents(results.talents.into_iter().map(search_result_to_proto).collect());
+    message.set_experiment(results.experiment.unwrap_or_default());
+    message
+}
+
+fn search_result_to_proto(result: SearchResult) -> proto::SearchResult {
+    let talent = match result.talent {
+        TalentView::Found(talent) => talent,
+        TalentView::Full(talent) => talent.into(),
+    };
+
+    let mut message = proto::SearchResult::new();
+    message.set_talent(found_talent_to_proto(talent));
+
+    if let Some(highlight) = result.highlight {
+        let highlight = highlight
+            .into_iter()
+            .filter(|&(ref field, _)| is_shown_highlight(field))
+            .map(|(field, fragments)| {
+                let mut message = proto::Fragments::new();
+                message.set_fragments(RepeatedField::from_vec(fragments));
+                (field, message)
+            })
+            .collect();
+        message.set_highlight(highlight);
+    }
+
+    message
+}
+
+fn found_talent_to_proto(talent: FoundTalent) -> proto::FoundTalent {
+    let mut message = proto::FoundTalent::new();
+    message.set_id(talent.id.to_string());
+    message.set_headline(talent.headline);
+    message.set_avatar_url(shown_avatar_url(&talent.avatar_url));
+    message.set_work_locations(RepeatedField::from_vec(talent.work_locations));
+    message.set_current_location(talent.current_location);
+    message.set_salary_expectations(
+        talent
+            .salary_expectations
+            .into_iter()
+            .map(salary_expectations_to_proto)
+            .collect(),
+    );
+    message.set_roles_experiences(
+        talent
+            .roles_experiences
+            .into_iter()
+            .map(roles_experience_to_proto)
+            .collect(),
+    );
+    message.set_latest_position(talent.latest_position);
+    message.set_batch_starts_at(talent.batch_starts_at);
+
+    if let Some(already_contacted) = talent.already_contacted {
+        message.set_already_contacted(bool_value(already_contacted));
+    }
+    if let Some(is_presented) = talent.is_presented {
+        message.set_is_presented(bool_value(is_presented));
+    }
+
+    message
+}
+
+fn bool_value(value: bool) -> BoolValue {
+    let mut message = BoolValue::new();
+    message.set_value(value);
+    message
+}
+
+fn salary_expectations_from_proto(mut salary: proto::SalaryExpectations) -> SalaryExpectations {
+    SalaryExpectations {
+        minimum: if salary.has_minimum() {
+            Some(salary.get_minimum().get_value())
+        } else {
+            None
+        },
+        currency: salary.take_currency(),
+        city: salary.take_city(),
+        // converted when the talent is normalized
+        minimum_eur: None,
+    }
+}
+
+fn salary_expectations_to_proto(salary: SalaryExpectations) -> proto::SalaryExpectations {
+    let mut message = proto::SalaryExpectations::new();
+
+    if let Some(minimum) = salary.minimum {
+        let mut value = UInt64Value::new();
+        value.set_value(minimum);
+        message.set_minimum(value);
+    }
+    message.set_currency(salary.currency);
+    message.set_city(salary.city);
+    message
+}
+
+fn roles_experience_from_proto(mut roles: proto::RolesExperience) -> RolesExperience {
+    RolesExperience {
+        role: roles.take_role(),
+        experience: roles.take_experience(),
+    }
+}
+
+fn roles_experience_to_proto(roles: RolesExperience) -> proto::RolesExperience {
+    let mut message = proto::RolesExperience::new();
+    message.set_role(roles.role);
+    message.set_experience(roles.experience);
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use protobuf::RepeatedField;
+
+    use context::{self, RequestContext};
+    use grpc::searchspot as proto;
+    use grpc::service::{search_result_to_proto, search_results_to_proto, talent_from_proto};
+    use resource::TotalRelation;
+    use resources::{FoundTalent, SearchResult, SearchResults, TalentId, TalentView};
+
+    fn found_talent() -> FoundTalent {
+        FoundTalent {
+            id: TalentId::Uuid("0b6c3bb4-5c2a-4f5a-9a3e-3fdc1c9d4e21".to_owned()),
+            headline: "Rustacean".to_owned(),
+            avatar_url: String::new(),
+            work_locations: vec!["Berlin".to_owned()],
+            current_location: "Berlin".to_owned(),
+            salary_expectations: vec![],
+            roles_experiences: vec![],
+            latest_position: "Developer".to_owned(),
+            batch_starts_at: "2016-03-04T12:24:00+01:00".to_owned(),
+            already_contacted: Some(false),
+            is_presented: None,
+        }
+    }
+
+    #[test]
+    fn test_talent_from_proto() {
+        let mut salary = proto::SalaryExpectations::new();
+        salary.set_currency("EUR".to_owned());
+        salary.set_city("Berlin".to_owned());
+
+        let mut talent = proto::Talent::new();
+        talent.set_id("42".to_owned());
+        talent.set_accepted(true);
+        talent.set_skills(RepeatedField::from_vec(vec!["Rust".to_owned()]));
+        talent.set_contacted_company_ids(vec![6]);
+        talent.set_salary_expectations(RepeatedField::from_vec(vec![salary]));
+
+        let talent = talent_from_proto(talent).unwrap();
+        assert_eq!(talent.id, TalentId::Numeric(42));
+        assert!(talent.accepted);
+        assert_eq!(talent.skills, vec!["Rust"]);
+        assert_eq!(talent.contacted_company_ids, vec![6]);
+        assert_eq!(talent.salary_expectations[0].minimum, None);
+        assert_eq!(talent.salary_expectations[0].city, "Berlin");
+
+        let mut talent = proto::Talent::new();
+        talent.set_id("forty-two".to_owned());
+        assert!(talent_from_proto(talent).is_err());
+    }
+
+    #[test]
+    fn test_search_results_to_proto() {
+        let talent = found_talent();
+
+        let results = SearchResults {
+            total: 11000,
+            total_relation: TotalRelation::Gte,
+            timed_out: true,
+            partial: true,
+            shard_failures: vec!["talents[2]: query_shard_exception".to_owned()],
+            talents: vec![SearchResult {
+                talent: TalentView::Found(talent),
+                highlight: None,
+            }],
+            ..SearchResults::default()
+        };
+
+        let results = search_results_to_proto(results);
+        assert_eq!(results.get_total(), 11000);
+        assert_eq!(results.get_total_relation(), "gte");
+        assert!(results.get_timed_out());
+        assert!(results.get_partial());
+        assert_eq!(results.get_shard_failures(), ["talents[2]: query_shard_exception"]);
+        assert_eq!(results.get_experiment(), "");
+
+        let talent = results.get_talents()[0].get_talent();
+        assert_eq!(talent.get_id(), "0b6c3bb4-5c2a-4f5a-9a3e-3fdc1c9d4e21");
+        assert!(talent.has_already_contacted());
+        assert!(!talent.get_already_contacted().get_value());
+        assert!(!talent.has_is_presented());
+    }
+
+    #[test]
+    fn test_anonymized_search_result_to_proto() {
+        let avatar_url = "https://example.com/avatar.png";
+        let result = || SearchResult {
+            talent: TalentView::Found(FoundTalent {
+                avatar_url: avatar_url.to_owned(),
+                ..found_talent()
+            }),
+            highlight: Some(
+                vec![
+                    ("summary".to_owned(), vec!["<em>Rust</em>".to_owned()]),
+                    ("skills.raw".to_owned(), vec!["<em>Rust</em>".to_owned()]),
+                ]
+                .into_iter()
+                .collect(),
+            ),
+        };
+
+        let message = search_result_to_proto(result());
+        assert_eq!(message.get_talent().get_avatar_url(), avatar_url);
+        assert_eq!(message.get_highlight().len(), 2);
+
+        let _request = context::enter(RequestContext {
+            anonymized: true,
+            ..RequestContext::default()
+        });
+        let message = search_result_to_proto(result());
+        assert_eq!(message.get_talent().get_avatar_url().len(), 64);
+        assert!(message.get_highlight().contains_key("skills.raw"));
+        assert!(!message.get_highlight().contains_key("summary"));
+    }
+}
@@ -0,0 +1,78 @@
+//! Periodic pings telling that Searchspot is alive, so that an alert can be
+//! raised when they stop even if the process isn't logging any error.
+//!
+//! A ping is sent only if the index can be searched, either to the generic
+//! `heartbeat.url` (i.e. a dead man's switch) or to the configured monitor.
+//! The read-only replicas don't send it, since it vouches for the writer.
+
+use reqwest;
+
+use backends::{self, SearchBackend};
+use config::{Config, Heartbeat};
+use monitor::{Monitor, MonitorProvider};
+use resource::split_indexes;
+use scheduler;
+
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// Send a heartbeat every `heartbeat.interval` seconds.
+/// Return `None` if no `[heartbeat]` is configured or there's nowhere to send it to.
+pub fn schedule(config: &Config) -> Option<JoinHandle<()>> {
+    let heartbeat = match config.heartbeat {
+        Some(ref heartbeat) => heartbeat.to_owned(),
+        None => return None,
+    };
+
+    let monitor = match config.monitor {
+        Some(ref monitor) if monitor.enabled => {
+            MonitorProvider::find_with_config(&monitor.provider, monitor)
+        }
+        _ => None,
+    };
+
+    if heartbeat.url.is_none() && monitor.is_none() {
+        warn!("Heartbeat not started: neither a URL nor a monitor is configured.");
+        return None;
+    }
+
+    let index = config.es.index.to_owned();
+    let mut client = match backends::from_config(&config.es) {
+        Ok(client) => client,
+        Err(error) => {
+            error!("Heartbeat not started: {}", error);
+            return None;
+        }
+    };
+
+    let interval = Duration::from_secs(heartbeat.interval);
+    Some(scheduler::every("heartbeat", interval, move || {
+        if let Err(error) = ping_backend(&mut *client, &index) {
+            warn!("Heartbeat skipped, {} is not available: {}", index, error);
+            return;
+        }
+
+        beat(&heartbeat, monitor.as_ref());
+    }))
+}
+
+fn ping_backend(client: &mut dyn SearchBackend, index: &str) -> Result<(), String> {
+    client
+        .search_json(&split_indexes(index), &json!({ "size": 0 }))
+        .map(|_| ())
+        .map_err(|error| error.to_string())
+}
+
+fn beat<M: Monitor>(heartbeat: &Heartbeat, monitor: Option<&M>) {
+    if let Some(ref url) = heartbeat.url {
+        match reqwest::blocking::get(url) {
+            Ok(ref response) if response.status().is_success() => {}
+            Ok(response) => warn!("Heartbeat rejected by {}: {}", url, response.status()),
+            Err(error) => warn!("Heartbeat not delivered to {}: {}", url, error),
+        }
+    }
+
+    if let Some(monitor) = monitor {
+        monitor.send_heartbeat();
+    }
+}
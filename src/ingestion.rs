@@ -0,0 +1,288 @@
+//! Applies the events published on a Kafka topic (i.e. by the Rails app
+//! whenever a talent changes) to the index, so that keeping it in sync
+//! doesn't depend on the latency of the HTTP requests.
+//!
+//! Every message is a JSON event, either `{"action": "upsert", "document": {...}}`
+//! or `{"action": "delete", "id": 1}`. The consumer is only available when
+//! Searchspot is built with the `kafka` feature.
+//!
+//! The events that fail because of the backend (i.e. it's unreachable) are retried
+//! with a backoff, and their offsets aren't committed until they're applied. The ones
+//! that can't be applied at all (i.e. invalid events) are logged and skipped.
+
+use serde::de::DeserializeOwned;
+use serde_json::{self, Value};
+
+use backends::SearchBackend;
+use config::Config;
+use error::SearchspotError;
+use resource::{BulkResult, EsError, IndexSet, Resource};
+
+use std::thread::JoinHandle;
+
+/// A change to a document of `R`.
+#[derive(Deserialize, Debug)]
+#[serde(tag = "action", rename_all = "lowercase")]
+#[serde(bound(deserialize = "R: DeserializeOwned"))]
+pub enum Event<R> {
+    Upsert { document: R },
+    Delete { id: Value },
+}
+
+/// Parse `message` as an `Event` and apply it to `indexes`
+/// through `Resource::index_in` or `Resource::delete_in`, normalizing
+/// the documents as configured in `config` (see `Resource::normalize`).
+/// The events that can't be parsed fail as a `BadRequest`.
+pub fn apply<R: Resource>(
+    es: &mut dyn SearchBackend,
+    indexes: &IndexSet,
+    config: &Config,
+    message: &[u8],
+) -> Result<(), SearchspotError> {
+    let event: Event<R> = serde_json::from_slice(message)
+        .map_err(|error| SearchspotError::BadRequest(format!("Invalid event: {}", error)))?;
+
+    match event {
+        Event::Upsert { mut document } => {
+            document.normalize(config);
+            ensure_stored(&R::index_in(es, indexes, vec![document])?)
+        }
+        Event::Delete { id } => {
+            let id = match id {
+                Value::String(id) => id,
+                id => id.to_string(),
+            };
+            let id = id.parse::<R::Id>().map_err(|_| {
+                SearchspotError::BadRequest(format!("Invalid event: invalid id {}", id))
+            })?;
+
+            R::delete_in(es, &id, indexes).map(|_| ())
+        }
+    }
+}
+
+/// Fail with the first document of `result` that couldn't be stored, unless it was only
+/// older than the stored one (see `Action::with_external_version`), which is ignored on purpose.
+/// The documents refused because the cluster is overloaded fail as `EsUnavailable`.
+fn ensure_stored(result: &BulkResult) -> Result<(), SearchspotError> {
+    let item = match result.failures().into_iter().find(|item| item.status != 409) {
+        Some(item) => item,
+        None => return Ok(()),
+    };
+
+    let reason = item.error.as_ref().and_then(|error| error["type"].as_str());
+    let error = EsError::EsServerError(format!(
+        "{} - {} couldn't be stored: {}",
+        item.status,
+        item.id,
+        reason.unwrap_or("unknown")
+    ));
+    match item.status {
+        429 => Err(SearchspotError::EsUnavailable(error)),
+        _ => Err(SearchspotError::from(error)),
+    }
+}
+
+/// The longest wait between the attempts to apply an event.
+#[cfg(feature = "kafka")]
+const MAX_BACKOFF_SECONDS: u64 = 60;
+
+/// Apply `message` as `apply` does, retrying with an exponential backoff for as long as
+/// the backend is unavailable. Only the errors that retrying wouldn't fix are returned.
+#[cfg(feature = "kafka")]
+fn apply_retrying<R: Resource>(
+    es: &mut dyn SearchBackend,
+    indexes: &IndexSet,
+    config: &Config,
+    message: &[u8],
+) -> Result<(), SearchspotError> {
+    use std::cmp;
+    use std::thread;
+    use std::time::Duration;
+
+    let mut backoff = 1;
+    loop {
+        match apply::<R>(es, indexes, config, message) {
+            Err(SearchspotError::EsUnavailable(error)) => {
+                warn!("Event not applied, retrying in {}s: {}", backoff, error);
+                thread::sleep(Duration::from_secs(backoff));
+                backoff = cmp::min(backoff * 2, MAX_BACKOFF_SECONDS);
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Start consuming the events of `R` from the configured topic in a thread
+/// of its own, using a client of its own.
+/// Return `None` if no `[kafka]` is configured.
+#[cfg(feature = "kafka")]
+pub fn start<R: Resource>(config: &Config) -> Option<JoinHandle<()>> {
+    use backends;
+    use kafka::consumer::{Consumer, FetchOffset, GroupOffsetStorage};
+
+    use std::thread;
+    use std::time::Duration;
+
+    let kafka = match config.kafka {
+        Some(ref kafka) => kafka.to_owned(),
+        None => return None,
+    };
+    let indexes = IndexSet::from_config(&config.es);
+
+    let mut client = match backends::from_config(&config.es) {
+        Ok(client) => client,
+        Err(error) => {
+            error!("Kafka ingestion not started: {}", error);
+            return None;
+        }
+    };
+
+    let consumer = Consumer::from_hosts(kafka.brokers.to_owned())
+        .with_topic(kafka.topic.to_owned())
+        .with_group(kafka.group.to_owned())
+        .with_fallback_offset(FetchOffset::Earliest)
+        .with_offset_storage(GroupOffsetStorage::Kafka)
+        .create();
+
+    let mut consumer = match consumer {
+        Ok(consumer) => consumer,
+        Err(error) => {
+            error!("Kafka ingestion not started: {}", error);
+            return None;
+        }
+    };
+
+    let config = config.to_owned();
+    let handle = thread::Builder::new()
+        .name("kafka".to_owned())
+        .spawn(move || loop {
+            let message_sets = match consumer.poll() {
+                Ok(message_sets) => message_sets,
+                Err(error) => {
+                    error!("Polling {} failed: {}", kafka.topic, error);
+                    thread::sleep(Duration::from_secs(1));
+                    continue;
+                }
+            };
+
+            for message_set in message_sets.iter() {
+                // the set is only consumed once its events are applied or can't ever be
+                for message in message_set.messages() {
+                    let applied = apply_retrying::<R>(&mut *client, &indexes, &config, message.value);
+                    if let Err(error) = applied {
+                        error!("Event {} of {} skipped: {}", message.offset, kafka.topic, error);
+                    }
+                }
+
+                if let Err(error) = consumer.consume_messageset(message_set) {
+                    error!("Events of {} not marked as consumed: {}", kafka.topic, error);
+                }
+            }
+
+            if let Err(error) = consumer.commit_consumed() {
+                error!("Offsets of {} not committed: {}", kafka.topic, error);
+            }
+        })
+        .unwrap();
+
+    Some(handle)
+}
+
+/// Kafka isn't available without the `kafka` feature, so nothing is started.
+#[cfg(not(feature = "kafka"))]
+pub fn start<R: Resource>(config: &Config) -> Option<JoinHandle<()>> {
+    if config.kafka.is_some() {
+        warn!("Kafka ingestion not started: Searchspot has been built without the `kafka` feature.");
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use backends::{Memory, SearchBackend};
+    use config::Config;
+    use error::SearchspotError;
+    use ingestion::{self, ensure_stored};
+    use resource::{BulkResult, IndexSet, Resource};
+    use resources::Company;
+
+    use params::Map;
+
+    const INDEX: &'static str = "memory_ingestion";
+
+    fn ids(memory: &mut dyn SearchBackend) -> Vec<u32> {
+        Company::search(memory, INDEX, &Map::new())
+            .companies
+            .into_iter()
+            .map(|company| company.id)
+            .collect()
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut memory: Box<dyn SearchBackend> = Box::new(Memory::new());
+        let upsert = |id: u32| {
+            json!({
+                "action":   "upsert",
+                "document": {
+                    "id":        id,
+                    "name":      "Honeypot",
+                    "industry":  "Recruiting",
+                    "locations": ["Berlin"],
+                }
+            })
+            .to_string()
+        };
+
+        let config = Config::from_file("examples/tests.toml".to_owned());
+        let indexes = IndexSet::new(INDEX);
+        let apply = |memory: &mut dyn SearchBackend, message: String| {
+            ingestion::apply::<Company>(memory, &indexes, &config, message.as_bytes())
+        };
+
+        apply(&mut *memory, upsert(1)).unwrap();
+        apply(&mut *memory, upsert(2)).unwrap();
+        assert_eq!(ids(&mut *memory), vec![1, 2]);
+
+        let delete = json!({ "action": "delete", "id": 1 }).to_string();
+        apply(&mut *memory, delete).unwrap();
+        assert_eq!(ids(&mut *memory), vec![2]);
+
+        let unknown = json!({ "action": "rename", "id": 2 }).to_string();
+        assert!(apply(&mut *memory, unknown).is_err());
+    }
+
+    #[test]
+    fn test_ensure_stored() {
+        let result = |status: u16, error: &str| -> BulkResult {
+            ::serde_json::from_value(json!({
+                "took":   1,
+                "errors": status >= 300,
+                "items":  [{ "index": {
+                    "_index": INDEX,
+                    "_id":    "1",
+                    "status": status,
+                    "error":  if status >= 300 { json!({ "type": error }) } else { json!(null) },
+                } }]
+            }))
+            .unwrap()
+        };
+
+        assert!(ensure_stored(&result(201, "")).is_ok());
+        // an older version of the document is ignored on purpose
+        assert!(ensure_stored(&result(409, "version_conflict_engine_exception")).is_ok());
+
+        match ensure_stored(&result(400, "mapper_parsing_exception")) {
+            Err(SearchspotError::BadRequest(message)) => {
+                assert!(message.contains("mapper_parsing_exception"))
+            }
+            result => panic!("Expected a BadRequest, got {:?}", result),
+        }
+        match ensure_stored(&result(429, "es_rejected_execution_exception")) {
+            Err(SearchspotError::EsUnavailable(_)) => (),
+            result => panic!("Expected EsUnavailable, got {:?}", result),
+        }
+    }
+}
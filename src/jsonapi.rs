@@ -0,0 +1,205 @@
+//! Renders the search results as JSON:API documents, for the clients asking
+//! for them through `Accept: application/vnd.api+json`. Every document
+//! becomes a resource object (`{"type": ..., "id": ..., "attributes": ...}`),
+//! the other fields of the results end up in `meta` and the pages are linked
+//! through `links` (`self`, `first`, `prev` and `next`).
+
+use serde::ser::Serialize;
+use serde_json::{self, Map, Value};
+
+use iron::Headers;
+
+pub const MEDIA_TYPE: &'static str = "application/vnd.api+json";
+
+/// Where the documents of a resource are in its search results.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Collection {
+    /// The JSON:API type of the documents (i.e. `talents`).
+    pub kind: &'static str,
+    /// The field of the results listing the documents.
+    pub items: &'static str,
+    /// The field of the items holding the document, when they're wrapped
+    /// (i.e. `talent`), whose other fields are the `meta` of the resource object.
+    pub document: Option<&'static str>,
+    /// The field of the document identifying it.
+    pub id: &'static str,
+}
+
+/// Tell whether JSON:API is among the media types accepted by the client.
+pub fn is_requested(headers: &Headers) -> bool {
+    match headers.get_raw("Accept") {
+        Some(values) => values.iter().any(|value| {
+            String::from_utf8_lossy(value)
+                .split(',')
+                .any(|media_type| media_type.trim().starts_with(MEDIA_TYPE))
+        }),
+        None => false,
+    }
+}
+
+/// Return `value` as the `id` of a resource object, which must be a string.
+fn resource_id(value: &Value) -> Value {
+    match *value {
+        Value::String(_) => value.to_owned(),
+        Value::Null => Value::Null,
+        _ => Value::String(value.to_string()),
+    }
+}
+
+fn resource_object(collection: &Collection, item: Value) -> Value {
+    let (mut attributes, meta) = match collection.document {
+        Some(document) => {
+            let mut item = match item {
+                Value::Object(item) => item,
+                _ => Map::new(),
+            };
+            let attributes = item.remove(document).unwrap_or(Value::Null);
+            (attributes, item)
+        }
+        None => (item, Map::new()),
+    };
+
+    let id = match attributes.as_object_mut() {
+        Some(attributes) => attributes.remove(collection.id).unwrap_or(Value::Null),
+        None => Value::Null,
+    };
+
+    let mut object = json!({
+        "type":       collection.kind,
+        "id":         resource_id(&id),
+        "attributes": attributes,
+    });
+    if !meta.is_empty() {
+        object["meta"] = Value::Object(meta);
+    }
+
+    object
+}
+
+/// Return `path` with the query string `query`, whose `offset`
+/// and `per_page` are replaced by the given ones.
+fn page_link(path: &str, query: Option<&str>, offset: u64, per_page: u64) -> String {
+    let mut pairs: Vec<String> = query
+        .unwrap_or("")
+        .split('&')
+        .filter(|pair| {
+            let name = pair.split('=').next().unwrap_or("");
+            !pair.is_empty() && name != "offset" && name != "per_page"
+        })
+        .map(str::to_owned)
+        .collect();
+    pairs.push(format!("offset={}", offset));
+    pairs.push(format!("per_page={}", per_page));
+
+    format!("{}?{}", path, pairs.join("&"))
+}
+
+/// Build the JSON:API document of the `results` of a search made
+/// through `path?query`, whose page starts at `offset`.
+pub fn document<T: Serialize>(
+    collection: &Collection,
+    results: &T,
+    path: &str,
+    query: Option<&str>,
+    offset: u64,
+    per_page: u64,
+) -> Result<Value, serde_json::Error> {
+    let mut meta = match serde_json::to_value(results)? {
+        Value::Object(results) => results,
+        _ => Map::new(),
+    };
+
+    let items = match meta.remove(collection.items) {
+        Some(Value::Array(items)) => items,
+        _ => vec![],
+    };
+    let data: Vec<Value> = items
+        .into_iter()
+        .map(|item| resource_object(collection, item))
+        .collect();
+
+    let total = meta.get("total").and_then(Value::as_u64);
+    meta.insert("offset".to_owned(), json!(offset));
+    meta.insert("per_page".to_owned(), json!(per_page));
+
+    let mut links = Map::new();
+    links.insert("self".to_owned(), json!(page_link(path, query, offset, per_page)));
+    links.insert("first".to_owned(), json!(page_link(path, query, 0, per_page)));
+    if offset > 0 {
+        let previous = offset.saturating_sub(per_page);
+        links.insert("prev".to_owned(), json!(page_link(path, query, previous, per_page)));
+    }
+    match total {
+        Some(total) if offset + per_page < total => {
+            let next = offset + per_page;
+            links.insert("next".to_owned(), json!(page_link(path, query, next, per_page)));
+        }
+        _ => {}
+    }
+
+    Ok(json!({
+        "data":  data,
+        "meta":  meta,
+        "links": links,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use iron::Headers;
+
+    use jsonapi::{self, Collection};
+
+    const TALENTS: Collection = Collection {
+        kind: "talents",
+        items: "talents",
+        document: Some("talent"),
+        id: "id",
+    };
+
+    #[test]
+    fn test_is_requested() {
+        let mut headers = Headers::new();
+        assert!(!jsonapi::is_requested(&headers));
+
+        headers.set_raw("Accept", vec![b"application/json".to_vec()]);
+        assert!(!jsonapi::is_requested(&headers));
+
+        headers.set_raw("Accept", vec![b"text/html, application/vnd.api+json".to_vec()]);
+        assert!(jsonapi::is_requested(&headers));
+    }
+
+    #[test]
+    fn test_document() {
+        let results = json!({
+            "total":   25,
+            "talents": [
+                { "talent": { "id": 1, "headline": "Rustacean" }, "highlight": null },
+                { "talent": { "id": "0b6c3bb4-5c2a-4f5a-9a3e-3fdc1c9d4e21" }, "highlight": null },
+            ],
+        });
+
+        let query = Some("keywords=rust&offset=10&per_page=10");
+        let document = jsonapi::document(&TALENTS, &results, "/talents", query, 10, 10).unwrap();
+
+        assert_eq!(
+            document["data"][0],
+            json!({
+                "type":       "talents",
+                "id":         "1",
+                "attributes": { "headline": "Rustacean" },
+                "meta":       { "highlight": null },
+            })
+        );
+        assert_eq!(document["data"][1]["id"], "0b6c3bb4-5c2a-4f5a-9a3e-3fdc1c9d4e21");
+        assert_eq!(document["meta"], json!({ "total": 25, "offset": 10, "per_page": 10 }));
+        assert_eq!(document["links"]["self"], "/talents?keywords=rust&offset=10&per_page=10");
+        assert_eq!(document["links"]["first"], "/talents?keywords=rust&offset=0&per_page=10");
+        assert_eq!(document["links"]["prev"], "/talents?keywords=rust&offset=0&per_page=10");
+        assert_eq!(document["links"]["next"], "/talents?keywords=rust&offset=20&per_page=10");
+
+        let document = jsonapi::document(&TALENTS, &results, "/talents", None, 20, 10).unwrap();
+        assert!(document["links"]["next"].is_null());
+        assert_eq!(document["links"]["prev"], "/talents?offset=10&per_page=10");
+    }
+}
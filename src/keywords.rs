@@ -0,0 +1,76 @@
+//! The keywords of a search end up in a `query_string` query, whose syntax
+//! makes ElasticSearch fail on unbalanced quotes, or run regular expressions
+//! and wildcards the users never meant to write.
+//!
+//! Unless the search asks for `features[]=raw_query_syntax`, the keywords
+//! are sanitized: quoted phrases are kept (when balanced), every other
+//! reserved character is escaped and the ones that can't be escaped are
+//! dropped.
+
+/// The terms kept whole by the `protect_keywords` filter of the talent
+/// analyzers, unless `es.protected_keywords` lists other ones.
+pub const PROTECTED_KEYWORDS: &'static [&'static str] = &[
+    "C++",
+    "C#",
+    "F#",
+    ".NET",
+    "ASP.NET",
+    "Objective-C",
+    "Node.js",
+    "Vue.js",
+    "Next.js",
+];
+
+/// The characters with a meaning in the `query_string` syntax, besides `"`.
+const RESERVED: &'static [char] = &[
+    '+', '-', '=', '&', '|', '!', '(', ')', '{', '}', '[', ']', '^', '~', '*', '?', ':', '\\', '/',
+];
+
+/// `<` and `>` can't be escaped at all.
+const UNESCAPABLE: &'static [char] = &['<', '>'];
+
+/// Return `keywords` as plain text for a `query_string` query.
+pub fn sanitize(keywords: &str) -> String {
+    // the last quote is left without its pair
+    let unbalanced_quote = if keywords.matches('"').count() % 2 == 1 {
+        keywords.rfind('"')
+    } else {
+        None
+    };
+
+    let mut sanitized = String::with_capacity(keywords.len());
+    for (position, character) in keywords.char_indices() {
+        if Some(position) == unbalanced_quote || UNESCAPABLE.contains(&character) {
+            continue;
+        }
+
+        if RESERVED.contains(&character) {
+            sanitized.push('\\');
+        }
+        sanitized.push(character);
+    }
+
+    sanitized.trim().to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use keywords::sanitize;
+
+    #[test]
+    fn test_sanitize() {
+        assert_eq!(sanitize("rust developer"), "rust developer");
+        assert_eq!(sanitize("\"project manager\" scrum"), "\"project manager\" scrum");
+
+        // unbalanced quotes
+        assert_eq!(sanitize("\"project manager"), "project manager");
+        assert_eq!(sanitize("\"project\" \"manager"), "\"project\" manager");
+
+        assert_eq!(sanitize("C++"), "C\\+\\+");
+        assert_eq!(sanitize("/jav.*/"), "\\/jav.\\*\\/");
+        assert_eq!(sanitize("title:(java OR go)"), "title\\:\\(java OR go\\)");
+        assert_eq!(sanitize("a\\b"), "a\\\\b");
+        assert_eq!(sanitize("<rust>"), "rust");
+        assert_eq!(sanitize(" \" "), "");
+    }
+}
@@ -0,0 +1,70 @@
+//! The languages whose text is analyzed by a dedicated analyzer (with its
+//! stemming, stopwords and decompounding) besides the language-agnostic ones.
+//!
+//! Every language follows the same naming: its analyzer is called after it
+//! (i.e. `german`), its filters are prefixed by it (i.e. `german_stop`) and
+//! the fields it analyzes get a sub-field named after it (i.e. `summary.german`).
+//! The languages that aren't listed in `es.languages` are left out of the
+//! indexes created or migrated afterwards.
+
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Language {
+    German,
+}
+
+impl Language {
+    pub const ALL: &'static [Language] = &[Language::German];
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Language::German => "german",
+        }
+    }
+
+    /// Return the sub-field of `field` analyzed in this language.
+    pub fn sub_field(&self, field: &str) -> String {
+        format!("{}.{}", field, self.name())
+    }
+
+    /// Return whether the filter called `filter` belongs to this language.
+    pub fn owns_filter(&self, filter: &str) -> bool {
+        filter.starts_with(&*format!("{}_", self.name()))
+    }
+}
+
+impl fmt::Display for Language {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Language {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Language, String> {
+        Language::ALL
+            .iter()
+            .cloned()
+            .find(|language| language.name() == name)
+            .ok_or_else(|| format!("unknown language {}", name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use languages::Language;
+
+    #[test]
+    fn test_naming() {
+        assert_eq!("german".parse::<Language>(), Ok(Language::German));
+        assert!("klingon".parse::<Language>().is_err());
+
+        assert_eq!(Language::German.sub_field("summary"), "summary.german");
+        assert!(Language::German.owns_filter("german_stop"));
+        assert!(!Language::German.owns_filter("english_words_filter"));
+    }
+}
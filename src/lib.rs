@@ -6,27 +6,47 @@ extern crate serde;
 extern crate serde_json;
 #[macro_use]
 extern crate serde_derive;
+extern crate serde_yaml;
 
 extern crate chrono;
 extern crate iron;
-extern crate logger as http_logger;
 extern crate params;
 extern crate persistent;
 extern crate router;
 extern crate unicase;
 
 extern crate backtrace;
+extern crate elasticsearch;
+extern crate hmac;
 extern crate oath;
+extern crate sha2;
+extern crate reqwest;
+extern crate opentelemetry;
+extern crate opentelemetry_otlp;
+#[cfg(feature = "tantivy")]
+#[macro_use]
+extern crate tantivy;
+#[cfg(feature = "kafka")]
+extern crate kafka;
+#[cfg(feature = "postgres")]
+extern crate postgres;
+#[cfg(feature = "msgpack")]
+extern crate rmp_serde;
+#[cfg(feature = "grpc")]
+extern crate futures;
+#[cfg(feature = "grpc")]
+extern crate grpcio;
+#[cfg(feature = "grpc")]
+extern crate protobuf;
 extern crate rollbar;
-extern crate rs_es;
+extern crate tokio;
 extern crate toml;
 #[macro_use]
 extern crate log;
 
 extern crate num_cpus;
 
-#[cfg_attr(test, macro_use)]
-#[cfg(test)]
+#[macro_use]
 extern crate lazy_static;
 
 #[cfg(test)]
@@ -38,12 +58,47 @@ extern crate url;
 #[macro_use]
 pub mod macros;
 
+pub mod alerts;
+pub mod backends;
+pub mod backfill;
+pub mod banner;
+pub mod batches;
+pub mod cleanup;
+#[cfg(feature = "client")]
+pub mod client;
+pub mod concurrency;
 pub mod config;
+pub mod events;
+pub mod features;
+pub mod grpc;
+pub mod heartbeat;
+pub mod ingestion;
+pub mod jsonapi;
+pub mod context;
+pub mod error;
+pub mod experiments;
+pub mod keywords;
+pub mod languages;
+pub mod locations;
 pub mod logger;
+pub mod maintenance;
+pub mod mapping;
 pub mod matches;
+pub mod metrics;
+pub mod migrations;
 pub mod monitor;
+pub mod openapi;
+pub mod preprocessing;
+pub mod presented_talents;
+pub mod query;
+pub mod recommendations;
+pub mod request_log;
 pub mod resource;
+pub mod scheduler;
 pub mod server;
+pub mod telemetry;
 pub mod terms;
+#[cfg(any(test, feature = "test_support"))]
+pub mod test_support;
 
 pub mod resources;
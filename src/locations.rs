@@ -0,0 +1,102 @@
+//! Canonicalizes the names of the locations through the aliases of `[locations]`
+//! (i.e. `Köln` to `Cologne` and `NYC` to `New York`), both in the indexed documents
+//! and in the params of the searches: the locations are matched as exact terms,
+//! so every city must be stored and searched by the same name.
+
+use params::{Map, Value};
+
+use config::Locations;
+
+/// The params of the searches listing locations.
+const PARAMS: &'static [&'static str] = &["work_locations", "current_location", "locations"];
+
+/// Return the canonical name of `location`, whose aliases are matched regardless
+/// of their case and of the surrounding whitespaces, or `location` itself.
+pub fn canonicalize(locations: &Locations, location: &str) -> String {
+    let name = location.trim().to_lowercase();
+
+    locations
+        .aliases
+        .iter()
+        .find(|&(alias, _)| alias.trim().to_lowercase() == name)
+        .map_or(location, |(_, canonical)| &**canonical)
+        .to_owned()
+}
+
+/// Return `params` with the locations they list, if any, canonicalized.
+pub fn apply(locations: &Locations, params: &Map) -> Map {
+    let mut params = params.to_owned();
+    if locations.aliases.is_empty() {
+        return params;
+    }
+
+    let canonical = |value: &Value| match *value {
+        Value::String(ref location) => Value::String(canonicalize(locations, location)),
+        ref value => value.to_owned(),
+    };
+
+    for name in PARAMS.iter() {
+        let value = match params.get(*name) {
+            Some(&Value::Array(ref values)) => {
+                Value::Array(values.iter().map(&canonical).collect())
+            }
+            Some(value) => canonical(value),
+            None => continue,
+        };
+
+        params.insert((*name).to_owned(), value);
+    }
+
+    params
+}
+
+#[cfg(test)]
+mod tests {
+    use config::Locations;
+    use locations;
+
+    use params::{Map, Value};
+
+    fn configured() -> Locations {
+        Locations {
+            aliases: vec![
+                ("Köln".to_owned(), "Cologne".to_owned()),
+                ("NYC".to_owned(), "New York".to_owned()),
+            ]
+            .into_iter()
+            .collect(),
+        }
+    }
+
+    #[test]
+    fn test_canonicalize() {
+        let config = configured();
+        assert_eq!(locations::canonicalize(&config, "Köln"), "Cologne");
+        assert_eq!(locations::canonicalize(&config, " nyc "), "New York");
+        assert_eq!(locations::canonicalize(&config, "Cologne"), "Cologne");
+        assert_eq!(locations::canonicalize(&config, "Berlin"), "Berlin");
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut params = Map::new();
+        params.assign("work_locations[]", Value::String("Köln".to_owned())).unwrap();
+        params.assign("work_locations[]", Value::String("Berlin".to_owned())).unwrap();
+        params.assign("current_location", Value::String("NYC".to_owned())).unwrap();
+        params.assign("keywords", Value::String("NYC".to_owned())).unwrap();
+
+        let params = locations::apply(&configured(), &params);
+        assert_eq!(
+            params.get("work_locations"),
+            Some(&Value::Array(vec![
+                Value::String("Cologne".to_owned()),
+                Value::String("Berlin".to_owned()),
+            ]))
+        );
+        assert_eq!(
+            params.get("current_location"),
+            Some(&Value::String("New York".to_owned()))
+        );
+        assert_eq!(params.get("keywords"), Some(&Value::String("NYC".to_owned())));
+    }
+}
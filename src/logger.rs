@@ -1,4 +1,5 @@
 use config::Config;
+use context;
 use log::{self, Log, LogLevel, LogLevelFilter, LogMetadata, LogRecord, SetLoggerError};
 use monitor::{Monitor, MonitorProvider};
 
@@ -39,7 +40,9 @@ impl<T: Monitor> Log for Logger<T> {
             let error_message = format!("{} - {}", record.level(), record.args());
 
             if record.level() == LogLevel::Error {
-                self.monitor.send(&error_message, record.location());
+                let context = context::current();
+                self.monitor
+                    .send(&error_message, record.location(), context.as_ref());
             }
 
             println!("{}", error_message);
@@ -2,28 +2,180 @@ extern crate backtrace;
 extern crate searchspot;
 #[macro_use]
 extern crate router;
+#[macro_use]
+extern crate serde_json;
 
 use backtrace::Backtrace;
+use router::Router;
+use searchspot::alerts;
+use searchspot::backends::{self, SearchBackend};
+use searchspot::backfill;
+use searchspot::cleanup;
 use searchspot::config::Config;
+use searchspot::context;
+use searchspot::grpc;
+use searchspot::heartbeat;
+use searchspot::ingestion;
+use searchspot::migrations;
 use searchspot::monitor::{Monitor, MonitorProvider};
-use searchspot::resources::{Score, Talent};
-use searchspot::server::Server;
-use searchspot::server::{DeletableHandler, IndexableHandler, ResettableHandler, SearchableHandler};
-use std::{env, panic};
+use searchspot::resource::{split_indexes, IndexSet, Resource};
+use searchspot::resources::{Company, Job, Score, Talent};
+use searchspot::server::{ApiVersion, Server};
+use searchspot::server::{BatchDeletionHandler, BatchHidingHandler, BatchesHandler,
+                         BulkDeletionHandler, DeletableHandler, DeletedTalentsHandler, DistinctCountsHandler,
+                         FeaturesHandler, HydratableHandler, IndexSwitchHandler, IndexableHandler,
+                         JobTalentsHandler, MaintenanceHandler, MetricsHandler, MigratableHandler,
+                         OpenApiHandler, RawQueryHandler, RecommendationsHandler, RefreshHandler,
+                         ResettableHandler, RestoreHandler, SalaryDistributionHandler,
+                         ScoreStatsHandler, ScoresDeletionHandler, ScoresIndexingHandler,
+                         SearchableHandler, TalentExportHandler, TalentStreamHandler};
+use std::{env, panic, process};
+
+/// Add the routes to an existing `router`, as `router!` does to a new one.
+macro_rules! route {
+    ($router:ident, { $($name:ident: $method:ident $glob:expr => $handler:expr),+ $(,)* }) => {
+        $($router.$method($glob, $handler, stringify!($name));)+
+    };
+}
+
+const USAGE: &'static str = "Usage: searchspot [COMMAND] [CONFIG_FILE]
+
+Commands:
+  serve         Start the server (default)
+  reset-index   Delete and recreate the index with the latest mapping
+  reindex       Apply the pending migrations, reindexing the documents
+  check-config  Validate the configuration and print it
+  health        Check that the index can be searched
+  backfill      Index the talents read from the database (with --from-postgres)
+
+Options:
+  --check          Validate the configuration and exit
+  --from-postgres  Read the talents to backfill from `postgres.query`
+
+The configuration is read from the environment when CONFIG_FILE is omitted.";
 
 fn main() {
-    let config = match env::args().nth(1) {
-        Some(file) => Config::from_file(file),
-        None => Config::from_env(),
+    let mut args: Vec<String> = env::args().skip(1).collect();
+
+    let check = args.iter().any(|arg| arg == "--check");
+    let from_postgres = args.iter().any(|arg| arg == "--from-postgres");
+    args.retain(|arg| arg != "--check" && arg != "--from-postgres");
+
+    // `searchspot config.toml` is still accepted as a shortcut for `serve`
+    let command = match args.first().map(|arg| &**arg) {
+        Some("serve") | Some("reset-index") | Some("reindex") | Some("check-config")
+        | Some("health") | Some("backfill") => args.remove(0),
+        Some("help") | Some("--help") | Some("-h") => {
+            println!("{}", USAGE);
+            return;
+        }
+        _ => "serve".to_owned(),
     };
 
+    if args.len() > 1 {
+        fail(USAGE);
+    }
+
+    let config = Config::load(args.pop()).unwrap_or_else(|errors| fail(errors.to_string()));
+
+    if check {
+        println!("The configuration is valid.");
+        return;
+    }
+
+    match &*command {
+        "reset-index" => reset_index(&config),
+        "reindex" => reindex(&config),
+        "check-config" => check_config(&config),
+        "health" => health(&config),
+        "backfill" if from_postgres => backfill_from_postgres(&config),
+        "backfill" => fail("The source of the backfill must be given (i.e. --from-postgres)."),
+        _ => serve(config),
+    }
+}
+
+fn fail<S: AsRef<str>>(message: S) -> ! {
+    eprintln!("{}", message.as_ref());
+    process::exit(1);
+}
+
+fn connect(config: &Config) -> Box<dyn SearchBackend> {
+    backends::from_config(&config.es)
+        .unwrap_or_else(|error| fail(format!("Cannot connect to {}: {}", config.es, error)))
+}
+
+fn reset_index(config: &Config) {
+    let definition = config.es.index_definition().unwrap_or_else(|error| fail(error));
+    let mut client = connect(config);
+
+    match Talent::reset_index(&mut *client, &config.es.index, definition.as_ref()) {
+        Ok(_) => println!("The index {} has been reset.", config.es.index),
+        Err(error) => fail(format!("Cannot reset {}: {}", config.es.index, error)),
+    }
+}
+
+fn reindex(config: &Config) {
+    let definition = config.es.index_definition().unwrap_or_else(|error| fail(error));
+    let mut client = connect(config);
+
+    match migrations::migrate::<Talent>(&mut *client, &config.es.index, definition.as_ref()) {
+        Ok(report) => println!(
+            "Migrated {} from version {} to {}: {} documents reindexed.",
+            config.es.index, report.from, report.to, report.documents
+        ),
+        Err(error) => fail(format!("Cannot migrate {}: {}", config.es.index, error)),
+    }
+}
+
+/// The configuration has already been validated by `Config::load`.
+fn check_config(config: &Config) {
+    println!("{}", config);
+}
+
+fn health(config: &Config) {
+    let mut client = connect(config);
+    let indexes = split_indexes(&config.es.index);
+
+    match client.search_json(&indexes, &json!({ "size": 0 })) {
+        Ok(result) => println!("OK: {} ({} documents)", config.es, result.hits.total),
+        Err(error) => fail(format!("{} is not available: {}", config.es, error)),
+    }
+}
+
+fn backfill_from_postgres(config: &Config) {
+    let postgres = match config.postgres {
+        Some(ref postgres) => postgres,
+        None => fail("PostgreSQL is not configured (see `postgres.url` and `postgres.query`)."),
+    };
+    let mut client = connect(config);
+    let indexes = IndexSet::from_config(&config.es);
+
+    match backfill::from_postgres::<Talent>(&mut *client, &indexes, config, postgres) {
+        Ok(indexed) => println!("Backfilled {} talents into {}.", indexed, indexes),
+        Err(error) => fail(format!("Cannot backfill {}: {}", indexes, error)),
+    }
+}
+
+fn serve(config: Config) {
+    // without a monitor, the request being handled is printed along with the panic
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |panic_info| {
+        if let Some(context) = context::current() {
+            eprintln!("Panicked while handling {:?}", context);
+        }
+        default_hook(panic_info);
+    }));
+
     if let Some(monitor) = config.monitor.to_owned() {
         if monitor.enabled == true {
             match MonitorProvider::find_with_config(&monitor.provider, &monitor) {
                 Some(monitor) => {
                     panic::set_hook(Box::new(move |panic_info| {
                         let backtrace = Backtrace::new();
-                        let _ = monitor.send_panic(panic_info, &backtrace).join();
+                        let context = context::current();
+                        let _ = monitor
+                            .send_panic(panic_info, &backtrace, context.as_ref())
+                            .join();
                     }));
                 }
                 None => {
@@ -35,16 +187,131 @@ fn main() {
 
     let _ = panic::catch_unwind(|| {
         let server = Server::new(config.to_owned());
+        grpc::start(&config, server.maintenance());
+
+        // the read-only replicas leave the writes (and the alerts, not to notify
+        // every talent once per instance) to the other instances, and don't
+        // ping the dead man's switch on their behalf
+        if !config.read_only {
+            heartbeat::schedule(&config);
+            alerts::schedule(&config);
+            cleanup::schedule::<Talent>(&config);
+            cleanup::schedule::<Score>(&config);
+            ingestion::start::<Talent>(&config);
+        }
 
-        let router = router!{
+        let mut router = router!{
           get_talents:    get    "/talents" => SearchableHandler::<Talent>::new(config.to_owned()),
-          create_talents: post   "/talents" => IndexableHandler::<Talent>::new(config.to_owned()),
-          delete_talents: delete "/talents" => ResettableHandler::<Talent>::new(config.to_owned()),
-          delete_talent:  delete "/talents/:id" => DeletableHandler::<Talent>::new(config.to_owned()),
+          hydrate_talents: post  "/talents/hydrate" => HydratableHandler::<Talent>::new(config.to_owned()),
+          recommend_talent: get  "/talents/:id/recommendations" => RecommendationsHandler::new(config.to_owned()),
+          talent_salaries:  get  "/talents/salaries" => SalaryDistributionHandler::new(config.to_owned()),
+          stream_talents:   get  "/talents/stream" => TalentStreamHandler::new(config.to_owned()),
+          export_talents:   get  "/talents/export" => TalentExportHandler::new(config.to_owned()),
+          get_batches:      get    "/talents/batches" => BatchesHandler::new(config.to_owned()),
 
-          create_scores: post "/scores" => IndexableHandler::<Score>::new(config.to_owned()),
+          get_scores:    get  "/scores" => SearchableHandler::<Score>::new(config.to_owned()),
+
+          job_talents:  get  "/jobs/:id/talents" => JobTalentsHandler::new(config.to_owned()),
+
+          get_companies:    get  "/companies" => SearchableHandler::<Company>::new(config.to_owned()),
+
+          get_talents_v2:    get    "/v2/talents" => SearchableHandler::<Talent>::new(config.to_owned()).with_version(ApiVersion::V2),
+          hydrate_talents_v2: post  "/v2/talents/hydrate" => HydratableHandler::<Talent>::new(config.to_owned()).with_version(ApiVersion::V2),
+          recommend_talent_v2: get  "/v2/talents/:id/recommendations" => RecommendationsHandler::new(config.to_owned()).with_version(ApiVersion::V2),
+          talent_salaries_v2:  get  "/v2/talents/salaries" => SalaryDistributionHandler::new(config.to_owned()).with_version(ApiVersion::V2),
+          stream_talents_v2:   get  "/v2/talents/stream" => TalentStreamHandler::new(config.to_owned()).with_version(ApiVersion::V2),
+          get_batches_v2:      get    "/v2/talents/batches" => BatchesHandler::new(config.to_owned()).with_version(ApiVersion::V2),
+
+          get_scores_v2:    get  "/v2/scores" => SearchableHandler::<Score>::new(config.to_owned()).with_version(ApiVersion::V2),
+
+          job_talents_v2: get  "/v2/jobs/:id/talents" => JobTalentsHandler::new(config.to_owned()).with_version(ApiVersion::V2),
+
+          get_companies_v2:    get  "/v2/companies" => SearchableHandler::<Company>::new(config.to_owned()).with_version(ApiVersion::V2),
+
+          features:    get "/features" => FeaturesHandler::new(config.to_owned()),
+          features_v2: get "/v2/features" => FeaturesHandler::new(config.to_owned()).with_version(ApiVersion::V2),
+
+          openapi: get "/openapi.json" => OpenApiHandler,
         };
 
-        server.start(router);
+        // the read-only replicas don't even route the writes
+        if !config.read_only {
+          route!(router, {
+            create_talents:  post   "/talents" => IndexableHandler::<Talent>::new(config.to_owned()),
+            delete_talent:   delete "/talents/:id" => DeletableHandler::<Talent>::new(config.to_owned()),
+            bulk_delete_talents: post "/talents/delete" => BulkDeletionHandler::<Talent>::new(config.to_owned()),
+            restore_talent:  post   "/talents/:id/restore" => RestoreHandler::new(config.to_owned()),
+            delete_batch:    delete "/talents/batches" => BatchDeletionHandler::new(config.to_owned()),
+            hide_batch:      post   "/talents/batches/hide" => BatchHidingHandler::new(config.to_owned()),
+            refresh_talents: post   "/talents/refresh" => RefreshHandler::new(config.to_owned()),
+
+            create_scores: post   "/scores" => ScoresIndexingHandler::new(config.to_owned()),
+            delete_scores: delete "/scores" => ScoresDeletionHandler::new(config.to_owned()),
+
+            create_jobs:      post "/jobs" => IndexableHandler::<Job>::new(config.to_owned()),
+            create_companies: post "/companies" => IndexableHandler::<Company>::new(config.to_owned()),
+
+            create_talents_v2:  post   "/v2/talents" => IndexableHandler::<Talent>::new(config.to_owned()).with_version(ApiVersion::V2),
+            delete_talent_v2:   delete "/v2/talents/:id" => DeletableHandler::<Talent>::new(config.to_owned()).with_version(ApiVersion::V2),
+            bulk_delete_talents_v2: post "/v2/talents/delete" => BulkDeletionHandler::<Talent>::new(config.to_owned()).with_version(ApiVersion::V2),
+            restore_talent_v2:  post   "/v2/talents/:id/restore" => RestoreHandler::new(config.to_owned()).with_version(ApiVersion::V2),
+            delete_batch_v2:    delete "/v2/talents/batches" => BatchDeletionHandler::new(config.to_owned()).with_version(ApiVersion::V2),
+            hide_batch_v2:      post   "/v2/talents/batches/hide" => BatchHidingHandler::new(config.to_owned()).with_version(ApiVersion::V2),
+            refresh_talents_v2: post   "/v2/talents/refresh" => RefreshHandler::new(config.to_owned()).with_version(ApiVersion::V2),
+
+            create_scores_v2: post   "/v2/scores" => ScoresIndexingHandler::new(config.to_owned()).with_version(ApiVersion::V2),
+            delete_scores_v2: delete "/v2/scores" => ScoresDeletionHandler::new(config.to_owned()).with_version(ApiVersion::V2),
+
+            create_jobs_v2:      post "/v2/jobs" => IndexableHandler::<Job>::new(config.to_owned()).with_version(ApiVersion::V2),
+            create_companies_v2: post "/v2/companies" => IndexableHandler::<Company>::new(config.to_owned()).with_version(ApiVersion::V2),
+          });
+        }
+
+        match config.http.admin {
+            Some(_) => {
+                let mut admin = Router::new();
+                route_admin(&mut admin, &config);
+                server.start_with_admin(router, Some(admin));
+            }
+            None => {
+                route_admin(&mut router, &config);
+                server.start(router);
+            }
+        }
+    });
+}
+
+/// Add the admin endpoints (reset, reindex, index switch, maintenance, stats, distinct counts,
+/// raw queries, deleted talents and metrics) to `router`, which is the one of the admin listener when `http.admin` is configured.
+fn route_admin(router: &mut Router, config: &Config) {
+    route!(router, {
+      score_stats:    get "/scores/stats" => ScoreStatsHandler::new(config.to_owned()),
+      score_stats_v2: get "/v2/scores/stats" => ScoreStatsHandler::new(config.to_owned()).with_version(ApiVersion::V2),
+
+      distinct_talents:    get "/admin/talents/distinct" => DistinctCountsHandler::new(config.to_owned()),
+      distinct_talents_v2: get "/v2/admin/talents/distinct" => DistinctCountsHandler::new(config.to_owned()).with_version(ApiVersion::V2),
+
+      query_talents:      post "/admin/talents/query" => RawQueryHandler::new(config.to_owned()),
+      deleted_talents:    get  "/admin/talents/deleted" => DeletedTalentsHandler::new(config.to_owned()),
+      query_talents_v2:   post "/v2/admin/talents/query" => RawQueryHandler::new(config.to_owned()).with_version(ApiVersion::V2),
+      deleted_talents_v2: get  "/v2/admin/talents/deleted" => DeletedTalentsHandler::new(config.to_owned()).with_version(ApiVersion::V2),
+
+      metrics: get "/metrics" => MetricsHandler,
+
+      get_maintenance: get "/maintenance" => MaintenanceHandler::new(config.to_owned()),
+      put_maintenance: put "/maintenance" => MaintenanceHandler::new(config.to_owned()),
     });
+
+    if !config.read_only {
+      route!(router, {
+        delete_talents:  delete "/talents" => ResettableHandler::<Talent>::new(config.to_owned()),
+        migrate_talents: post   "/talents/migrations" => MigratableHandler::<Talent>::new(config.to_owned()),
+
+        switch_indexes:  post   "/admin/indexes/switch" => IndexSwitchHandler::new(config.to_owned()),
+
+        delete_talents_v2:  delete "/v2/talents" => ResettableHandler::<Talent>::new(config.to_owned()).with_version(ApiVersion::V2),
+        migrate_talents_v2: post   "/v2/talents/migrations" => MigratableHandler::<Talent>::new(config.to_owned()).with_version(ApiVersion::V2),
+        switch_indexes_v2:  post   "/v2/admin/indexes/switch" => IndexSwitchHandler::new(config.to_owned()).with_version(ApiVersion::V2),
+      });
+    }
 }
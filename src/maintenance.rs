@@ -0,0 +1,181 @@
+//! Maintenance mode: while it's on, the write endpoints (or all of them)
+//! reply with `503 Service Unavailable`, i.e. during the index migrations.
+//! The writes are told apart by their `EndpointClass` rather than by their
+//! method, since some reads are `POST`s (i.e. `POST /talents/hydrate`).
+//! It can be turned on by the configuration and toggled by `PUT /maintenance`.
+
+use iron::mime::Mime;
+use iron::prelude::*;
+use iron::typemap::Key;
+use iron::{status, BeforeMiddleware};
+
+use persistent::State;
+
+use concurrency::EndpointClass;
+
+use serde_json;
+
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// The endpoint that toggles the maintenance mode, which is always available.
+const MAINTENANCE_PATH: &'static str = "maintenance";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Mode {
+    Off,
+    /// Only the reads (the `Search` and `Stream` endpoints) are served.
+    ReadOnly,
+    /// No request is served.
+    Full,
+}
+
+impl Default for Mode {
+    fn default() -> Mode {
+        Mode::Off
+    }
+}
+
+impl FromStr for Mode {
+    type Err = String;
+
+    fn from_str(mode: &str) -> Result<Mode, String> {
+        match mode {
+            "off" => Ok(Mode::Off),
+            "read_only" => Ok(Mode::ReadOnly),
+            "full" => Ok(Mode::Full),
+            _ => Err("expected off, read_only or full".to_owned()),
+        }
+    }
+}
+
+/// The current maintenance mode and the reason given to the rejected requests.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Maintenance {
+    #[serde(default)]
+    pub mode: Mode,
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+impl Maintenance {
+    /// Return whether a request to `path` must be rejected before it's routed:
+    /// only the full maintenance does, the handlers check the read-only one.
+    pub fn rejects(&self, path: &[&str]) -> bool {
+        path.first() != Some(&MAINTENANCE_PATH) && self.mode == Mode::Full
+    }
+
+    /// Return whether a request to an endpoint of `class` must be rejected.
+    pub fn rejects_class(&self, class: EndpointClass) -> bool {
+        match self.mode {
+            Mode::Off => false,
+            Mode::ReadOnly => match class {
+                EndpointClass::Search | EndpointClass::Stream => false,
+                EndpointClass::Index | EndpointClass::Reset => true,
+            },
+            Mode::Full => true,
+        }
+    }
+
+    /// Return the response to the rejected requests, telling the mode and the reason.
+    pub fn rejection(&self) -> (Mime, status::Status, String) {
+        let body = json!({
+            "error":  UnderMaintenance.to_string(),
+            "mode":   self.mode,
+            "reason": self.reason,
+        });
+
+        let content_type = "application/json".parse::<Mime>().unwrap();
+        (content_type, status::ServiceUnavailable, serde_json::to_string(&body).unwrap())
+    }
+}
+
+impl fmt::Display for Maintenance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.mode {
+            Mode::Off => write!(f, "Maintenance mode is off."),
+            Mode::ReadOnly => write!(f, "Maintenance mode is on: only reads are served."),
+            Mode::Full => write!(f, "Maintenance mode is on: no request is served."),
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct MaintenanceState;
+
+impl Key for MaintenanceState {
+    type Value = Maintenance;
+}
+
+#[derive(Debug)]
+struct UnderMaintenance;
+
+impl fmt::Display for UnderMaintenance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "The service is under maintenance")
+    }
+}
+
+impl Error for UnderMaintenance {}
+
+/// Reject every request under the full maintenance, before it's routed: the
+/// read-only one is up to the handlers, which know the class of their endpoint.
+/// Requires `State<MaintenanceState>` to be linked first.
+pub struct MaintenanceMiddleware;
+
+impl BeforeMiddleware for MaintenanceMiddleware {
+    fn before(&self, req: &mut Request) -> IronResult<()> {
+        let maintenance = req.get::<State<MaintenanceState>>().unwrap();
+        let maintenance = maintenance.read().unwrap();
+
+        if !maintenance.rejects(&req.url.path()) {
+            return Ok(());
+        }
+
+        Err(IronError::new(UnderMaintenance, maintenance.rejection()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use concurrency::EndpointClass;
+    use maintenance::{Maintenance, Mode};
+
+    #[test]
+    fn test_rejects() {
+        let mut maintenance = Maintenance::default();
+        assert!(!maintenance.rejects(&["talents"]));
+        assert!(!maintenance.rejects_class(EndpointClass::Index));
+
+        maintenance.mode = Mode::ReadOnly;
+        assert!(!maintenance.rejects(&["talents"]));
+        assert!(!maintenance.rejects_class(EndpointClass::Search));
+        assert!(!maintenance.rejects_class(EndpointClass::Stream));
+        assert!(maintenance.rejects_class(EndpointClass::Index));
+        assert!(maintenance.rejects_class(EndpointClass::Reset));
+
+        maintenance.mode = Mode::Full;
+        assert!(maintenance.rejects(&["talents"]));
+        assert!(maintenance.rejects_class(EndpointClass::Search));
+        assert!(!maintenance.rejects(&["maintenance"]));
+    }
+
+    #[test]
+    fn test_read_only_hydration() {
+        // `POST /talents/hydrate` is a read despite its method, and is served
+        let maintenance = Maintenance {
+            mode: Mode::ReadOnly,
+            reason: Some("Migrating the index".to_owned()),
+        };
+        assert!(!maintenance.rejects(&["talents", "hydrate"]));
+        assert!(!maintenance.rejects_class(EndpointClass::Search));
+    }
+
+    #[test]
+    fn test_mode_from_str() {
+        assert_eq!("read_only".parse::<Mode>(), Ok(Mode::ReadOnly));
+        assert!("readonly".parse::<Mode>().is_err());
+    }
+}
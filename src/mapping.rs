@@ -0,0 +1,319 @@
+use serde_json::{self, Map, Value};
+
+use languages::Language;
+use resource::Settings;
+
+use toml;
+
+use std::fs::File;
+use std::io::prelude::*;
+
+/// Mappings and analysis settings that replace the built-in ones of a resource.
+///
+/// Every part is optional: what's missing falls back to the built-in definition.
+/// When present, `mappings`, `number_of_shards`, `analysis.filter` and
+/// `analysis.analyzer` replace their built-in counterpart as a whole.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct IndexDefinition {
+    pub mappings: Option<Value>,
+    #[serde(default)]
+    pub settings: IndexSettings,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct IndexSettings {
+    pub number_of_shards: Option<u32>,
+    #[serde(default)]
+    pub analysis: IndexAnalysis,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct IndexAnalysis {
+    pub filter: Option<Map<String, Value>>,
+    pub analyzer: Option<Map<String, Value>>,
+    /// When `false`, the `asciifolding` filter is removed from every analyzer
+    /// (see `es.ascii_folding`).
+    pub ascii_folding: Option<bool>,
+    /// When given, the analyzers, filters and sub-fields of the other
+    /// languages are removed (see `es.languages`).
+    pub languages: Option<Vec<Language>>,
+    /// When given, the terms kept whole by the `protect_keywords` filter
+    /// (see `es.protected_keywords`).
+    pub protected_keywords: Option<Vec<String>>,
+}
+
+/// The built-in filter that folds accented letters into their ASCII counterpart.
+const ASCII_FOLDING: &'static str = "asciifolding";
+
+/// The filter that keeps the tech keywords whole.
+const PROTECT_KEYWORDS: &'static str = "protect_keywords";
+
+impl IndexDefinition {
+    /// Read and parse the definition at `path`. Files ending with `.toml`
+    /// are parsed as TOML, anything else as JSON.
+    pub fn from_file(path: &str) -> Result<IndexDefinition, String> {
+        let mut file = File::open(path)
+            .map_err(|err| format!("Error while reading mapping file {}: {}", path, err))?;
+
+        let mut content = String::new();
+        file.read_to_string(&mut content)
+            .map_err(|err| format!("Error while reading mapping file {}: {}", path, err))?;
+
+        if path.ends_with(".toml") {
+            IndexDefinition::parse_toml(&content)
+        } else {
+            IndexDefinition::parse_json(&content)
+        }
+    }
+
+    pub fn parse_json(json: &str) -> Result<IndexDefinition, String> {
+        serde_json::from_str(json)
+            .map_err(|err| format!("Error while parsing the mapping file: {}", err))
+    }
+
+    pub fn parse_toml(toml: &str) -> Result<IndexDefinition, String> {
+        toml::from_str(toml).map_err(|err| format!("Error while parsing the mapping file: {}", err))
+    }
+
+    /// Return the mappings of this definition, or `builtin` if none is given.
+    pub fn mappings_or(&self, builtin: Value) -> Value {
+        self.mappings.to_owned().unwrap_or(builtin)
+    }
+
+    /// Replace the parts of `settings` defined here.
+    pub fn apply_to_settings(&self, settings: &mut Settings) {
+        if let Some(number_of_shards) = self.settings.number_of_shards {
+            settings.number_of_shards = number_of_shards;
+        }
+
+        if let Some(ref filter) = self.settings.analysis.filter {
+            settings.analysis.filter = filter.to_owned();
+        }
+
+        if let Some(ref analyzer) = self.settings.analysis.analyzer {
+            settings.analysis.analyzer = analyzer.to_owned();
+        }
+    }
+
+    /// Return this definition with `asciifolding` turned on or off.
+    pub fn with_ascii_folding(mut self, enabled: bool) -> IndexDefinition {
+        self.settings.analysis.ascii_folding = Some(enabled);
+        self
+    }
+
+    /// Return this definition with only the given `languages` analyzed.
+    pub fn with_languages(mut self, languages: Vec<Language>) -> IndexDefinition {
+        self.settings.analysis.languages = Some(languages);
+        self
+    }
+
+    /// Return this definition with `keywords` kept whole by `protect_keywords`.
+    pub fn with_protected_keywords(mut self, keywords: Vec<String>) -> IndexDefinition {
+        self.settings.analysis.protected_keywords = Some(keywords);
+        self
+    }
+
+    /// Return the known languages that aren't analyzed according to this definition.
+    fn disabled_languages(&self) -> Vec<Language> {
+        match self.settings.analysis.languages {
+            Some(ref enabled) => Language::ALL
+                .iter()
+                .cloned()
+                .filter(|language| !enabled.contains(language))
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    /// Remove the filters and the analyzers disabled here from `settings`
+    /// and set the terms of `protect_keywords`, if given.
+    /// Unlike `apply_to_settings`, this runs after the migrations defined their analyzers.
+    pub fn adjust_settings(&self, settings: &mut Settings) {
+        let ascii_folding = self.settings.analysis.ascii_folding != Some(false);
+
+        if let Some(ref keywords) = self.settings.analysis.protected_keywords {
+            let filter = settings.analysis.filter.get_mut(PROTECT_KEYWORDS);
+            if let Some(filter) = filter.and_then(Value::as_object_mut) {
+                filter.insert("keywords".to_owned(), json!(keywords));
+            }
+        }
+
+        for language in self.disabled_languages() {
+            settings.analysis.analyzer.remove(language.name());
+            let filters: Vec<String> = settings
+                .analysis
+                .filter
+                .keys()
+                .filter(|name| language.owns_filter(name))
+                .cloned()
+                .collect();
+            for filter in filters.iter() {
+                settings.analysis.filter.remove(filter);
+            }
+        }
+
+        if ascii_folding {
+            return;
+        }
+
+        for analyzer in settings.analysis.analyzer.values_mut() {
+            if let Some(filters) = analyzer.get_mut("filter").and_then(Value::as_array_mut) {
+                filters.retain(|filter| *filter != ASCII_FOLDING);
+            }
+        }
+    }
+
+    /// Remove the sub-fields of the languages disabled here from `mappings`.
+    pub fn adjust_mappings(&self, mappings: &mut Value) {
+        let disabled = self.disabled_languages();
+        if !disabled.is_empty() {
+            remove_sub_fields(mappings, &disabled);
+        }
+    }
+}
+
+/// Remove the sub-fields named after `languages` wherever they are in `mapping`.
+fn remove_sub_fields(mapping: &mut Value, languages: &[Language]) {
+    if let Some(mapping) = mapping.as_object_mut() {
+        if let Some(fields) = mapping.get_mut("fields").and_then(Value::as_object_mut) {
+            for language in languages.iter() {
+                fields.remove(language.name());
+            }
+        }
+
+        for (_, child) in mapping.iter_mut() {
+            remove_sub_fields(child, languages);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mapping::IndexDefinition;
+    use resource::Settings;
+
+    #[test]
+    fn test_parse_json() {
+        let definition = IndexDefinition::parse_json(
+            r#"{
+              "settings": {
+                "analysis": {
+                  "filter": {
+                    "protect_keywords": { "type": "keyword_marker", "keywords": ["C++", "F#"] }
+                  }
+                }
+              }
+            }"#,
+        ).unwrap();
+
+        assert!(definition.mappings.is_none());
+        assert!(definition.settings.number_of_shards.is_none());
+        assert!(definition.settings.analysis.analyzer.is_none());
+
+        let filter = definition.settings.analysis.filter.unwrap();
+        assert_eq!(filter["protect_keywords"]["keywords"], json!(["C++", "F#"]));
+    }
+
+    #[test]
+    fn test_parse_toml() {
+        let definition = IndexDefinition::parse_toml(
+            r#"
+            [settings]
+            number_of_shards = 3
+
+            [settings.analysis.analyzer.keywords]
+            type      = "custom"
+            tokenizer = "standard"
+            filter    = ["lowercase", "trim"]
+            "#,
+        ).unwrap();
+
+        assert_eq!(definition.settings.number_of_shards, Some(3));
+
+        let analyzer = definition.settings.analysis.analyzer.unwrap();
+        assert_eq!(analyzer["keywords"]["filter"], json!(["lowercase", "trim"]));
+    }
+
+    #[test]
+    fn test_adjust_settings() {
+        let mut settings = Settings::default();
+        settings.analysis.analyzer.insert(
+            "folded".to_owned(),
+            json!({ "tokenizer": "keyword", "filter": ["lowercase", "asciifolding"] }),
+        );
+
+        IndexDefinition::default().adjust_settings(&mut settings);
+        assert_eq!(
+            settings.analysis.analyzer["folded"]["filter"],
+            json!(["lowercase", "asciifolding"])
+        );
+
+        let definition = IndexDefinition::default().with_ascii_folding(false);
+        definition.adjust_settings(&mut settings);
+        assert_eq!(settings.analysis.analyzer["folded"]["filter"], json!(["lowercase"]));
+    }
+
+    #[test]
+    fn test_protected_keywords() {
+        let mut settings = Settings::default();
+        settings.analysis.filter.insert(
+            "protect_keywords".to_owned(),
+            json!({ "type": "keyword_marker", "keywords": ["C++"], "ignore_case": true }),
+        );
+
+        let definition = IndexDefinition::default().with_protected_keywords(vec!["F#".to_owned()]);
+        definition.adjust_settings(&mut settings);
+        assert_eq!(
+            settings.analysis.filter["protect_keywords"],
+            json!({ "type": "keyword_marker", "keywords": ["F#"], "ignore_case": true })
+        );
+    }
+
+    #[test]
+    fn test_remove_disabled_languages() {
+        let mut settings = Settings::default();
+        settings.analysis.filter.insert("german_stop".to_owned(), json!({ "type": "stop" }));
+        settings.analysis.filter.insert("strip_js".to_owned(), json!({ "type": "pattern_replace" }));
+        settings.analysis.analyzer.insert("german".to_owned(), json!({ "filter": ["german_stop"] }));
+
+        let mut mappings = json!({
+          "talent": {
+            "properties": {
+              "summary": {
+                "type": "text",
+                "fields": { "raw": { "type": "keyword" }, "german": { "type": "text" } }
+              }
+            }
+          }
+        });
+        let expected = mappings.clone();
+
+        let definition = IndexDefinition::default();
+        definition.adjust_settings(&mut settings);
+        definition.adjust_mappings(&mut mappings);
+        assert!(settings.analysis.analyzer.contains_key("german"));
+        assert_eq!(mappings, expected);
+
+        let definition = IndexDefinition::default().with_languages(vec![]);
+        definition.adjust_settings(&mut settings);
+        definition.adjust_mappings(&mut mappings);
+        assert!(!settings.analysis.analyzer.contains_key("german"));
+        assert!(!settings.analysis.filter.contains_key("german_stop"));
+        assert!(settings.analysis.filter.contains_key("strip_js"));
+        assert_eq!(
+            mappings["talent"]["properties"]["summary"]["fields"],
+            json!({ "raw": { "type": "keyword" } })
+        );
+    }
+
+    #[test]
+    fn test_mappings_fallback() {
+        let builtin = json!({ "talent": { "properties": {} } });
+
+        let definition = IndexDefinition::default();
+        assert_eq!(definition.mappings_or(builtin.clone()), builtin);
+
+        let definition = IndexDefinition::parse_json(r#"{ "mappings": { "talent": {} } }"#).unwrap();
+        assert_eq!(definition.mappings_or(builtin), json!({ "talent": {} }));
+    }
+}
@@ -1,5 +1,4 @@
-use rs_es::query::full_text::MatchType;
-use rs_es::query::Query;
+use query::{MatchType, Query};
 
 pub trait VectorOfMatches<T> {
     /// Extract the elements inside `Vec<T>` into `Vec<Filter>`, if present.
@@ -31,8 +30,7 @@ impl VectorOfMatches<String> for Query {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use rs_es::query::full_text::MatchType;
-    use rs_es::query::Query;
+    use query::{MatchType, Query};
     use serde_json;
 
     #[test]
@@ -0,0 +1,174 @@
+//! Histograms of the latency and of the payload size of the requests sent
+//! to the search backend, split by endpoint and result status, and counters
+//! of the noteworthy failures, exposed in the Prometheus text format by
+//! `GET /metrics`.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// The upper bounds of the buckets of the latency histograms, in seconds.
+pub const LATENCY_BUCKETS: &'static [f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// The upper bounds of the buckets of the size histograms, in documents.
+pub const SIZE_BUCKETS: &'static [f64] = &[
+    0.0, 1.0, 5.0, 10.0, 50.0, 100.0, 500.0, 1000.0, 5000.0,
+];
+
+/// A Prometheus histogram: `counts[i]` is the number of values
+/// not greater than `buckets[i]`, excluding the previous buckets.
+#[derive(Debug, Clone)]
+pub struct Histogram {
+    buckets: &'static [f64],
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    pub fn new(buckets: &'static [f64]) -> Histogram {
+        Histogram {
+            buckets: buckets,
+            counts: vec![0; buckets.len()],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    pub fn observe(&mut self, value: f64) {
+        if let Some(bucket) = self.buckets.iter().position(|bound| value <= *bound) {
+            self.counts[bucket] += 1;
+        }
+
+        self.sum += value;
+        self.count += 1;
+    }
+}
+
+/// A metric name and its labels, already formatted (i.e. `endpoint="talents",status="ok"`).
+type Series = (&'static str, String);
+
+lazy_static! {
+    static ref HISTOGRAMS: Mutex<BTreeMap<Series, Histogram>> = Mutex::new(BTreeMap::new());
+    static ref COUNTERS: Mutex<BTreeMap<Series, u64>> = Mutex::new(BTreeMap::new());
+}
+
+fn observe(name: &'static str, buckets: &'static [f64], endpoint: &str, ok: bool, value: f64) {
+    let labels = format!(
+        "endpoint=\"{}\",status=\"{}\"",
+        endpoint,
+        if ok { "ok" } else { "error" }
+    );
+
+    HISTOGRAMS
+        .lock()
+        .unwrap()
+        .entry((name, labels))
+        .or_insert_with(|| Histogram::new(buckets))
+        .observe(value);
+}
+
+/// Record the time elapsed since `started` in the latency histogram `name`.
+pub fn record_latency(name: &'static str, endpoint: &str, ok: bool, started: Instant) {
+    let elapsed = started.elapsed();
+    let seconds = elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) / 1e9;
+    observe(name, LATENCY_BUCKETS, endpoint, ok, seconds);
+}
+
+/// Record `size` in the size histogram `name`.
+pub fn record_size(name: &'static str, endpoint: &str, ok: bool, size: usize) {
+    observe(name, SIZE_BUCKETS, endpoint, ok, size as f64);
+}
+
+/// Increment the counter `name` of `endpoint`, labelled with `field`.
+pub fn increment(name: &'static str, endpoint: &str, field: &str) {
+    let labels = format!("endpoint=\"{}\",field=\"{}\"", endpoint, field.replace('"', "\\\""));
+    *COUNTERS.lock().unwrap().entry((name, labels)).or_insert(0) += 1;
+}
+
+/// Render every histogram and every counter in the Prometheus text format.
+pub fn render() -> String {
+    let histograms = HISTOGRAMS.lock().unwrap();
+    let mut output = String::new();
+    let mut last_name = "";
+
+    for (&(name, ref labels), histogram) in histograms.iter() {
+        if name != last_name {
+            let _ = writeln!(output, "# TYPE {} histogram", name);
+            last_name = name;
+        }
+
+        let mut cumulative = 0;
+        for (bound, count) in histogram.buckets.iter().zip(histogram.counts.iter()) {
+            cumulative += count;
+            let _ = writeln!(output, "{}_bucket{{{},le=\"{}\"}} {}", name, labels, bound, cumulative);
+        }
+
+        let _ = writeln!(output, "{}_bucket{{{},le=\"+Inf\"}} {}", name, labels, histogram.count);
+        let _ = writeln!(output, "{}_sum{{{}}} {}", name, labels, histogram.sum);
+        let _ = writeln!(output, "{}_count{{{}}} {}", name, labels, histogram.count);
+    }
+
+    let counters = COUNTERS.lock().unwrap();
+    for (&(name, ref labels), count) in counters.iter() {
+        if name != last_name {
+            let _ = writeln!(output, "# TYPE {} counter", name);
+            last_name = name;
+        }
+
+        let _ = writeln!(output, "{}{{{}}} {}", name, labels, count);
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use metrics::{self, Histogram};
+
+    #[test]
+    fn test_histogram() {
+        let mut histogram = Histogram::new(&[1.0, 10.0]);
+        histogram.observe(0.5);
+        histogram.observe(1.0);
+        histogram.observe(5.0);
+        histogram.observe(50.0);
+
+        assert_eq!(histogram.counts, vec![2, 1]);
+        assert_eq!(histogram.count, 4);
+        assert_eq!(histogram.sum, 56.5);
+    }
+
+    #[test]
+    fn test_render() {
+        metrics::record_size("searchspot_test_documents", "tests", true, 3);
+        metrics::record_size("searchspot_test_documents", "tests", true, 300);
+
+        let output = metrics::render();
+        assert!(output.contains("# TYPE searchspot_test_documents histogram\n"));
+        assert!(output.contains(
+            "searchspot_test_documents_bucket{endpoint=\"tests\",status=\"ok\",le=\"10\"} 1\n"
+        ));
+        assert!(output.contains(
+            "searchspot_test_documents_bucket{endpoint=\"tests\",status=\"ok\",le=\"+Inf\"} 2\n"
+        ));
+        assert!(output.contains("searchspot_test_documents_sum{endpoint=\"tests\",status=\"ok\"} 303\n"));
+    }
+
+    #[test]
+    fn test_counters() {
+        metrics::increment("searchspot_test_failures_total", "tests", "weight");
+        metrics::increment("searchspot_test_failures_total", "tests", "weight");
+        metrics::increment("searchspot_test_failures_total", "tests", "skills");
+
+        let output = metrics::render();
+        assert!(output.contains("# TYPE searchspot_test_failures_total counter\n"));
+        assert!(output
+            .contains("searchspot_test_failures_total{endpoint=\"tests\",field=\"weight\"} 2\n"));
+        assert!(output
+            .contains("searchspot_test_failures_total{endpoint=\"tests\",field=\"skills\"} 1\n"));
+    }
+}
@@ -0,0 +1,778 @@
+use chrono::prelude::*;
+
+use serde_json::Value;
+
+use std::collections::BTreeMap;
+
+use mapping::IndexDefinition;
+use query::Query;
+use backends::SearchBackend;
+use resource::{Action, EsError, MappingResult, Resource, Settings};
+
+/// The type that we use in ElasticSearch for recording applied migrations.
+const ES_TYPE: &'static str = "migration";
+
+/// How many documents are copied for each bulk request while reindexing.
+const PAGE_SIZE: u64 = 500;
+
+/// How long the scroll of a reindexing is kept alive between two batches.
+const COPY_KEEP_ALIVE: &'static str = "1m";
+
+/// A single change to the mapping of a resource.
+#[derive(Clone, Debug)]
+pub enum Step {
+    /// Add a new field. `name` can be dotted (i.e. `skills.lowercase`)
+    /// to add a sub-field to a field (which becomes a multi-field)
+    /// or a property to a `nested` one.
+    AddField { name: &'static str, mapping: Value },
+
+    /// Change the analyzers used by an existing field.
+    ChangeAnalyzer {
+        field: &'static str,
+        analyzer: &'static str,
+        search_analyzer: Option<&'static str>,
+    },
+
+    /// Add (or replace) the mapping of another type stored in the same index
+    /// (i.e. the scores stored next to the talents).
+    AddType { name: &'static str, mapping: Value },
+
+    /// Add (or replace) an analyzer in the index settings.
+    DefineAnalyzer { name: &'static str, definition: Value },
+
+    /// Add (or replace) a token filter in the index settings.
+    DefineFilter { name: &'static str, definition: Value },
+
+    /// Transform every document while it's being reindexed.
+    Backfill { script: fn(&mut Value) },
+}
+
+/// An ordered set of `Step`s identified by an increasing `version`.
+#[derive(Clone, Debug)]
+pub struct Migration {
+    pub version: u32,
+    pub name: &'static str,
+    pub steps: Vec<Step>,
+}
+
+/// The document stored in `<index>_migrations` for every applied `Migration`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AppliedMigration {
+    pub version: u32,
+    pub name: String,
+    pub applied_at: String,
+}
+
+/// The outcome of `migrate`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct MigrationReport {
+    pub from: u32,
+    pub to: u32,
+    pub applied: Vec<String>,
+    pub documents: u64,
+}
+
+/// A blue/green switch of the serving `alias` (`es.index` unless given) from the
+/// index `from` to the index `to`, which has been created and filled beforehand
+/// (i.e. with a new mapping).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Switch {
+    #[serde(default)]
+    pub alias: Option<String>,
+    pub from: String,
+    pub to: String,
+}
+
+impl Switch {
+    /// Check that the indexes are single (no lists or patterns) and different ones.
+    pub fn validate(&self) -> Result<(), String> {
+        let mut indexes = vec![("from", &self.from), ("to", &self.to)];
+        if let Some(ref alias) = self.alias {
+            indexes.push(("alias", alias));
+        }
+
+        for (name, index) in indexes.into_iter() {
+            if index.is_empty() || index.contains(',') || index.contains('*') {
+                return Err(format!("{} must name a single index", name));
+            }
+        }
+
+        if self.from == self.to {
+            return Err("from and to must be different indexes".to_owned());
+        }
+
+        Ok(())
+    }
+}
+
+/// A `Resource` whose mapping is described by a baseline plus
+/// an ordered list of `Migration`s.
+pub trait Migratable: Resource {
+    /// The ElasticSearch type the mapping refers to.
+    fn doc_type() -> &'static str;
+
+    /// The mapping before any migration has been applied.
+    fn base_mappings() -> Value;
+
+    /// The settings (shards and analysis) before any migration has been applied.
+    fn base_settings() -> Settings;
+
+    /// Every migration written so far, oldest first.
+    fn migrations() -> Vec<Migration>;
+}
+
+/// Return the name of the index that keeps track of the migrations applied to `index`.
+pub fn migrations_index(index: &str) -> String {
+    format!("{}_migrations", index)
+}
+
+/// Return the migrations of `R` sorted by version.
+fn sorted_migrations<R: Migratable>() -> Vec<Migration> {
+    let mut migrations = R::migrations();
+    migrations.sort_by_key(|migration| migration.version);
+    migrations
+}
+
+/// Return the highest version among the migrations of `R`, `0` if there are none.
+pub fn latest_version<R: Migratable>() -> u32 {
+    R::migrations()
+        .iter()
+        .map(|migration| migration.version)
+        .max()
+        .unwrap_or(0)
+}
+
+/// Return the mapping of `R` after applying every migration up to `version`.
+/// The baseline is taken from `definition`, if it provides one, which can
+/// also disable some sub-fields of the result.
+pub fn mappings_at<R: Migratable>(version: u32, definition: Option<&IndexDefinition>) -> Value {
+    let mut mappings = match definition {
+        Some(definition) => definition.mappings_or(R::base_mappings()),
+        None => R::base_mappings(),
+    };
+
+    for migration in sorted_migrations::<R>().iter().filter(|m| m.version <= version) {
+        for step in migration.steps.iter() {
+            apply_to_mappings(&mut mappings, R::doc_type(), step);
+        }
+    }
+
+    if let Some(definition) = definition {
+        definition.adjust_mappings(&mut mappings);
+    }
+
+    mappings
+}
+
+/// Return the settings of `R` after applying every migration up to `version`.
+/// The baseline is overridden by `definition`, if given, which can also
+/// disable some filters of the resulting analyzers.
+pub fn settings_at<R: Migratable>(version: u32, definition: Option<&IndexDefinition>) -> Settings {
+    let mut settings = R::base_settings();
+    if let Some(definition) = definition {
+        definition.apply_to_settings(&mut settings);
+    }
+
+    for migration in sorted_migrations::<R>().iter().filter(|m| m.version <= version) {
+        for step in migration.steps.iter() {
+            match *step {
+                Step::DefineAnalyzer { name, ref definition } => {
+                    settings
+                        .analysis
+                        .analyzer
+                        .insert(name.to_owned(), definition.to_owned());
+                }
+                Step::DefineFilter { name, ref definition } => {
+                    settings
+                        .analysis
+                        .filter
+                        .insert(name.to_owned(), definition.to_owned());
+                }
+                _ => {}
+            }
+        }
+    }
+
+    if let Some(definition) = definition {
+        definition.adjust_settings(&mut settings);
+    }
+
+    settings
+}
+
+/// Find the mapping of the field at the dotted `path`, walking through
+/// the sub-fields of the multi-fields and the `nested` properties.
+fn field_mut<'a>(mappings: &'a mut Value, doc_type: &str, path: &str) -> Option<&'a mut Value> {
+    let mut current = mappings.get_mut(doc_type)?;
+
+    for segment in path.split('.') {
+        let parent = current;
+        let is_sub_field = parent
+            .get("fields")
+            .and_then(|fields| fields.get(segment))
+            .is_some();
+
+        current = if is_sub_field {
+            parent.get_mut("fields")?.get_mut(segment)?
+        } else {
+            parent.get_mut("properties")?.get_mut(segment)?
+        };
+    }
+
+    Some(current)
+}
+
+/// Apply the mapping-related part of `step` to `mappings`.
+/// Steps that don't concern the mapping are ignored.
+pub fn apply_to_mappings(mappings: &mut Value, doc_type: &str, step: &Step) {
+    match *step {
+        Step::AddField { name, ref mapping } => {
+            let (parent, field) = match name.rfind('.') {
+                Some(idx) => (Some(&name[..idx]), &name[idx + 1..]),
+                None => (None, name),
+            };
+
+            let parent = match parent {
+                Some(parent) => field_mut(mappings, doc_type, parent),
+                None => mappings.get_mut(doc_type),
+            };
+
+            match parent {
+                Some(parent) => {
+                    // objects get properties, while any other typed field gets multi-fields
+                    let is_object = parent.get("properties").is_some()
+                        || match parent.get("type").and_then(Value::as_str) {
+                            None | Some("object") | Some("nested") => true,
+                            Some(_) => false,
+                        };
+                    let key = if is_object { "properties" } else { "fields" };
+
+                    if let Some(parent) = parent.as_object_mut() {
+                        if let Some(fields) = parent
+                            .entry(key)
+                            .or_insert(json!({}))
+                            .as_object_mut()
+                        {
+                            fields.insert(field.to_owned(), mapping.to_owned());
+                        }
+                    }
+                }
+                None => error!("Cannot add `{}`: its parent doesn't exist.", name),
+            }
+        }
+
+        Step::ChangeAnalyzer {
+            field,
+            analyzer,
+            search_analyzer,
+        } => {
+            match field_mut(mappings, doc_type, field) {
+                Some(mapping) => {
+                    if let Some(mapping) = mapping.as_object_mut() {
+                        mapping.insert("analyzer".to_owned(), json!(analyzer));
+                        mapping.insert(
+                            "search_analyzer".to_owned(),
+                            json!(search_analyzer.unwrap_or(analyzer)),
+                        );
+                    }
+                }
+                None => error!("Cannot change the analyzer of `{}`: it doesn't exist.", field),
+            }
+        }
+
+        Step::AddType { name, ref mapping } => {
+            if let Some(mappings) = mappings.as_object_mut() {
+                mappings.insert(name.to_owned(), mapping.to_owned());
+            }
+        }
+
+        Step::DefineAnalyzer { .. } | Step::DefineFilter { .. } | Step::Backfill { .. } => {}
+    }
+}
+
+/// Create `index` with the mapping of `R` at the given `version`.
+fn create_with_version<R: Migratable>(
+    es: &mut dyn SearchBackend,
+    index: &str,
+    version: u32,
+    definition: Option<&IndexDefinition>,
+) -> Result<MappingResult, EsError> {
+    let mappings = mappings_at::<R>(version, definition);
+    let settings = settings_at::<R>(version, definition);
+
+    es.create_index(index, &mappings, &settings)
+}
+
+/// Create `index` with the latest mapping of `R` and mark every
+/// known migration as applied.
+pub fn create_index<R: Migratable>(
+    es: &mut dyn SearchBackend,
+    index: &str,
+    definition: Option<&IndexDefinition>,
+) -> Result<MappingResult, EsError> {
+    let result = create_with_version::<R>(es, index, latest_version::<R>(), definition)?;
+
+    if let Err(error) = es.delete_index(&*migrations_index(index)) {
+        info!("{}", error);
+    }
+
+    for migration in sorted_migrations::<R>().iter() {
+        record(es, index, migration)?;
+    }
+
+    Ok(result)
+}
+
+/// Store `migration` as applied to `index`.
+fn record(es: &mut dyn SearchBackend, index: &str, migration: &Migration) -> Result<(), EsError> {
+    let applied = AppliedMigration {
+        version: migration.version,
+        name: migration.name.to_owned(),
+        applied_at: Utc::now().to_rfc3339(),
+    };
+
+    es.bulk(
+        &*migrations_index(index),
+        ES_TYPE,
+        vec![Action::index(applied).with_id(migration.version.to_string())],
+    ).map(|_| ())
+}
+
+/// Return the version of the latest migration applied to `index`.
+/// Indexes that predate this subsystem are considered at version `0`.
+pub fn current_version(es: &mut dyn SearchBackend, index: &str) -> Result<u32, EsError> {
+    let result = es.search::<AppliedMigration>(
+        &[&*migrations_index(index)],
+        &json!({ "size": 1000 }),
+    );
+
+    match result {
+        Ok(result) => Ok(result
+            .hits
+            .hits
+            .into_iter()
+            .filter_map(|hit| hit.source)
+            .map(|applied| applied.version)
+            .max()
+            .unwrap_or(0)),
+        Err(ref err) if err.to_string().contains("index_not_found") => Ok(0),
+        Err(err) => Err(err),
+    }
+}
+
+/// Return the migrations of `R` that haven't been applied to `index` yet.
+pub fn pending<R: Migratable>(
+    es: &mut dyn SearchBackend,
+    index: &str,
+) -> Result<Vec<Migration>, EsError> {
+    let version = current_version(es, index)?;
+
+    Ok(sorted_migrations::<R>()
+        .into_iter()
+        .filter(|migration| migration.version > version)
+        .collect())
+}
+
+/// Copy every document from `from` to `to` under its own type, passing the ones
+/// of `doc_type` through `scripts`. Return the number of copied documents.
+///
+/// The index also holds other types (i.e. the scores stored next to the talents),
+/// which are copied as they are. Documents are read through a scroll rather than
+/// with `from`/`size`, so that indexes larger than the result window can be copied too.
+fn copy_documents(
+    es: &mut dyn SearchBackend,
+    from: &str,
+    to: &str,
+    doc_type: &str,
+    scripts: &[fn(&mut Value)],
+) -> Result<u64, EsError> {
+    let mut scroll_id = None;
+    let copied = copy_batches(es, from, to, doc_type, scripts, &mut scroll_id);
+
+    if let Some(scroll_id) = scroll_id {
+        if let Err(error) = es.clear_scroll(&scroll_id) {
+            info!("{}", error);
+        }
+    }
+
+    copied
+}
+
+fn copy_batches(
+    es: &mut dyn SearchBackend,
+    from: &str,
+    to: &str,
+    doc_type: &str,
+    scripts: &[fn(&mut Value)],
+    scroll_id: &mut Option<String>,
+) -> Result<u64, EsError> {
+    let body = json!({
+        "query": Query::build_match_all().build(),
+        "size":  PAGE_SIZE,
+    });
+    let mut result = es.scroll_json(&[from], &body, COPY_KEEP_ALIVE)?;
+    let mut copied = 0;
+
+    loop {
+        *scroll_id = result.scroll_id.clone();
+        if result.hits.hits.is_empty() {
+            return Ok(copied);
+        }
+
+        let mut actions: BTreeMap<String, Vec<Action<Value>>> = BTreeMap::new();
+        for hit in result.hits.hits.into_iter() {
+            let hit_type = hit.doc_type.unwrap_or_else(|| doc_type.to_owned());
+
+            if let Some(mut document) = hit.source {
+                if hit_type == doc_type {
+                    for script in scripts.iter() {
+                        script(&mut document);
+                    }
+                }
+
+                let action = Action::index(*document).with_id(hit.id);
+                actions.entry(hit_type).or_insert_with(Vec::new).push(action);
+            }
+        }
+
+        // a document left behind would be lost once `to` replaces `from`
+        for (hit_type, actions) in actions.into_iter() {
+            copied += actions.len() as u64;
+            es.bulk(to, &*hit_type, actions)?.ensure_applied()?;
+        }
+
+        result = match *scroll_id {
+            Some(ref scroll_id) => es.next_scroll_json(scroll_id, COPY_KEEP_ALIVE)?,
+            None => return Ok(copied),
+        };
+    }
+}
+
+/// Apply the pending migrations of `R` to `index`.
+///
+/// ElasticSearch cannot change the mapping of existing fields, so the documents
+/// are copied (and backfilled) into `<index>_v<version>`, created with the new
+/// mapping. Once every document has been stored there, `index` becomes an alias
+/// of it: the searches keep reading the old documents until then, and a failed copy
+/// leaves `index` untouched. The index replaced by a migration is deleted just before
+/// the alias is added (see `SearchBackend::replace_with_alias`), unless `index` was an
+/// alias already, which is switched in a single step and keeps the old index around.
+pub fn migrate<R: Migratable>(
+    es: &mut dyn SearchBackend,
+    index: &str,
+    definition: Option<&IndexDefinition>,
+) -> Result<MigrationReport, EsError> {
+    let from = current_version(es, index)?;
+    let pending = pending::<R>(es, index)?;
+
+    let to = match pending.last() {
+        Some(migration) => migration.version,
+        None => {
+            return Ok(MigrationReport {
+                from: from,
+                to: from,
+                ..MigrationReport::default()
+            })
+        }
+    };
+
+    let scripts: Vec<fn(&mut Value)> = pending
+        .iter()
+        .flat_map(|migration| migration.steps.iter())
+        .filter_map(|step| match *step {
+            Step::Backfill { script } => Some(script),
+            _ => None,
+        })
+        .collect();
+
+    let target = format!("{}_v{}", index, to);
+    let serving = es.alias_targets(index)?;
+    if serving.len() > 1 || serving.contains(&target) {
+        return Err(EsError::EsServerError(format!(
+            "Cannot migrate {}: it's an alias of {} rather than of a single older index",
+            index,
+            serving.join(", ")
+        )));
+    }
+
+    // left behind by a migration that failed
+    if let Err(error) = es.delete_index(&*target) {
+        info!("{}", error);
+    }
+
+    create_with_version::<R>(es, &*target, to, definition)?;
+    let documents = copy_documents(es, index, &*target, R::doc_type(), &scripts)?;
+    es.refresh(&[&*target])?;
+
+    let stored = es
+        .search::<Value>(&[&*target], &json!({ "size": 0 }))?
+        .hits
+        .total;
+    if stored != documents {
+        return Err(EsError::EsServerError(format!(
+            "Cannot migrate {}: {} documents were copied, but {} are stored in {}",
+            index, documents, stored, target
+        )));
+    }
+
+    match serving.first() {
+        Some(serving) => es.switch_alias(index, serving, &*target)?,
+        None => es.replace_with_alias(index, &*target)?,
+    }
+
+    for migration in pending.iter() {
+        record(es, index, migration)?;
+    }
+
+    Ok(MigrationReport {
+        from: from,
+        to: to,
+        applied: pending.iter().map(|m| m.name.to_owned()).collect(),
+        documents: documents,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apply_to_mappings, latest_version, migrate, Step, Switch, PAGE_SIZE};
+    use backends::{Memory, SearchBackend};
+    use resource::Action;
+    use resources::Talent;
+
+    use serde_json::Value;
+
+    fn sample_mappings() -> ::serde_json::Value {
+        json!({
+          "talent": {
+            "properties": {
+              "id": { "type": "integer" },
+              "skills": {
+                "type": "text",
+                "analyzer": "trigrams",
+                "fields": {
+                  "raw": { "type": "keyword" }
+                }
+              },
+              "salary_expectations": {
+                "type": "nested",
+                "properties": {
+                  "minimum": { "type": "long" }
+                }
+              }
+            }
+          }
+        })
+    }
+
+    #[test]
+    fn test_add_field() {
+        let mut mappings = sample_mappings();
+
+        apply_to_mappings(
+            &mut mappings,
+            "talent",
+            &Step::AddField {
+                name: "deleted",
+                mapping: json!({ "type": "boolean" }),
+            },
+        );
+        assert_eq!(
+            mappings["talent"]["properties"]["deleted"],
+            json!({ "type": "boolean" })
+        );
+
+        apply_to_mappings(
+            &mut mappings,
+            "talent",
+            &Step::AddField {
+                name: "skills.lowercase",
+                mapping: json!({ "type": "text", "analyzer": "lowercase" }),
+            },
+        );
+        assert_eq!(
+            mappings["talent"]["properties"]["skills"]["fields"]["lowercase"]["analyzer"],
+            json!("lowercase")
+        );
+
+        apply_to_mappings(
+            &mut mappings,
+            "talent",
+            &Step::AddField {
+                name: "salary_expectations.minimum_eur",
+                mapping: json!({ "type": "long" }),
+            },
+        );
+        assert_eq!(
+            mappings["talent"]["properties"]["salary_expectations"]["properties"]["minimum_eur"],
+            json!({ "type": "long" })
+        );
+
+        apply_to_mappings(
+            &mut mappings,
+            "talent",
+            &Step::AddField {
+                name: "id.text",
+                mapping: json!({ "type": "text" }),
+            },
+        );
+        assert_eq!(
+            mappings["talent"]["properties"]["id"],
+            json!({ "type": "integer", "fields": { "text": { "type": "text" } } })
+        );
+    }
+
+    #[test]
+    fn test_add_type() {
+        let mut mappings = sample_mappings();
+
+        apply_to_mappings(
+            &mut mappings,
+            "talent",
+            &Step::AddType {
+                name: "score",
+                mapping: json!({ "properties": { "score": { "type": "float" } } }),
+            },
+        );
+        assert_eq!(
+            mappings["score"]["properties"]["score"],
+            json!({ "type": "float" })
+        );
+        assert_eq!(mappings["talent"], sample_mappings()["talent"]);
+    }
+
+    #[test]
+    fn test_change_analyzer() {
+        let mut mappings = sample_mappings();
+
+        // the main field of a multi-field is changed, keeping its sub-fields
+        apply_to_mappings(
+            &mut mappings,
+            "talent",
+            &Step::ChangeAnalyzer {
+                field: "skills",
+                analyzer: "folded_trigrams",
+                search_analyzer: Some("folded_words"),
+            },
+        );
+
+        let skills = &mappings["talent"]["properties"]["skills"];
+        assert_eq!(skills["analyzer"], json!("folded_trigrams"));
+        assert_eq!(skills["search_analyzer"], json!("folded_words"));
+        assert_eq!(skills["fields"]["raw"], json!({ "type": "keyword" }));
+
+        // sub-fields can be addressed directly
+        apply_to_mappings(
+            &mut mappings,
+            "talent",
+            &Step::ChangeAnalyzer {
+                field: "skills.raw",
+                analyzer: "keywords",
+                search_analyzer: None,
+            },
+        );
+
+        let raw = &mappings["talent"]["properties"]["skills"]["fields"]["raw"];
+        assert_eq!(raw["analyzer"], json!("keywords"));
+        assert_eq!(raw["search_analyzer"], json!("keywords"));
+    }
+
+    #[test]
+    fn test_unknown_fields_are_ignored() {
+        let mut mappings = sample_mappings();
+
+        apply_to_mappings(
+            &mut mappings,
+            "talent",
+            &Step::ChangeAnalyzer {
+                field: "headline",
+                analyzer: "keywords",
+                search_analyzer: None,
+            },
+        );
+        assert_eq!(mappings, sample_mappings());
+    }
+
+    #[test]
+    fn test_switch_validation() {
+        let switch = |from: &str, to: &str| Switch {
+            alias: None,
+            from: from.to_owned(),
+            to: to.to_owned(),
+        };
+
+        assert!(switch("talents_blue", "talents_green").validate().is_ok());
+        assert_eq!(
+            switch("talents_blue", "talents_*").validate().unwrap_err(),
+            "to must name a single index"
+        );
+        assert!(switch("talents_blue,talents", "talents_green").validate().is_err());
+        assert!(switch("talents_blue", "talents_blue").validate().is_err());
+
+        let mut switch = switch("talents_blue", "talents_green");
+        switch.alias = Some("".to_owned());
+        assert_eq!(switch.validate().unwrap_err(), "alias must name a single index");
+    }
+
+    #[test]
+    fn test_migrate_uuid_ids() {
+        let mut memory = Memory::new();
+        let es: &mut dyn SearchBackend = &mut memory;
+        let index = "migrations_uuid_talents";
+
+        // more than a page of talents identified by UUIDs, without any migration applied
+        let actions = (0..PAGE_SIZE + 1)
+            .map(|n| {
+                let id = format!("7c9e6679-7425-40de-944b-{:012x}", n);
+                Action::index(json!({ "id": id, "headline": "Developer" })).with_id(id)
+            })
+            .collect();
+        es.bulk(index, "talent", actions).unwrap();
+
+        let report = migrate::<Talent>(es, index, None).unwrap();
+        assert_eq!(report.from, 0);
+        assert_eq!(report.to, latest_version::<Talent>());
+        assert_eq!(report.documents, PAGE_SIZE + 1);
+
+        let result = es.search::<Value>(&[index], &json!({ "size": 1000 })).unwrap();
+        assert_eq!(result.hits.total, PAGE_SIZE + 1);
+        let last = "7c9e6679-7425-40de-944b-0000000001f4";
+        assert!(result.hits.hits.iter().any(|hit| hit.id == last));
+
+        // the index has been replaced by an alias of the migrated one
+        let target = format!("{}_v{}", index, report.to);
+        assert_eq!(es.alias_targets(index).unwrap(), vec![target.to_owned()]);
+        assert_eq!(es.search::<Value>(&[&*target], &json!({})).unwrap().hits.total, PAGE_SIZE + 1);
+    }
+
+    #[test]
+    fn test_migrate_other_types() {
+        let mut memory = Memory::new();
+        let es: &mut dyn SearchBackend = &mut memory;
+        let index = "migrations_typed_talents";
+
+        let talent = Action::index(json!({ "id": 1, "headline": "Developer" })).with_id("1");
+        es.bulk(index, "talent", vec![talent]).unwrap();
+        let score = Action::index(json!({ "job_id": 7, "talent_id": 1, "score": 0.5 }));
+        es.bulk(index, "score", vec![score.with_id("7_1")]).unwrap();
+
+        let report = migrate::<Talent>(es, index, None).unwrap();
+        assert_eq!(report.documents, 2);
+
+        // the scores are copied as they are, under their own type
+        let result = es.search::<Value>(&[index], &json!({})).unwrap();
+        let mut types: Vec<_> = result
+            .hits
+            .hits
+            .iter()
+            .map(|hit| (hit.id.to_owned(), hit.doc_type.to_owned().unwrap()))
+            .collect();
+        types.sort();
+        assert_eq!(
+            types,
+            vec![
+                ("1".to_owned(), "talent".to_owned()),
+                ("7_1".to_owned(), "score".to_owned())
+            ]
+        );
+    }
+}
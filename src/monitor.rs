@@ -1,5 +1,6 @@
 use backtrace::Backtrace;
 use config::Monitor as MonitorConfig;
+use context::RequestContext;
 use log::LogLocation;
 use std::panic::PanicInfo;
 use std::thread::JoinHandle;
@@ -23,16 +24,28 @@ pub trait Monitor: Send + Sync {
     type ResponseType;
 
     fn from_config(config: &MonitorConfig) -> Self::MonitorType;
-    fn send(&self, error_message: &String, location: &LogLocation);
+    /// Report an error, along with the request that caused it, if any.
+    fn send(
+        &self,
+        error_message: &String,
+        location: &LogLocation,
+        context: Option<&RequestContext>,
+    );
+    /// Tell that the service is alive.
+    fn send_heartbeat(&self);
+
+    /// Report a panic, along with the request that was being handled, if any.
     fn send_panic(
         &self,
         panic_info: &PanicInfo,
         backtrace: &Backtrace,
+        context: Option<&RequestContext>,
     ) -> JoinHandle<Self::ResponseType>;
 }
 
 mod null_monitor {
-    use super::{Backtrace, JoinHandle, LogLocation, Monitor, MonitorConfig, PanicInfo};
+    use super::{Backtrace, JoinHandle, LogLocation, Monitor, MonitorConfig, PanicInfo,
+                RequestContext};
 
     pub struct NullMonitor;
 
@@ -44,19 +57,30 @@ mod null_monitor {
             NullMonitor
         }
 
-        fn send(&self, _: &String, _: &LogLocation) {
+        fn send(&self, _: &String, _: &LogLocation, _: Option<&RequestContext>) {
             /* noop */
         }
 
-        fn send_panic(&self, _: &PanicInfo, _: &Backtrace) -> JoinHandle<Self::ResponseType> {
+        fn send_heartbeat(&self) {
+            /* noop */
+        }
+
+        fn send_panic(
+            &self,
+            _: &PanicInfo,
+            _: &Backtrace,
+            _: Option<&RequestContext>,
+        ) -> JoinHandle<Self::ResponseType> {
             unimplemented!()
         }
     }
 }
 
 mod rollbar {
-    use super::{Backtrace, JoinHandle, LogLocation, Monitor, MonitorConfig, PanicInfo};
+    use super::{Backtrace, JoinHandle, LogLocation, Monitor, MonitorConfig, PanicInfo,
+                RequestContext};
     use rollbar::{Client, FrameBuilder, ResponseStatus};
+    use serde_json;
 
     pub struct Rollbar {
         client: Client,
@@ -75,10 +99,21 @@ mod rollbar {
             }
         }
 
-        fn send(&self, error_message: &String, location: &LogLocation) {
+        /// The context is appended to the message as JSON.
+        fn send(
+            &self,
+            error_message: &String,
+            location: &LogLocation,
+            context: Option<&RequestContext>,
+        ) {
+            let error_message = match context.and_then(|context| serde_json::to_string(context).ok()) {
+                Some(context) => format!("{}\nRequest: {}", error_message, context),
+                None => error_message.to_owned(),
+            };
+
             self.client
                 .build_report()
-                .from_error_message(error_message)
+                .from_error_message(&error_message)
                 .with_frame(
                     FrameBuilder::new()
                         .with_line_number(location.line())
@@ -88,16 +123,34 @@ mod rollbar {
                 .send();
         }
 
+        fn send_heartbeat(&self) {
+            self.client
+                .build_report()
+                .from_message("Searchspot is alive")
+                .with_level("info")
+                .send();
+        }
+
         fn send_panic(
             &self,
             panic_info: &PanicInfo,
             backtrace: &Backtrace,
+            context: Option<&RequestContext>,
         ) -> JoinHandle<Self::ResponseType> {
-            self.client
-                .build_report()
-                .from_panic(&panic_info)
-                .with_backtrace(&backtrace)
-                .send()
+            match context.and_then(|context| serde_json::to_string(context).ok()) {
+                Some(context) => self
+                    .client
+                    .build_report()
+                    .from_error_message(&format!("{}\nRequest: {}", panic_info, context))
+                    .with_backtrace(&backtrace)
+                    .send(),
+                None => self
+                    .client
+                    .build_report()
+                    .from_panic(&panic_info)
+                    .with_backtrace(&backtrace)
+                    .send(),
+            }
         }
     }
 }
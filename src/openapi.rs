@@ -0,0 +1,1475 @@
+//! Describes the HTTP API as an OpenAPI 3 document, served at
+//! `GET /openapi.json` so that the client SDKs can be generated.
+//!
+//! `ROUTES` mirrors the router built by `main` and must be kept in sync
+//! with it: every route is described once, and the versioned ones are
+//! listed under `/v2` as well, with their bodies wrapped as `ApiVersion::V2` does.
+
+use serde_json::{Map, Value};
+
+/// The credentials needed to call a route.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Access {
+    Public,
+    Read,
+    Write,
+}
+
+/// The body of a request or of a response, referring to `schemas`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Body {
+    One(&'static str),
+    Many(&'static str),
+    /// A stream of server-sent events, whose data are the given schema.
+    Events(&'static str),
+}
+
+/// A parameter of the query string or of the path (i.e. `:id`),
+/// whose `kind` is its JSON schema type.
+#[derive(Debug, Clone, Copy)]
+pub struct Param {
+    pub name: &'static str,
+    pub kind: &'static str,
+    pub description: &'static str,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Route {
+    pub name: &'static str,
+    pub method: &'static str,
+    pub path: &'static str,
+    pub summary: &'static str,
+    /// What the summary doesn't tell (i.e. a change of the route), empty if nothing.
+    pub description: &'static str,
+    pub access: Access,
+    /// Whether the route writes to the indexes, which the read-only
+    /// replicas don't route (see `Config::read_only`).
+    pub writes: bool,
+    /// Whether the route is served under `/v2` as well.
+    pub versioned: bool,
+    /// Whether the `V2` response is paginated (see `ApiVersion::search_body`).
+    pub paginated: bool,
+    pub params: &'static [Param],
+    pub request: Option<Body>,
+    pub status: u16,
+    pub response: Option<Body>,
+}
+
+const PAGINATION: &'static [Param] = &[
+    Param {
+        name: "offset",
+        kind: "integer",
+        description: "The number of results to skip (0 by default).",
+    },
+    Param {
+        name: "per_page",
+        kind: "integer",
+        description: "The number of results to return (10 by default).",
+    },
+];
+
+const TALENT_SEARCH: &'static [Param] = &[
+    Param {
+        name: "keywords",
+        kind: "string",
+        description: "The words to search, sanitized unless features[]=raw_query_syntax is given.",
+    },
+    Param {
+        name: "keywords_operator",
+        kind: "string",
+        description: "How the keywords are combined: and or or.",
+    },
+    Param {
+        name: "offset",
+        kind: "integer",
+        description: "The number of results to skip (0 by default).",
+    },
+    Param {
+        name: "per_page",
+        kind: "integer",
+        description: "The number of results to return (10 by default).",
+    },
+    Param {
+        name: "index",
+        kind: "string",
+        description: "The indexes to search, comma-separated.",
+    },
+    Param {
+        name: "epoch",
+        kind: "string",
+        description: "The moment in which the batches are searched, now by default.",
+    },
+    Param {
+        name: "company_id",
+        kind: "integer",
+        description: "The company on whose behalf the search is made.",
+    },
+    Param {
+        name: "view",
+        kind: "string",
+        description: "full returns the complete documents.",
+    },
+    Param {
+        name: "track_total_hits",
+        kind: "boolean",
+        description: "false stops counting the matching talents at 10000.",
+    },
+    Param {
+        name: "timeout_ms",
+        kind: "integer",
+        description: "The milliseconds given to the search before returning what it found.",
+    },
+    Param {
+        name: "track_min_score",
+        kind: "boolean",
+        description: "Count the talents scoring too low to be returned.",
+    },
+    Param {
+        name: "debug_es_query",
+        kind: "boolean",
+        description: "Return the query sent to ElasticSearch.",
+    },
+    Param {
+        name: "maximum_salary",
+        kind: "integer",
+        description: "The highest minimum salary the talents can expect.",
+    },
+    Param {
+        name: "sort",
+        kind: "string",
+        description: "salary_asc or salary_desc sorts the talents by their lowest minimum salary.",
+    },
+    Param {
+        name: "sort_by[]",
+        kind: "array",
+        description: "The fields sorting the talents without keywords (i.e. weight:desc).",
+    },
+    Param {
+        name: "weight_factor",
+        kind: "number",
+        description: "How much the weight of the talents counts with the weight_decay feature.",
+    },
+    Param {
+        name: "recency_scale",
+        kind: "string",
+        description: "How quickly the recency of the talents decays with weight_decay (i.e. 7d).",
+    },
+    Param {
+        name: "features[]",
+        kind: "array",
+        description: "The search features to enable (see GET /features).",
+    },
+    Param {
+        name: "work_locations[]",
+        kind: "array",
+        description: "The locations the talents want to work in.",
+    },
+    Param {
+        name: "current_location[]",
+        kind: "array",
+        description: "The locations the talents are based in.",
+    },
+    Param {
+        name: "desired_work_roles[]",
+        kind: "array",
+        description: "The roles the talents want, optionally as role:minimum:maximum.",
+    },
+    Param {
+        name: "professional_experience[]",
+        kind: "array",
+        description: "The ranges of experience of the talents (i.e. 2..4).",
+    },
+    Param {
+        name: "work_authorization[]",
+        kind: "array",
+        description: "Whether the talents need a visa: yes, no or unsure.",
+    },
+    Param {
+        name: "languages[]",
+        kind: "array",
+        description: "The languages spoken by the talents.",
+    },
+    Param {
+        name: "presented_talents[]",
+        kind: "array",
+        description: "The ids of the talents presented to the company.",
+    },
+    Param {
+        name: "bookmarked_talents",
+        kind: "string",
+        description: "The ids of the talents bookmarked by the company, comma-separated.",
+    },
+    Param {
+        name: "contacted_talents",
+        kind: "string",
+        description: "The ids of the talents contacted by the company, comma-separated.",
+    },
+    Param {
+        name: "ignored_talents",
+        kind: "string",
+        description: "The ids of the talents ignored by the company, comma-separated.",
+    },
+];
+
+const HYDRATION: &'static [Param] = &[
+    Param {
+        name: "epoch",
+        kind: "string",
+        description: "The moment in which the batches are searched, now by default.",
+    },
+    Param {
+        name: "company_id",
+        kind: "integer",
+        description: "The company on whose behalf the talents are fetched.",
+    },
+    Param {
+        name: "presented_talents[]",
+        kind: "array",
+        description: "The ids of the talents presented to the company.",
+    },
+];
+
+const ID: &'static [Param] = &[Param {
+    name: "id",
+    kind: "string",
+    description: "The id of the document.",
+}];
+
+/// Taken by the writes of the documents.
+const REFRESH_PARAM: Param = Param {
+    name: "refresh",
+    kind: "string",
+    description: "When the changes become searchable: true, wait_for or false (the default).",
+};
+
+const REFRESH: &'static [Param] = &[REFRESH_PARAM];
+
+const TALENT_INDEXING: &'static [Param] = &[
+    Param {
+        name: "op_type",
+        kind: "string",
+        description: "index (the default) replaces the existing talents, create rejects them.",
+    },
+    REFRESH_PARAM,
+];
+
+const BATCH: &'static [Param] = &[
+    Param {
+        name: "starts_at",
+        kind: "string",
+        description: "The beginning of the batch.",
+    },
+    Param {
+        name: "ends_at",
+        kind: "string",
+        description: "The end of the batch.",
+    },
+    REFRESH_PARAM,
+];
+
+const SOFT: &'static [Param] = &[
+    Param {
+        name: "id",
+        kind: "string",
+        description: "The id of the talent.",
+    },
+    Param {
+        name: "soft",
+        kind: "boolean",
+        description: "Hide the talent instead of deleting it, so that it can be restored.",
+    },
+    REFRESH_PARAM,
+];
+
+const SCORE_SEARCH: &'static [Param] = &[
+    Param {
+        name: "job_id",
+        kind: "integer",
+        description: "The job the scores refer to.",
+    },
+    Param {
+        name: "talent_id",
+        kind: "integer",
+        description: "The talent the scores refer to.",
+    },
+    Param {
+        name: "min_score",
+        kind: "number",
+        description: "The lowest score to return.",
+    },
+    Param {
+        name: "model_version",
+        kind: "string",
+        description: "The version of the model that computed the scores.",
+    },
+];
+
+const SCORE_STATS: &'static [Param] = &[
+    Param {
+        name: "job_id",
+        kind: "integer",
+        description: "The job whose scores are described.",
+    },
+    Param {
+        name: "model_version",
+        kind: "string",
+        description: "The version of the model that computed the scores.",
+    },
+    Param {
+        name: "interval",
+        kind: "number",
+        description: "The width of the buckets of the histogram.",
+    },
+];
+
+/// Every route served by `main`, where the read-only replicas leave out the `writes`.
+pub const ROUTES: &'static [Route] = &[
+    Route {
+        name: "get_talents",
+        method: "get",
+        path: "/talents",
+        summary: "Search the talents",
+        description: "",
+        access: Access::Read,
+        writes: false,
+        versioned: true,
+        paginated: true,
+        params: TALENT_SEARCH,
+        request: None,
+        status: 200,
+        response: Some(Body::One("SearchResults")),
+    },
+    Route {
+        name: "create_talents",
+        method: "post",
+        path: "/talents",
+        summary: "Index the talents",
+        description: "",
+        access: Access::Write,
+        writes: true,
+        versioned: true,
+        paginated: false,
+        params: TALENT_INDEXING,
+        request: Some(Body::Many("Talent")),
+        status: 201,
+        response: None,
+    },
+    Route {
+        name: "delete_talents",
+        method: "delete",
+        path: "/talents",
+        summary: "Reset the index",
+        description: "Takes no body: the talents are deleted by id through POST /talents/delete, \
+                      which replaced the bulk deletion formerly served by this route.",
+        access: Access::Write,
+        writes: true,
+        versioned: true,
+        paginated: false,
+        params: &[],
+        request: None,
+        status: 204,
+        response: None,
+    },
+    Route {
+        name: "bulk_delete_talents",
+        method: "post",
+        path: "/talents/delete",
+        summary: "Delete the talents with the given ids",
+        description: "Replaces the bulk deletion formerly served by DELETE /talents, \
+                      which resets the index instead.",
+        access: Access::Write,
+        writes: true,
+        versioned: true,
+        paginated: false,
+        params: REFRESH,
+        request: Some(Body::Many("TalentId")),
+        status: 204,
+        response: None,
+    },
+    Route {
+        name: "delete_talent",
+        method: "delete",
+        path: "/talents/:id",
+        summary: "Delete a talent",
+        description: "",
+        access: Access::Write,
+        writes: true,
+        versioned: true,
+        paginated: false,
+        params: SOFT,
+        request: None,
+        status: 204,
+        response: None,
+    },
+    Route {
+        name: "migrate_talents",
+        method: "post",
+        path: "/talents/migrations",
+        summary: "Apply the pending migrations",
+        description: "",
+        access: Access::Write,
+        writes: true,
+        versioned: true,
+        paginated: false,
+        params: &[],
+        request: None,
+        status: 200,
+        response: Some(Body::One("MigrationReport")),
+    },
+    Route {
+        name: "hydrate_talents",
+        method: "post",
+        path: "/talents/hydrate",
+        summary: "Fetch the visible talents with the given ids",
+        description: "",
+        access: Access::Read,
+        writes: false,
+        versioned: true,
+        paginated: false,
+        params: HYDRATION,
+        request: Some(Body::Many("TalentId")),
+        status: 200,
+        response: Some(Body::Many("Talent")),
+    },
+    Route {
+        name: "recommend_talent",
+        method: "get",
+        path: "/talents/:id/recommendations",
+        summary: "Return the talents similar to a talent",
+        description: "",
+        access: Access::Read,
+        writes: false,
+        versioned: true,
+        paginated: false,
+        params: ID,
+        request: None,
+        status: 200,
+        response: Some(Body::One("SearchResults")),
+    },
+    Route {
+        name: "talent_salaries",
+        method: "get",
+        path: "/talents/salaries",
+        summary: "Return the distribution of the salaries expected by the matching talents",
+        description: "",
+        access: Access::Read,
+        writes: false,
+        versioned: true,
+        paginated: false,
+        params: TALENT_SEARCH,
+        request: None,
+        status: 200,
+        response: Some(Body::One("SalaryDistribution")),
+    },
+    Route {
+        name: "export_talents",
+        method: "get",
+        path: "/talents/export",
+        summary: "Stream every talent matching the search, without pagination",
+        description: "",
+        access: Access::Read,
+        writes: false,
+        versioned: false,
+        paginated: false,
+        params: TALENT_SEARCH,
+        request: None,
+        status: 200,
+        response: Some(Body::Many("SearchResult")),
+    },
+    Route {
+        name: "stream_talents",
+        method: "get",
+        path: "/talents/stream",
+        summary: "Stream the talents indexed, deleted or restored as server-sent events",
+        description: "",
+        access: Access::Read,
+        writes: false,
+        versioned: true,
+        paginated: false,
+        params: &[Param {
+            name: "index",
+            kind: "string",
+            description: "The index whose changes are streamed (the configured one by default).",
+        }],
+        request: None,
+        status: 200,
+        response: Some(Body::Events("Change")),
+    },
+    Route {
+        name: "query_talents",
+        method: "post",
+        path: "/admin/talents/query",
+        summary: "Run a raw ElasticSearch query",
+        description: "",
+        access: Access::Write,
+        writes: false,
+        versioned: true,
+        paginated: false,
+        params: &[],
+        request: Some(Body::One("Object")),
+        status: 200,
+        response: Some(Body::One("RawQueryResults")),
+    },
+    Route {
+        name: "deleted_talents",
+        method: "get",
+        path: "/admin/talents/deleted",
+        summary: "List the soft deleted talents",
+        description: "",
+        access: Access::Write,
+        writes: false,
+        versioned: true,
+        paginated: false,
+        params: PAGINATION,
+        request: None,
+        status: 200,
+        response: Some(Body::One("DeletedTalents")),
+    },
+    Route {
+        name: "distinct_talents",
+        method: "get",
+        path: "/admin/talents/distinct",
+        summary: "Count the distinct skills, locations and languages of the talents",
+        description: "",
+        access: Access::Write,
+        writes: false,
+        versioned: true,
+        paginated: false,
+        params: &[
+            Param {
+                name: "index",
+                kind: "string",
+                description: "The index whose talents are counted (the configured one by default).",
+            },
+            Param {
+                name: "top",
+                kind: "integer",
+                description: "How many of the most frequent values are listed per field.",
+            },
+        ],
+        request: None,
+        status: 200,
+        response: Some(Body::One("DistinctCounts")),
+    },
+    Route {
+        name: "switch_indexes",
+        method: "post",
+        path: "/admin/indexes/switch",
+        summary: "Point the serving alias from an index to another one, atomically",
+        description: "",
+        access: Access::Write,
+        writes: true,
+        versioned: true,
+        paginated: false,
+        params: &[],
+        request: Some(Body::One("IndexSwitch")),
+        status: 200,
+        response: Some(Body::One("IndexSwitch")),
+    },
+    Route {
+        name: "restore_talent",
+        method: "post",
+        path: "/talents/:id/restore",
+        summary: "Restore a soft deleted talent",
+        description: "",
+        access: Access::Write,
+        writes: true,
+        versioned: true,
+        paginated: false,
+        params: &[
+            Param {
+                name: "id",
+                kind: "string",
+                description: "The id of the talent.",
+            },
+            REFRESH_PARAM,
+        ],
+        request: None,
+        status: 204,
+        response: None,
+    },
+    Route {
+        name: "get_batches",
+        method: "get",
+        path: "/talents/batches",
+        summary: "List the batches of talents",
+        description: "",
+        access: Access::Read,
+        writes: false,
+        versioned: true,
+        paginated: false,
+        params: &[],
+        request: None,
+        status: 200,
+        response: Some(Body::Many("Batch")),
+    },
+    Route {
+        name: "delete_batch",
+        method: "delete",
+        path: "/talents/batches",
+        summary: "Delete the talents of a batch",
+        description: "",
+        access: Access::Write,
+        writes: true,
+        versioned: true,
+        paginated: false,
+        params: BATCH,
+        request: None,
+        status: 204,
+        response: None,
+    },
+    Route {
+        name: "hide_batch",
+        method: "post",
+        path: "/talents/batches/hide",
+        summary: "Hide the accepted talents of a batch",
+        description: "",
+        access: Access::Write,
+        writes: true,
+        versioned: true,
+        paginated: false,
+        params: BATCH,
+        request: None,
+        status: 200,
+        response: Some(Body::One("Hidden")),
+    },
+    Route {
+        name: "refresh_talents",
+        method: "post",
+        path: "/talents/refresh",
+        summary: "Make the changes to the indexes searchable right away",
+        description: "",
+        access: Access::Write,
+        writes: true,
+        versioned: true,
+        paginated: false,
+        params: &[],
+        request: None,
+        status: 204,
+        response: None,
+    },
+    Route {
+        name: "get_scores",
+        method: "get",
+        path: "/scores",
+        summary: "Search the scores",
+        description: "",
+        access: Access::Read,
+        writes: false,
+        versioned: true,
+        paginated: true,
+        params: SCORE_SEARCH,
+        request: None,
+        status: 200,
+        response: Some(Body::One("ScoreSearchResults")),
+    },
+    Route {
+        name: "create_scores",
+        method: "post",
+        path: "/scores",
+        summary: "Index the scores",
+        description: "",
+        access: Access::Write,
+        writes: true,
+        versioned: true,
+        paginated: false,
+        params: REFRESH,
+        request: Some(Body::Many("Score")),
+        status: 201,
+        response: Some(Body::One("ScoreIndexingResults")),
+    },
+    Route {
+        name: "delete_scores",
+        method: "delete",
+        path: "/scores",
+        summary: "Delete the scores with the given request ids, or reset them without a body",
+        description: "",
+        access: Access::Write,
+        writes: true,
+        versioned: true,
+        paginated: false,
+        params: REFRESH,
+        request: Some(Body::Many("Id")),
+        status: 204,
+        response: None,
+    },
+    Route {
+        name: "score_stats",
+        method: "get",
+        path: "/scores/stats",
+        summary: "Describe the scores of a job",
+        description: "",
+        access: Access::Read,
+        writes: false,
+        versioned: true,
+        paginated: false,
+        params: SCORE_STATS,
+        request: None,
+        status: 200,
+        response: Some(Body::One("ScoreStats")),
+    },
+    Route {
+        name: "create_jobs",
+        method: "post",
+        path: "/jobs",
+        summary: "Index the jobs",
+        description: "",
+        access: Access::Write,
+        writes: true,
+        versioned: true,
+        paginated: false,
+        params: REFRESH,
+        request: Some(Body::Many("Job")),
+        status: 201,
+        response: None,
+    },
+    Route {
+        name: "job_talents",
+        method: "get",
+        path: "/jobs/:id/talents",
+        summary: "Search the talents matching a job",
+        description: "",
+        access: Access::Read,
+        writes: false,
+        versioned: true,
+        paginated: true,
+        params: ID,
+        request: None,
+        status: 200,
+        response: Some(Body::One("SearchResults")),
+    },
+    Route {
+        name: "get_companies",
+        method: "get",
+        path: "/companies",
+        summary: "Search the companies",
+        description: "",
+        access: Access::Read,
+        writes: false,
+        versioned: true,
+        paginated: true,
+        params: PAGINATION,
+        request: None,
+        status: 200,
+        response: Some(Body::One("CompanySearchResults")),
+    },
+    Route {
+        name: "create_companies",
+        method: "post",
+        path: "/companies",
+        summary: "Index the companies",
+        description: "",
+        access: Access::Write,
+        writes: true,
+        versioned: true,
+        paginated: false,
+        params: REFRESH,
+        request: Some(Body::Many("Company")),
+        status: 201,
+        response: None,
+    },
+    Route {
+        name: "features",
+        method: "get",
+        path: "/features",
+        summary: "List the search features",
+        description: "",
+        access: Access::Read,
+        writes: false,
+        versioned: true,
+        paginated: false,
+        params: &[],
+        request: None,
+        status: 200,
+        response: Some(Body::Many("Feature")),
+    },
+    Route {
+        name: "openapi",
+        method: "get",
+        path: "/openapi.json",
+        summary: "Describe the API as an OpenAPI 3 document",
+        description: "",
+        access: Access::Public,
+        writes: false,
+        versioned: false,
+        paginated: false,
+        params: &[],
+        request: None,
+        status: 200,
+        response: Some(Body::One("Object")),
+    },
+    Route {
+        name: "metrics",
+        method: "get",
+        path: "/metrics",
+        summary: "Expose the metrics to Prometheus",
+        description: "",
+        access: Access::Public,
+        writes: false,
+        versioned: false,
+        paginated: false,
+        params: &[],
+        request: None,
+        status: 200,
+        response: None,
+    },
+    Route {
+        name: "get_maintenance",
+        method: "get",
+        path: "/maintenance",
+        summary: "Describe the maintenance mode",
+        description: "",
+        access: Access::Read,
+        writes: false,
+        versioned: false,
+        paginated: false,
+        params: &[],
+        request: None,
+        status: 200,
+        response: Some(Body::One("Maintenance")),
+    },
+    Route {
+        name: "put_maintenance",
+        method: "put",
+        path: "/maintenance",
+        summary: "Change the maintenance mode",
+        description: "",
+        access: Access::Write,
+        writes: false,
+        versioned: false,
+        paginated: false,
+        params: &[],
+        request: Some(Body::One("Maintenance")),
+        status: 200,
+        response: Some(Body::One("Maintenance")),
+    },
+];
+
+/// The schemas of the bodies, by name.
+pub fn schemas() -> Value {
+    let talent_id = json!({ "$ref": "#/components/schemas/TalentId" });
+    let strings = json!({ "type": "array", "items": { "type": "string" } });
+    let ids = json!({ "type": "array", "items": { "type": "integer" } });
+
+    json!({
+        "Object": { "type": "object" },
+        "Id": { "oneOf": [{ "type": "integer" }, { "type": "string" }] },
+        "TalentId": {
+            "description": "Either a number or a UUID.",
+            "oneOf": [{ "type": "integer" }, { "type": "string", "format": "uuid" }],
+        },
+        "Error": {
+            "type": "object",
+            "properties": {
+                "error":   { "type": "string" },
+                "records": { "type": "array", "items": { "type": "object" } },
+            },
+        },
+        "ErrorV2": {
+            "type": "object",
+            "properties": {
+                "error": {
+                    "type": "object",
+                    "properties": {
+                        "status":  { "type": "integer" },
+                        "message": { "type": "string" },
+                        "records": { "type": "array", "items": { "type": "object" } },
+                    },
+                },
+            },
+        },
+        "SalaryExpectations": {
+            "type": "object",
+            "properties": {
+                "minimum":     { "type": "integer", "nullable": true },
+                "currency":    { "type": "string" },
+                "city":        { "type": "string" },
+                "minimum_eur": { "type": "integer", "readOnly": true },
+            },
+        },
+        "RolesExperience": {
+            "type": "object",
+            "properties": {
+                "role":       { "type": "string" },
+                "experience": { "type": "string" },
+            },
+        },
+        "Talent": {
+            "type": "object",
+            "required": ["id", "accepted"],
+            "properties": {
+                "id":                            talent_id,
+                "accepted":                      { "type": "boolean" },
+                "desired_work_roles":            strings,
+                "desired_work_roles_experience": strings,
+                "desired_roles": {
+                    "type": "array",
+                    "items": { "$ref": "#/components/schemas/RolesExperience" },
+                },
+                "professional_experience":       { "type": "string" },
+                "work_locations":                strings,
+                "current_location":              { "type": "string" },
+                "work_authorization":            { "type": "string" },
+                "skills":                        strings,
+                "summary":                       { "type": "string" },
+                "headline":                      { "type": "string" },
+                "contacted_company_ids":         ids,
+                "batch_starts_at":               { "type": "string", "format": "date-time" },
+                "batch_ends_at":                 { "type": "string", "format": "date-time" },
+                "added_to_batch_at":             { "type": "string", "format": "date-time" },
+                "weight":                        { "type": "integer" },
+                "blocked_companies":             ids,
+                "work_experiences":              strings,
+                "avatar_url":                    { "type": "string" },
+                "salary_expectations": {
+                    "type": "array",
+                    "items": { "$ref": "#/components/schemas/SalaryExpectations" },
+                },
+                "latest_position":               { "type": "string" },
+                "languages":                     strings,
+                "educations":                    strings,
+                "external_version": {
+                    "type": "integer",
+                    "description": "Talents older than the stored version are ignored.",
+                },
+            },
+        },
+        "FoundTalent": {
+            "type": "object",
+            "properties": {
+                "id":                  talent_id,
+                "headline":            { "type": "string" },
+                "avatar_url":          { "type": "string" },
+                "work_locations":      strings,
+                "current_location":    { "type": "string" },
+                "salary_expectations": {
+                    "type": "array",
+                    "items": { "$ref": "#/components/schemas/SalaryExpectations" },
+                },
+                "roles_experiences": {
+                    "type": "array",
+                    "items": { "$ref": "#/components/schemas/RolesExperience" },
+                },
+                "latest_position":     { "type": "string" },
+                "batch_starts_at":     { "type": "string", "format": "date-time" },
+                "already_contacted":   { "type": "boolean" },
+                "is_presented":        { "type": "boolean" },
+            },
+        },
+        "SearchResult": {
+            "type": "object",
+            "properties": {
+                "talent": {
+                    "oneOf": [
+                        { "$ref": "#/components/schemas/FoundTalent" },
+                        { "$ref": "#/components/schemas/Talent" },
+                    ],
+                },
+                "highlight": {
+                    "type": "object",
+                    "nullable": true,
+                    "additionalProperties": strings,
+                },
+            },
+        },
+        "SearchResults": {
+            "type": "object",
+            "properties": {
+                "total":          { "type": "integer" },
+                "total_relation": { "type": "string", "enum": ["eq", "gte"] },
+                "timed_out":      { "type": "boolean" },
+                "partial":        { "type": "boolean" },
+                "shard_failures": { "type": "array", "items": { "type": "string" } },
+                "talents": {
+                    "type": "array",
+                    "items": { "$ref": "#/components/schemas/SearchResult" },
+                },
+                "raw_es_query":          { "type": "string", "nullable": true },
+                "hits_per_index": {
+                    "type": "object",
+                    "additionalProperties": { "type": "integer" },
+                },
+                "experiment":            { "type": "string", "nullable": true },
+                "excluded_by_company":   { "type": "integer" },
+                "excluded_by_contacted": { "type": "integer" },
+                "excluded_by_ignored":   { "type": "integer" },
+                "filtered_by_min_score": { "type": "integer" },
+            },
+        },
+        "DeletedTalents": {
+            "type": "object",
+            "properties": {
+                "total": { "type": "integer" },
+                "talents": {
+                    "type": "array",
+                    "items": { "$ref": "#/components/schemas/FoundTalent" },
+                },
+            },
+        },
+        "Change": {
+            "type": "object",
+            "description": "The data of the indexed, deleted and reset events.",
+            "properties": {
+                "index": { "type": "string" },
+                "ids":   { "type": "array", "items": { "type": "string" } },
+            },
+        },
+        "SalaryDistribution": {
+            "type": "object",
+            "properties": {
+                "count":  { "type": "integer" },
+                "groups": { "type": "array", "items": { "type": "object" } },
+            },
+        },
+        "DistinctCounts": {
+            "type": "object",
+            "properties": {
+                "total":  { "type": "integer" },
+                "fields": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "field": { "type": "string" },
+                            "count": { "type": "integer" },
+                            "top":   { "type": "array", "items": { "type": "object" } },
+                        },
+                    },
+                },
+            },
+        },
+        "RawQueryResults": {
+            "type": "object",
+            "properties": {
+                "total": { "type": "integer" },
+                "hits":  { "type": "array", "items": { "type": "object" } },
+            },
+        },
+        "MigrationReport": {
+            "type": "object",
+            "properties": {
+                "from":      { "type": "integer" },
+                "to":        { "type": "integer" },
+                "applied":   strings,
+                "documents": { "type": "integer" },
+            },
+        },
+        "IndexSwitch": {
+            "type": "object",
+            "required": ["from", "to"],
+            "properties": {
+                "alias": { "type": "string", "description": "es.index unless given." },
+                "from":  { "type": "string" },
+                "to":    { "type": "string" },
+            },
+        },
+        "Batch": {
+            "type": "object",
+            "properties": {
+                "starts_at": { "type": "string", "format": "date-time" },
+                "ends_at":   { "type": "string", "format": "date-time" },
+                "talents":   { "type": "integer" },
+            },
+        },
+        "Hidden": {
+            "type": "object",
+            "properties": { "hidden": { "type": "integer" } },
+        },
+        "Score": {
+            "type": "object",
+            "required": ["request_id", "job_id", "talent_id", "score"],
+            "properties": {
+                "request_id":    { "type": "string" },
+                "person_id":     { "type": "string", "nullable": true },
+                "company_id":    { "type": "string", "nullable": true },
+                "position_id":   { "type": "string", "nullable": true },
+                "job_id":        { "type": "integer" },
+                "talent_id":     { "type": "integer" },
+                "score":         { "type": "number" },
+                "model_version": { "type": "string", "nullable": true },
+                "created_at":    { "type": "string", "nullable": true },
+            },
+        },
+        "ScoreSearchResults": {
+            "type": "object",
+            "properties": {
+                "total": { "type": "integer" },
+                "scores": {
+                    "type": "array",
+                    "items": { "$ref": "#/components/schemas/Score" },
+                },
+            },
+        },
+        "ScoreIndexingResults": { "type": "object" },
+        "ScoreStats": { "type": "object" },
+        "Job": {
+            "type": "object",
+            "properties": {
+                "id":              { "type": "integer" },
+                "title":           { "type": "string" },
+                "required_skills": strings,
+                "location":        { "type": "string" },
+                "salary_budget":   { "type": "integer", "nullable": true },
+                "experience":      { "type": "integer", "nullable": true },
+            },
+        },
+        "Company": {
+            "type": "object",
+            "properties": {
+                "id":           { "type": "integer" },
+                "name":         { "type": "string" },
+                "industry":     { "type": "string" },
+                "locations":    strings,
+                "hiring_roles": strings,
+            },
+        },
+        "CompanySearchResults": {
+            "type": "object",
+            "properties": {
+                "total": { "type": "integer" },
+                "companies": {
+                    "type": "array",
+                    "items": { "$ref": "#/components/schemas/Company" },
+                },
+            },
+        },
+        "Feature": {
+            "type": "object",
+            "properties": {
+                "name":        { "type": "string" },
+                "description": { "type": "string" },
+            },
+        },
+        "Maintenance": { "type": "object" },
+    })
+}
+
+fn body_schema(body: Body) -> Value {
+    match body {
+        Body::One(name) | Body::Events(name) => {
+            json!({ "$ref": format!("#/components/schemas/{}", name) })
+        }
+        Body::Many(name) => json!({
+            "type": "array",
+            "items": { "$ref": format!("#/components/schemas/{}", name) },
+        }),
+    }
+}
+
+/// Convert the routes of `router` (i.e. `/talents/:id`) into OpenAPI paths.
+fn openapi_path(path: &str) -> String {
+    path.split('/')
+        .map(|segment| {
+            if segment.starts_with(':') {
+                format!("{{{}}}", &segment[1..])
+            } else {
+                segment.to_owned()
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("/")
+}
+
+/// Tell whether `path` (i.e. `/talents/:id`) matches the `segments` of a request.
+fn matches(path: &str, segments: &[&str]) -> bool {
+    let route: Vec<&str> = path.split('/').filter(|segment| !segment.is_empty()).collect();
+
+    route.len() == segments.len()
+        && route
+            .iter()
+            .zip(segments)
+            .all(|(route, segment)| route.starts_with(':') || route == segment)
+}
+
+/// Tell whether `segment` is a fixed segment of the path of any route.
+fn is_fixed_segment(segment: &str) -> bool {
+    segment == "v2" || ROUTES.iter().any(|route| route.path.split('/').any(|s| s == segment))
+}
+
+/// Return the path made of `segments` (i.e. `["Talents", ""]`) without the trailing
+/// slashes and with the fixed segments of the routes in lowercase (i.e. `/talents`),
+/// unless it's already normalized. The other segments, like the ids, are left alone.
+pub fn normalize_path(segments: &[&str]) -> Option<String> {
+    let normalized: Vec<String> = segments
+        .iter()
+        .filter(|segment| !segment.is_empty())
+        .map(|segment| {
+            let lowercase = segment.to_lowercase();
+            if is_fixed_segment(&lowercase) {
+                lowercase
+            } else {
+                (*segment).to_owned()
+            }
+        })
+        .collect();
+
+    let normalized = format!("/{}", normalized.join("/"));
+    if normalized == format!("/{}", segments.join("/")) {
+        None
+    } else {
+        Some(normalized)
+    }
+}
+
+/// Return the methods (i.e. `GET`) that `ROUTES` serve at the path made of `segments`
+/// (i.e. `["v2", "talents", "1"]`), sorted. The read-only replicas serve no writes.
+pub fn allowed_methods(segments: &[&str], read_only: bool) -> Vec<String> {
+    let segments: Vec<&str> = segments.iter().cloned().filter(|s| !s.is_empty()).collect();
+    let mut methods = vec![];
+
+    for route in ROUTES.iter().filter(|route| !(read_only && route.writes)) {
+        let v2 = route.versioned
+            && segments.first() == Some(&"v2")
+            && matches(route.path, &segments[1..]);
+
+        let method = route.method.to_uppercase();
+        if (v2 || matches(route.path, &segments)) && !methods.contains(&method) {
+            methods.push(method);
+        }
+    }
+
+    methods.sort();
+    methods
+}
+
+fn json_content(schema: Value) -> Value {
+    json!({ "application/json": { "schema": schema } })
+}
+
+fn operation(route: &Route, v2: bool) -> Value {
+    let parameters: Vec<Value> = route
+        .params
+        .iter()
+        .map(|param| {
+            let location = if route.path.contains(&format!(":{}", param.name)) {
+                "path"
+            } else {
+                "query"
+            };
+            let schema = if param.kind == "array" {
+                json!({ "type": "array", "items": { "type": "string" } })
+            } else {
+                json!({ "type": param.kind })
+            };
+
+            json!({
+                "name":        param.name,
+                "in":          location,
+                "required":    location == "path",
+                "description": param.description,
+                "schema":      schema,
+            })
+        })
+        .collect();
+
+    let mut responses = Map::new();
+    let success = match route.response {
+        Some(body @ Body::Events(_)) => json!({
+            "description": "Success",
+            "content": { "text/event-stream": { "schema": body_schema(body) } },
+        }),
+        Some(body) => {
+            let schema = body_schema(body);
+            let schema = match (v2, route.paginated) {
+                (false, _) => schema,
+                (true, false) => json!({ "type": "object", "properties": { "data": schema } }),
+                (true, true) => json!({
+                    "type": "object",
+                    "properties": {
+                        "data": schema,
+                        "meta": {
+                            "type": "object",
+                            "properties": {
+                                "offset":      { "type": "integer" },
+                                "per_page":    { "type": "integer" },
+                                "total":       { "type": "integer", "nullable": true },
+                                "next_offset": { "type": "integer", "nullable": true },
+                            },
+                        },
+                    },
+                }),
+            };
+            json!({ "description": "Success", "content": json_content(schema) })
+        }
+        None => json!({ "description": "Success" }),
+    };
+    responses.insert(route.status.to_string(), success);
+
+    if route.access != Access::Public {
+        responses.insert("401".to_owned(), json!({ "description": "Invalid or missing token" }));
+
+        let error = if v2 { "ErrorV2" } else { "Error" };
+        let failures = [
+            ("400", "The request is not valid"),
+            ("404", "The index doesn't exist"),
+            ("422", "The request can't be processed"),
+            ("503", "ElasticSearch can't serve the request right now"),
+        ];
+        for &(status, description) in failures.iter() {
+            responses.insert(
+                status.to_owned(),
+                json!({
+                    "description": description,
+                    "content": json_content(body_schema(Body::One(error))),
+                }),
+            );
+        }
+    }
+
+    let operation_id = if v2 {
+        format!("{}_v2", route.name)
+    } else {
+        route.name.to_owned()
+    };
+
+    let mut operation = json!({
+        "operationId": operation_id,
+        "summary":     route.summary,
+        "parameters":  parameters,
+        "responses":   responses,
+    });
+
+    if !route.description.is_empty() {
+        operation["description"] = json!(route.description);
+    }
+
+    if let Some(body) = route.request {
+        operation["requestBody"] = json!({ "content": json_content(body_schema(body)) });
+    }
+
+    match route.access {
+        Access::Public => operation["security"] = json!([]),
+        Access::Read => operation["security"] = json!([{ "read": [] }]),
+        Access::Write => operation["security"] = json!([{ "write": [] }]),
+    }
+
+    operation
+}
+
+/// Build the OpenAPI 3 document of `ROUTES`, as the `version` of Searchspot.
+pub fn document(version: &str) -> Value {
+    let mut paths = Map::new();
+
+    for route in ROUTES {
+        let mut variants = vec![(route.path.to_owned(), false)];
+        if route.versioned {
+            variants.push((format!("/v2{}", route.path), true));
+        }
+
+        for (path, v2) in variants {
+            let item = paths.entry(openapi_path(&path)).or_insert_with(|| json!({}));
+            item[route.method] = operation(route, v2);
+        }
+    }
+
+    let scheme = json!({
+        "type":        "apiKey",
+        "in":          "header",
+        "name":        "Authorization",
+        "description": "A TOTP token of the secret (i.e. `token 123456`).",
+    });
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title":       "Searchspot",
+            "description": "The service responsible for Honeypot's ElasticSearch data",
+            "version":     version,
+        },
+        "paths": paths,
+        "components": {
+            "schemas": schemas(),
+            "securitySchemes": { "read": scheme, "write": scheme },
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use openapi::{self, Body, ROUTES};
+
+    #[test]
+    fn test_document() {
+        let document = openapi::document("0.16.0");
+        assert_eq!(document["openapi"], "3.0.3");
+        assert_eq!(document["info"]["version"], "0.16.0");
+
+        let paths = &document["paths"];
+        assert!(paths["/talents"]["get"].is_object());
+        assert!(paths["/talents"]["post"].is_object());
+        assert!(paths["/v2/talents"]["get"].is_object());
+        assert!(paths["/maintenance"]["put"].is_object());
+        assert!(paths["/v2/maintenance"].is_null());
+
+        // the move of the bulk deletion is told by both routes
+        assert!(paths["/talents"]["delete"]["description"]
+            .as_str()
+            .unwrap()
+            .contains("POST /talents/delete"));
+        assert!(paths["/talents/delete"]["post"]["description"].is_string());
+        assert!(paths["/talents"]["get"]["description"].is_null());
+
+        let delete = &paths["/talents/{id}"]["delete"];
+        assert_eq!(delete["parameters"][0]["in"], "path");
+        assert_eq!(delete["parameters"][1]["in"], "query");
+
+        let search = &paths["/v2/talents"]["get"]["responses"]["200"]["content"];
+        let search = &search["application/json"]["schema"]["properties"];
+        assert_eq!(search["data"]["$ref"], "#/components/schemas/SearchResults");
+        assert!(search["meta"].is_object());
+        assert!(paths["/v2/talents"]["get"]["responses"]["503"].is_object());
+
+        let stream = &paths["/v2/talents/stream"]["get"]["responses"]["200"]["content"];
+        let stream = &stream["text/event-stream"]["schema"];
+        assert_eq!(stream["$ref"], "#/components/schemas/Change");
+    }
+
+    #[test]
+    fn test_allowed_methods() {
+        let allowed = |segments: &[&str]| openapi::allowed_methods(segments, false);
+        assert_eq!(allowed(&["talents"]), vec!["DELETE", "GET", "POST"]);
+        assert_eq!(allowed(&["v2", "talents", ""]), vec!["DELETE", "GET", "POST"]);
+        assert_eq!(allowed(&["talents", "42", "restore"]), vec!["POST"]);
+        assert_eq!(allowed(&["maintenance"]), vec!["GET", "PUT"]);
+        assert!(allowed(&["v2", "maintenance"]).is_empty());
+        assert!(allowed(&["unknown"]).is_empty());
+
+        // the read-only replicas don't route the writes
+        assert_eq!(openapi::allowed_methods(&["talents"], true), vec!["GET"]);
+        assert_eq!(openapi::allowed_methods(&["talents", "hydrate"], true), vec!["POST"]);
+        assert!(openapi::allowed_methods(&["talents", "42", "restore"], true).is_empty());
+    }
+
+    #[test]
+    fn test_normalize_path() {
+        assert_eq!(openapi::normalize_path(&["talents"]), None);
+        assert_eq!(openapi::normalize_path(&[""]), None);
+        assert_eq!(openapi::normalize_path(&["talents", ""]), Some("/talents".to_owned()));
+        assert_eq!(openapi::normalize_path(&["V2", "Talents"]), Some("/v2/talents".to_owned()));
+        assert_eq!(
+            openapi::normalize_path(&["Talents", "0B6C3BB4", "Restore", ""]),
+            Some("/talents/0B6C3BB4/restore".to_owned())
+        );
+        assert_eq!(openapi::normalize_path(&["talents", "0B6C3BB4"]), None);
+    }
+
+    #[test]
+    fn test_schemas() {
+        let schemas = openapi::schemas();
+
+        for route in ROUTES {
+            for body in route.request.iter().chain(route.response.iter()) {
+                let name = match *body {
+                    Body::One(name) | Body::Many(name) | Body::Events(name) => name,
+                };
+                assert!(schemas[name].is_object(), "{} has no schema", name);
+            }
+        }
+
+        let names: Vec<&str> = ROUTES.iter().map(|route| route.name).collect();
+        let mut unique = names.to_owned();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(names.len(), unique.len());
+    }
+}
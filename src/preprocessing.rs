@@ -0,0 +1,228 @@
+//! Normalizes the keywords of the talent searches before they're turned into
+//! a `query_string` query. The steps run in the order given by
+//! `preprocessing.steps`, and `trim` is the only one enabled by default.
+//!
+//! The boolean operators (`AND`, `OR`, `NOT`) and the quoted phrases are
+//! left as they are by every step.
+
+use params::{Map, Value};
+
+use config::Preprocessing;
+
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Normalizer {
+    /// Remove the leading, trailing and repeated whitespaces.
+    Trim,
+    /// Lowercase every word.
+    Lowercase,
+    /// Remove the emoji.
+    StripEmoji,
+    /// Replace the words listed in `preprocessing.aliases`.
+    Aliases,
+    /// Remove the words listed in `preprocessing.stopwords`.
+    Stopwords,
+}
+
+impl Normalizer {
+    pub const ALL: &'static [Normalizer] = &[
+        Normalizer::Trim,
+        Normalizer::Lowercase,
+        Normalizer::StripEmoji,
+        Normalizer::Aliases,
+        Normalizer::Stopwords,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Normalizer::Trim => "trim",
+            Normalizer::Lowercase => "lowercase",
+            Normalizer::StripEmoji => "strip_emoji",
+            Normalizer::Aliases => "aliases",
+            Normalizer::Stopwords => "stopwords",
+        }
+    }
+}
+
+impl fmt::Display for Normalizer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Normalizer {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Normalizer, String> {
+        Normalizer::ALL
+            .iter()
+            .cloned()
+            .find(|normalizer| normalizer.name() == name)
+            .ok_or_else(|| format!("unknown step {}", name))
+    }
+}
+
+const OPERATORS: &'static [&'static str] = &["AND", "OR", "NOT", "&&", "||"];
+
+/// Whether `word` must be left as it is: boolean operators and quoted phrases.
+fn is_protected(word: &str) -> bool {
+    OPERATORS.contains(&word) || word.contains('"')
+}
+
+fn is_emoji(character: char) -> bool {
+    match character as u32 {
+        0x1F000..=0x1FAFF | 0x2600..=0x27BF | 0x2B00..=0x2BFF | 0xFE0F | 0x200D => true,
+        _ => false,
+    }
+}
+
+/// Apply `normalize` to every word of `keywords` that isn't protected.
+/// Words mapped to nothing are removed.
+fn map_words<F>(keywords: &str, mut normalize: F) -> String
+where
+    F: FnMut(&str) -> Option<String>,
+{
+    let mut words = vec![];
+    let mut in_phrase = false;
+
+    for word in keywords.split(' ') {
+        let quotes = word.matches('"').count();
+        let protected = in_phrase || is_protected(word);
+        if quotes % 2 == 1 {
+            in_phrase = !in_phrase;
+        }
+
+        if protected {
+            words.push(word.to_owned());
+        } else if let Some(word) = normalize(word) {
+            words.push(word);
+        }
+    }
+
+    words.join(" ")
+}
+
+impl Normalizer {
+    fn normalize(&self, keywords: &str, preprocessing: &Preprocessing) -> String {
+        match *self {
+            Normalizer::Trim => keywords.split_whitespace().collect::<Vec<&str>>().join(" "),
+            Normalizer::Lowercase => map_words(keywords, |word| Some(word.to_lowercase())),
+            Normalizer::StripEmoji => keywords.chars().filter(|c| !is_emoji(*c)).collect(),
+            Normalizer::Aliases => map_words(keywords, |word| {
+                let alias = preprocessing
+                    .aliases
+                    .iter()
+                    .find(|&(alias, _)| alias.to_lowercase() == word.to_lowercase());
+
+                match alias {
+                    Some((_, replacement)) => Some(replacement.to_owned()),
+                    None => Some(word.to_owned()),
+                }
+            }),
+            Normalizer::Stopwords => map_words(keywords, |word| {
+                let stopword = preprocessing
+                    .stopwords
+                    .iter()
+                    .any(|stopword| stopword.to_lowercase() == word.to_lowercase());
+
+                if stopword || word.is_empty() {
+                    None
+                } else {
+                    Some(word.to_owned())
+                }
+            }),
+        }
+    }
+}
+
+/// Run the configured steps on `keywords`.
+pub fn normalize(preprocessing: &Preprocessing, keywords: &str) -> String {
+    preprocessing
+        .steps
+        .iter()
+        .fold(keywords.to_owned(), |keywords, normalizer| {
+            normalizer.normalize(&keywords, preprocessing)
+        })
+}
+
+/// Return `params` with their `keywords`, if any, normalized.
+pub fn apply(preprocessing: &Preprocessing, params: &Map) -> Map {
+    let mut params = params.to_owned();
+
+    let keywords = match params.get("keywords") {
+        Some(&Value::String(ref keywords)) => normalize(preprocessing, keywords),
+        _ => return params,
+    };
+
+    params.insert("keywords".to_owned(), Value::String(keywords));
+    params
+}
+
+#[cfg(test)]
+mod tests {
+    use config::Preprocessing;
+    use preprocessing::{self, Normalizer};
+
+    use params::{Map, Value};
+
+    fn configured(steps: Vec<Normalizer>) -> Preprocessing {
+        Preprocessing {
+            steps: steps,
+            aliases: vec![
+                ("js".to_owned(), "javascript".to_owned()),
+                ("k8s".to_owned(), "kubernetes".to_owned()),
+            ]
+            .into_iter()
+            .collect(),
+            stopwords: vec!["developer".to_owned(), "the".to_owned()],
+        }
+    }
+
+    #[test]
+    fn test_normalize() {
+        let trim = configured(vec![Normalizer::Trim]);
+        assert_eq!(preprocessing::normalize(&trim, "  rust   go "), "rust go");
+
+        let lowercase = configured(vec![Normalizer::Lowercase]);
+        assert_eq!(
+            preprocessing::normalize(&lowercase, "Rust AND \"Project Manager\" NOT Go"),
+            "rust AND \"Project Manager\" NOT go"
+        );
+
+        let emoji = configured(vec![Normalizer::StripEmoji, Normalizer::Trim]);
+        assert_eq!(preprocessing::normalize(&emoji, "rust 🦀 ❤️ go"), "rust go");
+
+        let aliases = configured(vec![Normalizer::Aliases]);
+        assert_eq!(preprocessing::normalize(&aliases, "JS OR k8s"), "javascript OR kubernetes");
+
+        let stopwords = configured(vec![Normalizer::Stopwords]);
+        assert_eq!(
+            preprocessing::normalize(&stopwords, "the rust developer \"the developer\""),
+            "rust \"the developer\""
+        );
+    }
+
+    #[test]
+    fn test_steps() {
+        assert_eq!("strip_emoji".parse::<Normalizer>(), Ok(Normalizer::StripEmoji));
+        assert!("stemming".parse::<Normalizer>().is_err());
+
+        // the steps run in the given order
+        let all = configured(Normalizer::ALL.to_vec());
+        assert_eq!(preprocessing::normalize(&all, " The JS 🚀 Developer "), "javascript");
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut params = Map::new();
+        params.assign("keywords", Value::String(" rust ".to_owned())).unwrap();
+
+        let params = preprocessing::apply(&Preprocessing::default(), &params);
+        assert_eq!(params.get("keywords"), Some(&Value::String("rust".to_owned())));
+
+        assert!(preprocessing::apply(&Preprocessing::default(), &Map::new()).is_empty());
+    }
+}
@@ -0,0 +1,133 @@
+//! `presented_talents` make the talents with those ids skip the visibility
+//! rules, but they come from the query string like any other parameter.
+//!
+//! When `auth.presented_talents_secret` is set, they must be signed by the
+//! caller: `presented_talents_signature` is the hex-encoded HMAC-SHA256 of
+//! `<company_id>:<ids>` (the ids sorted and comma-separated), so that the
+//! talents presented to a company can't be reused by another one.
+
+use hmac::{Hmac, Mac};
+use params::{FromValue, Map, Value};
+use sha2::Sha256;
+
+use resources::TalentId;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Return the message signed for the talents presented to `company_id`.
+fn message(company_id: &str, presented_talents: &[TalentId]) -> String {
+    let mut ids = presented_talents.to_vec();
+    ids.sort();
+    ids.dedup();
+
+    let ids: Vec<String> = ids.iter().map(|id| id.to_string()).collect();
+    format!("{}:{}", company_id, ids.join(","))
+}
+
+fn mac(secret: &str, company_id: &str, presented_talents: &[TalentId]) -> HmacSha256 {
+    // HMAC accepts keys of any length
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(message(company_id, presented_talents).as_bytes());
+    mac
+}
+
+/// Return the signature of the talents presented to `company_id`.
+pub fn sign(secret: &str, company_id: &str, presented_talents: &[TalentId]) -> String {
+    mac(secret, company_id, presented_talents)
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 || !hex.is_ascii() {
+        return None;
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
+
+/// Check that the `presented_talents` of `params`, if any, have been signed
+/// with `secret` for the given `company_id`.
+pub fn verify(secret: &str, params: &Map) -> Result<(), String> {
+    let presented_talents = type_vec_from_params!(TalentId, params, "presented_talents");
+    if presented_talents.is_empty() {
+        return Ok(());
+    }
+
+    let company_id = match params.get("company_id") {
+        Some(&Value::String(ref company_id)) => company_id.to_owned(),
+        _ => return Err("presented_talents require a company_id".to_owned()),
+    };
+
+    let signature = match params.get("presented_talents_signature") {
+        Some(&Value::String(ref signature)) => decode_hex(signature),
+        _ => return Err("presented_talents must be signed".to_owned()),
+    };
+
+    match signature {
+        Some(ref signature)
+            if mac(secret, &company_id, &presented_talents)
+                .verify_slice(signature)
+                .is_ok() =>
+        {
+            Ok(())
+        }
+        _ => Err("The signature of presented_talents is invalid".to_owned()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use presented_talents::{sign, verify};
+    use resources::TalentId;
+
+    use params::{Map, Value};
+
+    const SECRET: &'static str = "b5zrkr2kxgnvnvvh";
+
+    fn params(company_id: &str, presented_talent: Option<&str>, signature: Option<String>) -> Map {
+        let mut params = Map::new();
+        params
+            .assign("company_id", Value::String(company_id.to_owned()))
+            .unwrap();
+
+        if let Some(id) = presented_talent {
+            params
+                .assign("presented_talents", Value::String(id.to_owned()))
+                .unwrap();
+        }
+
+        if let Some(signature) = signature {
+            params
+                .assign("presented_talents_signature", Value::String(signature))
+                .unwrap();
+        }
+
+        params
+    }
+
+    #[test]
+    fn test_verify() {
+        // nothing to verify
+        assert!(verify(SECRET, &params("1", None, None)).is_ok());
+
+        let signature = sign(SECRET, "1", &[TalentId::Numeric(2)]);
+        assert!(verify(SECRET, &params("1", Some("2"), Some(signature.to_owned()))).is_ok());
+
+        assert!(verify(SECRET, &params("1", Some("2"), None)).is_err());
+        assert!(verify(SECRET, &params("1", Some("3"), Some(signature.to_owned()))).is_err());
+        assert!(verify(SECRET, &params("2", Some("2"), Some(signature.to_owned()))).is_err());
+        assert!(verify("another secret", &params("1", Some("2"), Some(signature))).is_err());
+        assert!(verify(SECRET, &params("1", Some("2"), Some("zz".to_owned()))).is_err());
+
+        let uuid = "0b6c3bb4-5c2a-4f5a-9a3e-3fdc1c9d4e21";
+        let signature = sign(SECRET, "1", &[uuid.parse().unwrap()]);
+        assert!(verify(SECRET, &params("1", Some(uuid), Some(signature))).is_ok());
+    }
+}
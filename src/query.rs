@@ -0,0 +1,444 @@
+//! A minimal builder for the ElasticSearch query DSL.
+//!
+//! It mirrors the subset of the rs_es builders we used to rely on, so that
+//! the filters can be written the same way regardless of the client sending them.
+//! Every builder serializes to the plain JSON that ElasticSearch expects.
+
+use serde::ser::{Serialize, Serializer};
+use serde_json::{Map, Value};
+
+/// The `default_operator` of a `query_string` query.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operator {
+    And,
+    Or,
+}
+
+impl Operator {
+    fn as_str(&self) -> &'static str {
+        match *self {
+            Operator::And => "AND",
+            Operator::Or => "OR",
+        }
+    }
+}
+
+/// A query ready to be sent to ElasticSearch.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query(Value);
+
+impl Serialize for Query {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+impl From<Query> for Value {
+    fn from(query: Query) -> Value {
+        query.0
+    }
+}
+
+/// One or more queries, accepted wherever a bool clause is expected.
+pub struct Queries(Vec<Query>);
+
+impl From<Query> for Queries {
+    fn from(query: Query) -> Queries {
+        Queries(vec![query])
+    }
+}
+
+impl From<Vec<Query>> for Queries {
+    fn from(queries: Vec<Query>) -> Queries {
+        Queries(queries)
+    }
+}
+
+/// The `type` of a `match` query.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MatchType {
+    Boolean,
+    Phrase,
+    PhrasePrefix,
+}
+
+impl MatchType {
+    fn as_str(&self) -> &'static str {
+        match *self {
+            MatchType::Boolean => "boolean",
+            MatchType::Phrase => "phrase",
+            MatchType::PhrasePrefix => "phrase_prefix",
+        }
+    }
+}
+
+impl Query {
+    pub fn build_match_all() -> MatchAllQueryBuilder {
+        MatchAllQueryBuilder
+    }
+
+    pub fn build_bool() -> BoolQueryBuilder {
+        BoolQueryBuilder::default()
+    }
+
+    pub fn build_term<V: Into<Value>>(field: &str, value: V) -> TermQueryBuilder {
+        TermQueryBuilder {
+            field: field.to_owned(),
+            value: value.into(),
+        }
+    }
+
+    pub fn build_terms(field: &str) -> TermsQueryBuilder {
+        TermsQueryBuilder {
+            field: field.to_owned(),
+            values: Value::Array(vec![]),
+        }
+    }
+
+    pub fn build_range(field: &str) -> RangeQueryBuilder {
+        RangeQueryBuilder {
+            field: field.to_owned(),
+            options: Map::new(),
+        }
+    }
+
+    pub fn build_exists(field: &str) -> ExistsQueryBuilder {
+        ExistsQueryBuilder {
+            field: field.to_owned(),
+        }
+    }
+
+    pub fn build_nested(path: &str, query: Query) -> NestedQueryBuilder {
+        NestedQueryBuilder {
+            path: path.to_owned(),
+            query: query,
+        }
+    }
+
+    pub fn build_match<V: Into<Value>>(field: &str, value: V) -> MatchQueryBuilder {
+        MatchQueryBuilder {
+            field: field.to_owned(),
+            query: value.into(),
+            match_type: None,
+        }
+    }
+
+    pub fn build_query_string<S: Into<String>>(query: S) -> QueryStringQueryBuilder {
+        QueryStringQueryBuilder {
+            query: query.into(),
+            fields: vec![],
+            default_operator: None,
+        }
+    }
+}
+
+pub struct MatchAllQueryBuilder;
+
+impl MatchAllQueryBuilder {
+    pub fn build(self) -> Query {
+        Query(json!({ "match_all": {} }))
+    }
+}
+
+#[derive(Default)]
+pub struct BoolQueryBuilder {
+    must: Vec<Query>,
+    should: Vec<Query>,
+    filter: Vec<Query>,
+    must_not: Vec<Query>,
+}
+
+impl BoolQueryBuilder {
+    pub fn with_must<Q: Into<Queries>>(mut self, queries: Q) -> Self {
+        self.must = queries.into().0;
+        self
+    }
+
+    pub fn with_should<Q: Into<Queries>>(mut self, queries: Q) -> Self {
+        self.should = queries.into().0;
+        self
+    }
+
+    pub fn with_filter<Q: Into<Queries>>(mut self, queries: Q) -> Self {
+        self.filter = queries.into().0;
+        self
+    }
+
+    pub fn with_must_not<Q: Into<Queries>>(mut self, queries: Q) -> Self {
+        self.must_not = queries.into().0;
+        self
+    }
+
+    pub fn build(self) -> Query {
+        let mut clauses = Map::new();
+
+        for (clause, queries) in vec![
+            ("must", self.must),
+            ("should", self.should),
+            ("filter", self.filter),
+            ("must_not", self.must_not),
+        ] {
+            if !queries.is_empty() {
+                let queries = queries.into_iter().map(Value::from).collect();
+                clauses.insert(clause.to_owned(), Value::Array(queries));
+            }
+        }
+
+        Query(json!({ "bool": clauses }))
+    }
+}
+
+pub struct TermQueryBuilder {
+    field: String,
+    value: Value,
+}
+
+impl TermQueryBuilder {
+    pub fn build(self) -> Query {
+        let mut term = Map::new();
+        term.insert(self.field, self.value);
+        Query(json!({ "term": term }))
+    }
+}
+
+pub struct TermsQueryBuilder {
+    field: String,
+    values: Value,
+}
+
+impl TermsQueryBuilder {
+    pub fn with_values<V: Into<Value>>(mut self, values: V) -> Self {
+        self.values = values.into();
+        self
+    }
+
+    pub fn build(self) -> Query {
+        let mut terms = Map::new();
+        terms.insert(self.field, self.values);
+        Query(json!({ "terms": terms }))
+    }
+}
+
+pub struct RangeQueryBuilder {
+    field: String,
+    options: Map<String, Value>,
+}
+
+impl RangeQueryBuilder {
+    pub fn with_gt<V: Into<Value>>(mut self, value: V) -> Self {
+        self.options.insert("gt".to_owned(), value.into());
+        self
+    }
+
+    pub fn with_gte<V: Into<Value>>(mut self, value: V) -> Self {
+        self.options.insert("gte".to_owned(), value.into());
+        self
+    }
+
+    pub fn with_lt<V: Into<Value>>(mut self, value: V) -> Self {
+        self.options.insert("lt".to_owned(), value.into());
+        self
+    }
+
+    pub fn with_lte<V: Into<Value>>(mut self, value: V) -> Self {
+        self.options.insert("lte".to_owned(), value.into());
+        self
+    }
+
+    pub fn with_format(mut self, format: &str) -> Self {
+        self.options.insert("format".to_owned(), json!(format));
+        self
+    }
+
+    pub fn build(self) -> Query {
+        let mut range = Map::new();
+        range.insert(self.field, Value::Object(self.options));
+        Query(json!({ "range": range }))
+    }
+}
+
+pub struct ExistsQueryBuilder {
+    field: String,
+}
+
+impl ExistsQueryBuilder {
+    pub fn build(self) -> Query {
+        Query(json!({ "exists": { "field": self.field } }))
+    }
+}
+
+pub struct NestedQueryBuilder {
+    path: String,
+    query: Query,
+}
+
+impl NestedQueryBuilder {
+    pub fn build(self) -> Query {
+        Query(json!({
+            "nested": {
+                "path":  self.path,
+                "query": self.query,
+            }
+        }))
+    }
+}
+
+pub struct MatchQueryBuilder {
+    field: String,
+    query: Value,
+    match_type: Option<MatchType>,
+}
+
+impl MatchQueryBuilder {
+    pub fn with_type(mut self, match_type: MatchType) -> Self {
+        self.match_type = Some(match_type);
+        self
+    }
+
+    pub fn build(self) -> Query {
+        let mut options = Map::new();
+        options.insert("query".to_owned(), self.query);
+
+        if let Some(match_type) = self.match_type {
+            options.insert("type".to_owned(), json!(match_type.as_str()));
+        }
+
+        let mut field = Map::new();
+        field.insert(self.field, Value::Object(options));
+        Query(json!({ "match": field }))
+    }
+}
+
+pub struct QueryStringQueryBuilder {
+    query: String,
+    fields: Vec<String>,
+    default_operator: Option<Operator>,
+}
+
+impl QueryStringQueryBuilder {
+    pub fn with_fields(mut self, fields: Vec<String>) -> Self {
+        self.fields = fields;
+        self
+    }
+
+    pub fn with_default_operator(mut self, operator: Operator) -> Self {
+        self.default_operator = Some(operator);
+        self
+    }
+
+    pub fn build(self) -> Query {
+        let mut options = Map::new();
+        options.insert("query".to_owned(), json!(self.query));
+
+        if !self.fields.is_empty() {
+            options.insert("fields".to_owned(), json!(self.fields));
+        }
+
+        if let Some(operator) = self.default_operator {
+            options.insert("default_operator".to_owned(), json!(operator.as_str()));
+        }
+
+        Query(json!({ "query_string": options }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+
+    #[test]
+    fn test_bool_query() {
+        let query = Query::build_bool()
+            .with_must(vec![Query::build_term("accepted", true).build()])
+            .with_filter(Query::build_exists("headline").build())
+            .build();
+
+        assert_eq!(
+            serde_json::to_value(&query).unwrap(),
+            json!({
+                "bool": {
+                    "must":   [{ "term": { "accepted": true } }],
+                    "filter": [{ "exists": { "field": "headline" } }]
+                }
+            })
+        );
+
+        // empty clauses are omitted
+        assert_eq!(
+            serde_json::to_string(&Query::build_bool().build()).unwrap(),
+            "{\"bool\":{}}"
+        );
+    }
+
+    #[test]
+    fn test_range_query() {
+        let query = Query::build_range("batch_starts_at")
+            .with_lte("2018-01-01T00:00:00Z")
+            .with_format("dateOptionalTime")
+            .build();
+
+        assert_eq!(
+            serde_json::to_value(&query).unwrap(),
+            json!({
+                "range": {
+                    "batch_starts_at": {
+                        "lte":    "2018-01-01T00:00:00Z",
+                        "format": "dateOptionalTime"
+                    }
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn test_nested_query() {
+        let query = Query::build_nested(
+            "salary_expectations",
+            Query::build_range("salary_expectations.minimum")
+                .with_lte(40000)
+                .build(),
+        ).build();
+
+        assert_eq!(
+            serde_json::to_value(&query).unwrap(),
+            json!({
+                "nested": {
+                    "path":  "salary_expectations",
+                    "query": { "range": { "salary_expectations.minimum": { "lte": 40000 } } }
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn test_query_string_query() {
+        let query = Query::build_query_string("rust AND ember")
+            .with_fields(vec!["skills".to_owned(), "summary".to_owned()])
+            .build();
+
+        assert_eq!(
+            serde_json::to_value(&query).unwrap(),
+            json!({
+                "query_string": {
+                    "query":  "rust AND ember",
+                    "fields": ["skills", "summary"]
+                }
+            })
+        );
+
+        let query = Query::build_query_string("rust ember")
+            .with_default_operator(Operator::And)
+            .build();
+
+        assert_eq!(
+            serde_json::to_value(&query).unwrap(),
+            json!({
+                "query_string": {
+                    "query":            "rust ember",
+                    "default_operator": "AND"
+                }
+            })
+        );
+    }
+}
@@ -0,0 +1,213 @@
+//! The reverse of the talent search: given a talent, find the jobs and the
+//! companies matching its desired roles, locations and salary, ranked by
+//! how many of them they match.
+
+use serde::de::DeserializeOwned;
+
+use backends::SearchBackend;
+use query::Query;
+use resource::{EsError, IndexSet};
+use resources::{Company, Job, Talent};
+use terms::VectorOfTerms;
+
+/// A recommended job or company, along with its relevance.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Recommendation<T> {
+    pub score: Option<f64>,
+    pub item: T,
+}
+
+/// The jobs and the companies recommended to a talent, the best first.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Recommendations {
+    pub jobs: Vec<Recommendation<Job>>,
+    pub companies: Vec<Recommendation<Company>>,
+}
+
+/// The lowest salary the talent would accept, in any city.
+fn minimum_salary(talent: &Talent) -> Option<u64> {
+    talent
+        .salary_expectations
+        .iter()
+        .filter_map(|expectations| expectations.minimum)
+        .min()
+}
+
+/// Return the query matching the jobs (those with `required_skills`)
+/// whose title, location, skills or salary budget match `talent`.
+pub fn jobs_query(talent: &Talent) -> Query {
+    let mut criteria = vec![
+        <Query as VectorOfTerms<String>>::build_terms("title", &talent.desired_work_roles),
+        <Query as VectorOfTerms<String>>::build_terms("location", &talent.work_locations),
+        <Query as VectorOfTerms<String>>::build_terms("required_skills", &talent.skills),
+    ];
+
+    if let Some(minimum_salary) = minimum_salary(talent) {
+        criteria.push(vec![
+            Query::build_range("salary_budget")
+                .with_gte(minimum_salary)
+                .build(),
+        ]);
+    }
+
+    matching(
+        "required_skills",
+        criteria.into_iter().flat_map(|x| x).collect(),
+    )
+}
+
+/// Return the query matching the companies (those with an `industry`)
+/// hiring for the roles desired by `talent` or based where it wants to work.
+pub fn companies_query(talent: &Talent) -> Query {
+    let criteria = vec![
+        <Query as VectorOfTerms<String>>::build_terms("hiring_roles", &talent.desired_work_roles),
+        <Query as VectorOfTerms<String>>::build_terms("locations", &talent.work_locations),
+    ];
+
+    matching(
+        "industry",
+        criteria.into_iter().flat_map(|x| x).collect(),
+    )
+}
+
+/// Match the documents with `field` satisfying at least one of `criteria`,
+/// which add up to their score.
+fn matching(field: &str, criteria: Vec<Query>) -> Query {
+    Query::build_bool()
+        .with_must(vec![
+            Query::build_exists(field).build(),
+            Query::build_bool().with_should(criteria).build(),
+        ])
+        .build()
+}
+
+fn search<T: DeserializeOwned>(
+    es: &mut dyn SearchBackend,
+    index: &str,
+    query: Query,
+    size: u64,
+) -> Result<Vec<Recommendation<T>>, EsError> {
+    let result = es.search::<T>(&[index], &json!({ "query": query, "size": size }))?;
+
+    Ok(result
+        .hits
+        .hits
+        .into_iter()
+        .filter_map(|hit| {
+            let score = hit.score;
+            hit.source.map(|item| Recommendation {
+                score: score,
+                item: *item,
+            })
+        })
+        .collect())
+}
+
+/// Return the best `size` jobs and companies stored in the primary index of `indexes`
+/// for the talent identified by `talent_id`, which can be stored in any of them,
+/// or `None` if there is no such talent.
+pub fn recommend(
+    es: &mut dyn SearchBackend,
+    indexes: &IndexSet,
+    talent_id: &str,
+    size: u64,
+) -> Result<Option<Recommendations>, EsError> {
+    let talents = indexes.mget::<Talent>(es, "talent", &[talent_id.to_owned()])?;
+    let talent = match talents.into_iter().next() {
+        Some(talent) => talent,
+        None => return Ok(None),
+    };
+
+    let index = &*indexes.index;
+    Ok(Some(Recommendations {
+        jobs: search(es, index, jobs_query(&talent), size)?,
+        companies: search(es, index, companies_query(&talent), size)?,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use backends::{Memory, SearchBackend};
+    use recommendations;
+    use resource::{IndexSet, Resource};
+    use resources::{Company, Job, Talent};
+    use test_support;
+
+    const INDEX: &'static str = "memory_recommendations";
+
+    fn job(id: u32, title: &str, location: &str, salary_budget: Option<u64>) -> Job {
+        Job {
+            id: id,
+            title: title.to_owned(),
+            required_skills: vec!["Rust".to_owned()],
+            location: location.to_owned(),
+            salary_budget: salary_budget,
+            experience: None,
+        }
+    }
+
+    fn company(id: u32, hiring_roles: &[&str], locations: &[&str]) -> Company {
+        Company {
+            id: id,
+            name: format!("Company {}", id),
+            industry: "Software".to_owned(),
+            locations: locations.iter().map(|location| location.to_string()).collect(),
+            hiring_roles: hiring_roles.iter().map(|role| role.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_recommend() {
+        let mut memory: Box<dyn SearchBackend> = Box::new(Memory::new());
+        let talent = test_support::talent(
+            1,
+            json!({
+                "desired_work_roles":            ["Backend Developer"],
+                "desired_work_roles_experience": ["2..4"],
+                "work_locations":                ["Berlin"],
+                "current_location":              "Berlin",
+                "skills":                        ["Go"],
+                "salary_expectations":           [{ "minimum": 50000, "currency": "EUR", "city": "Berlin" }],
+            }),
+        );
+
+        // the memory backend doesn't tell the types apart by their ids
+        Talent::index(&mut *memory, INDEX, vec![talent]).unwrap();
+        Job::index(
+            &mut *memory,
+            INDEX,
+            vec![
+                job(11, "Backend Developer", "Munich", None),
+                job(12, "Backend Developer", "Berlin", Some(60000)),
+                job(13, "Frontend Developer", "Amsterdam", Some(40000)),
+            ],
+        )
+        .unwrap();
+        Company::index(
+            &mut *memory,
+            INDEX,
+            vec![
+                company(21, &["Frontend Developer"], &["Berlin"]),
+                company(22, &["DevOps"], &["Amsterdam"]),
+            ],
+        )
+        .unwrap();
+
+        let indexes = IndexSet::new(INDEX);
+        let recommendations = recommendations::recommend(&mut *memory, &indexes, "1", 10)
+            .unwrap()
+            .unwrap();
+        let jobs: Vec<u32> = recommendations.jobs.iter().map(|job| job.item.id).collect();
+        let companies: Vec<u32> = recommendations
+            .companies
+            .iter()
+            .map(|company| company.item.id)
+            .collect();
+        assert_eq!(jobs, vec![12, 11]);
+        assert_eq!(companies, vec![21]);
+
+        assert!(recommendations::recommend(&mut *memory, &indexes, "2", 10)
+            .unwrap()
+            .is_none());
+    }
+}
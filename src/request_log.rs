@@ -0,0 +1,175 @@
+//! Logs the requests served (i.e. `GET /talents?keywords=rust -> 200 OK (12.3 ms)`)
+//! without the values of the parameters listing talents or carrying secrets,
+//! which are redacted, and with the other values truncated. Only a share of the
+//! successful requests is logged when `request_log.sample_rate` is below 1.
+
+use iron::prelude::*;
+use iron::status::Status;
+use iron::typemap::Key;
+use iron::{AfterMiddleware, BeforeMiddleware};
+
+use config::RequestLog;
+use context;
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Instant;
+
+const REDACTED: &'static str = "[REDACTED]";
+
+/// When the request started being served.
+struct StartedAt;
+
+impl Key for StartedAt {
+    type Value = Instant;
+}
+
+/// Return the name of a parameter of the query string without its
+/// (possibly percent-encoded) brackets, i.e. `contacted_talents` for
+/// both `contacted_talents[]` and `contacted_talents%5B%5D`.
+fn param_name(name: &str) -> &str {
+    let name = name.trim_end_matches("[]");
+    let brackets = name.len().saturating_sub(6);
+    match name.get(brackets..) {
+        Some(suffix) if suffix.eq_ignore_ascii_case("%5B%5D") => &name[..brackets],
+        _ => name,
+    }
+}
+
+/// Return `query` with the values of the `redacted_params` and of the sensitive
+/// ones (see `context::is_sensitive`) hidden, and the other values truncated to `max_value_length` characters.
+pub fn sanitize_query(query: &str, config: &RequestLog) -> String {
+    query
+        .split('&')
+        .map(|pair| {
+            let mut pair = pair.splitn(2, '=');
+            let name = pair.next().unwrap_or("");
+            let value = match pair.next() {
+                Some(value) => value,
+                None => return name.to_owned(),
+            };
+
+            let param = param_name(name);
+            if context::is_sensitive(param) || config.redacted_params.iter().any(|p| p == param) {
+                return format!("{}={}", name, REDACTED);
+            }
+
+            match value.char_indices().nth(config.max_value_length) {
+                Some((end, _)) => format!("{}={}...", name, &value[..end]),
+                None => format!("{}={}", name, value),
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("&")
+}
+
+/// Tell whether a request is among the `sample_rate` share of the logged ones.
+fn is_sampled(sample_rate: f64) -> bool {
+    if sample_rate >= 1.0 {
+        return true;
+    }
+
+    let random = RandomState::new().build_hasher().finish();
+    ((random % 10000) as f64) < sample_rate * 10000.0
+}
+
+/// Logs the requests as configured in `request_log`, replacing the
+/// `logger` middleware, which logs the complete query strings.
+#[derive(Clone)]
+pub struct RequestLogger {
+    config: RequestLog,
+}
+
+impl RequestLogger {
+    /// Return the middlewares to link before and after the handler.
+    pub fn new(config: RequestLog) -> (RequestLogger, RequestLogger) {
+        let logger = RequestLogger { config: config };
+        (logger.clone(), logger)
+    }
+
+    fn log(&self, req: &mut Request, status: Option<Status>) {
+        let status = status.unwrap_or(Status::NotFound);
+        if status.to_u16() < 400 && !is_sampled(self.config.sample_rate) {
+            return;
+        }
+
+        let path = format!("/{}", req.url.path().join("/"));
+        let target = match req.url.query() {
+            Some(query) => format!("{}?{}", path, sanitize_query(query, &self.config)),
+            None => path,
+        };
+
+        match req.extensions.get::<StartedAt>() {
+            Some(started) => {
+                let elapsed = started.elapsed();
+                let elapsed = elapsed.as_secs() as f64 * 1000.0
+                    + f64::from(elapsed.subsec_nanos()) / 1_000_000.0;
+                info!("{} {} -> {} ({:.1} ms)", req.method, target, status, elapsed);
+            }
+            None => info!("{} {} -> {}", req.method, target, status),
+        }
+    }
+}
+
+impl BeforeMiddleware for RequestLogger {
+    fn before(&self, req: &mut Request) -> IronResult<()> {
+        req.extensions.insert::<StartedAt>(Instant::now());
+        Ok(())
+    }
+}
+
+impl AfterMiddleware for RequestLogger {
+    fn after(&self, req: &mut Request, res: Response) -> IronResult<Response> {
+        self.log(req, res.status);
+        Ok(res)
+    }
+
+    fn catch(&self, req: &mut Request, err: IronError) -> IronResult<Response> {
+        self.log(req, err.response.status);
+        Err(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use config::RequestLog;
+    use request_log::{self, param_name};
+
+    #[test]
+    fn test_param_name() {
+        assert_eq!(param_name("keywords"), "keywords");
+        assert_eq!(param_name("contacted_talents[]"), "contacted_talents");
+        assert_eq!(param_name("contacted_talents%5B%5D"), "contacted_talents");
+        assert_eq!(param_name("contacted_talents%5b%5d"), "contacted_talents");
+    }
+
+    #[test]
+    fn test_sanitize_query() {
+        let config = RequestLog {
+            max_value_length: 5,
+            ..RequestLog::default()
+        };
+
+        assert_eq!(
+            request_log::sanitize_query(
+                "keywords=rust&contacted_talents[]=1&contacted_talents%5B%5D=2&debug",
+                &config
+            ),
+            "keywords=rust&contacted_talents[]=[REDACTED]&contacted_talents%5B%5D=[REDACTED]&debug"
+        );
+        assert_eq!(
+            request_log::sanitize_query("keywords=javascript&company_id=7", &config),
+            "keywords=javas...&company_id=7"
+        );
+        assert_eq!(
+            request_log::sanitize_query("presented_talents_signature=abcdef&token=1", &config),
+            "presented_talents_signature=[REDACTED]&token=[REDACTED]"
+        );
+    }
+
+    #[test]
+    fn test_is_sampled() {
+        assert!(request_log::is_sampled(1.0));
+        assert!(!request_log::is_sampled(0.0));
+    }
+}
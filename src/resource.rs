@@ -1,29 +1,1181 @@
-use serde::de::DeserializeOwned;
+use serde::de::{self, DeserializeOwned, Deserializer, Visitor};
 use serde::ser::Serialize;
+use serde_json::{self, Map, Value};
 
-use rs_es::error::EsError;
-use rs_es::operations::bulk::BulkResult;
-use rs_es::operations::delete::DeleteResult;
-use rs_es::operations::mapping::MappingResult;
-use rs_es::Client;
+use params::Map as ParamsMap;
 
-use params::Map;
+use backends::SearchBackend;
+use config::{Config, Limits, ES};
+use error::SearchspotError;
+use jsonapi::Collection;
+use mapping::IndexDefinition;
 
 use std::any::Any;
-use std::fmt::Debug;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{self, Debug, Display};
+use std::io;
+use std::str::FromStr;
+
+/// The ids of the documents of a `Resource`, given as strings in the
+/// routes (i.e. `DELETE /talents/:id`) and as JSON in the bodies.
+pub trait ResourceId:
+    Serialize + DeserializeOwned + FromStr + Display + Debug + Clone + PartialEq + Send + Sync
+{
+}
+
+impl ResourceId for u32 {}
+impl ResourceId for String {}
 
 pub trait Resource: Send + Sync + Any + Serialize + DeserializeOwned + Debug {
-    type Results: Serialize + DeserializeOwned;
+    type Results: Serialize + DeserializeOwned + Default;
+    type Id: ResourceId;
+
+    /// Respond to GET requests returning an array with found ids,
+    /// which is empty when the search fails.
+    fn search(
+        es: &mut dyn SearchBackend,
+        default_index: &str,
+        params: &ParamsMap,
+    ) -> Self::Results {
+        Self::try_search(es, default_index, params).unwrap_or_else(|error| {
+            error!("{}", error);
+            Self::Results::default()
+        })
+    }
+
+    /// Like `search`, returning why the search failed instead of no results.
+    fn try_search(
+        es: &mut dyn SearchBackend,
+        default_index: &str,
+        params: &ParamsMap,
+    ) -> Result<Self::Results, SearchspotError>;
+
+    /// Check the `params` of a search before running it, returning
+    /// why they can't be used or exceed `limits` otherwise.
+    fn validate_search(_params: &ParamsMap, _limits: &Limits) -> Result<(), SearchspotError> {
+        Ok(())
+    }
 
-    /// Respond to GET requests returning an array with found ids
-    fn search(es: &mut Client, default_index: &str, params: &Map) -> Self::Results;
+    /// Where the documents are in `Results`, to render them as JSON:API
+    /// (see `jsonapi`). `None` if they can't be rendered that way.
+    fn json_api() -> Option<Collection> {
+        None
+    }
+
+    /// Normalize the entity as configured in `config` before it's indexed,
+    /// i.e. replace the names of its locations with the canonical ones (see `locations`).
+    fn normalize(&mut self, _config: &Config) {}
 
     /// Respond to POST requests indexing given entity
-    fn index(es: &mut Client, index: &str, resources: Vec<Self>) -> Result<BulkResult, EsError>;
+    fn index(
+        es: &mut dyn SearchBackend,
+        index: &str,
+        resources: Vec<Self>,
+    ) -> Result<BulkResult, EsError>;
+
+    /// Respond to POST requests with `op_type=create`, indexing given entities
+    /// unless their ids are taken already. Not supported by default.
+    fn create(
+        _es: &mut dyn SearchBackend,
+        _index: &str,
+        _resources: Vec<Self>,
+    ) -> Result<BulkResult, EsError> {
+        Err(EsError::Unsupported("Create-only indexing is not supported".to_owned()))
+    }
 
     /// Respond to DELETE requests on given id deleting it from given index
-    fn delete(es: &mut Client, id: &str, index: &str) -> Result<DeleteResult, EsError>;
+    fn delete(
+        es: &mut dyn SearchBackend,
+        id: &Self::Id,
+        index: &str,
+    ) -> Result<DeleteResult, EsError>;
+
+    /// Respond to DELETE requests with `soft=true` on given id, marking it as
+    /// deleted in given index so that it can be restored. Not supported by default.
+    fn soft_delete(
+        _es: &mut dyn SearchBackend,
+        _id: &Self::Id,
+        _index: &str,
+    ) -> Result<DeleteResult, EsError> {
+        Err(EsError::Unsupported("Soft deletion is not supported".to_owned()))
+    }
+
+    /// Respond to DELETE requests on given ids deleting them from given index
+    /// through a single bulk request
+    fn delete_many(
+        es: &mut dyn SearchBackend,
+        ids: &[Self::Id],
+        index: &str,
+    ) -> Result<BulkResult, EsError>;
+
+    /// Respond to DELETE requests rebuilding and reindexing given index.
+    /// `definition`, when given, replaces the built-in mapping and analysis.
+    fn reset_index(
+        es: &mut dyn SearchBackend,
+        index: &str,
+        definition: Option<&IndexDefinition>,
+    ) -> Result<MappingResult, EsError>;
+
+    /// The value routing the document to its own index of an `IndexSet` (see `IndexSet::route`),
+    /// i.e. the location of a talent. `None` keeps it in the primary index.
+    fn route(&self) -> Option<&str> {
+        None
+    }
+
+    /// Like `try_search`, on every index of `indexes` at once.
+    fn search_in(
+        es: &mut dyn SearchBackend,
+        indexes: &IndexSet,
+        params: &ParamsMap,
+    ) -> Result<Self::Results, SearchspotError> {
+        Self::try_search(es, &indexes.to_string(), params)
+    }
+
+    /// Like `index`, storing each of `resources` in the index it's routed to
+    /// through a bulk request per index. The copies left in the other indexes
+    /// by the documents routed elsewhere before (i.e. a talent that moved) are deleted.
+    fn index_in(
+        es: &mut dyn SearchBackend,
+        indexes: &IndexSet,
+        resources: Vec<Self>,
+    ) -> Result<BulkResult, EsError> {
+        indexes.store(es, resources, Self::index)
+    }
+
+    /// Like `create`, storing each of `resources` in the index it's routed to.
+    fn create_in(
+        es: &mut dyn SearchBackend,
+        indexes: &IndexSet,
+        resources: Vec<Self>,
+    ) -> Result<BulkResult, EsError> {
+        indexes.store(es, resources, Self::create)
+    }
+
+    /// Like `delete`, deleting the document from every index of `indexes`,
+    /// since only the document itself tells where it's routed to.
+    fn delete_in(
+        es: &mut dyn SearchBackend,
+        id: &Self::Id,
+        indexes: &IndexSet,
+    ) -> Result<DeleteResult, SearchspotError> {
+        indexes.remove(|index| Self::delete(es, id, index))
+    }
+
+    /// Like `soft_delete`, marking the document in every index of `indexes` that contains it.
+    fn soft_delete_in(
+        es: &mut dyn SearchBackend,
+        id: &Self::Id,
+        indexes: &IndexSet,
+    ) -> Result<DeleteResult, SearchspotError> {
+        indexes.remove(|index| Self::soft_delete(es, id, index))
+    }
+
+    /// Like `delete_many`, deleting `ids` from every index of `indexes`.
+    /// The ids missing from an index are reported as `not_found` by its items.
+    fn delete_many_in(
+        es: &mut dyn SearchBackend,
+        ids: &[Self::Id],
+        indexes: &IndexSet,
+    ) -> Result<BulkResult, EsError> {
+        let mut result = BulkResult::empty();
+        for index in indexes.writable() {
+            result.merge(Self::delete_many(es, ids, index)?);
+        }
+
+        Ok(result)
+    }
+}
+
+/// The indexes storing the documents of a `Resource`: the primary `index` (`config.es.index`,
+/// or a comma-separated list when searching) and the ones the documents are routed to by the value of their
+/// `Resource::route` (i.e. `Berlin = "talents_de"`, see `config.es.routes`).
+///
+/// The searches read all of them at once, while the writes go to a single index per document,
+/// deleting the copies a document left in the other ones when its route changed.
+/// Without `routes`, every operation is the same as on the primary index alone.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexSet {
+    pub index: String,
+    pub routes: HashMap<String, String>,
+}
+
+impl IndexSet {
+    pub fn new(index: &str) -> IndexSet {
+        IndexSet {
+            index: index.to_owned(),
+            routes: HashMap::new(),
+        }
+    }
+
+    /// The indexes configured in `es.index` and `es.routes`.
+    pub fn from_config(es: &ES) -> IndexSet {
+        IndexSet {
+            index: es.index.to_owned(),
+            routes: es.routes.to_owned(),
+        }
+    }
+
+    /// Return every index of the set once: the primary ones first,
+    /// then the routed ones by name.
+    pub fn names(&self) -> Vec<&str> {
+        let mut routed: Vec<&str> = self.routes.values().map(String::as_str).collect();
+        routed.sort();
+
+        let mut names = split_indexes(&self.index);
+        for index in routed.into_iter() {
+            if !names.contains(&index) {
+                names.push(index);
+            }
+        }
+        names
+    }
+
+    /// Return the index of the documents routed by `route`, matched regardless of its case.
+    /// The documents routed nowhere are stored in the primary index.
+    pub fn route(&self, route: Option<&str>) -> &str {
+        let route = route.map(str::to_lowercase);
+        self.routes
+            .iter()
+            .find(|&(value, _)| route.as_ref() == Some(&value.to_lowercase()))
+            .map_or(&*self.index, |(_, index)| &**index)
+    }
+
+    /// The indexes the documents can be written to: the patterns match nothing to write.
+    pub fn writable(&self) -> Vec<&str> {
+        if self.routes.is_empty() {
+            return vec![&*self.index];
+        }
+
+        self.names().into_iter().filter(|index| !index.contains('*')).collect()
+    }
+
+    /// Return whether `error` only means that a routed index doesn't exist yet,
+    /// which then stores none of the documents.
+    pub fn is_missing_route(&self, error: &EsError) -> bool {
+        match *error {
+            EsError::EsServerError(ref message) => {
+                !self.routes.is_empty() && message.starts_with("404")
+            }
+            _ => false,
+        }
+    }
+
+    /// Fetch the documents of type `doc_type` identified by `ids` from the writable indexes,
+    /// in the order of `ids` and leaving out the ones that can't be found. Every index is
+    /// only asked for the documents the previous ones didn't have.
+    pub fn mget<T: DeserializeOwned>(
+        &self,
+        es: &mut dyn SearchBackend,
+        doc_type: &str,
+        ids: &[String],
+    ) -> Result<Vec<Box<T>>, EsError> {
+        let mut found: HashMap<String, Box<T>> = HashMap::new();
+        for index in self.writable() {
+            let missing: Vec<String> =
+                ids.iter().filter(|id| !found.contains_key(*id)).cloned().collect();
+            if missing.is_empty() {
+                break;
+            }
+
+            let result = match es.mget::<T>(index, doc_type, &missing) {
+                Ok(result) => result,
+                Err(ref error) if self.is_missing_route(error) => continue,
+                Err(error) => return Err(error),
+            };
+            for doc in result.docs.into_iter() {
+                if let Some(source) = doc.source {
+                    found.insert(doc.id, source);
+                }
+            }
+        }
+
+        Ok(ids.iter().filter_map(|id| found.remove(id)).collect())
+    }
+
+    /// Group `resources` by the index they're routed to, storing each group through `store`,
+    /// then delete the documents stored from the other writable indexes, where they're
+    /// stale copies left by a previous route. Their deletions aren't part of the result.
+    fn store<R, F>(
+        &self,
+        es: &mut dyn SearchBackend,
+        resources: Vec<R>,
+        store: F,
+    ) -> Result<BulkResult, EsError>
+    where
+        R: Resource,
+        F: Fn(&mut dyn SearchBackend, &str, Vec<R>) -> Result<BulkResult, EsError>,
+    {
+        if self.routes.is_empty() {
+            return store(es, &self.index, resources);
+        }
+
+        let mut groups: Vec<(&str, Vec<R>)> = vec![];
+        for resource in resources.into_iter() {
+            let index = self.route(resource.route());
+            match groups.iter().position(|&(group, _)| group == index) {
+                Some(position) => groups[position].1.push(resource),
+                None => groups.push((index, vec![resource])),
+            }
+        }
+
+        let mut result = BulkResult::empty();
+        for (index, resources) in groups.into_iter() {
+            let stored = store(&mut *es, index, resources)?;
+            let ids: Vec<R::Id> = stored
+                .items
+                .iter()
+                .flat_map(|item| item.values())
+                .filter(|item| item.status < 300)
+                .filter_map(|item| item.id.parse().ok())
+                .collect();
+            result.merge(stored);
+
+            if ids.is_empty() {
+                continue;
+            }
+            for other in self.writable().into_iter().filter(|other| *other != index) {
+                let deleted = match R::delete_many(&mut *es, &ids, other) {
+                    Ok(deleted) => deleted,
+                    Err(ref error) if self.is_missing_route(error) => continue,
+                    Err(error) => return Err(error),
+                };
+                for failure in deleted.failures() {
+                    warn!("Stale copy of {} left in {}: {:?}", failure.id, other, failure.error);
+                }
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Delete a document through `delete` from every index that contains it,
+    /// skipping the routed indexes that don't exist yet. The result is the one
+    /// of the first index where it was found.
+    fn remove<F>(&self, mut delete: F) -> Result<DeleteResult, SearchspotError>
+    where
+        F: FnMut(&str) -> Result<DeleteResult, EsError>,
+    {
+        let mut deleted = None;
+        let mut missing = None;
+        for index in self.writable() {
+            match delete(index).map_err(SearchspotError::from) {
+                Ok(result) if result.found => {
+                    if deleted.is_none() {
+                        deleted = Some(result);
+                    }
+                }
+                Ok(result) => missing = Some(result),
+                Err(SearchspotError::NotFound(_)) if !self.routes.is_empty() => (),
+                Err(error) => return Err(error),
+            }
+        }
+
+        match deleted.or(missing) {
+            Some(result) => Ok(result),
+            None => Err(SearchspotError::NotFound(format!("No such index: {}", self))),
+        }
+    }
+}
+
+/// The comma-separated `names`, as ElasticSearch takes them.
+impl fmt::Display for IndexSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.names().join(","))
+    }
+}
+
+/// A `Resource` whose documents can be fetched by their ids.
+pub trait Hydratable: Resource {
+    type Hydrated: Serialize;
+
+    /// Respond to POST requests returning the documents with given ids
+    /// in the same order, skipping the ones that can't be found in any of `indexes`
+    /// and the ones that a search made with `params` wouldn't return.
+    fn hydrate(
+        es: &mut dyn SearchBackend,
+        indexes: &IndexSet,
+        ids: &[Self::Id],
+        params: &ParamsMap,
+    ) -> Result<Vec<Self::Hydrated>, SearchspotError>;
+}
+
+/// Everything that can go wrong while talking to the search backend.
+#[derive(Debug)]
+pub enum EsError {
+    /// ElasticSearch could not be reached.
+    HttpError(::elasticsearch::Error),
+    /// ElasticSearch replied with an error status.
+    EsServerError(String),
+    /// The request or the response could not be (de)serialized.
+    JsonError(serde_json::Error),
+    IoError(io::Error),
+    /// The backend does not exist or does not support the operation.
+    Unsupported(String),
+}
+
+impl fmt::Display for EsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            EsError::HttpError(ref err) => write!(f, "{}", err),
+            EsError::EsServerError(ref err) => write!(f, "{}", err),
+            EsError::JsonError(ref err) => write!(f, "{}", err),
+            EsError::IoError(ref err) => write!(f, "{}", err),
+            EsError::Unsupported(ref err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl Error for EsError {}
+
+impl From<::elasticsearch::Error> for EsError {
+    fn from(err: ::elasticsearch::Error) -> EsError {
+        EsError::HttpError(err)
+    }
+}
+
+impl From<serde_json::Error> for EsError {
+    fn from(err: serde_json::Error) -> EsError {
+        EsError::JsonError(err)
+    }
+}
+
+impl From<io::Error> for EsError {
+    fn from(err: io::Error) -> EsError {
+        EsError::IoError(err)
+    }
+}
+
+/// Split a comma-separated list of indexes (or patterns, i.e. `talents_*`).
+pub fn split_indexes(indexes: &str) -> Vec<&str> {
+    indexes
+        .split(',')
+        .map(str::trim)
+        .filter(|index| !index.is_empty())
+        .collect()
+}
+
+/// The most buckets the histograms of the aggregations may have (i.e. `GET /talents/salaries`),
+/// since the search backend builds every bucket between the smallest and the largest value.
+pub const MAX_HISTOGRAM_BUCKETS: f64 = 1000.0;
+
+/// Check that a histogram with buckets `interval` wide, of the values between `min` and `max`
+/// (none when nothing matches), has at most `MAX_HISTOGRAM_BUCKETS` buckets.
+pub fn check_histogram(
+    interval: f64,
+    min: Option<f64>,
+    max: Option<f64>,
+) -> Result<(), SearchspotError> {
+    if !(interval > 0.0) {
+        return Err(SearchspotError::Unprocessable("interval must be positive".to_owned()));
+    }
+
+    let buckets = match (min, max) {
+        (Some(min), Some(max)) => ((max - min) / interval).floor() + 1.0,
+        _ => 0.0,
+    };
+    if buckets > MAX_HISTOGRAM_BUCKETS {
+        return Err(SearchspotError::Unprocessable(format!(
+            "interval {} makes {} buckets, more than {}",
+            interval, buckets, MAX_HISTOGRAM_BUCKETS
+        )));
+    }
+
+    Ok(())
+}
+
+/// Return the top-level fields of `document` that are dropped while
+/// deserializing it into `R`, since `R` doesn't declare them.
+/// The fields `R` doesn't serialize when empty (i.e. `external_version: null`)
+/// are known all the same.
+pub fn unknown_fields<R: DeserializeOwned>(document: &Value) -> Vec<String> {
+    let known = declared_fields::<R>();
+
+    match document.as_object() {
+        Some(document) if !known.is_empty() => document
+            .keys()
+            .filter(|field| !known.contains(&&***field))
+            .cloned()
+            .collect(),
+        _ => vec![],
+    }
+}
+
+/// Return the fields declared by the struct `R`, as its `Deserialize` implementation
+/// asks for them, or none if `R` isn't deserialized as a struct.
+fn declared_fields<R: DeserializeOwned>() -> &'static [&'static str] {
+    /// Records the fields it's asked to deserialize, then fails.
+    struct FieldsRecorder<'a> {
+        fields: &'a mut &'static [&'static str],
+    }
+
+    impl<'de, 'a> Deserializer<'de> for FieldsRecorder<'a> {
+        type Error = de::value::Error;
+
+        fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+            Err(de::Error::custom("not a struct"))
+        }
+
+        fn deserialize_struct<V: Visitor<'de>>(
+            self,
+            _name: &'static str,
+            fields: &'static [&'static str],
+            _visitor: V,
+        ) -> Result<V::Value, Self::Error> {
+            *self.fields = fields;
+            Err(de::Error::custom("only the fields are recorded"))
+        }
+
+        ::serde::forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string bytes byte_buf option
+            unit unit_struct newtype_struct seq tuple tuple_struct map enum identifier ignored_any
+        }
+    }
+
+    let mut fields: &'static [&'static str] = &[];
+    let _ = R::deserialize(FieldsRecorder {
+        fields: &mut fields,
+    });
+    fields
+}
+
+/// Deserialize every document of a bulk payload on its own, so that the
+/// records that can't be deserialized are reported by their position
+/// in the payload and their id (when they have any) instead of failing
+/// on the first one with a bare serde message.
+pub fn from_documents<R: DeserializeOwned>(documents: &[Value]) -> Result<Vec<R>, Vec<Value>> {
+    let mut resources = Vec::with_capacity(documents.len());
+    let mut failures = vec![];
+
+    for (record, document) in documents.iter().enumerate() {
+        match serde_json::from_value(document.to_owned()) {
+            Ok(resource) => resources.push(resource),
+            Err(err) => failures.push(json!({
+                "record": record,
+                "id":     document.get("id").cloned().unwrap_or(Value::Null),
+                "error":  err.to_string(),
+            })),
+        }
+    }
+
+    if failures.is_empty() {
+        Ok(resources)
+    } else {
+        Err(failures)
+    }
+}
+
+/// The settings an index is created with.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Settings {
+    pub number_of_shards: u32,
+    pub analysis: Analysis,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            number_of_shards: 1,
+            analysis: Analysis::default(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Analysis {
+    pub filter: Map<String, Value>,
+    pub analyzer: Map<String, Value>,
+}
+
+/// The fragments of the fields matched by a search.
+pub type HighlightResult = HashMap<String, Vec<String>>;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ShardsResult {
+    pub total: u32,
+    pub successful: u32,
+    pub failed: u32,
+    /// Why the `failed` shards failed, whose hits are missing from the results.
+    #[serde(default)]
+    pub failures: Vec<ShardFailure>,
+}
+
+/// A shard that couldn't be searched, as reported in `_shards.failures`,
+/// displayed as `talents[2]: query_shard_exception: failed to create query`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ShardFailure {
+    #[serde(default)]
+    pub index: Option<String>,
+    #[serde(default)]
+    pub shard: Option<i64>,
+    #[serde(default)]
+    pub reason: Value,
+}
+
+impl Display for ShardFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(ref index) = self.index {
+            match self.shard {
+                Some(shard) => write!(f, "{}[{}]: ", index, shard)?,
+                None => write!(f, "{}: ", index)?,
+            }
+        }
+
+        match (self.reason["type"].as_str(), self.reason["reason"].as_str()) {
+            (Some(kind), Some(reason)) => write!(f, "{}: {}", kind, reason),
+            (Some(reason), None) | (None, Some(reason)) => write!(f, "{}", reason),
+            (None, None) => write!(f, "{}", self.reason),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SearchResult<T> {
+    pub took: u64,
+    pub timed_out: bool,
+    #[serde(rename = "_shards")]
+    pub shards: ShardsResult,
+    pub hits: SearchHitsResult<T>,
+    pub aggregations: Option<Value>,
+    /// The context of the following batches, when searching through `SearchBackend::scroll_json`.
+    #[serde(rename = "_scroll_id", default)]
+    pub scroll_id: Option<String>,
+}
+
+/// Whether `SearchHitsResult::total` is the exact number of hits (`Eq`)
+/// or a lower bound (`Gte`), when ElasticSearch was asked to stop counting
+/// through `track_total_hits`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TotalRelation {
+    Eq,
+    Gte,
+}
+
+impl Default for TotalRelation {
+    fn default() -> TotalRelation {
+        TotalRelation::Eq
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SearchHitsResult<T> {
+    pub total: u64,
+    #[serde(default)]
+    pub total_relation: TotalRelation,
+    pub max_score: Option<f64>,
+    pub hits: Vec<SearchHitsHitsResult<T>>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SearchHitsHitsResult<T> {
+    #[serde(rename = "_index")]
+    pub index: String,
+    /// Not given by the embedded backends, which store a single type per index.
+    #[serde(rename = "_type", default)]
+    pub doc_type: Option<String>,
+    #[serde(rename = "_id")]
+    pub id: String,
+    #[serde(rename = "_score")]
+    pub score: Option<f64>,
+    #[serde(rename = "_source")]
+    pub source: Option<Box<T>>,
+    pub highlight: Option<HighlightResult>,
+}
+
+impl SearchResult<Value> {
+    /// Read the response of a search, whose `hits.total` is given as
+    /// `{ "value": 10000, "relation": "gte" }` by ElasticSearch 7 and later.
+    pub fn from_response(mut response: Value) -> Result<SearchResult<Value>, EsError> {
+        if let Some(total) = response["hits"]["total"].as_object().cloned() {
+            response["hits"]["total"] = total.get("value").cloned().unwrap_or(json!(0));
+            response["hits"]["total_relation"] =
+                total.get("relation").cloned().unwrap_or(json!("eq"));
+        }
+
+        Ok(serde_json::from_value(response)?)
+    }
+
+    /// Deserialize the `_source` of every hit into `T`.
+    pub fn into_typed<T: DeserializeOwned>(self) -> Result<SearchResult<T>, EsError> {
+        let mut hits = Vec::with_capacity(self.hits.hits.len());
+
+        for hit in self.hits.hits.into_iter() {
+            let source = match hit.source {
+                Some(source) => Some(Box::new(serde_json::from_value(*source)?)),
+                None => None,
+            };
+
+            hits.push(SearchHitsHitsResult {
+                index: hit.index,
+                doc_type: hit.doc_type,
+                id: hit.id,
+                score: hit.score,
+                source: source,
+                highlight: hit.highlight,
+            });
+        }
+
+        Ok(SearchResult {
+            took: self.took,
+            timed_out: self.timed_out,
+            shards: self.shards,
+            hits: SearchHitsResult {
+                total: self.hits.total,
+                total_relation: self.hits.total_relation,
+                max_score: self.hits.max_score,
+                hits: hits,
+            },
+            aggregations: self.aggregations,
+            scroll_id: self.scroll_id,
+        })
+    }
+}
+
+/// The documents returned by a multi get, in the order they were requested.
+#[derive(Deserialize, Debug)]
+pub struct MgetResult<T> {
+    pub docs: Vec<MgetDocResult<T>>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct MgetDocResult<T> {
+    #[serde(rename = "_index")]
+    pub index: String,
+    #[serde(rename = "_id")]
+    pub id: String,
+    #[serde(default)]
+    pub found: bool,
+    #[serde(rename = "_source")]
+    pub source: Option<Box<T>>,
+}
+
+impl MgetResult<Value> {
+    /// Deserialize the `_source` of every document into `T`.
+    pub fn into_typed<T: DeserializeOwned>(self) -> Result<MgetResult<T>, EsError> {
+        let mut docs = Vec::with_capacity(self.docs.len());
+
+        for doc in self.docs.into_iter() {
+            let source = match doc.source {
+                Some(source) => Some(Box::new(serde_json::from_value(*source)?)),
+                None => None,
+            };
+
+            docs.push(MgetDocResult {
+                index: doc.index,
+                id: doc.id,
+                found: doc.found,
+                source: source,
+            });
+        }
+
+        Ok(MgetResult { docs: docs })
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct BulkResult {
+    pub took: u64,
+    pub errors: bool,
+    pub items: Vec<HashMap<String, BulkItemResult>>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct BulkItemResult {
+    #[serde(rename = "_index")]
+    pub index: String,
+    #[serde(rename = "_id")]
+    pub id: String,
+    #[serde(rename = "_version")]
+    pub version: Option<u64>,
+    pub status: u16,
+    pub error: Option<Value>,
+}
+
+impl BulkResult {
+    /// The result of a bulk request without actions.
+    pub fn empty() -> BulkResult {
+        BulkResult {
+            took: 0,
+            errors: false,
+            items: vec![],
+        }
+    }
+
+    /// Add the items of `other`, sent as another bulk request.
+    pub fn merge(&mut self, other: BulkResult) {
+        self.took += other.took;
+        self.errors |= other.errors;
+        self.items.extend(other.items);
+    }
+
+    /// Return the items that couldn't be applied, whatever the reason.
+    pub fn failures(&self) -> Vec<&BulkItemResult> {
+        self.items
+            .iter()
+            .flat_map(|item| item.values())
+            .filter(|item| item.error.is_some())
+            .collect()
+    }
+
+    /// Fail naming the items that couldn't be applied, if any, for the callers
+    /// that can't leave some documents behind (i.e. the migrations).
+    pub fn ensure_applied(self) -> Result<BulkResult, EsError> {
+        let failed: Vec<String> = self
+            .failures()
+            .into_iter()
+            .map(|item| {
+                let reason = item.error.as_ref().and_then(|error| error["type"].as_str());
+                format!("{} ({})", item.id, reason.unwrap_or("unknown"))
+            })
+            .collect();
+
+        if failed.is_empty() {
+            return Ok(self);
+        }
+
+        let shown = failed.iter().take(10).cloned().collect::<Vec<String>>().join(", ");
+        Err(EsError::EsServerError(format!(
+            "{} documents couldn't be stored: {}{}",
+            failed.len(),
+            shown,
+            if failed.len() > 10 { ", ..." } else { "" }
+        )))
+    }
+
+    /// Return the items rejected because their documents don't match the mapping.
+    pub fn mapping_conflicts(&self) -> Vec<MappingConflict> {
+        self.items
+            .iter()
+            .flat_map(|item| item.values())
+            .filter_map(MappingConflict::from_item)
+            .collect()
+    }
+}
+
+/// A document rejected with a `mapper_parsing_exception`: the value of `field` doesn't
+/// fit the `expected` type of the mapping, meaning that the producer of the documents
+/// and the mapping drifted apart. `field`, `value` and `expected` are read from the
+/// reasons given by ElasticSearch, whose wording changed across versions, when found.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct MappingConflict {
+    pub id: String,
+    pub field: Option<String>,
+    pub value: Option<String>,
+    pub expected: Option<String>,
+    pub reason: String,
+}
+
+impl MappingConflict {
+    /// Classify the failure of `item`, if it's a mapping conflict.
+    pub fn from_item(item: &BulkItemResult) -> Option<MappingConflict> {
+        let error = item.error.as_ref()?;
+        if error["type"] != "mapper_parsing_exception" {
+            return None;
+        }
+
+        // i.e. `failed to parse field [weight] of type [integer] in document with id '1'.
+        // Preview of field's value: 'heavy'`, caused by `For input string: "heavy"`
+        let reason = error["reason"].as_str().unwrap_or("").to_owned();
+        let cause = error["caused_by"]["reason"].as_str().unwrap_or("");
+
+        Some(MappingConflict {
+            id: item.id.to_owned(),
+            field: between(&reason, "[", "]"),
+            value: between(&reason, "value: '", "'").or_else(|| between(cause, "\"", "\"")),
+            expected: between(&reason, "of type [", "]"),
+            reason: reason,
+        })
+    }
+}
+
+/// Return the part of `text` between the first `start` and the following `end`.
+fn between(text: &str, start: &str, end: &str) -> Option<String> {
+    let from = text.find(start)? + start.len();
+    let to = from + text[from..].find(end)?;
+    Some(text[from..to].to_owned())
+}
+
+#[derive(Deserialize, Debug)]
+pub struct DeleteResult {
+    #[serde(rename = "_index")]
+    pub index: String,
+    #[serde(rename = "_id")]
+    pub id: String,
+    #[serde(default)]
+    pub found: bool,
+}
+
+impl DeleteResult {
+    /// Read the response of a deletion, which tells whether the document
+    /// existed through its `result` (`deleted` or `not_found`).
+    pub fn from_response(response: Value) -> Result<DeleteResult, EsError> {
+        let found = response["result"] == "deleted";
+        let mut result: DeleteResult = serde_json::from_value(response)?;
+        result.found = found;
+
+        Ok(result)
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct DeleteByQueryResult {
+    pub took: u64,
+    pub deleted: u64,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct MappingResult {
+    pub acknowledged: bool,
+}
+
+/// A single operation of a bulk request.
+/// `version` is the external version of the document, when it has any.
+pub struct Action<T> {
+    pub operation: &'static str,
+    pub id: Option<String>,
+    pub source: Option<T>,
+    pub version: Option<u64>,
+}
+
+impl<T: Serialize> Action<T> {
+    pub fn index(source: T) -> Action<T> {
+        Action {
+            operation: "index",
+            id: None,
+            source: Some(source),
+            version: None,
+        }
+    }
+
+    /// Like `index`, failing if a document with the same id exists already.
+    pub fn create(source: T) -> Action<T> {
+        Action {
+            operation: "create",
+            id: None,
+            source: Some(source),
+            version: None,
+        }
+    }
+
+    pub fn delete<S: Into<String>>(id: S) -> Action<T> {
+        Action {
+            operation: "delete",
+            id: Some(id.into()),
+            source: None,
+            version: None,
+        }
+    }
+
+    pub fn with_id<S: Into<String>>(mut self, id: S) -> Action<T> {
+        self.id = Some(id.into());
+        self
+    }
+
+    /// Apply the action only if `version` is newer than the one of the stored
+    /// document (ElasticSearch's external versioning), failing with status 409 otherwise.
+    /// ElasticSearch refuses the external versions of the creations, which are only
+    /// given the internal ones: `version` is ignored for them.
+    pub fn with_external_version(mut self, version: u64) -> Action<T> {
+        if self.operation != "create" {
+            self.version = Some(version);
+        }
+        self
+    }
+
+    /// Serialize the source of this action, as expected by `SearchBackend::bulk_json`.
+    pub fn into_json(self) -> Result<Action<Value>, serde_json::Error> {
+        let source = match self.source {
+            Some(source) => Some(serde_json::to_value(source)?),
+            None => None,
+        };
+
+        Ok(Action {
+            operation: self.operation,
+            id: self.id,
+            source: source,
+            version: self.version,
+        })
+    }
+}
+
+impl Action<Value> {
+    /// Change the fields given in `doc` of the document identified by `id`,
+    /// which must exist, leaving the others as they are. Updates can't be externally
+    /// versioned: ElasticSearch bumps the stored version by one instead.
+    pub fn update<S: Into<String>>(id: S, doc: Value) -> Action<Value> {
+        Action {
+            operation: "update",
+            id: Some(id.into()),
+            source: Some(json!({ "doc": doc })),
+            version: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use resource::{self, BulkResult, DeleteResult, SearchResult, TotalRelation};
+    use resources::{Company, Talent};
+
+    fn response(total: ::serde_json::Value) -> ::serde_json::Value {
+        json!({
+            "took":      1,
+            "timed_out": false,
+            "_shards":   { "total": 1, "successful": 1, "failed": 0 },
+            "hits":      { "total": total, "max_score": null, "hits": [] }
+        })
+    }
+
+    #[test]
+    fn test_total_hits() {
+        let result = SearchResult::from_response(response(json!(25))).unwrap();
+        assert_eq!(result.hits.total, 25);
+        assert_eq!(result.hits.total_relation, TotalRelation::Eq);
+
+        let total = json!({ "value": 10000, "relation": "gte" });
+        let result = SearchResult::from_response(response(total)).unwrap();
+        assert_eq!(result.hits.total, 10000);
+        assert_eq!(result.hits.total_relation, TotalRelation::Gte);
+    }
+
+    #[test]
+    fn test_delete_result() {
+        let deleted = json!({
+            "found": true, "_index": "talents", "_type": "talent", "_id": "1", "result": "deleted"
+        });
+        assert!(DeleteResult::from_response(deleted).unwrap().found);
+
+        let missing = json!({
+            "found": false, "_index": "talents", "_type": "talent", "_id": "2", "result": "not_found"
+        });
+        assert!(!DeleteResult::from_response(missing).unwrap().found);
+    }
+
+    #[test]
+    fn test_shard_failures() {
+        let result = SearchResult::from_response(response(json!(25))).unwrap();
+        assert!(result.shards.failures.is_empty());
+
+        let mut response = response(json!(20));
+        response["_shards"] = json!({
+            "total":      3,
+            "successful": 2,
+            "failed":     1,
+            "failures":   [{
+                "shard":  2,
+                "index":  "talents",
+                "node":   "a1b2c3",
+                "reason": { "type": "query_shard_exception", "reason": "failed to create query" }
+            }]
+        });
+        let result = SearchResult::from_response(response).unwrap();
+        assert_eq!(result.shards.failed, 1);
+        assert_eq!(
+            result.shards.failures[0].to_string(),
+            "talents[2]: query_shard_exception: failed to create query"
+        );
+    }
+
+    #[test]
+    fn test_mapping_conflicts() {
+        let result: BulkResult = ::serde_json::from_value(json!({
+            "took":   3,
+            "errors": true,
+            "items":  [
+                { "index": { "_index": "talents", "_id": "1", "status": 201 } },
+                { "index": {
+                    "_index": "talents",
+                    "_id":    "2",
+                    "status": 400,
+                    "error":  {
+                        "type":      "mapper_parsing_exception",
+                        "reason":    "failed to parse field [weight] of type [integer] in document \
+                                      with id '2'. Preview of field's value: 'heavy'",
+                        "caused_by": {
+                            "type":   "number_format_exception",
+                            "reason": "For input string: \"heavy\"",
+                        },
+                    },
+                } },
+                { "index": {
+                    "_index": "talents",
+                    "_id":    "3",
+                    "status": 400,
+                    "error":  {
+                        "type":      "mapper_parsing_exception",
+                        "reason":    "failed to parse [batch_starts_at]",
+                        "caused_by": {
+                            "type":   "illegal_argument_exception",
+                            "reason": "Invalid format: \"tomorrow\"",
+                        },
+                    },
+                } },
+                { "index": {
+                    "_index": "talents",
+                    "_id":    "4",
+                    "status": 409,
+                    "error":  { "type": "version_conflict_engine_exception", "reason": "[4]" },
+                } },
+            ]
+        }))
+        .unwrap();
+
+        assert_eq!(result.failures().len(), 3);
+
+        let conflicts = result.mapping_conflicts();
+        assert_eq!(conflicts.len(), 2);
+        assert_eq!(conflicts[0].id, "2");
+        assert_eq!(conflicts[0].field, Some("weight".to_owned()));
+        assert_eq!(conflicts[0].value, Some("heavy".to_owned()));
+        assert_eq!(conflicts[0].expected, Some("integer".to_owned()));
+        assert_eq!(conflicts[1].field, Some("batch_starts_at".to_owned()));
+        assert_eq!(conflicts[1].value, Some("tomorrow".to_owned()));
+        assert_eq!(conflicts[1].expected, None);
+
+        assert_eq!(
+            result.ensure_applied().unwrap_err().to_string(),
+            "3 documents couldn't be stored: 2 (mapper_parsing_exception), \
+             3 (mapper_parsing_exception), 4 (version_conflict_engine_exception)"
+        );
+        assert!(BulkResult::empty().ensure_applied().is_ok());
+    }
+
+    #[test]
+    fn test_unknown_fields() {
+        let document = json!({
+            "id":        1,
+            "name":      "Honeypot",
+            "industry":  "Recruiting",
+            "locations": ["Berlin"],
+            "founded":   2015,
+        });
+        assert_eq!(resource::unknown_fields::<Company>(&document), vec!["founded"]);
+
+        // the fields skipped when serializing are known all the same
+        let document = json!({ "id": 1, "external_version": null, "nickname": "Giovanni" });
+        assert_eq!(resource::unknown_fields::<Talent>(&document), vec!["nickname"]);
+        assert!(resource::unknown_fields::<Talent>(&json!([1])).is_empty());
+    }
+
+    #[test]
+    fn test_from_documents() {
+        let documents = vec![
+            json!({ "id": 1, "name": "Honeypot", "industry": "Recruiting", "locations": [] }),
+            json!({ "id": 2, "name": "Acme", "industry": 42, "locations": [] }),
+            json!({ "name": "Nameless" }),
+        ];
+
+        let failures = resource::from_documents::<Company>(&documents).unwrap_err();
+        assert_eq!(failures.len(), 2);
+        assert_eq!(failures[0]["record"], 1);
+        assert_eq!(failures[0]["id"], 2);
+        assert!(failures[0]["error"].as_str().unwrap().contains("invalid type"));
+        assert_eq!(failures[1]["record"], 2);
+        assert_eq!(failures[1]["id"], json!(null));
 
-    /// Respond to DELETE requests rebuilding and reindexing given index
-    fn reset_index(es: &mut Client, index: &str) -> Result<MappingResult, EsError>;
+        let companies = resource::from_documents::<Company>(&documents[..1]).unwrap();
+        assert_eq!(companies.len(), 1);
+        assert_eq!(companies[0].id, 1);
+    }
 }
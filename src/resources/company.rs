@@ -0,0 +1,223 @@
+use params::{FromValue, Map, Value};
+
+use backends::SearchBackend;
+use config::Config;
+use error::SearchspotError;
+use locations;
+use mapping::IndexDefinition;
+use query::Query;
+use resource::{self, Action, BulkResult, DeleteResult, EsError, MappingResult, Resource,
+               SearchHitsHitsResult};
+use terms::VectorOfTerms;
+
+/// The type that we use in ElasticSearch for defining a `Company`.
+const ES_TYPE: &'static str = "company";
+
+/// A collection of `Company`s.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SearchResults {
+    pub total: u64,
+    pub companies: Vec<Company>,
+}
+
+/// The company that will be indexed into ElasticSearch.
+/// `hiring_roles` are the roles the company is currently hiring for.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Company {
+    pub id: u32,
+    pub name: String,
+    pub industry: String,
+    pub locations: Vec<String>,
+    #[serde(default)]
+    pub hiring_roles: Vec<String>,
+}
+
+/// Convert an ElasticSearch result into a `Company`.
+impl From<SearchHitsHitsResult<Company>> for Company {
+    fn from(hit: SearchHitsHitsResult<Company>) -> Company {
+        *hit.source.unwrap()
+    }
+}
+
+impl Company {
+    /// Given parameters inside the query string mapped inside a `Map`,
+    /// return a `Query` for ElasticSearch: the companies must be in any
+    /// of `industries`, any of `locations` and hire for any of `hiring_roles`,
+    /// while `keywords` are looked for in their names.
+    pub fn search_filters(params: &Map) -> Query {
+        let mut must = vec![
+            vec![Query::build_exists("industry").build()],
+            <Query as VectorOfTerms<String>>::build_terms(
+                "industry",
+                &vec_from_params!(params, "industries"),
+            ),
+            <Query as VectorOfTerms<String>>::build_terms(
+                "locations",
+                &vec_from_params!(params, "locations"),
+            ),
+            <Query as VectorOfTerms<String>>::build_terms(
+                "hiring_roles",
+                &vec_from_params!(params, "hiring_roles"),
+            ),
+        ];
+
+        if let Some(&Value::String(ref keywords)) = params.get("keywords") {
+            if !keywords.is_empty() {
+                must.push(vec![
+                    Query::build_query_string(keywords.to_owned())
+                        .with_fields(vec!["name".to_owned()])
+                        .build(),
+                ]);
+            }
+        }
+
+        Query::build_bool()
+            .with_must(must.into_iter().flat_map(|x| x).collect::<Vec<Query>>())
+            .build()
+    }
+}
+
+impl Resource for Company {
+    type Results = SearchResults;
+    type Id = u32;
+
+    fn normalize(&mut self, config: &Config) {
+        for location in self.locations.iter_mut() {
+            *location = locations::canonicalize(&config.locations, location);
+        }
+    }
+
+    /// Populate the ElasticSearch index with `Vec<Company>`
+    fn index(
+        es: &mut dyn SearchBackend,
+        index: &str,
+        resources: Vec<Self>,
+    ) -> Result<BulkResult, EsError> {
+        es.bulk(
+            index,
+            ES_TYPE,
+            resources
+                .into_iter()
+                .map(|r| {
+                    let id = r.id.to_string();
+                    Action::index(r).with_id(id)
+                })
+                .collect(),
+        )
+    }
+
+    /// Query ElasticSearch on given `params` and return the found companies.
+    fn try_search(
+        es: &mut dyn SearchBackend,
+        default_index: &str,
+        params: &Map,
+    ) -> Result<Self::Results, SearchspotError> {
+        let offset: u64 = match params.get("offset") {
+            Some(&Value::String(ref offset)) => offset.parse().unwrap_or(0),
+            Some(&Value::U64(ref offset)) => *offset,
+            _ => 0,
+        };
+
+        let per_page: u64 = match params.get("per_page") {
+            Some(&Value::String(ref per_page)) => per_page.parse().unwrap_or(10),
+            Some(&Value::U64(ref per_page)) => *per_page,
+            _ => 10,
+        };
+
+        let body = json!({
+            "query": Company::search_filters(params),
+            "from":  offset,
+            "size":  per_page,
+        });
+
+        let result = es.search::<Company>(&resource::split_indexes(default_index), &body)?;
+        Ok(SearchResults {
+            total: result.hits.total,
+            companies: result.hits.hits.into_iter().map(Company::from).collect(),
+        })
+    }
+
+    /// Delete the company associated to given id.
+    fn delete(es: &mut dyn SearchBackend, id: &u32, index: &str) -> Result<DeleteResult, EsError> {
+        es.delete(index, ES_TYPE, &id.to_string())
+    }
+
+    /// Delete the companies associated to given ids.
+    fn delete_many(
+        es: &mut dyn SearchBackend,
+        ids: &[u32],
+        index: &str,
+    ) -> Result<BulkResult, EsError> {
+        let actions = ids.iter().map(|id| Action::<Company>::delete(id.to_string())).collect();
+        es.bulk(index, ES_TYPE, actions)
+    }
+
+    /// Companies share the index of the talents, which must be reset through them.
+    fn reset_index(
+        _es: &mut dyn SearchBackend,
+        _index: &str,
+        _definition: Option<&IndexDefinition>,
+    ) -> Result<MappingResult, EsError> {
+        Err(EsError::Unsupported(
+            "Companies are stored in the index of the talents".to_owned(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use backends::{Memory, SearchBackend};
+    use resource::Resource;
+    use resources::Company;
+
+    use params::{Map, Value};
+
+    const INDEX: &'static str = "memory_companies";
+
+    fn company(id: u32, name: &str, industry: &str, locations: &[&str]) -> Company {
+        Company {
+            id: id,
+            name: name.to_owned(),
+            industry: industry.to_owned(),
+            locations: locations.iter().map(|location| location.to_string()).collect(),
+            hiring_roles: vec!["Backend Developer".to_owned()],
+        }
+    }
+
+    fn search(memory: &mut dyn SearchBackend, params: Vec<(&str, &str)>) -> Vec<u32> {
+        let mut map = Map::new();
+        for (key, value) in params.into_iter() {
+            map.assign(key, Value::String(value.to_owned())).unwrap();
+        }
+
+        Company::search(memory, INDEX, &map)
+            .companies
+            .into_iter()
+            .map(|company| company.id)
+            .collect()
+    }
+
+    #[test]
+    fn test_search() {
+        let mut memory: Box<dyn SearchBackend> = Box::new(Memory::new());
+        Company::index(
+            &mut *memory,
+            INDEX,
+            vec![
+                company(1, "Honeypot", "Recruiting", &["Berlin"]),
+                company(2, "Acme", "Manufacturing", &["Berlin", "Munich"]),
+                company(3, "Initech", "Software", &["Amsterdam"]),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(search(&mut *memory, vec![]), vec![1, 2, 3]);
+        assert_eq!(search(&mut *memory, vec![("locations[]", "Berlin")]), vec![1, 2]);
+        assert_eq!(
+            search(&mut *memory, vec![("industries[]", "Software")]),
+            vec![3]
+        );
+        assert_eq!(search(&mut *memory, vec![("keywords", "acme")]), vec![2]);
+        assert!(search(&mut *memory, vec![("hiring_roles[]", "DevOps")]).is_empty());
+    }
+}
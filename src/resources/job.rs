@@ -0,0 +1,267 @@
+use params::{FromValue, Map, Value};
+
+use backends::SearchBackend;
+use config::Config;
+use error::SearchspotError;
+use locations;
+use mapping::IndexDefinition;
+use query::Query;
+use resource::{self, Action, BulkResult, DeleteResult, EsError, IndexSet, MappingResult,
+               Resource, SearchHitsHitsResult};
+use resources::talent::{self, Talent};
+use terms::VectorOfTerms;
+
+/// The type that we use in ElasticSearch for defining a `Job`.
+const ES_TYPE: &'static str = "job";
+
+/// A collection of `Job`s.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SearchResults {
+    pub total: u64,
+    pub jobs: Vec<Job>,
+}
+
+/// The job that will be indexed into ElasticSearch.
+/// `experience` is the minimum number of years of experience in the role,
+/// while `salary_budget` is the highest salary the company can offer.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Job {
+    pub id: u32,
+    pub title: String,
+    pub required_skills: Vec<String>,
+    pub location: String,
+    pub salary_budget: Option<u64>,
+    pub experience: Option<u8>,
+}
+
+/// Convert an ElasticSearch result into a `Job`.
+impl From<SearchHitsHitsResult<Job>> for Job {
+    fn from(hit: SearchHitsHitsResult<Job>) -> Job {
+        *hit.source.unwrap()
+    }
+}
+
+impl Job {
+    /// Return the job associated to given id, if any.
+    pub fn find(es: &mut dyn SearchBackend, index: &str, id: &str) -> Result<Option<Job>, EsError> {
+        let result = es.mget::<Job>(index, ES_TYPE, &[id.to_owned()])?;
+        Ok(result.docs.into_iter().filter_map(|doc| doc.source).map(|job| *job).next())
+    }
+
+    /// Translate the job into the params of a talent search, on top of `params`:
+    /// the talents must desire the role of `title` with at least `experience` years,
+    /// work in `location` and expect no more than `salary_budget`, while
+    /// `required_skills` are looked for as keywords.
+    pub fn talent_params(&self, params: &Map) -> Map {
+        let mut params = params.to_owned();
+
+        let desired_role = match self.experience {
+            Some(experience) => format!("{}:{}", self.title, experience),
+            None => self.title.to_owned(),
+        };
+        let _ = params.assign("desired_work_roles[]", Value::String(desired_role));
+        let _ = params.assign("work_locations[]", Value::String(self.location.to_owned()));
+
+        if let Some(salary_budget) = self.salary_budget {
+            let _ = params.assign("maximum_salary", Value::String(salary_budget.to_string()));
+        }
+
+        if !self.required_skills.is_empty() && !params.contains_key("keywords") {
+            let _ = params.assign("keywords", Value::String(self.required_skills.join(" ")));
+        }
+
+        params
+    }
+
+    /// Search the talents matching the job in `indexes`, as `Talent::search_in` does.
+    pub fn matching_talents(
+        &self,
+        es: &mut dyn SearchBackend,
+        indexes: &IndexSet,
+        params: &Map,
+    ) -> Result<talent::SearchResults, SearchspotError> {
+        Talent::search_in(es, indexes, &self.talent_params(params))
+    }
+}
+
+impl Resource for Job {
+    type Results = SearchResults;
+    type Id = u32;
+
+    fn normalize(&mut self, config: &Config) {
+        self.location = locations::canonicalize(&config.locations, &self.location);
+    }
+
+    /// Populate the ElasticSearch index with `Vec<Job>`
+    fn index(
+        es: &mut dyn SearchBackend,
+        index: &str,
+        resources: Vec<Self>,
+    ) -> Result<BulkResult, EsError> {
+        es.bulk(
+            index,
+            ES_TYPE,
+            resources
+                .into_iter()
+                .map(|r| {
+                    let id = r.id.to_string();
+                    Action::index(r).with_id(id)
+                })
+                .collect(),
+        )
+    }
+
+    /// Query ElasticSearch for the jobs requiring any of `required_skills`
+    /// in any of `locations`.
+    fn try_search(
+        es: &mut dyn SearchBackend,
+        default_index: &str,
+        params: &Map,
+    ) -> Result<Self::Results, SearchspotError> {
+        let must = vec![
+            vec![Query::build_exists("required_skills").build()],
+            <Query as VectorOfTerms<String>>::build_terms(
+                "required_skills",
+                &vec_from_params!(params, "required_skills"),
+            ),
+            <Query as VectorOfTerms<String>>::build_terms(
+                "location",
+                &vec_from_params!(params, "locations"),
+            ),
+        ];
+        let query = Query::build_bool()
+            .with_must(must.into_iter().flat_map(|x| x).collect::<Vec<Query>>())
+            .build();
+
+        let indexes = resource::split_indexes(default_index);
+        let result = es.search::<Job>(&indexes, &json!({ "query": query }))?;
+        Ok(SearchResults {
+            total: result.hits.total,
+            jobs: result.hits.hits.into_iter().map(Job::from).collect(),
+        })
+    }
+
+    /// Delete the job associated to given id.
+    fn delete(es: &mut dyn SearchBackend, id: &u32, index: &str) -> Result<DeleteResult, EsError> {
+        es.delete(index, ES_TYPE, &id.to_string())
+    }
+
+    /// Delete the jobs associated to given ids.
+    fn delete_many(
+        es: &mut dyn SearchBackend,
+        ids: &[u32],
+        index: &str,
+    ) -> Result<BulkResult, EsError> {
+        let actions = ids.iter().map(|id| Action::<Job>::delete(id.to_string())).collect();
+        es.bulk(index, ES_TYPE, actions)
+    }
+
+    /// Jobs share the index of the talents, which must be reset through them.
+    fn reset_index(
+        _es: &mut dyn SearchBackend,
+        _index: &str,
+        _definition: Option<&IndexDefinition>,
+    ) -> Result<MappingResult, EsError> {
+        Err(EsError::Unsupported(
+            "Jobs are stored in the index of the talents".to_owned(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use backends::{Memory, SearchBackend};
+    use resource::{IndexSet, Resource};
+    use resources::{Job, Talent};
+    use test_support;
+
+    use params::{Map, Value};
+
+    const INDEX: &'static str = "memory_jobs";
+
+    fn talent(id: u32, role: &str, experience: &str, location: &str, skills: &[&str]) -> Talent {
+        test_support::talent(
+            id,
+            json!({
+                "desired_work_roles":            [role],
+                "desired_work_roles_experience": [experience],
+                "professional_experience":       experience,
+                "work_locations":                [location],
+                "current_location":              location,
+                "skills":                        skills,
+            }),
+        )
+    }
+
+    // the memory backend doesn't tell jobs and talents apart by their ids
+    fn job() -> Job {
+        Job {
+            id: 100,
+            title: "Backend Developer".to_owned(),
+            required_skills: vec!["Rust".to_owned()],
+            location: "Berlin".to_owned(),
+            salary_budget: None,
+            experience: Some(4),
+        }
+    }
+
+    #[test]
+    fn test_matching_talents() {
+        let mut memory: Box<dyn SearchBackend> = Box::new(Memory::new());
+        Talent::reset_index(&mut *memory, INDEX, None).unwrap();
+        Talent::index(
+            &mut *memory,
+            INDEX,
+            vec![
+                talent(1, "Backend Developer", "4..6", "Berlin", &["Rust"]),
+                talent(2, "Backend Developer", "0..1", "Berlin", &["Rust"]),
+                talent(3, "Backend Developer", "6..8", "Munich", &["Rust"]),
+                talent(4, "Frontend Developer", "4..6", "Berlin", &["Rust"]),
+                talent(5, "Backend Developer", "8+", "Berlin", &["Java"]),
+            ],
+        )
+        .unwrap();
+        Job::index(&mut *memory, INDEX, vec![job()]).unwrap();
+
+        let job = Job::find(&mut *memory, INDEX, "100").unwrap().unwrap();
+        assert!(Job::find(&mut *memory, INDEX, "101").unwrap().is_none());
+
+        let mut params = Map::new();
+        params
+            .assign("epoch", Value::String("2018-01-01T00:00:00Z".to_owned()))
+            .unwrap();
+        let ids: Vec<u32> = job
+            .matching_talents(&mut *memory, &IndexSet::new(INDEX), &params)
+            .unwrap()
+            .talents
+            .iter()
+            .map(|result| result.talent.id())
+            .collect();
+        assert_eq!(ids, vec![1]);
+    }
+
+    #[test]
+    fn test_search() {
+        let mut memory: Box<dyn SearchBackend> = Box::new(Memory::new());
+        Talent::reset_index(&mut *memory, INDEX, None).unwrap();
+        Talent::index(
+            &mut *memory,
+            INDEX,
+            vec![talent(1, "Backend Developer", "4..6", "Berlin", &["Rust"])],
+        )
+        .unwrap();
+        Job::index(&mut *memory, INDEX, vec![job()]).unwrap();
+
+        let mut params = Map::new();
+        params
+            .assign("locations[]", Value::String("Berlin".to_owned()))
+            .unwrap();
+        let results = Job::search(&mut *memory, INDEX, &params);
+        assert_eq!(results.jobs, vec![job()]);
+
+        params
+            .assign("required_skills[]", Value::String("Java".to_owned()))
+            .unwrap();
+        assert_eq!(Job::search(&mut *memory, INDEX, &params).total, 0);
+    }
+}
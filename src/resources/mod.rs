@@ -2,28 +2,62 @@ mod talent;
 
 pub use self::talent::Talent;
 pub use self::talent::FoundTalent;
+pub use self::talent::TalentView;
 pub use self::talent::SearchResults;
+pub use self::talent::SalaryDistribution;
+pub use self::talent::DistinctCounts;
+pub use self::talent::RawQueryResults;
+pub use self::talent::DeletedTalents;
+pub use self::talent::SearchResult;
+pub use self::talent::SalaryExpectations;
+pub use self::talent::RolesExperience;
+pub use self::talent::is_shown_highlight;
+pub use self::talent::shown_avatar_url;
+
+mod talent_params;
+pub use self::talent_params::TalentSearchParams;
+pub use self::talent_params::SalarySort;
+pub use self::talent_params::SortField;
+pub use self::talent_params::DEFAULT_SORT;
+
+mod talent_query;
+pub use self::talent_query::TalentQuery;
+pub use self::talent_query::TalentQueryBuilder;
+
+mod talent_id;
+pub use self::talent_id::TalentId;
 
 mod score;
 pub use self::score::Score;
+pub use self::score::SearchBuilder as ScoreSearchBuilder;
+pub use self::score::IndexingResults as ScoreIndexingResults;
+
+mod job;
+pub use self::job::Job;
+
+mod company;
+pub use self::company::Company;
 
 #[cfg(test)]
 mod tests {
-    use rs_es::Client;
-
+    use backends::{self, SearchBackend};
     use config::Config;
 
+    use std::env;
+
     const CONFIG_FILE: &'static str = "examples/tests.toml";
 
     lazy_static! {
-        pub static ref CONFIG: Config = Config::from_file(CONFIG_FILE.to_owned());
+        pub static ref CONFIG: Config = Config::from_file(
+            env::var("SEARCHSPOT_TEST_CONFIG").unwrap_or(CONFIG_FILE.to_owned())
+        );
     }
 
-    pub fn make_client() -> Client {
-        Client::new(&*CONFIG.es.url).unwrap()
+    pub fn make_client() -> Box<dyn SearchBackend> {
+        backends::from_config(&CONFIG.es).unwrap()
     }
 
-    pub fn refresh_index(client: &mut Client, index: &str) {
-        client.refresh().with_indexes(&[&index]).send().unwrap();
+    pub fn refresh_index(client: &mut dyn SearchBackend, index: &str) {
+        client.refresh(&[index]).unwrap();
     }
 }
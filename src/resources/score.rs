@@ -1,26 +1,30 @@
-use params::Map;
-
-use rs_es::error::EsError;
-use rs_es::operations::bulk::{Action, BulkResult};
-use rs_es::operations::delete::DeleteResult;
-use rs_es::operations::mapping::MappingResult;
-use rs_es::operations::search::SearchHitsHitsResult;
-use rs_es::query::Query;
-use rs_es::Client;
-
-use resource::Resource;
+use chrono::prelude::*;
+use params::{FromValue, Map};
+use serde_json::{self, Value as JsonValue};
+
+use backends::SearchBackend;
+use cleanup::Expirable;
+use config::Cleanup;
+use error::SearchspotError;
+use jsonapi::Collection;
+use mapping::IndexDefinition;
+use query::Query;
+use resource::{self, Action, BulkResult, DeleteByQueryResult, DeleteResult, EsError,
+               MappingResult, Resource, SearchHitsHitsResult};
 
 /// The type that we use in ElasticSearch for defining a `Score`.
 const ES_TYPE: &'static str = "score";
 
 /// A collection of `Score`s.
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct SearchResults {
     pub total: u64,
     pub scores: Vec<Score>,
 }
 
 /// The representation of the score that will be indexed into ElasticSearch.
+/// `model_version` identifies the model that computed it, while `created_at`
+/// is set when it's indexed unless given.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Score {
     pub request_id: String,
@@ -30,12 +34,86 @@ pub struct Score {
     pub job_id: u32,
     pub talent_id: u32,
     pub score: f32,
+    pub model_version: Option<String>,
+    pub created_at: Option<String>,
+}
+
+/// A score that couldn't be indexed, along with the reason.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RejectedScore {
+    pub request_id: String,
+    pub error: String,
+}
+
+/// The outcome of indexing a batch of scores, score by score,
+/// so that only the rejected ones need to be sent again.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct IndexingResults {
+    pub accepted: Vec<String>,
+    pub rejected: Vec<RejectedScore>,
+}
+
+impl IndexingResults {
+    /// Pair the items of `result` with the `request_ids` of the scores
+    /// they refer to, which were sent in the same order.
+    pub fn new(request_ids: Vec<String>, result: &BulkResult) -> IndexingResults {
+        let mut results = IndexingResults::default();
+
+        for (i, request_id) in request_ids.into_iter().enumerate() {
+            let item = result.items.get(i).and_then(|item| item.values().next());
+
+            let error = match item {
+                Some(item) => match item.error {
+                    Some(ref error) => Some(
+                        error["reason"]
+                            .as_str()
+                            .map(str::to_owned)
+                            .unwrap_or_else(|| error.to_string()),
+                    ),
+                    None if item.status >= 300 => Some(format!("Rejected with status {}", item.status)),
+                    None => None,
+                },
+                None => Some("Missing from the bulk response".to_owned()),
+            };
+
+            match error {
+                Some(error) => results.rejected.push(RejectedScore {
+                    request_id: request_id,
+                    error: error,
+                }),
+                None => results.accepted.push(request_id),
+            }
+        }
+
+        results
+    }
+}
+
+/// A bucket of the histogram of the scores, counting the ones
+/// between `key` and `key` plus the interval.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct HistogramBucket {
+    pub key: f64,
+    pub doc_count: u64,
+}
+
+/// The distribution of the scores matching a `SearchBuilder`.
+/// `avg`, `min` and `max` are `None` when there are no scores at all.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Stats {
+    pub count: u64,
+    pub avg: Option<f64>,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub histogram: Vec<HistogramBucket>,
 }
 
 #[derive(Default, Clone)]
 pub struct SearchBuilder {
     pub job_id: Option<u32>,
     pub talent_id: Option<u32>,
+    pub min_score: Option<f32>,
+    pub model_version: Option<String>,
 }
 
 impl SearchBuilder {
@@ -53,6 +131,16 @@ impl SearchBuilder {
         self
     }
 
+    pub fn with_min_score(&mut self, min_score: f32) -> &mut SearchBuilder {
+        self.min_score = Some(min_score);
+        self
+    }
+
+    pub fn with_model_version(&mut self, model_version: &str) -> &mut SearchBuilder {
+        self.model_version = Some(model_version.to_owned());
+        self
+    }
+
     pub fn build(&self) -> SearchBuilder {
         self.to_owned()
     }
@@ -68,6 +156,14 @@ impl SearchBuilder {
             terms.push(Query::build_term("talent_id", talent_id).build());
         }
 
+        if let Some(min_score) = self.min_score {
+            terms.push(Query::build_range("score").with_gte(min_score).build());
+        }
+
+        if let Some(ref model_version) = self.model_version {
+            terms.push(Query::build_term("model_version", model_version.to_owned()).build());
+        }
+
         Query::build_bool().with_must(terms).build()
     }
 }
@@ -80,80 +176,239 @@ impl From<SearchHitsHitsResult<Score>> for Score {
 }
 
 impl Score {
-    pub fn search(es: &mut Client, index: &str, search_builder: &SearchBuilder) -> SearchResults {
-        let result = es.search_query()
-            .with_indexes(&[index])
-            .with_query(&search_builder.to_query())
-            .send::<Score>();
-
-        match result {
-            Ok(result) => {
-                let scores: Vec<Score> = result.hits.hits.into_iter().map(Score::from).collect();
-
-                SearchResults {
-                    total: result.hits.total,
-                    scores: scores,
-                }
-            }
-            Err(err) => {
-                error!("{:?}", err);
-                SearchResults {
-                    total: 0,
-                    scores: vec![],
-                }
-            }
+    pub fn search(
+        es: &mut dyn SearchBackend,
+        index: &str,
+        search_builder: &SearchBuilder,
+    ) -> SearchResults {
+        Score::try_search_with(es, index, search_builder).unwrap_or_else(|err| {
+            error!("{:?}", err);
+            SearchResults::default()
+        })
+    }
+
+    /// Like `search`, returning why ElasticSearch failed instead of no scores.
+    pub fn try_search_with(
+        es: &mut dyn SearchBackend,
+        index: &str,
+        search_builder: &SearchBuilder,
+    ) -> Result<SearchResults, EsError> {
+        let indexes = resource::split_indexes(index);
+        let result = es.search::<Score>(&indexes, &json!({ "query": search_builder.to_query() }))?;
+        let scores: Vec<Score> = result.hits.hits.into_iter().map(Score::from).collect();
+
+        Ok(SearchResults {
+            total: result.hits.total,
+            scores: scores,
+        })
+    }
+
+    /// Return the distribution of the scores matching `search_builder`,
+    /// whose histogram has buckets `interval` wide. The scores are ranged first,
+    /// refusing the intervals that make more than `MAX_HISTOGRAM_BUCKETS` buckets.
+    pub fn stats(
+        es: &mut dyn SearchBackend,
+        index: &str,
+        search_builder: &SearchBuilder,
+        interval: f64,
+    ) -> Result<Stats, SearchspotError> {
+        let query = search_builder.to_query();
+        let body = json!({
+            "query": query,
+            "size":  0,
+            "aggs":  { "scores": { "stats": { "field": "score" } } }
+        });
+
+        let result = es.search_json(&[index], &body)?;
+        let aggregations = result.aggregations.unwrap_or(JsonValue::Null);
+        let ref scores = aggregations["scores"];
+        resource::check_histogram(interval, scores["min"].as_f64(), scores["max"].as_f64())?;
+
+        let body = json!({
+            "query": query,
+            "size":  0,
+            "aggs":  { "histogram": { "histogram": { "field": "score", "interval": interval } } }
+        });
+        let result = es.search_json(&[index], &body)?;
+        let mut histogram = result.aggregations.unwrap_or(JsonValue::Null);
+
+        Ok(Stats {
+            count: scores["count"].as_u64().unwrap_or(0),
+            avg: scores["avg"].as_f64(),
+            min: scores["min"].as_f64(),
+            max: scores["max"].as_f64(),
+            histogram: serde_json::from_value(histogram["histogram"]["buckets"].take())
+                .unwrap_or_default(),
+        })
+    }
+
+    /// Delete every score matching `search_builder` through a single delete by query.
+    /// A `job_id` or a `talent_id` must be given, not to delete the other documents of the index.
+    pub fn delete_matching(
+        es: &mut dyn SearchBackend,
+        index: &str,
+        search_builder: &SearchBuilder,
+    ) -> Result<DeleteByQueryResult, EsError> {
+        if search_builder.job_id.is_none() && search_builder.talent_id.is_none() {
+            return Err(EsError::Unsupported(
+                "Either a job_id or a talent_id must be given".to_owned(),
+            ));
         }
+
+        es.delete_by_query(&[index], &search_builder.to_query().into())
     }
 
-    pub fn delete(&self, es: &mut Client, index: &str) -> Result<DeleteResult, EsError> {
-        es.delete(index, ES_TYPE, &*self.request_id).send()
+    /// Return the id of the score of a job and a talent,
+    /// which is shared by all of the scores of the same pair.
+    pub fn pair_id(&self) -> String {
+        format!("{}:{}", self.job_id, self.talent_id)
+    }
+
+    /// Index `resources` using their `pair_id`s as ids, so that the latest
+    /// score of a job and a talent replaces the previous ones.
+    pub fn upsert(
+        es: &mut dyn SearchBackend,
+        index: &str,
+        resources: Vec<Score>,
+    ) -> Result<BulkResult, EsError> {
+        Score::index_with_ids(es, index, resources, Score::pair_id)
+    }
+
+    fn index_with_ids<F: Fn(&Score) -> String>(
+        es: &mut dyn SearchBackend,
+        index: &str,
+        resources: Vec<Score>,
+        id: F,
+    ) -> Result<BulkResult, EsError> {
+        let now = Utc::now().to_rfc3339();
+
+        es.bulk(
+            index,
+            ES_TYPE,
+            resources
+                .into_iter()
+                .map(|mut r| {
+                    if r.created_at.is_none() {
+                        r.created_at = Some(now.to_owned());
+                    }
+
+                    let id = id(&r);
+                    Action::index(r).with_id(id)
+                })
+                .collect(),
+        )
+    }
+
+    pub fn delete(&self, es: &mut dyn SearchBackend, index: &str) -> Result<DeleteResult, EsError> {
+        es.delete(index, ES_TYPE, &*self.request_id)
     }
 }
 
 impl Resource for Score {
     type Results = SearchResults;
+    type Id = String;
 
     /// Populate the ElasticSearch index with `Vec<Score>`
-    fn index(es: &mut Client, index: &str, resources: Vec<Self>) -> Result<BulkResult, EsError> {
-        es.bulk(&resources
-            .into_iter()
-            .map(|r| {
-                let request_id = r.request_id.to_owned();
-                Action::index(r).with_id(request_id)
-            })
-            .collect::<Vec<Action<Score>>>())
-            .with_index(index)
-            .with_doc_type(ES_TYPE)
-            .send()
-    }
-
-    /// We'll call this one from `talent` as a normal function, we won't expose it outside.
-    fn search(_es: &mut Client, _default_index: &str, _params: &Map) -> Self::Results {
-        unimplemented!();
+    fn index(
+        es: &mut dyn SearchBackend,
+        index: &str,
+        resources: Vec<Self>,
+    ) -> Result<BulkResult, EsError> {
+        Score::index_with_ids(es, index, resources, |score| score.request_id.to_owned())
     }
 
-    /// We'll call this one from `talent` as a normal function, we won't expose it outside.
-    fn delete(_es: &mut Client, _id: &str, _index: &str) -> Result<DeleteResult, EsError> {
-        unimplemented!();
+    /// Search the scores by `job_id`, `talent_id`, `min_score` and `model_version`.
+    fn try_search(
+        es: &mut dyn SearchBackend,
+        default_index: &str,
+        params: &Map,
+    ) -> Result<Self::Results, SearchspotError> {
+        let mut search = SearchBuilder::new();
+
+        if let Some(job_id) = params.get("job_id").and_then(u32::from_value) {
+            search.with_job_id(job_id);
+        }
+        if let Some(talent_id) = params.get("talent_id").and_then(u32::from_value) {
+            search.with_talent_id(talent_id);
+        }
+        if let Some(min_score) = params.get("min_score").and_then(f32::from_value) {
+            search.with_min_score(min_score);
+        }
+        if let Some(model_version) = params.get("model_version").and_then(String::from_value) {
+            search.with_model_version(&model_version);
+        }
+
+        Ok(Score::try_search_with(es, default_index, &search.build())?)
+    }
+
+    fn json_api() -> Option<Collection> {
+        Some(Collection {
+            kind: "scores",
+            items: "scores",
+            document: None,
+            id: "request_id",
+        })
+    }
+
+    /// Delete the scores associated to given request ids.
+    fn delete_many(
+        es: &mut dyn SearchBackend,
+        ids: &[String],
+        index: &str,
+    ) -> Result<BulkResult, EsError> {
+        let actions = ids.iter().map(|id| Action::<Score>::delete(&**id)).collect();
+        es.bulk(index, ES_TYPE, actions)
+    }
+
+    /// Delete the score associated to given request id.
+    fn delete(
+        es: &mut dyn SearchBackend,
+        id: &String,
+        index: &str,
+    ) -> Result<DeleteResult, EsError> {
+        es.delete(index, ES_TYPE, id)
     }
 
     /// We leave ES to create the mapping by inferring it from the input.
-    fn reset_index(_es: &mut Client, _index: &str) -> Result<MappingResult, EsError> {
+    fn reset_index(
+        _es: &mut dyn SearchBackend,
+        _index: &str,
+        _definition: Option<&IndexDefinition>,
+    ) -> Result<MappingResult, EsError> {
         unimplemented!();
     }
 }
 
+/// Scores are kept for `cleanup.score_retention_days` after being indexed.
+impl Expirable for Score {
+    fn expired_query(before: &DateTime<Utc>) -> JsonValue {
+        Query::build_range("created_at")
+            .with_lt(before.to_rfc3339())
+            .with_format("dateOptionalTime")
+            .build()
+            .into()
+    }
+
+    fn retention_days(cleanup: &Cleanup) -> Option<u64> {
+        cleanup.score_retention_days
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use rs_es::Client;
-
+    use backends::{Memory, SearchBackend};
+    use cleanup::delete_expired;
     use resource::Resource;
 
-    use resources::score::{SearchBuilder, SearchResults};
+    use params::{Map, Value};
+    use serde_json;
+
+    use resources::score::{HistogramBucket, IndexingResults, RejectedScore, SearchBuilder,
+                           SearchResults, Stats};
     use resources::tests::{make_client, refresh_index, CONFIG};
     use resources::{Score, Talent};
 
-    pub fn populate_index(mut client: &mut Client, index: &str) -> bool {
+    pub fn populate_index(mut client: &mut dyn SearchBackend, index: &str) -> bool {
         let scores = vec![
             Score {
                 request_id: "515ec9bb-0511-4464-92bb-bd21c5ed7b22".to_owned(),
@@ -163,6 +418,8 @@ mod tests {
                 job_id: 1,
                 talent_id: 1,
                 score: 0.545,
+                model_version: Some("2018.1".to_owned()),
+                created_at: None,
             },
             Score {
                 request_id: "9ac871a8-d936-41d8-bd35-9bc3c0c5be42".to_owned(),
@@ -172,6 +429,8 @@ mod tests {
                 job_id: 1,
                 talent_id: 2,
                 score: 0.442,
+                model_version: Some("2018.2".to_owned()),
+                created_at: Some("2018-01-01T00:00:00Z".to_owned()),
             },
         ];
 
@@ -192,8 +451,8 @@ mod tests {
         let mut client = make_client();
         let index = format!("{}_{}", CONFIG.es.index, "score");
 
-        if let Err(_) = Talent::reset_index(&mut client, &*index) {
-            let _ = Talent::reset_index(&mut client, &*index);
+        if let Err(_) = Talent::reset_index(&mut client, &*index, None) {
+            let _ = Talent::reset_index(&mut client, &*index, None);
         }
 
         refresh_index(&mut client, &*index);
@@ -230,6 +489,26 @@ mod tests {
             );
         }
 
+        // min_score is given
+        {
+            let search = SearchBuilder::new().with_job_id(1).with_min_score(0.5).build();
+            let results = Score::search(&mut client, &*index, &search);
+            assert_eq!(
+                vec!["515ec9bb-0511-4464-92bb-bd21c5ed7b22"],
+                results.request_ids()
+            );
+        }
+
+        // model_version is given
+        {
+            let search = SearchBuilder::new().with_model_version("2018.2").build();
+            let results = Score::search(&mut client, &*index, &search);
+            assert_eq!(
+                vec!["9ac871a8-d936-41d8-bd35-9bc3c0c5be42"],
+                results.request_ids()
+            );
+        }
+
         // delete between searches
         {
             let search = SearchBuilder::new().with_talent_id(1).build();
@@ -244,4 +523,158 @@ mod tests {
             assert_eq!(0, results.total);
         }
     }
+
+    #[test]
+    fn test_stats() {
+        let mut memory: Box<dyn SearchBackend> = Box::new(Memory::new());
+        let index = "memory_scores";
+        assert!(populate_index(&mut *memory, index));
+
+        let search = SearchBuilder::new().with_job_id(1).build();
+        let stats = Score::stats(&mut *memory, index, &search, 0.25).unwrap();
+        assert_eq!(stats.count, 2);
+        assert_eq!(stats.min.map(|min| min as f32), Some(0.442));
+        assert_eq!(stats.max.map(|max| max as f32), Some(0.545));
+        assert_eq!(
+            stats.histogram,
+            vec![
+                HistogramBucket {
+                    key: 0.25,
+                    doc_count: 1,
+                },
+                HistogramBucket {
+                    key: 0.5,
+                    doc_count: 1,
+                },
+            ]
+        );
+
+        // from 0.442 to 0.545, the buckets would be too many
+        let search = SearchBuilder::new().with_job_id(1).build();
+        let error = Score::stats(&mut *memory, index, &search, 0.00001).unwrap_err();
+        assert_eq!(error.status(), 422);
+
+        let search = SearchBuilder::new().with_job_id(2).build();
+        assert_eq!(
+            Score::stats(&mut *memory, index, &search, 0.25).unwrap(),
+            Stats::default()
+        );
+    }
+
+    #[test]
+    fn test_search_params() {
+        let mut memory: Box<dyn SearchBackend> = Box::new(Memory::new());
+        let index = "memory_scores";
+        assert!(populate_index(&mut *memory, index));
+
+        let mut params = Map::new();
+        params.assign("job_id", Value::String("1".to_owned())).unwrap();
+        assert_eq!(<Score as Resource>::search(&mut *memory, index, &params).total, 2);
+
+        params.assign("talent_id", Value::String("2".to_owned())).unwrap();
+        assert_eq!(
+            <Score as Resource>::search(&mut *memory, index, &params).request_ids(),
+            vec!["9ac871a8-d936-41d8-bd35-9bc3c0c5be42"]
+        );
+    }
+
+    #[test]
+    fn test_delete() {
+        let mut memory: Box<dyn SearchBackend> = Box::new(Memory::new());
+        let index = "memory_scores";
+        assert!(populate_index(&mut *memory, index));
+
+        // no job_id nor talent_id is given
+        let search = SearchBuilder::new().build();
+        assert!(Score::delete_matching(&mut *memory, index, &search).is_err());
+
+        let search = SearchBuilder::new().with_job_id(1).with_talent_id(2).build();
+        assert_eq!(
+            Score::delete_matching(&mut *memory, index, &search).unwrap().deleted,
+            1
+        );
+
+        Score::delete_many(
+            &mut *memory,
+            &["515ec9bb-0511-4464-92bb-bd21c5ed7b22".to_owned()],
+            index,
+        ).unwrap();
+        assert_eq!(Score::search(&mut *memory, index, &SearchBuilder::new().build()).total, 0);
+    }
+
+    #[test]
+    fn test_expiration() {
+        let mut memory: Box<dyn SearchBackend> = Box::new(Memory::new());
+        let index = "memory_scores";
+        assert!(populate_index(&mut *memory, index));
+
+        // the first score has been indexed right now
+        assert_eq!(delete_expired::<Score>(&mut *memory, index, 30).unwrap().deleted, 1);
+
+        let results = Score::search(&mut *memory, index, &SearchBuilder::new().build());
+        assert_eq!(
+            vec!["515ec9bb-0511-4464-92bb-bd21c5ed7b22"],
+            results.request_ids()
+        );
+    }
+
+    #[test]
+    fn test_upsert() {
+        let mut memory: Box<dyn SearchBackend> = Box::new(Memory::new());
+        let index = "memory_scores";
+        assert!(populate_index(&mut *memory, index));
+
+        let search = SearchBuilder::new().with_job_id(1).with_talent_id(1).build();
+        let mut score = Score::search(&mut *memory, index, &search).scores[0].to_owned();
+        Score::delete_many(&mut *memory, &[score.request_id.to_owned()], index).unwrap();
+
+        Score::upsert(&mut *memory, index, vec![score.to_owned()]).unwrap();
+        score.request_id = "0e4b4ad7-3f8b-4a32-8cb4-4f3e1f0c2d9a".to_owned();
+        score.score = 0.9;
+        Score::upsert(&mut *memory, index, vec![score]).unwrap();
+
+        let results = Score::search(&mut *memory, index, &search);
+        assert_eq!(
+            vec!["0e4b4ad7-3f8b-4a32-8cb4-4f3e1f0c2d9a"],
+            results.request_ids()
+        );
+    }
+
+    #[test]
+    fn test_indexing_results() {
+        let result = serde_json::from_value(json!({
+            "took":   3,
+            "errors": true,
+            "items":  [
+                { "index": { "_index": "scores", "_id": "a", "status": 201 } },
+                {
+                    "index": {
+                        "_index": "scores",
+                        "_id":    "b",
+                        "status": 400,
+                        "error":  { "type": "mapper_parsing_exception", "reason": "failed to parse [score]" }
+                    }
+                }
+            ]
+        }))
+        .unwrap();
+
+        let ids = vec!["a".to_owned(), "b".to_owned(), "c".to_owned()];
+        assert_eq!(
+            IndexingResults::new(ids, &result),
+            IndexingResults {
+                accepted: vec!["a".to_owned()],
+                rejected: vec![
+                    RejectedScore {
+                        request_id: "b".to_owned(),
+                        error: "failed to parse [score]".to_owned(),
+                    },
+                    RejectedScore {
+                        request_id: "c".to_owned(),
+                        error: "Missing from the bulk response".to_owned(),
+                    },
+                ],
+            }
+        );
+    }
 }
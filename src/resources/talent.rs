@@ -1,62 +1,241 @@
 use chrono::prelude::*;
 
-use params::{FromValue, Map, Value};
-
-use rs_es::error::EsError;
-use rs_es::operations::bulk::{Action, BulkResult};
-use rs_es::operations::delete::DeleteResult;
-use rs_es::operations::mapping::{Analysis, MappingOperation, MappingResult, Settings};
-use rs_es::operations::search::highlight::{Encoders, Highlight, HighlightResult, Setting,
-                                           SettingTypes, TermVector};
-use rs_es::operations::search::{Order, SearchHitsHitsResult, Sort, SortField};
-use rs_es::query::Query;
-use rs_es::Client;
-
-use resource::Resource;
+use params::Map;
+
+use serde::{Serialize, Serializer};
+use serde_json::{self, Map as JsonMap, Value as JsonValue};
+use sha2::{Digest, Sha256};
+
+use backends::SearchBackend;
+use cleanup::Expirable;
+use config::{Config, Limits, Ranking};
+use context;
+use error::SearchspotError;
+use events::{self, Change};
+use features::Feature;
+use jsonapi::Collection;
+use keywords;
+use languages::Language;
+use locations;
+use mapping::IndexDefinition;
+use metrics;
+use migrations::{self, Migratable, Migration, Step};
+use query::{BoolQueryBuilder, Query};
+use resource::{self, Action, Analysis, BulkResult, DeleteResult, EsError, HighlightResult,
+               Hydratable, IndexSet, MappingResult, Resource, SearchHitsHitsResult, Settings,
+               TotalRelation};
+use resources::{SalarySort, SortField, TalentId, TalentQueryBuilder, TalentSearchParams};
+use resources::score::HistogramBucket;
 use terms::VectorOfTerms;
 
-use std::collections::{HashSet, HashMap};
+use std::cmp;
+use std::collections::HashMap;
+use std::time::Instant;
 
 /// The type that we use in ElasticSearch for defining a `Talent`.
 const ES_TYPE: &'static str = "talent";
 
+/// The minimum score of the talents found through keywords.
+pub const KEYWORDS_MIN_SCORE: f64 = 0.56;
+
+/// Where the counting of the hits stops when `track_total_hits` is disabled.
+pub const TOTAL_HITS_CAP: u64 = 10000;
+
+/// The fields counted by `Talent::distinct_counts`, by name, as aggregated.
+const DISTINCT_FIELDS: &'static [(&'static str, &'static str)] = &[
+    ("skills", "skills.raw"),
+    ("work_locations", "work_locations"),
+    ("current_location", "current_location"),
+    ("languages", "languages"),
+];
+
 /// A collection of `SearchResult`s.
+/// `hits_per_index` is filled when more indexes are searched at once,
+/// `experiment` when the search took part in one (see `experiments`).
+/// `total_relation` is `gte` when `total` is only a lower bound (see `track_total_hits`),
+/// `timed_out` when ElasticSearch ran out of the `timeout_ms` and returned what it found.
+/// `partial` when some shards failed, whose talents are missing: `shard_failures` tells why.
+///
+/// The `excluded_by_*` fields count the talents that matched the search but
+/// were hidden because the company (`company_id`) already contacted them or is
+/// blocked by them, or because they're listed in `contacted_talents` or in
+/// `ignored_talents`. A talent hidden for more reasons is counted in each.
+///
+/// `filtered_by_min_score` counts the talents matching the keywords whose score
+/// is too low to be returned, when asked through `track_min_score`.
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct SearchResults {
     pub total: u64,
+    #[serde(default)]
+    pub total_relation: TotalRelation,
+    #[serde(default)]
+    pub timed_out: bool,
+    #[serde(default)]
+    pub partial: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub shard_failures: Vec<String>,
     pub talents: Vec<SearchResult>,
     pub raw_es_query: Option<String>,
+    #[serde(default)]
+    pub hits_per_index: HashMap<String, u64>,
+    #[serde(default)]
+    pub experiment: Option<String>,
+    #[serde(default)]
+    pub excluded_by_company: u64,
+    #[serde(default)]
+    pub excluded_by_contacted: u64,
+    #[serde(default)]
+    pub excluded_by_ignored: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filtered_by_min_score: Option<u64>,
+}
+
+/// The distribution of the minimum salaries expected by the talents matching
+/// a search, grouped by city and currency since they can't be compared otherwise.
+/// `count` is the number of salary expectations, which a talent can have many of.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct SalaryDistribution {
+    pub count: u64,
+    pub groups: Vec<SalaryGroup>,
+}
+
+/// The minimum salaries expected in `city` in `currency`, whose histogram
+/// counts the ones between `key` and `key` plus the interval.
+/// `avg`, `min` and `max` are `None` when none of them has a minimum.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SalaryGroup {
+    pub city: String,
+    pub currency: String,
+    pub count: u64,
+    pub avg: Option<f64>,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub histogram: Vec<HistogramBucket>,
+}
+
+/// How many distinct values the `total` indexed talents have for each of the
+/// `DISTINCT_FIELDS`, along with the most frequent ones, to spot the regressions
+/// of the source system after a reindex (i.e. skills disappearing or doubling).
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct DistinctCounts {
+    pub total: u64,
+    pub fields: Vec<DistinctField>,
+}
+
+/// The `count` of the distinct values of `field`, approximated by ElasticSearch
+/// above some thousands, and its `top` values, the most frequent first.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DistinctField {
+    pub field: String,
+    pub count: u64,
+    pub top: Vec<TopValue>,
+}
+
+/// A value of a field and how many talents have it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TopValue {
+    pub key: String,
+    pub doc_count: u64,
+}
+
+/// A page of the talents marked as `deleted`, which can still be restored.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct DeletedTalents {
+    pub total: u64,
+    pub talents: Vec<FoundTalent>,
+}
+
+/// The hits of a raw query.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct RawQueryResults {
+    pub total: u64,
+    pub hits: Vec<RawHit>,
+}
+
+/// A talent found by a raw query, restricted to the `FOUND_TALENT_FIELDS`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RawHit {
+    pub index: String,
+    pub id: String,
+    pub score: Option<f64>,
+    pub source: Option<JsonValue>,
 }
 
 /// A single search result returned by ElasticSearch.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct SearchResult {
-    pub talent: FoundTalent,
+    pub talent: TalentView,
+    #[serde(serialize_with = "serialize_highlight")]
     pub highlight: Option<HighlightResult>,
 }
 
+impl SearchResult {
+    /// Like `SearchResult::from`, keeping the complete document.
+    pub fn full(result: SearchHitsHitsResult<Talent>) -> SearchResult {
+        SearchResult {
+            talent: TalentView::Full(result.source.unwrap()),
+            highlight: result.highlight,
+        }
+    }
+}
+
 /// Convert an ElasticSearch result into a `SearchResult`.
 impl From<SearchHitsHitsResult<Talent>> for SearchResult {
     fn from(result: SearchHitsHitsResult<Talent>) -> SearchResult {
         SearchResult {
-            talent: result.source.unwrap().into(),
+            talent: TalentView::Found(result.source.unwrap().into()),
             highlight: result.highlight,
         }
     }
 }
 
+/// The talent of a `SearchResult`: a `FoundTalent`, unless
+/// the complete documents are requested through `view=full`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum TalentView {
+    Found(FoundTalent),
+    Full(Box<Talent>),
+}
+
+impl TalentView {
+    pub fn id(&self) -> TalentId {
+        match *self {
+            TalentView::Found(ref talent) => talent.id.to_owned(),
+            TalentView::Full(ref talent) => talent.id.to_owned(),
+        }
+    }
+
+    /// Return the `FoundTalent`, unless the complete document was requested.
+    pub fn found(&self) -> Option<&FoundTalent> {
+        match *self {
+            TalentView::Found(ref talent) => Some(talent),
+            TalentView::Full(_) => None,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct SalaryExpectations {
     pub minimum: Option<u64>,
     pub currency: String,
     pub city: String,
+    /// `minimum` converted to euros through `currencies.rates` when the talent
+    /// is indexed, so that the salaries in any currency can be compared.
+    /// `None` when the rate of `currency` isn't known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub minimum_eur: Option<u64>,
 }
 
 /// A representation of `Talent` with limited fields.
+/// `already_contacted` and `is_presented` are only given
+/// when the search is made on behalf of a company.
+/// `avatar_url` is replaced by its hash in the anonymized responses.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct FoundTalent {
-    pub id: u32,
+    pub id: TalentId,
     pub headline: String,
+    #[serde(serialize_with = "serialize_avatar_url")]
     pub avatar_url: String,
     pub work_locations: Vec<String>,
     pub current_location: String,
@@ -64,6 +243,66 @@ pub struct FoundTalent {
     pub roles_experiences: Vec<RolesExperience>,
     pub latest_position: String,
     pub batch_starts_at: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub already_contacted: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub is_presented: Option<bool>,
+}
+
+/// The highlighted fields left out of the anonymized responses,
+/// whose fragments are free text written by the talents.
+const ANONYMIZED_HIGHLIGHTS: &'static [&'static str] = &["summary"];
+
+/// Return `avatar_url` as its SHA-256 when the response is anonymized,
+/// so that the talents can still be told apart.
+pub fn shown_avatar_url(avatar_url: &str) -> String {
+    if !context::is_anonymized() || avatar_url.is_empty() {
+        return avatar_url.to_owned();
+    }
+
+    let digest = Sha256::digest(avatar_url.as_bytes());
+    digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Tell whether the highlighted `field` is shown: the `ANONYMIZED_HIGHLIGHTS`
+/// (and their subfields) are left out when the response is anonymized.
+pub fn is_shown_highlight(field: &str) -> bool {
+    let field = field.split('.').next().unwrap_or("");
+    !context::is_anonymized() || !ANONYMIZED_HIGHLIGHTS.contains(&field)
+}
+
+fn serialize_avatar_url<S: Serializer>(
+    avatar_url: &str,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&shown_avatar_url(avatar_url))
+}
+
+fn serialize_highlight<S: Serializer>(
+    highlight: &Option<HighlightResult>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    if !context::is_anonymized() {
+        return highlight.serialize(serializer);
+    }
+
+    let highlight: Option<HighlightResult> = highlight.as_ref().map(|highlight| {
+        highlight
+            .iter()
+            .filter(|&(field, _)| is_shown_highlight(field))
+            .map(|(field, fragments)| (field.to_owned(), fragments.to_owned()))
+            .collect()
+    });
+    highlight.serialize(serializer)
+}
+
+impl FoundTalent {
+    /// Tell whether the talent has already been contacted by the company
+    /// searching it and whether it's among its `presented_talents`.
+    pub fn set_contact_status(&mut self, already_contacted: bool, presented_talents: &[TalentId]) {
+        self.already_contacted = Some(already_contacted);
+        self.is_presented = Some(presented_talents.contains(&self.id));
+    }
 }
 
 impl PartialEq<Talent> for FoundTalent {
@@ -84,6 +323,12 @@ impl<'a> PartialEq<u32> for &'a Talent {
     }
 }
 
+impl<'a> PartialEq<TalentId> for &'a Talent {
+    fn eq(&self, other: &TalentId) -> bool {
+        self.id == *other
+    }
+}
+
 /// A struct that joins `desired_work_roles` and `desired_work_roles_experience`.
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct RolesExperience {
@@ -100,18 +345,110 @@ impl RolesExperience {
     }
 }
 
+/// Join `desired_work_roles` and `desired_work_roles_experience`.
+fn roles_experiences(roles: &[String], experiences: &[String]) -> Vec<RolesExperience> {
+    roles
+        .iter()
+        .enumerate()
+        .map(|(i, role)| RolesExperience::new(role, experiences.get(i)))
+        .collect()
+}
+
+/// The fields fetched by the searches (through `_source` filtering) in order
+/// to build a `FoundTalent`, unless the complete documents are requested.
+pub const FOUND_TALENT_FIELDS: &'static [&'static str] = &[
+    "id",
+    "headline",
+    "avatar_url",
+    "work_locations",
+    "current_location",
+    "salary_expectations",
+    "desired_work_roles",
+    "desired_work_roles_experience",
+    "latest_position",
+    "batch_starts_at",
+    "contacted_company_ids",
+];
+
+/// The parts of a raw query (see `Talent::raw_query`) passed on to ElasticSearch.
+/// The ones left out could either return more than the `FOUND_TALENT_FIELDS`
+/// (i.e. `highlight`, `script_fields`, or the `top_hits` and the `terms` of `aggs`)
+/// or aren't meant for searching.
+const RAW_QUERY_KEYS: &'static [&'static str] = &[
+    "query",
+    "post_filter",
+    "sort",
+    "from",
+    "size",
+    "min_score",
+    "track_scores",
+];
+
+/// The free-text fields that are also analyzed in every language of `languages`.
+const TEXT_FIELDS: &'static [&'static str] =
+    &["summary", "headline", "work_experiences", "desired_work_roles"];
+
+/// A `Talent` restricted to the `FOUND_TALENT_FIELDS`.
+#[derive(Deserialize, Debug, Clone)]
+struct FoundTalentSource {
+    id: TalentId,
+    headline: String,
+    avatar_url: String,
+    work_locations: Vec<String>,
+    current_location: String,
+    salary_expectations: Vec<SalaryExpectations>,
+    #[serde(default)]
+    desired_work_roles: Vec<String>,
+    #[serde(default)]
+    desired_work_roles_experience: Vec<String>,
+    latest_position: String,
+    batch_starts_at: String,
+    #[serde(default)]
+    contacted_company_ids: Vec<u32>,
+}
+
+impl FoundTalentSource {
+    /// Return whether the talent has already been contacted by `company_id`.
+    fn contacted_by(&self, company_id: i32) -> bool {
+        self.contacted_company_ids
+            .iter()
+            .any(|id| i64::from(*id) == i64::from(company_id))
+    }
+}
+
+impl From<Box<FoundTalentSource>> for FoundTalent {
+    fn from(talent: Box<FoundTalentSource>) -> FoundTalent {
+        let talent = *talent;
+
+        FoundTalent {
+            id: talent.id,
+            headline: talent.headline,
+            avatar_url: talent.avatar_url,
+            work_locations: talent.work_locations,
+            current_location: talent.current_location,
+            salary_expectations: talent.salary_expectations,
+            roles_experiences: roles_experiences(
+                &talent.desired_work_roles,
+                &talent.desired_work_roles_experience,
+            ),
+            latest_position: talent.latest_position,
+            batch_starts_at: talent.batch_starts_at,
+            already_contacted: None,
+            is_presented: None,
+        }
+    }
+}
+
 /// Convert a `Box<Talent>` returned by ElasticSearch into a `FoundTalent`.
 impl From<Box<Talent>> for FoundTalent {
     fn from(talent: Box<Talent>) -> FoundTalent {
-        let mut roles_experiences = vec![];
-
-        for (i, role) in talent.desired_work_roles.iter().enumerate() {
-            let experience = talent.desired_work_roles_experience.get(i);
-            roles_experiences.push(RolesExperience::new(role, experience));
-        }
+        let roles_experiences = roles_experiences(
+            &talent.desired_work_roles,
+            &talent.desired_work_roles_experience,
+        );
 
         FoundTalent {
-            id: talent.id,
+            id: talent.id.to_owned(),
             headline: talent.headline.to_owned(),
             avatar_url: talent.avatar_url.to_owned(),
             work_locations: talent.work_locations.to_owned(),
@@ -120,6 +457,8 @@ impl From<Box<Talent>> for FoundTalent {
             roles_experiences: roles_experiences,
             latest_position: talent.latest_position.to_owned(),
             batch_starts_at: talent.batch_starts_at.to_owned(),
+            already_contacted: None,
+            is_presented: None,
         }
     }
 }
@@ -127,7 +466,7 @@ impl From<Box<Talent>> for FoundTalent {
 /// The talent that will be indexed into ElasticSearch.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Talent {
-    pub id: u32,
+    pub id: TalentId,
     pub accepted: bool,
     #[serde(default)]
     pub desired_work_roles: Vec<String>,
@@ -154,6 +493,10 @@ pub struct Talent {
     pub latest_position: String, // the very last experience_entries#position
     pub languages: Vec<String>,
     pub educations: Vec<String>,
+    /// The version of the profile in the source system, which must grow with every
+    /// change: the talents given with an older version than the stored one are ignored.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub external_version: Option<u64>,
 }
 
 #[derive(Debug, PartialEq)]
@@ -185,6 +528,17 @@ fn parse_desired_role_filter(input: &str) -> Option<DesiredRoleFilter> {
     })
 }
 
+/// Return the ids of the documents of `result` that were written successfully.
+fn succeeded(result: &BulkResult) -> Vec<String> {
+    result
+        .items
+        .iter()
+        .flat_map(|item| item.values())
+        .filter(|item| item.status < 300)
+        .map(|item| item.id.to_owned())
+        .collect()
+}
+
 fn mapped_experience_ranges(minimum: u8) -> Vec<&'static str> {
     static WORK_EXPERIENCE_MAPPING: &'static [&'static str] = &[
         "0..1",
@@ -216,7 +570,7 @@ impl Talent {
     /// inside a living batch to match the visibility criteria.
     pub fn visibility_filters(
         epoch: &str,
-        presented_talents: Vec<i32>,
+        presented_talents: Vec<TalentId>,
         date_filter_present: bool,
     ) -> Vec<Query> {
         let visibility_rules;
@@ -247,7 +601,7 @@ impl Talent {
         if !presented_talents.is_empty() {
             let presented_talents_filters = Query::build_bool()
                 .with_must(
-                    vec![<Query as VectorOfTerms<i32>>::build_terms(
+                    vec![<Query as VectorOfTerms<TalentId>>::build_terms(
                         "ids",
                         &presented_talents,
                     )].into_iter()
@@ -265,14 +619,16 @@ impl Talent {
         }
     }
 
-    pub fn salary_expectations_filters(params: &Map) -> Vec<Query> {
-        if let Some(&Value::String(ref max_salary)) = params.get("maximum_salary") {
-            let max_salary: u64 = match max_salary.parse().ok() {
-                Some(max_salary) => max_salary,
-                None => return vec![],
-            };
+    /// Leave out the talents marked as `deleted` (see `Resource::soft_delete`).
+    pub fn not_deleted_filter() -> Query {
+        Query::build_bool()
+            .with_must_not(vec![Query::build_term("deleted", true).build()])
+            .build()
+    }
 
-            let mut salary_query =
+    pub fn salary_expectations_filters(params: &TalentSearchParams) -> Vec<Query> {
+        if let Some(max_salary) = params.maximum_salary {
+            let salary_query =
                 Query::build_nested(
                     "salary_expectations",
                     Query::build_range("salary_expectations.minimum")
@@ -281,13 +637,12 @@ impl Talent {
                 )
                 .build();
 
-            if !params.contains_key("work_locations") {
+            if params.work_locations.is_empty() {
                 return vec![salary_query];
             }
             let mut salary_location_query_terms = vec![];
 
-            let work_locations: Vec<String> = vec_from_params!(params, "work_locations");
-            for location in work_locations {
+            for location in params.work_locations.iter() {
                 salary_location_query_terms.push(
                     Query::build_nested(
                         "salary_expectations",
@@ -296,7 +651,7 @@ impl Talent {
                                 Query::build_range("salary_expectations.minimum")
                                     .with_lte(max_salary)
                                     .build(),
-                                Query::build_term("salary_expectations.city", location)
+                                Query::build_term("salary_expectations.city", &**location)
                                 .build()
                             ])
                             .build()
@@ -311,12 +666,17 @@ impl Talent {
         }
     }
 
-    pub fn desired_roles_filters(params: &Map) -> Vec<Query> {
+    pub fn desired_roles_filters(params: &TalentSearchParams) -> Vec<Query> {
         let mut terms = vec![];
         let mut basic_roles = vec![];
 
-        let query_params: Vec<String> = vec_from_params!(params, "desired_work_roles");
-        for filter in query_params.iter().map(AsRef::as_ref).filter_map(parse_desired_role_filter) {
+        let filters = params
+            .desired_work_roles
+            .iter()
+            .map(AsRef::as_ref)
+            .filter_map(parse_desired_role_filter);
+
+        for filter in filters {
             if let Some(minimum) = filter.minimum {
                 terms.extend(
                     mapped_experience_ranges(minimum).into_iter().map(|mapped_range| {
@@ -351,68 +711,109 @@ impl Talent {
         terms
     }
 
-    /// Given parameters inside the query string mapped inside a `Map`,
-    /// and the `epoch` (defined as UNIX time in seconds) for batches,
-    /// return a `Query` for ElasticSearch.
+    /// Match the talents having one of `locations` in `field`, either exactly
+    /// or ignoring the accents (i.e. "Zurich" matches "Zürich") on the indexes
+    /// that have the `folded` sub-field.
+    pub fn location_filters(field: &str, locations: &Vec<String>) -> Vec<Query> {
+        if locations.is_empty() {
+            return vec![];
+        }
+
+        let folded_field = format!("{}.folded", field);
+        let mut queries = <Query as VectorOfTerms<String>>::build_terms(field, locations);
+        queries.extend(
+            locations
+                .iter()
+                .map(|location| Query::build_match(&*folded_field, &**location).build()),
+        );
+
+        vec![Query::build_bool().with_should(queries).build()]
+    }
+
+    /// Given the parameters of the search and the `epoch` (defined as UNIX
+    /// time in seconds) for batches, return a `Query` for ElasticSearch.
     ///
     /// Considering a single row, the terms inside there are ORred,
     /// while through the rows there is an AND.
     /// I.e.: given ["Fullstack", "DevOps"] as `desired_work_roles`, found talents
     /// will present at least one of these roles), but both `desired_work_roles`
     /// and `work_location`, if provided, must be matched successfully.
-    pub fn search_filters(params: &Map, epoch: &str) -> Query {
-        let company_id = i32_vec_from_params!(params, "company_id");
-        let date_filter_present = params.get("epoch") != None;
+    pub fn search_filters(params: &TalentSearchParams, epoch: &str) -> Query {
+        let exclusions = Talent::exclusion_filters(params)
+            .into_iter()
+            .flat_map(|(_, queries)| queries)
+            .collect::<Vec<Query>>();
 
-        let search_features_param = params
-            .get("features")
-            .unwrap_or(&Value::Null);
-        let search_features: Vec<String> = <_>::from_value(search_features_param).unwrap_or(vec![]);
-        let search_features: HashSet<_> = search_features.into_iter().collect();
-        println!("search_features: {:?}", search_features);
+        Talent::candidate_filters(params, epoch)
+            .with_must_not(exclusions)
+            .build()
+    }
+
+    /// Return the filters hiding the talents that the company shouldn't see,
+    /// named after the `SearchResults` field that counts them.
+    pub fn exclusion_filters(params: &TalentSearchParams) -> Vec<(&'static str, Vec<Query>)> {
+        let company_id = params.company_ids();
+
+        vec![
+            (
+                "excluded_by_company",
+                vec![
+                    <Query as VectorOfTerms<i32>>::build_terms(
+                        "contacted_company_ids",
+                        &company_id,
+                    ),
+                    <Query as VectorOfTerms<i32>>::build_terms("blocked_companies", &company_id),
+                ].into_iter()
+                    .flat_map(|x| x)
+                    .collect(),
+            ),
+            (
+                "excluded_by_contacted",
+                <Query as VectorOfTerms<TalentId>>::build_terms("id", &params.contacted_talents),
+            ),
+            (
+                "excluded_by_ignored",
+                <Query as VectorOfTerms<TalentId>>::build_terms("id", &params.ignored_talents),
+            ),
+        ].into_iter()
+            .filter(|&(_, ref queries)| !queries.is_empty())
+            .collect()
+    }
 
+    /// Return the query of `search_filters` without the `exclusion_filters`.
+    pub fn candidate_filters(params: &TalentSearchParams, epoch: &str) -> BoolQueryBuilder {
         let mut must_filters = vec![
             vec![
                 Query::build_bool()
                     .with_must(
-                        vec_from_params!(params, "languages")
-                            .into_iter()
-                            .map(|language: String| {
-                                Query::build_term("languages", language).build()
-                            })
+                        params
+                            .languages
+                            .iter()
+                            .map(|language| Query::build_term("languages", &**language).build())
                             .collect::<Vec<Query>>(),
                     )
                     .build(),
             ],
             <Query as VectorOfTerms<String>>::build_terms(
                 "professional_experience",
-                &vec_from_params!(params, "professional_experience"),
+                &params.professional_experience,
             ),
             <Query as VectorOfTerms<String>>::build_terms(
                 "work_authorization",
-                &vec_from_params!(params, "work_authorization"),
-            ),
-            <Query as VectorOfTerms<String>>::build_terms(
-                "work_locations",
-                &vec_from_params!(params, "work_locations"),
-            ),
-            <Query as VectorOfTerms<String>>::build_terms(
-                "current_location",
-                &vec_from_params!(params, "current_location"),
-            ),
-            <Query as VectorOfTerms<i32>>::build_terms(
-                "id",
-                &vec_from_maybe_csv_params!(params, "bookmarked_talents"),
+                &params.work_authorization,
             ),
+            Talent::location_filters("work_locations", &params.work_locations),
+            Talent::location_filters("current_location", &params.current_location),
+            <Query as VectorOfTerms<TalentId>>::build_terms("id", &params.bookmarked_talents),
             Talent::visibility_filters(
                 epoch,
-                i32_vec_from_params!(params, "presented_talents"),
-                date_filter_present,
+                params.presented_talents.to_owned(),
+                params.epoch.is_some(),
             ),
         ];
 
         let mut should_filters = vec![];
-        let no_fulltext_search = search_features.contains("no_fulltext_search");
+        let no_fulltext_search = params.has_feature(Feature::NoFulltextSearch);
 
         let overrides = if no_fulltext_search {
             vec![
@@ -427,7 +828,7 @@ impl Talent {
             vec![]
         }.into_iter().collect();
 
-        let keywords_use_should = search_features.contains("keywords_should");
+        let keywords_use_should = params.has_feature(Feature::KeywordsShould);
         let keyword_filter = match Talent::full_text_search(params, overrides) {
             Some(keywords) => vec![keywords],
             None => vec![],
@@ -460,39 +861,19 @@ impl Talent {
                             Query::build_bool()
                                 .with_should(Talent::desired_roles_filters(params))
                                 .build(),
+                            Talent::not_deleted_filter(),
                         ]
                     )
                     .build()
             )
-            .with_must_not(
-                vec![
-                    <Query as VectorOfTerms<i32>>::build_terms(
-                        "contacted_company_ids",
-                        &company_id,
-                    ),
-                    <Query as VectorOfTerms<i32>>::build_terms("blocked_companies", &company_id),
-                    <Query as VectorOfTerms<i32>>::build_terms(
-                        "id",
-                        &vec_from_maybe_csv_params!(params, "contacted_talents"),
-                    ),
-                    <Query as VectorOfTerms<i32>>::build_terms(
-                        "id",
-                        &vec_from_maybe_csv_params!(params, "ignored_talents"),
-                    ),
-                ].into_iter()
-                    .flat_map(|x| x)
-                    .collect::<Vec<Query>>(),
-            )
-            .build()
     }
 
-    pub fn full_text_search(params: &Map, overrides: HashMap<&str, &str>) -> Option<Query> {
-        match params.get("keywords") {
-            Some(&Value::String(ref keywords)) => {
-                if keywords.is_empty() {
-                    return None;
-                }
-
+    pub fn full_text_search(
+        params: &TalentSearchParams,
+        overrides: HashMap<&str, &str>,
+    ) -> Option<Query> {
+        match params.keywords {
+            Some(ref keywords) => {
                 // TODO: refactor me
                 // This is a very bad approach but ATM I don't know
                 // how to do exact matching on ngrams. My temptative
@@ -506,44 +887,530 @@ impl Talent {
                         format!("{}{}", $field, field_modifier)
                     }};
                 }
-                let query = Query::build_query_string(keywords.to_owned())
-                    .with_fields(vec![
-                        maybe_raw!("skills"),
-                        maybe_raw!("summary"),
-                        maybe_raw!("headline"),
-                        maybe_raw!("desired_work_roles"),
-                        maybe_raw!("work_experiences"),
-                        maybe_raw!("educations"),
-                    ])
-                    .build();
-
-                Some(query)
+                let mut fields = vec![
+                    maybe_raw!("skills"),
+                    maybe_raw!("summary"),
+                    maybe_raw!("headline"),
+                    maybe_raw!("desired_work_roles"),
+                    maybe_raw!("work_experiences"),
+                    maybe_raw!("educations"),
+                ];
+
+                // the language sub-fields are missing on the indexes that don't analyze
+                // that language, where they just don't match anything
+                if !raw_query && overrides.is_empty() {
+                    for language in Language::ALL.iter() {
+                        fields.extend(TEXT_FIELDS.iter().map(|field| language.sub_field(field)));
+                    }
+                }
+
+                let query = Query::build_query_string(keywords.to_owned()).with_fields(fields);
+                let query = match params.keywords_operator {
+                    Some(operator) => query.with_default_operator(operator),
+                    None => query,
+                };
+
+                Some(query.build())
             }
             _ => None,
         }
     }
 
-    /// Return a `Sort` that makes values be sorted for given fields, descendently.
-    pub fn sorting_criteria() -> Sort {
-        Sort::new(vec![
-            SortField::new("batch_starts_at", Some(Order::Desc))
-                .with_unmapped_type("date")
-                .build(),
-            SortField::new("weight", Some(Order::Desc))
-                .with_unmapped_type("integer")
-                .build(),
-            SortField::new("added_to_batch_at", Some(Order::Desc))
-                .with_unmapped_type("date")
-                .build(),
+    /// Return a `sort` that makes values be sorted for the given `fields`
+    /// (see `DEFAULT_SORT`), also in the indexes that don't map them yet.
+    pub fn sorting_criteria(fields: &[SortField]) -> JsonValue {
+        let criteria = fields
+            .iter()
+            .map(|sort| {
+                let mut criterion = json!({ "order": sort.order() });
+                let unmapped_type = match &*sort.field {
+                    "batch_starts_at" | "batch_ends_at" | "added_to_batch_at" => Some("date"),
+                    "weight" => Some("integer"),
+                    _ => None,
+                };
+                if let Some(unmapped_type) = unmapped_type {
+                    criterion["unmapped_type"] = json!(unmapped_type);
+                }
+
+                let mut sort_field = JsonMap::new();
+                sort_field.insert(sort.field.to_owned(), criterion);
+                JsonValue::Object(sort_field)
+            })
+            .collect();
+
+        JsonValue::Array(criteria)
+    }
+
+    /// Return the `sort` of the `weight_decay` feature: the batches first, then the
+    /// score given by `Talent::weight_decay` to the talents of the same batch.
+    pub fn decayed_sorting_criteria() -> JsonValue {
+        json!([
+            { "batch_starts_at": { "order": "desc", "unmapped_type": "date" } },
+            { "_score":          { "order": "desc" } },
         ])
     }
-}
 
-impl Resource for Talent {
-    type Results = SearchResults;
+    /// Wrap `query` into a `function_score` scoring the talents by how recently,
+    /// before `epoch`, they were added to their batch, nudged by their `weight`
+    /// as configured in `ranking`, so that the weight doesn't dominate the recency.
+    pub fn weight_decay(query: &Query, ranking: &Ranking, epoch: &str) -> JsonValue {
+        json!({
+            "function_score": {
+                "query":      query,
+                "functions":  [
+                    {
+                        "gauss": {
+                            "added_to_batch_at": {
+                                "origin": epoch,
+                                "scale":  ranking.recency_scale,
+                                "decay":  0.5,
+                            }
+                        }
+                    },
+                    {
+                        "field_value_factor": {
+                            "field":    "weight",
+                            "factor":   ranking.weight_factor,
+                            "modifier": "log1p",
+                            "missing":  0,
+                        }
+                    },
+                ],
+                "score_mode": "sum",
+                "boost_mode": "replace",
+            }
+        })
+    }
 
-    /// Populate the ElasticSearch index with `Vec<Talent>`
-    fn index(es: &mut Client, index: &str, resources: Vec<Self>) -> Result<BulkResult, EsError> {
+    /// Return a `sort` that makes the talents be sorted by the lowest of their minimum
+    /// salaries, in the currency they're expected in, followed by `then`.
+    pub fn salary_sorting_criteria(sort: SalarySort, then: JsonValue) -> JsonValue {
+        let mut criteria = vec![json!({
+            "salary_expectations.minimum": {
+                "order":       sort.order(),
+                "mode":        "min",
+                "nested_path": "salary_expectations",
+            }
+        })];
+
+        match then {
+            JsonValue::Array(then) => criteria.extend(then),
+            then => criteria.push(then),
+        }
+        JsonValue::Array(criteria)
+    }
+
+    /// Return the `highlight` settings for given keywords. Quoted keywords
+    /// are matched against the `raw` fields, so those are the ones highlighted.
+    pub fn highlight(keywords: &str) -> JsonValue {
+        let fields = if keywords.contains('"') {
+            vec![
+                "skills.raw",
+                "summary.raw",
+                "headline.raw",
+                "desired_work_roles.raw",
+                "work_experiences.raw",
+                "educations.raw",
+            ]
+        } else {
+            vec![
+                "skills",
+                "skills.keyword",
+                "summary",
+                "summary.keyword",
+                "headline",
+                "headline.keyword",
+                "desired_work_roles",
+                "work_experiences",
+                "educations",
+            ]
+        };
+
+        let setting = json!({ "type": "plain", "fragment_size": 1 });
+        let fields: JsonMap<String, JsonValue> = fields
+            .into_iter()
+            .map(|field| (field.to_owned(), setting.to_owned()))
+            .collect();
+
+        json!({
+            "encoder":   "html",
+            "pre_tags":  [""],
+            "post_tags": [""],
+            "fields":    fields,
+        })
+    }
+
+    /// Read the number of distinct talents found across the searched indexes
+    /// from the `hits_per_index` aggregation.
+    fn distinct_hits(aggregations: &Option<JsonValue>) -> Option<u64> {
+        aggregations
+            .as_ref()
+            .and_then(|aggregations| aggregations["hits_per_index"]["talents"]["value"].as_u64())
+    }
+
+    /// Read the number of hits of every index from the `hits_per_index` aggregation.
+    fn hits_per_index(aggregations: &Option<JsonValue>) -> HashMap<String, u64> {
+        let buckets = aggregations.as_ref().and_then(|aggregations| {
+            aggregations["hits_per_index"]["indexes"]["buckets"].as_array()
+        });
+
+        buckets
+            .into_iter()
+            .flat_map(|buckets| buckets.iter())
+            .filter_map(|bucket| match (bucket["key"].as_str(), bucket["doc_count"].as_u64()) {
+                (Some(index), Some(count)) => Some((index.to_owned(), count)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Turn the hits of a search into `SearchResult`s: the complete documents,
+    /// when asked through `view=full`, or `FoundTalent`s, which know whether
+    /// the company searching (`company_id`) already contacted them.
+    pub fn results_of(
+        result: resource::SearchResult<JsonValue>,
+        params: &TalentSearchParams,
+    ) -> Result<Vec<SearchResult>, EsError> {
+        if params.full_view {
+            return result
+                .into_typed::<Talent>()
+                .map(|result| result.hits.hits.into_iter().map(SearchResult::full).collect());
+        }
+
+        let company_id = params.company_id;
+        let presented_talents = &params.presented_talents;
+        result.into_typed::<FoundTalentSource>().map(|result| {
+            result
+                .hits
+                .hits
+                .into_iter()
+                .map(|hit| {
+                    let source = hit.source.unwrap();
+                    let already_contacted =
+                        company_id.map(|company_id| source.contacted_by(company_id));
+                    let mut talent = FoundTalent::from(source);
+
+                    if let Some(already_contacted) = already_contacted {
+                        talent.set_contact_status(already_contacted, presented_talents);
+                    }
+
+                    SearchResult {
+                        talent: TalentView::Found(talent),
+                        highlight: hit.highlight,
+                    }
+                })
+                .collect()
+        })
+    }
+
+    /// Return the search of every talent matching `params`, in the order of
+    /// `GET /talents`, to be read `batch_size` talents at a time through a scroll.
+    /// Pagination, highlights and aggregations are left out.
+    pub fn export_body(params: &TalentSearchParams, epoch: &str, batch_size: u64) -> JsonValue {
+        let builder = TalentQueryBuilder::new(params, epoch);
+        let mut body = json!({
+            "query": builder.query(&builder.filters()),
+            "size":  batch_size,
+        });
+
+        if params.keywords.is_some() {
+            body["min_score"] = json!(KEYWORDS_MIN_SCORE);
+        }
+        if let Some(sort) = builder.sort() {
+            body["sort"] = sort;
+        }
+        if let Some(post_filter) = builder.post_filter() {
+            body["post_filter"] = post_filter;
+        }
+        if !params.full_view {
+            body["_source"] = json!(FOUND_TALENT_FIELDS);
+        }
+
+        body
+    }
+
+    /// Count the talents matching `params` that were left out of the `total`
+    /// found by the search because of the `KEYWORDS_MIN_SCORE`, by searching
+    /// them again without it. Only done for keyword searches asking for it.
+    fn filtered_by_min_score(
+        es: &mut dyn SearchBackend,
+        index: &[&str],
+        params: &TalentSearchParams,
+        epoch: &str,
+        total: u64,
+    ) -> Option<u64> {
+        if params.keywords.is_none() || !params.track_min_score {
+            return None;
+        }
+
+        let body = json!({
+            "query": Talent::search_filters(params, epoch),
+            "size":  0,
+        });
+
+        match es.search_json(index, &body) {
+            Ok(result) => Some(result.hits.total.saturating_sub(total)),
+            Err(err) => {
+                error!("{:?}", err);
+                None
+            }
+        }
+    }
+
+    /// Unmark the talent identified by `id` as `deleted`, returning
+    /// whether it could be found.
+    pub fn restore(
+        es: &mut dyn SearchBackend,
+        index: &str,
+        id: &TalentId,
+    ) -> Result<bool, EsError> {
+        let result = es.bulk_json(index, ES_TYPE, vec![
+            Action::update(id.to_string(), json!({ "deleted": false })),
+        ])?;
+        events::publish(Change::indexed(index, succeeded(&result)));
+        let item = result.items.iter().flat_map(|item| item.values()).next();
+
+        Ok(item.map_or(false, |item| item.status < 300))
+    }
+
+    /// Like `restore`, looking for the talent in every index of `indexes`,
+    /// since only the talent itself tells where it's routed to.
+    pub fn restore_in(
+        es: &mut dyn SearchBackend,
+        indexes: &IndexSet,
+        id: &TalentId,
+    ) -> Result<bool, EsError> {
+        for index in indexes.writable() {
+            match Talent::restore(es, index, id) {
+                Ok(true) => return Ok(true),
+                Ok(false) => (),
+                Err(ref error) if indexes.is_missing_route(error) => (),
+                Err(error) => return Err(error),
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// Return the talents marked as `deleted` in `indexes`, the latest batches first.
+    pub fn deleted(
+        es: &mut dyn SearchBackend,
+        indexes: &IndexSet,
+        offset: u64,
+        per_page: u64,
+    ) -> Result<DeletedTalents, EsError> {
+        let body = json!({
+            "query":   { "term": { "deleted": true } },
+            "sort":    [{ "batch_starts_at": { "order": "desc" } }, { "id": { "order": "asc" } }],
+            "from":    offset,
+            "size":    per_page,
+            "_source": FOUND_TALENT_FIELDS,
+        });
+
+        let result = es.search_json(&indexes.names(), &body)?.into_typed::<FoundTalentSource>()?;
+        Ok(DeletedTalents {
+            total: result.hits.total,
+            talents: result
+                .hits
+                .hits
+                .into_iter()
+                .filter_map(|hit| hit.source)
+                .map(FoundTalent::from)
+                .collect(),
+        })
+    }
+
+    /// Check that `body` only contains the `RAW_QUERY_KEYS`.
+    pub fn validate_raw_query(body: &JsonValue) -> Result<(), String> {
+        let body = match body.as_object() {
+            Some(body) => body,
+            None => return Err("The query must be a JSON object".to_owned()),
+        };
+
+        match body.keys().find(|key| !RAW_QUERY_KEYS.contains(&&***key)) {
+            Some(key) => Err(format!(
+                "{} is not allowed, only {} are",
+                key,
+                RAW_QUERY_KEYS.join(", ")
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Run the search described by `body` on `indexes`, which is meant to be checked by
+    /// `validate_raw_query` first, with its hits restricted to the `FOUND_TALENT_FIELDS`.
+    pub fn raw_query(
+        es: &mut dyn SearchBackend,
+        indexes: &IndexSet,
+        body: &JsonValue,
+    ) -> Result<RawQueryResults, EsError> {
+        let mut body = body.to_owned();
+        body["_source"] = json!(FOUND_TALENT_FIELDS);
+
+        let result = es.search_json(&indexes.names(), &body)?;
+        Ok(RawQueryResults {
+            total: result.hits.total,
+            hits: result
+                .hits
+                .hits
+                .into_iter()
+                .map(|hit| RawHit {
+                    index: hit.index,
+                    id: hit.id,
+                    score: hit.score,
+                    source: hit.source.map(|source| *source),
+                })
+                .collect(),
+        })
+    }
+
+    /// Return the distribution of the minimum salaries expected by the talents
+    /// matching `params` in `indexes`, whose histograms have buckets `interval` wide.
+    /// The talents hidden from the search results are left out as well.
+    /// The salaries are ranged first, refusing the intervals that make more than
+    /// `MAX_HISTOGRAM_BUCKETS` buckets.
+    pub fn salary_distribution(
+        es: &mut dyn SearchBackend,
+        indexes: &IndexSet,
+        params: &TalentSearchParams,
+        interval: f64,
+    ) -> Result<SalaryDistribution, SearchspotError> {
+        let epoch = match params.epoch {
+            Some(ref epoch) => epoch.to_owned(),
+            None => Utc::now().to_rfc3339(),
+        };
+        let index: Vec<&str> = match params.index {
+            Some(ref index) => resource::split_indexes(index),
+            None => indexes.names(),
+        };
+        let query = Talent::search_filters(params, &*epoch);
+
+        let mut range = json!({
+            "query": query,
+            "size":  0,
+            "aggs":  {
+                "salaries": {
+                    "nested": { "path": "salary_expectations" },
+                    "aggs":   {
+                        "minimum": { "stats": { "field": "salary_expectations.minimum" } }
+                    }
+                }
+            }
+        });
+        if params.keywords.is_some() {
+            range["min_score"] = json!(KEYWORDS_MIN_SCORE);
+        }
+        let range = es.search_json(&*index, &range)?.aggregations.unwrap_or(JsonValue::Null);
+        let ref minimum = range["salaries"]["minimum"];
+        resource::check_histogram(interval, minimum["min"].as_f64(), minimum["max"].as_f64())?;
+
+        let mut body = json!({
+            "query": query,
+            "size":  0,
+            "aggs":  {
+                "salaries": {
+                    "nested": { "path": "salary_expectations" },
+                    "aggs":   {
+                        "cities": {
+                            "terms": { "field": "salary_expectations.city", "size": 100 },
+                            "aggs":  {
+                                "currencies": {
+                                    "terms": { "field": "salary_expectations.currency" },
+                                    "aggs":  {
+                                        "minimum": {
+                                            "stats": { "field": "salary_expectations.minimum" }
+                                        },
+                                        "histogram": {
+                                            "histogram": {
+                                                "field":    "salary_expectations.minimum",
+                                                "interval": interval,
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        });
+        if params.keywords.is_some() {
+            body["min_score"] = json!(KEYWORDS_MIN_SCORE);
+        }
+
+        let result = es.search_json(&*index, &body)?;
+        let aggregations = result.aggregations.unwrap_or(JsonValue::Null);
+        let ref salaries = aggregations["salaries"];
+
+        let mut groups = vec![];
+        for city in salaries["cities"]["buckets"].as_array().into_iter().flat_map(|c| c.iter()) {
+            let currencies = city["currencies"]["buckets"].as_array();
+            for currency in currencies.into_iter().flat_map(|c| c.iter()) {
+                let ref minimum = currency["minimum"];
+                groups.push(SalaryGroup {
+                    city: city["key"].as_str().unwrap_or("").to_owned(),
+                    currency: currency["key"].as_str().unwrap_or("").to_owned(),
+                    count: currency["doc_count"].as_u64().unwrap_or(0),
+                    avg: minimum["avg"].as_f64(),
+                    min: minimum["min"].as_f64(),
+                    max: minimum["max"].as_f64(),
+                    histogram: serde_json::from_value(currency["histogram"]["buckets"].to_owned())
+                        .unwrap_or_default(),
+                });
+            }
+        }
+
+        Ok(SalaryDistribution {
+            count: salaries["doc_count"].as_u64().unwrap_or(0),
+            groups: groups,
+        })
+    }
+
+    /// Return the distinct values of the `DISTINCT_FIELDS` of every talent of `index`,
+    /// along with the `top` most frequent ones of each field.
+    pub fn distinct_counts(
+        es: &mut dyn SearchBackend,
+        index: &str,
+        top: u64,
+    ) -> Result<DistinctCounts, EsError> {
+        let mut aggs = JsonMap::new();
+        for &(name, field) in DISTINCT_FIELDS.iter() {
+            aggs.insert(format!("{}_count", name), json!({ "cardinality": { "field": field } }));
+            aggs.insert(name.to_owned(), json!({ "terms": { "field": field, "size": top } }));
+        }
+
+        let body = json!({
+            "query": { "match_all": {} },
+            "size":  0,
+            "aggs":  aggs,
+        });
+
+        let result = es.search_json(&[index], &body)?;
+        let aggregations = result.aggregations.unwrap_or(JsonValue::Null);
+
+        let fields = DISTINCT_FIELDS
+            .iter()
+            .map(|&(name, _)| DistinctField {
+                field: name.to_owned(),
+                count: aggregations[&format!("{}_count", name)]["value"].as_u64().unwrap_or(0),
+                top: serde_json::from_value(aggregations[name]["buckets"].to_owned())
+                    .unwrap_or_default(),
+            })
+            .collect();
+
+        Ok(DistinctCounts {
+            total: result.hits.total,
+            fields: fields,
+        })
+    }
+
+    /// Store `resources` in `index` through the bulk `action` (i.e. `Action::index`).
+    fn store(
+        es: &mut dyn SearchBackend,
+        index: &str,
+        resources: Vec<Talent>,
+        action: fn(Talent) -> Action<Talent>,
+    ) -> Result<BulkResult, EsError> {
         fn sync_desired_work_roles(r: &mut Talent) {
             // Handle the future upgrade to only sending `desired_roles`
             if !r.desired_roles.is_empty() {
@@ -563,413 +1430,540 @@ impl Resource for Talent {
             }
         }
 
-        es.bulk(&resources
-            .into_iter()
-            .map(|mut r| {
-                let id = r.id.to_string();
-                sync_desired_work_roles(&mut r);
-                Action::index(r).with_id(id)
-            })
-            .collect::<Vec<Action<Talent>>>())
-            .with_index(index)
-            .with_doc_type(ES_TYPE)
-            .send()
-    }
+        let documents = resources.len();
+        let started = Instant::now();
+        let result = es.bulk(
+            index,
+            ES_TYPE,
+            resources
+                .into_iter()
+                .map(|mut r| {
+                    let id = r.id.to_string();
+                    let external_version = r.external_version;
+                    sync_desired_work_roles(&mut r);
+
+                    let action = action(r).with_id(id);
+                    match external_version {
+                        Some(version) => action.with_external_version(version),
+                        None => action,
+                    }
+                })
+                .collect(),
+        );
 
-    /// Query ElasticSearch on given `indexes` and `params` and return the IDs of
-    /// the found talents.
-    fn search(es: &mut Client, default_index: &str, params: &Map) -> Self::Results {
-        let epoch = match params.get("epoch") {
-            Some(&Value::String(ref epoch)) => epoch.to_owned(),
-            _ => Utc::now().to_rfc3339(),
-        };
+        let ok = result.as_ref().map(|result| !result.errors).unwrap_or(false);
+        metrics::record_latency("searchspot_index_duration_seconds", ES_TYPE, ok, started);
+        metrics::record_size("searchspot_index_documents", ES_TYPE, ok, documents);
 
-        let index: Vec<&str> = match params.get("index") {
-            Some(&Value::String(ref index)) => vec![&index[..]],
-            _ => vec![default_index],
-        };
+        if let Ok(ref result) = result {
+            events::publish(Change::indexed(index, succeeded(result)));
+        }
 
-        let keywords_present = match params.get("keywords") {
-            Some(keywords) => match keywords {
-                &Value::String(ref keywords) => !keywords.is_empty(),
-                _ => false,
-            },
-            None => false,
-        };
+        result
+    }
+}
 
-        let offset: u64 = match params.get("offset") {
-            Some(&Value::String(ref offset)) => offset.parse().unwrap_or(0),
-            Some(&Value::U64(ref offset)) => *offset,
-            _ => 0,
-        };
+impl Resource for Talent {
+    type Results = SearchResults;
+    type Id = TalentId;
 
-        let per_page: u64 = match params.get("per_page") {
-            Some(&Value::String(ref per_page)) => per_page.parse().unwrap_or(10),
-            Some(&Value::U64(ref per_page)) => *per_page,
-            _ => 10,
-        };
+    /// Canonicalize the locations and convert the minimum salaries to euros
+    /// (see `SalaryExpectations::minimum_eur`).
+    fn normalize(&mut self, config: &Config) {
+        for location in self.work_locations.iter_mut() {
+            *location = locations::canonicalize(&config.locations, location);
+        }
+        self.current_location =
+            locations::canonicalize(&config.locations, &self.current_location);
 
-        let debug_es_query: bool = match params.get("debug_es_query") {
-            Some(&Value::String(ref boolean)) => boolean == "true",
-            _ => false,
-        };
+        for salary in self.salary_expectations.iter_mut() {
+            salary.minimum_eur = salary
+                .minimum
+                .and_then(|minimum| config.currencies.to_eur(minimum, &salary.currency));
+        }
+    }
 
-        let mut raw_es_query = None;
-        let search_filters = &Talent::search_filters(params, &*epoch);
-
-        let result = if keywords_present {
-            let mut highlight = Highlight::new()
-                .with_encoder(Encoders::HTML)
-                .with_pre_tags(vec![String::new()])
-                .with_post_tags(vec![String::new()])
-                .to_owned();
-
-            let settings = Setting::new()
-                .with_type(SettingTypes::Plain)
-                .with_term_vector(TermVector::WithPositionsOffsets)
-                .with_fragment_size(1)
-                .to_owned();
-
-            match params.get("keywords") {
-                Some(&Value::String(ref keywords)) => {
-                    if keywords.contains("\"") {
-                        highlight.add_setting("skills.raw".to_owned(), settings.clone());
-                        highlight.add_setting("summary.raw".to_owned(), settings.clone());
-                        highlight.add_setting("headline.raw".to_owned(), settings.clone());
-                        highlight
-                            .add_setting("desired_work_roles.raw".to_owned(), settings.clone());
-                        highlight.add_setting("work_experiences.raw".to_owned(), settings.clone());
-                        highlight.add_setting("educations.raw".to_owned(), settings.clone());
-                    } else {
-                        highlight.add_setting("skills".to_owned(), settings.clone());
-                        highlight.add_setting("skills.keyword".to_owned(), settings.clone());
-                        highlight.add_setting("summary".to_owned(), settings.clone());
-                        highlight.add_setting("summary.keyword".to_owned(), settings.clone());
-                        highlight.add_setting("headline".to_owned(), settings.clone());
-                        highlight.add_setting("headline.keyword".to_owned(), settings.clone());
-                        highlight.add_setting("desired_work_roles".to_owned(), settings.clone());
-                        highlight.add_setting("work_experiences".to_owned(), settings.clone());
-                        highlight.add_setting("educations".to_owned(), settings);
-                    }
-                }
-                _ => {
-                    highlight.add_setting("skills".to_owned(), settings.clone());
-                    highlight.add_setting("skills.keyword".to_owned(), settings.clone());
-                    highlight.add_setting("summary".to_owned(), settings.clone());
-                    highlight.add_setting("summary.keyword".to_owned(), settings.clone());
-                    highlight.add_setting("headline".to_owned(), settings.clone());
-                    highlight.add_setting("headline.keyword".to_owned(), settings.clone());
-                    highlight.add_setting("desired_work_roles".to_owned(), settings.clone());
-                    highlight.add_setting("work_experiences".to_owned(), settings.clone());
-                    highlight.add_setting("educations".to_owned(), settings);
-                }
-            }
+    /// The talents are routed by where they're based (see `config.es.routes`).
+    fn route(&self) -> Option<&str> {
+        Some(&self.current_location)
+    }
 
-            let mut query = es.search_query();
+    /// Populate the ElasticSearch index with `Vec<Talent>`
+    fn index(
+        es: &mut dyn SearchBackend,
+        index: &str,
+        resources: Vec<Self>,
+    ) -> Result<BulkResult, EsError> {
+        Talent::store(es, index, resources, Action::index)
+    }
 
-            let mut final_query = query.with_indexes(&*index)
-                    .with_query(search_filters)
-                    .with_highlight(&highlight)
-                    .with_from(offset)
-                    .with_size(per_page)
-                    .with_min_score(0.56)
-                    .with_track_scores(true);
+    /// Like `index`, failing on the talents whose id is taken already,
+    /// which are reported with status 409 instead of being replaced.
+    fn create(
+        es: &mut dyn SearchBackend,
+        index: &str,
+        resources: Vec<Self>,
+    ) -> Result<BulkResult, EsError> {
+        Talent::store(es, index, resources, Action::create)
+    }
 
-            if debug_es_query {
-                raw_es_query = final_query.es_query().ok();
-            }
-            final_query.send::<Talent>()
+    /// Query ElasticSearch on given `indexes` and `params` and return the IDs of
+    /// the found talents.
+    fn try_search(
+        es: &mut dyn SearchBackend,
+        default_index: &str,
+        params: &Map,
+    ) -> Result<Self::Results, SearchspotError> {
+        let params = TalentSearchParams::from_map(params).map_err(SearchspotError::BadRequest)?;
+
+        let epoch = match params.epoch {
+            Some(ref epoch) => epoch.to_owned(),
+            None => Utc::now().to_rfc3339(),
+        };
+
+        // both accept comma-separated lists and wildcards
+        let index: Vec<&str> = match params.index {
+            Some(ref index) => resource::split_indexes(index),
+            None => resource::split_indexes(default_index),
+        };
+        let many_indexes = index.len() > 1 || index.iter().any(|index| index.contains('*'));
+
+        let query = TalentQueryBuilder::new(&params, &*epoch)
+            .with_many_indexes(many_indexes)
+            .build();
+        let body = serde_json::to_value(&query).map_err(EsError::from)?;
+        let raw_es_query = if params.debug_es_query {
+            serde_json::to_string(&body).ok()
         } else {
-            let sorting_criteria = &Talent::sorting_criteria();
-            let mut query = es.search_query();
+            None
+        };
 
-            let mut final_query = query.with_indexes(&*index)
-                    .with_query(search_filters)
-                    .with_sort(sorting_criteria)
-                    .with_from(offset)
-                    .with_size(per_page);
+        let started = Instant::now();
+        let result = es.search_json(&*index, &body);
 
-            if debug_es_query {
-                raw_es_query = final_query.es_query().ok();
-            }
-            final_query.send::<Talent>()
-        };
+        let ok = result.is_ok();
+        let hits = result.as_ref().map(|result| result.hits.hits.len()).unwrap_or(0);
+        metrics::record_latency("searchspot_search_duration_seconds", ES_TYPE, ok, started);
+        metrics::record_size("searchspot_search_hits", ES_TYPE, ok, hits);
 
         match result {
             Ok(result) => {
                 // println!("{:?}", result);
-                let total = result.hits.total;
+                // the same talent can be stored in more than one of the searched indexes
+                let total = match Talent::distinct_hits(&result.aggregations) {
+                    Some(distinct) => cmp::min(result.hits.total, distinct),
+                    None => result.hits.total,
+                };
+                let total_relation = result.hits.total_relation;
+                let timed_out = result.timed_out;
+                let partial = result.shards.failed > 0;
+                let shard_failures: Vec<String> =
+                    result.shards.failures.iter().map(ToString::to_string).collect();
+                if partial {
+                    warn!(
+                        "{} of {} shards failed: {}",
+                        result.shards.failed,
+                        result.shards.total,
+                        shard_failures.join(", ")
+                    );
+                }
+                let excluded = |name: &str| {
+                    result.aggregations.as_ref().map_or(0, |aggregations| {
+                        aggregations[name]["doc_count"].as_u64().unwrap_or(0)
+                    })
+                };
+                let excluded_by_company = excluded("excluded_by_company");
+                let excluded_by_contacted = excluded("excluded_by_contacted");
+                let excluded_by_ignored = excluded("excluded_by_ignored");
+                let filtered_by_min_score =
+                    Talent::filtered_by_min_score(es, &index, &params, &*epoch, total);
 
                 if total == 0 {
-                    return SearchResults {
+                    return Ok(SearchResults {
+                        total_relation: total_relation,
+                        timed_out: timed_out,
+                        partial: partial,
+                        shard_failures: shard_failures,
                         raw_es_query: raw_es_query,
+                        experiment: params.experiment,
+                        excluded_by_company: excluded_by_company,
+                        excluded_by_contacted: excluded_by_contacted,
+                        excluded_by_ignored: excluded_by_ignored,
+                        filtered_by_min_score: filtered_by_min_score,
                         .. SearchResults::default()
-                    }
+                    });
                 }
 
-                let mut results: Vec<SearchResult> = result
-                    .hits
-                    .hits
-                    .into_iter()
-                    .map(SearchResult::from)
-                    .collect();
-                SearchResults {
+                let hits_per_index = Talent::hits_per_index(&result.aggregations);
+                // a talent found in more indexes is kept where it ranks best (see `collapse`)
+                let results = Talent::results_of(result, &params)?;
+                Ok(SearchResults {
                     total: total,
+                    total_relation: total_relation,
+                    timed_out: timed_out,
+                    partial: partial,
+                    shard_failures: shard_failures,
                     talents: results,
                     raw_es_query: raw_es_query,
-                }
-            }
-            Err(err) => {
-                error!("{:?}", err);
-                SearchResults::default()
+                    hits_per_index: hits_per_index,
+                    experiment: params.experiment,
+                    excluded_by_company: excluded_by_company,
+                    excluded_by_contacted: excluded_by_contacted,
+                    excluded_by_ignored: excluded_by_ignored,
+                    filtered_by_min_score: filtered_by_min_score,
+                })
             }
+            Err(err) => Err(err.into()),
         }
     }
 
+    fn validate_search(params: &Map, limits: &Limits) -> Result<(), SearchspotError> {
+        TalentSearchParams::from_map(params)
+            .and_then(|params| params.check_limits(limits))
+            .map_err(SearchspotError::BadRequest)
+    }
+
+    fn json_api() -> Option<Collection> {
+        Some(Collection {
+            kind: "talents",
+            items: "talents",
+            document: Some("talent"),
+            id: "id",
+        })
+    }
+
     /// Delete the talent associated to given id.
-    fn delete(es: &mut Client, id: &str, index: &str) -> Result<DeleteResult, EsError> {
-        es.delete(index, ES_TYPE, id).send()
+    fn delete(
+        es: &mut dyn SearchBackend,
+        id: &TalentId,
+        index: &str,
+    ) -> Result<DeleteResult, EsError> {
+        let result = es.delete(index, ES_TYPE, &id.to_string())?;
+        if result.found {
+            events::publish(Change::deleted(index, vec![result.id.clone()]));
+        }
+
+        Ok(result)
+    }
+
+    /// Mark the talent as `deleted`, which hides it from the searches
+    /// until it's restored or indexed again.
+    fn soft_delete(
+        es: &mut dyn SearchBackend,
+        id: &TalentId,
+        index: &str,
+    ) -> Result<DeleteResult, EsError> {
+        let result = es.bulk_json(index, ES_TYPE, vec![
+            Action::update(id.to_string(), json!({ "deleted": true })),
+        ])?;
+        events::publish(Change::deleted(index, succeeded(&result)));
+        let item = result.items.iter().flat_map(|item| item.values()).next();
+
+        Ok(DeleteResult {
+            index: index.to_owned(),
+            id: id.to_string(),
+            found: item.map_or(false, |item| item.status < 300),
+        })
+    }
+
+    /// Delete the talents associated to given ids.
+    fn delete_many(
+        es: &mut dyn SearchBackend,
+        ids: &[TalentId],
+        index: &str,
+    ) -> Result<BulkResult, EsError> {
+        let actions = ids.iter().map(|id| Action::<JsonValue>::delete(id.to_string())).collect();
+        let result = es.bulk(index, ES_TYPE, actions)?;
+        events::publish(Change::deleted(index, succeeded(&result)));
+
+        Ok(result)
     }
 
     /// Reset the given index. All the data will be destroyed and then the index
-    /// will be created again. The map that will be used is hardcoded.
-    fn reset_index(mut es: &mut Client, index: &str) -> Result<MappingResult, EsError> {
-        let mappings = json!({
+    /// will be created again with the mapping resulting from `Talent::migrations()`,
+    /// using `definition` as baseline instead of the built-in one when given.
+    fn reset_index(
+        es: &mut dyn SearchBackend,
+        index: &str,
+        definition: Option<&IndexDefinition>,
+    ) -> Result<MappingResult, EsError> {
+        if let Err(error) = es.delete_index(index) {
+            error!("{}", error);
+        }
+
+        let result = migrations::create_index::<Talent>(es, index, definition)?;
+        events::publish(Change::reset(index));
+
+        Ok(result)
+    }
+}
+
+impl Hydratable for Talent {
+    type Hydrated = FoundTalent;
+
+    /// Fetch the talents with given ids through a single search, leaving out the ones
+    /// that `GET /talents` hides as well: the talents that weren't accepted, the ones out
+    /// of the batch of the `epoch` of `params` (now by default) and the deleted ones.
+    /// The `presented_talents` of `params` skip the batch, as they do in the searches.
+    fn hydrate(
+        es: &mut dyn SearchBackend,
+        indexes: &IndexSet,
+        ids: &[TalentId],
+        params: &Map,
+    ) -> Result<Vec<FoundTalent>, SearchspotError> {
+        let params = TalentSearchParams::from_map(params).map_err(SearchspotError::BadRequest)?;
+        let epoch = match params.epoch {
+            Some(ref epoch) => epoch.to_owned(),
+            None => Utc::now().to_rfc3339(),
+        };
+
+        let mut filters = <Query as VectorOfTerms<TalentId>>::build_terms("id", &ids.to_vec());
+        filters.extend(Talent::visibility_filters(
+            &*epoch,
+            params.presented_talents.to_owned(),
+            params.epoch.is_some(),
+        ));
+        filters.push(Talent::not_deleted_filter());
+
+        let body = json!({
+            "query":   Query::build_bool().with_must(filters).build(),
+            "size":    ids.len(),
+            "_source": FOUND_TALENT_FIELDS,
+        });
+        let result = es.search_json(&indexes.names(), &body)?.into_typed::<FoundTalentSource>()?;
+
+        let mut found: HashMap<String, FoundTalent> = HashMap::new();
+        for hit in result.hits.hits.into_iter() {
+            if let Some(source) = hit.source {
+                found.entry(hit.id).or_insert_with(|| FoundTalent::from(source));
+            }
+        }
+
+        Ok(ids.iter().filter_map(|id| found.remove(&id.to_string())).collect())
+    }
+}
+
+/// Talents can't be found anymore once their batch is over.
+impl Expirable for Talent {
+    fn expired_query(before: &DateTime<Utc>) -> JsonValue {
+        Query::build_range("batch_ends_at")
+            .with_lt(before.to_rfc3339())
+            .with_format("dateOptionalTime")
+            .build()
+            .into()
+    }
+}
+
+/// Fill `minimum_eur` of the salaries expected in euros, which need no exchange rate.
+fn backfill_salaries_in_eur(document: &mut JsonValue) {
+    let salaries = match document["salary_expectations"].as_array_mut() {
+        Some(salaries) => salaries,
+        None => return,
+    };
+
+    for salary in salaries.iter_mut() {
+        if salary["currency"].as_str().map_or(false, |currency| currency == "EUR") {
+            salary["minimum_eur"] = salary["minimum"].to_owned();
+        }
+    }
+}
+
+impl Migratable for Talent {
+    fn doc_type() -> &'static str {
+        ES_TYPE
+    }
+
+    /// The mapping that the talent index had before the migrations were introduced.
+    /// Changes must be added as a new `Migration` rather than applied here.
+    /// It can be replaced per environment through `es.mapping_file`.
+    fn base_mappings() -> JsonValue {
+        json!({
       ES_TYPE: {
         "properties": {
           "id": {
-            "type":  "integer",
-            "index": "not_analyzed"
+            "type": "integer"
           },
 
           "desired_work_roles": {
-            "type": "multi_field",
+            "type":            "text",
+            "analyzer":        "trigrams",
+            "search_analyzer": "words",
             "fields": {
-                "desired_work_roles": {
-                    "type": "string",
-                    "analyzer":        "trigrams",
-                    "search_analyzer": "words",
-                },
                 "keyword": {
-                    "type": "string",
+                    "type":            "text",
                     "analyzer":        "keywords",
                     "search_analyzer": "keywords",
-                    "boost":           "2.0",
+                    "boost":           "2.0"
                 },
-                "raw": {
-                    "type": "string",
-                    "index": "not_analyzed"
-                }
+                "raw": { "type": "keyword" }
             }
           },
 
           "desired_work_roles_experience": {
-            "type":  "string",
-            "index": "not_analyzed"
+            "type": "keyword"
           },
 
           "desired_roles": {
             "type":  "nested",
             "properties": {
-                "role": { "type": "string", "index": "not_analyzed" },
-                "experience": { "type": "string", "index": "not_analyzed" }
+                "role": { "type": "keyword" },
+                "experience": { "type": "keyword" }
             }
           },
 
           "professional_experience": {
-            "type":  "string",
-            "index": "not_analyzed"
+            "type": "keyword"
           },
 
           "work_locations": {
-            "type":  "string",
-            "index": "not_analyzed"
+            "type": "keyword"
           },
 
           "educations": {
-            "type": "multi_field",
+            "type":            "text",
+            "analyzer":        "trigrams",
+            "search_analyzer": "words",
             "fields": {
-                "educations": {
-                    "type": "string",
-                    "analyzer":        "trigrams",
-                    "search_analyzer": "words",
-                },
                 "keyword": {
-                    "type": "string",
+                    "type":            "text",
                     "analyzer":        "keywords",
                     "search_analyzer": "keywords",
-                    "boost":           "2.0",
+                    "boost":           "2.0"
                 },
-                "raw": {
-                    "type": "string",
-                    "index": "not_analyzed"
-                }
+                "raw": { "type": "keyword" }
             }
           },
 
           "languages": {
-            "type":  "string",
-            "index": "not_analyzed"
+            "type": "keyword"
           },
 
           "current_location": {
-            "type":  "string",
-            "index": "not_analyzed"
+            "type": "keyword"
           },
 
           "work_authorization": {
-            "type":  "string",
-            "index": "not_analyzed"
+            "type": "keyword"
           },
 
           "skills": {
-            "type": "multi_field",
+            "type":            "text",
+            "analyzer":        "trigrams",
+            "search_analyzer": "words",
+            "boost":           "2.0",
             "fields": {
-                "skills": {
-                    "type": "string",
-                    "analyzer":        "trigrams",
-                    "search_analyzer": "words",
-                    "boost":           "2.0",
-                },
                 "keyword": {
-                    "type": "string",
+                    "type":            "text",
                     "analyzer":        "keywords",
                     "search_analyzer": "keywords",
-                    "boost":           "2.0",
+                    "boost":           "2.0"
                 },
-                "raw": {
-                    "type": "string",
-                    "index": "not_analyzed"
-                }
+                "raw": { "type": "keyword" }
             }
           },
 
           "summary": {
-            "type": "multi_field",
+            "type":            "text",
+            "analyzer":        "trigrams",
+            "search_analyzer": "words",
+            "boost":           "2.0",
             "fields": {
-                "summary": {
-                    "type":            "string",
-                    "analyzer":        "trigrams",
-                    "search_analyzer": "words",
-                    "boost":           "2.0",
-                },
                 "keyword": {
-                    "type":            "string",
+                    "type":            "text",
                     "analyzer":        "keywords",
                     "search_analyzer": "keywords",
-                    "boost":           "2.0",
+                    "boost":           "2.0"
                 },
-                "raw": {
-                    "type": "string",
-                    "index": "not_analyzed"
-                }
+                "raw": { "type": "keyword" }
             }
           },
 
           "headline": {
-            "type": "multi_field",
+            "type":            "text",
+            "analyzer":        "trigrams",
+            "search_analyzer": "words",
+            "boost":           "2.0",
             "fields": {
-                "headline": {
-                    "type": "string",
-                    "analyzer":        "trigrams",
-                    "search_analyzer": "words",
-                    "boost":           "2.0",
-                },
                 "keyword": {
-                    "type": "string",
+                    "type":            "text",
                     "analyzer":        "keywords",
                     "search_analyzer": "keywords",
-                    "boost":           "2.0",
+                    "boost":           "2.0"
                 },
-                "raw": {
-                    "type": "string",
-                    "index": "not_analyzed"
-                }
+                "raw": { "type": "keyword" }
             }
           },
 
           "work_experiences": {
-            "type": "multi_field",
+            "type":            "text",
+            "analyzer":        "trigrams",
+            "search_analyzer": "words",
             "fields": {
-                "work_experiences": {
-                    "type": "string",
-                    "analyzer":        "trigrams",
-                    "search_analyzer": "words",
-                },
                 "keyword": {
-                    "type": "string",
+                    "type":            "text",
                     "analyzer":        "keywords",
                     "search_analyzer": "keywords",
-                    "boost":           "2.0",
+                    "boost":           "2.0"
                 },
-                "raw": {
-                    "type": "string",
-                    "index": "not_analyzed"
-                }
+                "raw": { "type": "keyword" }
             }
           },
 
           "contacted_company_ids": {
-            "type":  "integer",
-            "index": "not_analyzed"
+            "type": "integer"
           },
 
           "accepted": {
-            "type":  "boolean",
-            "index": "not_analyzed"
+            "type": "boolean"
           },
 
           "batch_starts_at": {
             "type":   "date",
-            "format": "dateOptionalTime",
-            "index":  "not_analyzed"
+            "format": "date_optional_time"
           },
 
           "batch_ends_at": {
             "type":   "date",
-            "format": "dateOptionalTime",
-            "index":  "not_analyzed"
+            "format": "date_optional_time"
           },
 
           "added_to_batch_at": {
             "type":   "date",
-            "format": "dateOptionalTime",
-            "index":  "not_analyzed"
+            "format": "date_optional_time"
           },
 
           "weight": {
-            "type":  "integer",
-            "index": "not_analyzed"
+            "type": "integer"
           },
 
           "blocked_companies": {
-            "type":  "integer",
-            "index": "not_analyzed"
+            "type": "integer"
           },
 
           "avatar_url": {
-            "type":  "string",
-            "index": "not_analyzed"
+            "type": "keyword"
           },
 
           "salary_expectations": {
             "type":  "nested",
             "properties": {
-                "minimum": { "type": "long", "index": "not_analyzed" },
-                "city": { "type": "string", "index": "not_analyzed" },
-                "currency": { "type": "string", "index": "not_analyzed" }
+                "minimum": { "type": "long" },
+                "minimum_eur": { "type": "long" },
+                "city": { "type": "keyword" },
+                "currency": { "type": "keyword" }
             }
           },
 
           "latest_position": {
-            "type":  "string",
-            "index": "not_analyzed"
+            "type": "keyword"
           }
         }
       }
-    });
+    })
+    }
 
-        let settings = Settings {
+    fn base_settings() -> Settings {
+        Settings {
             number_of_shards: 1,
 
             analysis: Analysis {
@@ -1036,24 +2030,218 @@ impl Resource for Talent {
                     .unwrap()
                     .to_owned(),
             },
-        };
-
-        if let Err(error) = es.delete_index(index) {
-            error!("{}", error);
         }
+    }
 
-        MappingOperation::new(&mut es, index)
-            .with_mappings(&mappings)
-            .with_settings(&settings)
-            .send()
+    fn migrations() -> Vec<Migration> {
+        vec![Migration {
+            version: 1,
+            name: "score_model_version",
+            steps: vec![Step::AddType {
+                name: "score",
+                mapping: json!({
+                  "properties": {
+                    "model_version": {
+                      "type": "keyword"
+                    }
+                  }
+                }),
+            }],
+        }, Migration {
+            version: 2,
+            name: "ascii_folding",
+            steps: vec![
+                Step::DefineAnalyzer {
+                    name: "trigrams",
+                    definition: json!({
+                      "type":      "custom",
+                      "tokenizer": "whitespace",
+                      "filter":    ["lowercase", "asciifolding", "words_splitter",
+                                     "trigrams_filter", "english_words_filter",
+                                     "tech_words_filter"]
+                    }),
+                },
+                Step::DefineAnalyzer {
+                    name: "words",
+                    definition: json!({
+                      "type":      "custom",
+                      "tokenizer": "keyword",
+                      "filter":    ["lowercase", "asciifolding", "words_splitter",
+                                     "english_words_filter", "tech_words_filter"]
+                    }),
+                },
+                Step::DefineAnalyzer {
+                    name: "keywords",
+                    definition: json!({
+                      "type":      "custom",
+                      "tokenizer": "standard",
+                      "filter":    ["lowercase", "asciifolding", "protect_keywords", "trim",
+                                     "english_words_filter", "strip_js"]
+                    }),
+                },
+                Step::DefineAnalyzer {
+                    name: "folded",
+                    definition: json!({
+                      "type":      "custom",
+                      "tokenizer": "keyword",
+                      "filter":    ["lowercase", "asciifolding"]
+                    }),
+                },
+                Step::AddField {
+                    name: "work_locations.folded",
+                    mapping: json!({ "type": "text", "analyzer": "folded" }),
+                },
+                Step::AddField {
+                    name: "current_location.folded",
+                    mapping: json!({ "type": "text", "analyzer": "folded" }),
+                },
+            ],
+        }, Migration {
+            version: 3,
+            name: "german_analyzers",
+            steps: vec![
+                Step::DefineFilter {
+                    name: "german_stop",
+                    definition: json!({
+                      "type":      "stop",
+                      "stopwords": "_german_"
+                    }),
+                },
+                Step::DefineFilter {
+                    name: "german_decompounder",
+                    definition: json!({
+                      "type":               "dictionary_decompounder",
+                      "min_subword_size":   3,
+                      "only_longest_match": true,
+                      "word_list": [
+                          "software", "entwickler", "entwicklerin", "entwicklung",
+                          "ingenieur", "ingenieurin", "architekt", "architektin",
+                          "berater", "beraterin", "leiter", "leiterin", "projekt",
+                          "produkt", "daten", "system", "netzwerk", "anwendung",
+                          "anwendungs", "web", "informatik", "informatiker",
+                          "administrator", "administratorin", "manager", "managerin",
+                          "techniker", "technikerin", "spiele", "test", "tester",
+                          "qualität", "sicherheit", "analyst", "analystin",
+                          "wissenschaftler", "wissenschaftlerin", "team", "werkstudent",
+                          "praktikant", "praktikum"
+                      ]
+                    }),
+                },
+                Step::DefineFilter {
+                    name: "german_stemmer",
+                    definition: json!({
+                      "type":     "stemmer",
+                      "language": "light_german"
+                    }),
+                },
+                Step::DefineAnalyzer {
+                    name: "german",
+                    definition: json!({
+                      "type":      "custom",
+                      "tokenizer": "standard",
+                      "filter":    ["lowercase", "german_decompounder", "german_stop",
+                                     "german_normalization", "german_stemmer"]
+                    }),
+                },
+                Step::AddField {
+                    name: "summary.german",
+                    mapping: json!({ "type": "text", "analyzer": "german" }),
+                },
+                Step::AddField {
+                    name: "headline.german",
+                    mapping: json!({ "type": "text", "analyzer": "german" }),
+                },
+                Step::AddField {
+                    name: "work_experiences.german",
+                    mapping: json!({ "type": "text", "analyzer": "german" }),
+                },
+                Step::AddField {
+                    name: "desired_work_roles.german",
+                    mapping: json!({ "type": "text", "analyzer": "german" }),
+                },
+            ],
+        }, Migration {
+            version: 4,
+            name: "protected_keywords",
+            steps: vec![Step::DefineFilter {
+                name: "protect_keywords",
+                definition: json!({
+                  "type":        "keyword_marker",
+                  "keywords":    keywords::PROTECTED_KEYWORDS,
+                  "ignore_case": true,
+                }),
+            }],
+        }, Migration {
+            version: 5,
+            name: "soft_deletion",
+            steps: vec![Step::AddField {
+                name: "deleted",
+                mapping: json!({ "type": "boolean" }),
+            }],
+        }, Migration {
+            version: 6,
+            name: "string_ids",
+            // replaces the integer mapping, so that UUIDs can be stored as well
+            steps: vec![Step::AddField {
+                name: "id",
+                mapping: json!({ "type": "keyword" }),
+            }],
+        }, Migration {
+            version: 7,
+            name: "salaries_in_eur",
+            steps: vec![
+                Step::AddField {
+                    name: "salary_expectations.minimum_eur",
+                    mapping: json!({ "type": "long" }),
+                },
+                // the other currencies are only converted when the talents are indexed again
+                Step::Backfill { script: backfill_salaries_in_eur },
+            ],
+        }, Migration {
+            version: 8,
+            name: "jobs_and_companies",
+            // the ids must be strings like the ones of the talents, and the filters exact
+            steps: vec![
+                Step::AddType {
+                    name: "job",
+                    mapping: json!({
+                      "properties": {
+                        "id":              { "type": "keyword" },
+                        "title":           { "type": "keyword" },
+                        "required_skills": { "type": "keyword" },
+                        "location":        { "type": "keyword" },
+                        "salary_budget":   { "type": "long" },
+                        "experience":      { "type": "integer" }
+                      }
+                    }),
+                },
+                Step::AddType {
+                    name: "company",
+                    mapping: json!({
+                      "properties": {
+                        "id":           { "type": "keyword" },
+                        "name":         { "type": "text" },
+                        "industry":     { "type": "keyword" },
+                        "locations":    { "type": "keyword" },
+                        "hiring_roles": { "type": "keyword" }
+                      }
+                    }),
+                },
+            ],
+        }]
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::{parse_desired_role_filter, mapped_experience_ranges, DesiredRoleFilter, RolesExperience};
+    use super::backfill_salaries_in_eur;
     use serde_json;
+    use config::Config;
+    use context::{self, RequestContext};
+    use resource::Resource;
     use resources::Talent;
+    use resources::talent::{FoundTalent, SearchResult, TalentView};
 
     #[test]
     fn parsing_desired_roles() {
@@ -1234,4 +2422,122 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_anonymized_serialization() {
+        let talent: FoundTalent = serde_json::from_value(json!({
+            "id":                  1,
+            "headline":            "Rustacean",
+            "avatar_url":          "https://example.com/1.png",
+            "work_locations":      ["Berlin"],
+            "current_location":    "Berlin",
+            "salary_expectations": [],
+            "roles_experiences":   [],
+            "latest_position":     "Developer",
+            "batch_starts_at":     "2018-01-01T00:00:00Z",
+        }))
+        .unwrap();
+        let result = SearchResult {
+            talent: TalentView::Found(talent),
+            highlight: Some(
+                vec![
+                    ("summary.raw".to_owned(), vec!["I <em>love</em> Rust".to_owned()]),
+                    ("skills".to_owned(), vec!["<em>Rust</em>".to_owned()]),
+                ]
+                .into_iter()
+                .collect(),
+            ),
+        };
+
+        let serialized = serde_json::to_value(&result).unwrap();
+        assert_eq!(serialized["talent"]["avatar_url"], "https://example.com/1.png");
+        assert!(serialized["highlight"]["summary.raw"].is_array());
+
+        let _guard = context::enter(RequestContext::default());
+        context::set_anonymized();
+        let serialized = serde_json::to_value(&result).unwrap();
+        let avatar_url = serialized["talent"]["avatar_url"].as_str().unwrap();
+        assert_eq!(avatar_url.len(), 64);
+        assert!(!avatar_url.contains("example.com"));
+        assert!(serialized["highlight"]["summary.raw"].is_null());
+        assert!(serialized["highlight"]["skills"].is_array());
+    }
+
+    #[test]
+    fn test_validate_raw_query() {
+        let query = json!({ "query": { "match_all": {} }, "size": 0, "sort": ["_score"] });
+        assert!(Talent::validate_raw_query(&query).is_ok());
+
+        let top_hits = json!({ "aggs": { "talents": { "top_hits": { "_source": ["summary"] } } } });
+        assert!(Talent::validate_raw_query(&top_hits).is_err());
+        assert!(Talent::validate_raw_query(&json!({ "aggregations": {} })).is_err());
+
+        assert!(Talent::validate_raw_query(&json!({ "script_fields": {} })).is_err());
+        assert!(Talent::validate_raw_query(&json!({ "highlight": {} })).is_err());
+        assert!(Talent::validate_raw_query(&json!([])).is_err());
+    }
+
+    #[test]
+    fn test_normalize() {
+        let mut talent: Talent = serde_json::from_value(json!({
+            "id":                            13,
+            "desired_work_roles":            ["C/C++ Engineer"],
+            "desired_work_roles_experience": ["2..4"],
+            "work_languages":                ["C++"],
+            "professional_experience":       "8+",
+            "work_locations":                ["Köln", "Berlin"],
+            "educations":                    ["CS"],
+            "current_location":              "NYC",
+            "work_authorization":            "yes",
+            "skills":                        ["Rust"],
+            "summary":                       "Blabla",
+            "headline":                      "I see things, I do stuff",
+            "contacted_company_ids":         [1],
+            "accepted":                      true,
+            "batch_starts_at":               "2016-03-04T12:24:00+01:00",
+            "batch_ends_at":                 "2016-04-11T12:24:00+02:00",
+            "added_to_batch_at":             "2016-03-11T12:24:37+01:00",
+            "weight":                        0,
+            "blocked_companies":             [99],
+            "work_experiences":              ["SysAdmin"],
+            "avatar_url":                    "",
+            "salary_expectations": [
+                { "minimum": 40000, "currency": "EUR", "city": "Berlin" },
+                { "minimum": 50000, "currency": "USD", "city": "New York" },
+                { "minimum": 50000, "currency": "CHF", "city": "Zurich" },
+            ],
+            "latest_position":               "Developer",
+            "languages":                     ["English"],
+        }))
+        .unwrap();
+
+        let mut config = Config::from_file("examples/tests.toml".to_owned());
+        config.locations.aliases.insert("Köln".to_owned(), "Cologne".to_owned());
+        config.locations.aliases.insert("NYC".to_owned(), "New York".to_owned());
+        config.currencies.rates.insert("USD".to_owned(), 0.92);
+        talent.normalize(&config);
+
+        assert_eq!(talent.work_locations, vec!["Cologne", "Berlin"]);
+        assert_eq!(talent.current_location, "New York");
+        let minimums: Vec<Option<u64>> = talent
+            .salary_expectations
+            .iter()
+            .map(|salary| salary.minimum_eur)
+            .collect();
+        assert_eq!(minimums, vec![Some(40000), Some(46000), None]);
+    }
+
+    #[test]
+    fn test_backfill_salaries_in_eur() {
+        let mut document = json!({
+            "salary_expectations": [
+                { "minimum": 40000, "currency": "EUR", "city": "Berlin" },
+                { "minimum": 50000, "currency": "USD", "city": "New York" },
+            ]
+        });
+
+        backfill_salaries_in_eur(&mut document);
+        assert_eq!(document["salary_expectations"][0]["minimum_eur"], json!(40000));
+        assert!(document["salary_expectations"][1].get("minimum_eur").is_none());
+    }
 }
@@ -0,0 +1,153 @@
+use params::{FromValue, Value};
+use serde::de::{self, Deserialize, Deserializer};
+use serde_json::Value as JsonValue;
+
+use resource::ResourceId;
+
+use std::fmt;
+use std::str::FromStr;
+
+/// The id of a talent: a number, or the UUID given by the new platform.
+/// Numeric ids are (de)serialized as numbers, the UUIDs as lowercase strings.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(untagged)]
+pub enum TalentId {
+    Numeric(u32),
+    Uuid(String),
+}
+
+impl ResourceId for TalentId {}
+
+/// Tell whether `id` is written like a UUID (i.e. `0b6c3bb4-5c2a-4f5a-9a3e-3fdc1c9d4e21`).
+fn is_uuid(id: &str) -> bool {
+    id.len() == 36
+        && id.char_indices().all(|(position, character)| match position {
+            8 | 13 | 18 | 23 => character == '-',
+            _ => character.is_ascii_hexdigit(),
+        })
+}
+
+impl FromStr for TalentId {
+    type Err = String;
+
+    fn from_str(id: &str) -> Result<TalentId, String> {
+        let id = id.trim();
+
+        if let Ok(id) = id.parse::<u32>() {
+            Ok(TalentId::Numeric(id))
+        } else if is_uuid(id) {
+            Ok(TalentId::Uuid(id.to_lowercase()))
+        } else {
+            Err(format!("Invalid talent id: {}", id))
+        }
+    }
+}
+
+impl fmt::Display for TalentId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TalentId::Numeric(id) => write!(f, "{}", id),
+            TalentId::Uuid(ref id) => write!(f, "{}", id),
+        }
+    }
+}
+
+impl From<u32> for TalentId {
+    fn from(id: u32) -> TalentId {
+        TalentId::Numeric(id)
+    }
+}
+
+impl From<TalentId> for JsonValue {
+    fn from(id: TalentId) -> JsonValue {
+        match id {
+            TalentId::Numeric(id) => JsonValue::from(id),
+            TalentId::Uuid(id) => JsonValue::String(id),
+        }
+    }
+}
+
+impl PartialEq<u32> for TalentId {
+    fn eq(&self, other: &u32) -> bool {
+        *self == TalentId::Numeric(*other)
+    }
+}
+
+impl PartialEq<TalentId> for u32 {
+    fn eq(&self, other: &TalentId) -> bool {
+        other == self
+    }
+}
+
+/// Numeric ids given as strings (i.e. `"42"`) are read as numbers.
+impl<'de> Deserialize<'de> for TalentId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<TalentId, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Numeric(u32),
+            Text(String),
+        }
+
+        match Raw::deserialize(deserializer)? {
+            Raw::Numeric(id) => Ok(TalentId::Numeric(id)),
+            Raw::Text(id) => id.parse().map_err(de::Error::custom),
+        }
+    }
+}
+
+impl FromValue for TalentId {
+    fn from_value(value: &Value) -> Option<TalentId> {
+        match *value {
+            Value::U64(id) if id <= u32::max_value() as u64 => Some(TalentId::Numeric(id as u32)),
+            Value::I64(id) if id >= 0 && id <= u32::max_value() as i64 => {
+                Some(TalentId::Numeric(id as u32))
+            }
+            Value::String(ref id) => id.parse().ok(),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use params::{FromValue, Value};
+    use serde_json;
+
+    use resources::TalentId;
+
+    const UUID: &'static str = "0b6c3bb4-5c2a-4f5a-9a3e-3fdc1c9d4e21";
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("42".parse::<TalentId>(), Ok(TalentId::Numeric(42)));
+        assert_eq!(
+            UUID.to_uppercase().parse::<TalentId>(),
+            Ok(TalentId::Uuid(UUID.to_owned()))
+        );
+        assert!("42x".parse::<TalentId>().is_err());
+        assert!("0b6c3bb4-5c2a-4f5a-9a3e".parse::<TalentId>().is_err());
+
+        assert_eq!(TalentId::from_value(&Value::U64(7)), Some(TalentId::Numeric(7)));
+        assert_eq!(
+            TalentId::from_value(&Value::String(UUID.to_owned())),
+            Some(TalentId::Uuid(UUID.to_owned()))
+        );
+    }
+
+    #[test]
+    fn test_json() {
+        let ids: Vec<TalentId> = serde_json::from_value(json!([1, "2", UUID])).unwrap();
+        assert_eq!(
+            ids,
+            vec![
+                TalentId::Numeric(1),
+                TalentId::Numeric(2),
+                TalentId::Uuid(UUID.to_owned()),
+            ]
+        );
+        assert_eq!(serde_json::to_value(&ids).unwrap(), json!([1, 2, UUID]));
+
+        assert!(serde_json::from_value::<TalentId>(json!("nope")).is_err());
+    }
+}
@@ -0,0 +1,543 @@
+use params::{FromValue, Map, Value};
+
+use backends::dsl;
+use config::{Limits, Ranking};
+use features::Feature;
+use keywords;
+use query::Operator;
+use resources::TalentId;
+
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+
+/// The `sort` of the talents searched without keywords, unless `search.sort`
+/// is configured: the latest batch first, then the weight and the most recently
+/// added to the batch.
+pub const DEFAULT_SORT: &'static [&'static str] =
+    &["batch_starts_at:desc", "weight:desc", "added_to_batch_at:desc"];
+
+/// How the talents are sorted by the lowest of their minimum salaries
+/// (`sort=salary_asc|salary_desc`) instead of by relevance or by batch.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SalarySort {
+    /// The most budget-friendly talents first.
+    Ascending,
+    Descending,
+}
+
+impl SalarySort {
+    pub fn order(&self) -> &'static str {
+        match *self {
+            SalarySort::Ascending => "asc",
+            SalarySort::Descending => "desc",
+        }
+    }
+}
+
+/// A field the talents are sorted by, written as `field:asc` or `field:desc`
+/// (see `search.sort` and `sort_by[]`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct SortField {
+    pub field: String,
+    pub descending: bool,
+}
+
+impl SortField {
+    /// Return the fields of `DEFAULT_SORT`.
+    pub fn defaults() -> Vec<SortField> {
+        DEFAULT_SORT.iter().map(|sort| sort.parse().unwrap()).collect()
+    }
+
+    pub fn order(&self) -> &'static str {
+        if self.descending {
+            "desc"
+        } else {
+            "asc"
+        }
+    }
+}
+
+impl FromStr for SortField {
+    type Err = String;
+
+    fn from_str(sort: &str) -> Result<SortField, String> {
+        let invalid = || format!("{} is neither field:asc nor field:desc", sort);
+        let mut parts = sort.splitn(2, ':').map(|part| part.trim());
+        let field = parts.next().filter(|field| !field.is_empty()).ok_or_else(invalid)?;
+        let descending = match parts.next().map(|order| order.to_lowercase()) {
+            Some(ref order) if order == "asc" => false,
+            Some(ref order) if order == "desc" => true,
+            _ => return Err(invalid()),
+        };
+
+        Ok(SortField {
+            field: field.to_owned(),
+            descending: descending,
+        })
+    }
+}
+
+impl fmt::Display for SortField {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.field, self.order())
+    }
+}
+
+/// The parameters of a talent search, read once from the query string
+/// instead of looking them up in the `Map` wherever they're needed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TalentSearchParams {
+    /// The indexes to search (comma-separated, wildcards allowed).
+    pub index: Option<String>,
+    /// `None` when no keywords (or only an empty string) are given.
+    /// They're sanitized unless `features[]=raw_query_syntax` is given.
+    pub keywords: Option<String>,
+    /// How the words of the `keywords` are combined when no operator joins
+    /// them (`keywords_operator=and|or`), as ElasticSearch does when not given.
+    pub keywords_operator: Option<Operator>,
+    pub offset: u64,
+    pub per_page: u64,
+    pub debug_es_query: bool,
+    /// Whether the talents matching the keywords but scoring too low to be
+    /// returned are counted (`track_min_score=true`).
+    pub track_min_score: bool,
+    /// Whether every matching talent is counted (`track_total_hits=false` stops
+    /// at `TOTAL_HITS_CAP`, see `es.track_total_hits`).
+    pub track_total_hits: bool,
+    /// How many milliseconds ElasticSearch is given to search (`timeout_ms`, or
+    /// what's left until the `X-Request-Deadline`) before returning what it found.
+    pub timeout_ms: Option<u64>,
+    /// Whether the complete documents are asked (`view=full`)
+    /// instead of the `FoundTalent`s.
+    pub full_view: bool,
+    /// The moment in which the batches are searched, now if not given.
+    pub epoch: Option<String>,
+    pub company_id: Option<i32>,
+    pub presented_talents: Vec<TalentId>,
+    pub features: HashSet<Feature>,
+    /// The experiment that enabled some of the `features` (see `experiments`).
+    pub experiment: Option<String>,
+    pub languages: Vec<String>,
+    pub professional_experience: Vec<String>,
+    pub work_authorization: Vec<String>,
+    pub work_locations: Vec<String>,
+    pub current_location: Vec<String>,
+    pub desired_work_roles: Vec<String>,
+    pub maximum_salary: Option<u64>,
+    pub salary_sort: Option<SalarySort>,
+    /// How the talents searched without keywords are sorted (`sort_by[]`,
+    /// see `search.sort`), `DEFAULT_SORT` if not given.
+    pub sort_by: Vec<SortField>,
+    /// How the `weight_decay` feature ranks the talents (`weight_factor`
+    /// and `recency_scale`, see `config::Ranking`).
+    pub ranking: Ranking,
+    pub bookmarked_talents: Vec<TalentId>,
+    pub contacted_talents: Vec<TalentId>,
+    pub ignored_talents: Vec<TalentId>,
+}
+
+impl Default for TalentSearchParams {
+    fn default() -> TalentSearchParams {
+        TalentSearchParams {
+            index: None,
+            keywords: None,
+            keywords_operator: None,
+            offset: 0,
+            per_page: 10,
+            debug_es_query: false,
+            track_min_score: false,
+            track_total_hits: true,
+            timeout_ms: None,
+            full_view: false,
+            epoch: None,
+            company_id: None,
+            presented_talents: vec![],
+            features: HashSet::new(),
+            experiment: None,
+            languages: vec![],
+            professional_experience: vec![],
+            work_authorization: vec![],
+            work_locations: vec![],
+            current_location: vec![],
+            desired_work_roles: vec![],
+            maximum_salary: None,
+            salary_sort: None,
+            sort_by: SortField::defaults(),
+            ranking: Ranking::default(),
+            bookmarked_talents: vec![],
+            contacted_talents: vec![],
+            ignored_talents: vec![],
+        }
+    }
+}
+
+/// The words of the keywords counted by `Limits::max_boolean_operators`.
+const BOOLEAN_OPERATORS: &'static [&'static str] = &["AND", "OR", "NOT", "&&", "||"];
+
+/// Return the value of `name`, considering empty strings as missing.
+fn present<'a>(params: &'a Map, name: &str) -> Option<&'a Value> {
+    match params.get(name) {
+        Some(&Value::String(ref value)) if value.is_empty() => None,
+        value => value,
+    }
+}
+
+fn string(params: &Map, name: &str) -> Option<String> {
+    match present(params, name) {
+        Some(&Value::String(ref value)) => Some(value.to_owned()),
+        _ => None,
+    }
+}
+
+fn unsigned(params: &Map, name: &str) -> Result<Option<u64>, String> {
+    match present(params, name) {
+        None => Ok(None),
+        Some(&Value::U64(value)) => Ok(Some(value)),
+        Some(&Value::I64(value)) if value >= 0 => Ok(Some(value as u64)),
+        Some(&Value::String(ref value)) if value.parse::<u64>().is_ok() => Ok(value.parse().ok()),
+        Some(_) => Err(format!("{} must be a non-negative integer", name)),
+    }
+}
+
+fn number(params: &Map, name: &str) -> Result<Option<f64>, String> {
+    let number = match present(params, name) {
+        None => return Ok(None),
+        Some(&Value::F64(value)) => Some(value),
+        Some(&Value::U64(value)) => Some(value as f64),
+        Some(&Value::I64(value)) => Some(value as f64),
+        Some(&Value::String(ref value)) => value.parse::<f64>().ok(),
+        Some(_) => None,
+    };
+
+    match number {
+        Some(number) if number.is_finite() && number >= 0.0 => Ok(Some(number)),
+        _ => Err(format!("{} must be a non-negative number", name)),
+    }
+}
+
+impl TalentSearchParams {
+    /// Read and validate the parameters of a search from `params`,
+    /// falling back to the defaults for the missing ones.
+    ///
+    /// Lists of values are lenient as they've always been: the values that
+    /// can't be read are skipped. The numbers, instead, must be valid.
+    pub fn from_map(params: &Map) -> Result<TalentSearchParams, String> {
+        let company_id = match present(params, "company_id") {
+            None => None,
+            Some(value) => match i32::from_value(value) {
+                Some(company_id) => Some(company_id),
+                None => return Err("company_id must be an integer".to_owned()),
+            },
+        };
+
+        let features: Vec<String> = vec_from_params!(params, "features");
+        let features = features
+            .iter()
+            .map(|feature| feature.parse())
+            .collect::<Result<HashSet<Feature>, String>>()?;
+
+        let keywords = match string(params, "keywords") {
+            Some(ref keywords) if !features.contains(&Feature::RawQuerySyntax) => {
+                Some(keywords::sanitize(keywords)).filter(|keywords| !keywords.is_empty())
+            }
+            keywords => keywords,
+        };
+
+        let keywords_operator = match string(params, "keywords_operator") {
+            None => None,
+            Some(operator) => match &*operator.to_lowercase() {
+                "and" => Some(Operator::And),
+                "or" => Some(Operator::Or),
+                _ => return Err("keywords_operator must be either and or or".to_owned()),
+            },
+        };
+
+        let salary_sort = match string(params, "sort") {
+            None => None,
+            Some(sort) => match &*sort {
+                "salary_asc" => Some(SalarySort::Ascending),
+                "salary_desc" => Some(SalarySort::Descending),
+                _ => return Err("sort must be either salary_asc or salary_desc".to_owned()),
+            },
+        };
+
+        let sort_by: Vec<String> = vec_from_params!(params, "sort_by");
+        let sort_by = if sort_by.is_empty() {
+            SortField::defaults()
+        } else {
+            sort_by
+                .iter()
+                .map(|sort| sort.parse())
+                .collect::<Result<Vec<SortField>, String>>()
+                .map_err(|_| "sort_by must list field:asc or field:desc".to_owned())?
+        };
+
+        let default_ranking = Ranking::default();
+        let recency_scale = match string(params, "recency_scale") {
+            None => default_ranking.recency_scale,
+            Some(ref scale) if dsl::duration_millis(scale).is_none() => {
+                return Err("recency_scale must be a duration (i.e. 7d)".to_owned())
+            }
+            Some(scale) => scale,
+        };
+        let ranking = Ranking {
+            weight_factor: number(params, "weight_factor")?
+                .unwrap_or(default_ranking.weight_factor),
+            recency_scale: recency_scale,
+        };
+
+        Ok(TalentSearchParams {
+            index: string(params, "index"),
+            keywords: keywords,
+            keywords_operator: keywords_operator,
+            offset: unsigned(params, "offset")?.unwrap_or(0),
+            per_page: unsigned(params, "per_page")?.unwrap_or(10),
+            debug_es_query: string(params, "debug_es_query").map_or(false, |value| value == "true"),
+            track_min_score: string(params, "track_min_score").map_or(false, |value| value == "true"),
+            track_total_hits: string(params, "track_total_hits")
+                .map_or(true, |value| value != "false"),
+            timeout_ms: unsigned(params, "timeout_ms")?,
+            full_view: string(params, "view").map_or(false, |view| view == "full"),
+            epoch: string(params, "epoch"),
+            company_id: company_id,
+            presented_talents: type_vec_from_params!(TalentId, params, "presented_talents"),
+            features: features,
+            experiment: string(params, "experiment"),
+            languages: vec_from_params!(params, "languages"),
+            professional_experience: vec_from_params!(params, "professional_experience"),
+            work_authorization: vec_from_params!(params, "work_authorization"),
+            work_locations: vec_from_params!(params, "work_locations"),
+            current_location: vec_from_params!(params, "current_location"),
+            desired_work_roles: vec_from_params!(params, "desired_work_roles"),
+            maximum_salary: unsigned(params, "maximum_salary")?,
+            salary_sort: salary_sort,
+            sort_by: sort_by,
+            ranking: ranking,
+            bookmarked_talents: vec_from_maybe_csv_params!(params, "bookmarked_talents"),
+            contacted_talents: vec_from_maybe_csv_params!(params, "contacted_talents"),
+            ignored_talents: vec_from_maybe_csv_params!(params, "ignored_talents"),
+        })
+    }
+
+    /// Return whether the search `feature` (`features[]=...`) is enabled.
+    pub fn has_feature(&self, feature: Feature) -> bool {
+        self.features.contains(&feature)
+    }
+
+    /// Check that the search doesn't exceed the given `limits`.
+    pub fn check_limits(&self, limits: &Limits) -> Result<(), String> {
+        if let Some(ref keywords) = self.keywords {
+            if keywords.chars().count() > limits.max_keywords_length {
+                return Err(format!(
+                    "keywords can't be longer than {} characters",
+                    limits.max_keywords_length
+                ));
+            }
+
+            let operators = keywords
+                .split_whitespace()
+                .filter(|word| BOOLEAN_OPERATORS.contains(word))
+                .count();
+            if operators > limits.max_boolean_operators {
+                return Err(format!(
+                    "keywords can't contain more than {} boolean operators",
+                    limits.max_boolean_operators
+                ));
+            }
+        }
+
+        let filters = vec![
+            ("languages", self.languages.len()),
+            ("professional_experience", self.professional_experience.len()),
+            ("work_authorization", self.work_authorization.len()),
+            ("work_locations", self.work_locations.len()),
+            ("current_location", self.current_location.len()),
+            ("desired_work_roles", self.desired_work_roles.len()),
+            ("presented_talents", self.presented_talents.len()),
+            ("bookmarked_talents", self.bookmarked_talents.len()),
+            ("contacted_talents", self.contacted_talents.len()),
+            ("ignored_talents", self.ignored_talents.len()),
+        ];
+
+        match filters.into_iter().find(|&(_, values)| values > limits.max_filter_values) {
+            Some((name, _)) => Err(format!(
+                "{} can't list more than {} values",
+                name, limits.max_filter_values
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Return the ids of the companies whose contacts exclude talents.
+    pub fn company_ids(&self) -> Vec<i32> {
+        self.company_id.into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use config::Limits;
+    use features::Feature;
+    use query::Operator;
+    use resources::{SalarySort, SortField, TalentSearchParams};
+
+    use params::{Map, Value};
+
+    fn params(pairs: &[(&str, &str)]) -> Map {
+        let mut params = Map::new();
+        for &(name, value) in pairs {
+            params.assign(name, Value::String(value.to_owned())).unwrap();
+        }
+        params
+    }
+
+    #[test]
+    fn test_defaults() {
+        let search = TalentSearchParams::from_map(&Map::new()).unwrap();
+        assert_eq!(search, TalentSearchParams::default());
+        assert_eq!(search.offset, 0);
+        assert_eq!(search.per_page, 10);
+        assert!(search.keywords.is_none());
+        assert!(!search.full_view);
+
+        // empty values are missing values
+        let search = TalentSearchParams::from_map(&params(&[
+            ("keywords", ""),
+            ("company_id", ""),
+            ("offset", ""),
+        ]))
+        .unwrap();
+        assert_eq!(search, TalentSearchParams::default());
+    }
+
+    #[test]
+    fn test_from_map() {
+        let mut map = params(&[
+            ("keywords", "Rust"),
+            ("per_page", "5"),
+            ("view", "full"),
+            ("debug_es_query", "true"),
+            ("track_min_score", "true"),
+            ("track_total_hits", "false"),
+            ("company_id", "7"),
+            ("presented_talents", "3"),
+            ("maximum_salary", "50000"),
+            ("work_locations[]", "Berlin"),
+            ("work_locations[]", "Amsterdam"),
+            ("features[]", "keywords_should"),
+            ("contacted_talents", "1, 2,x"),
+            ("keywords_operator", "AND"),
+            ("timeout_ms", "250"),
+            ("sort", "salary_asc"),
+            ("weight_factor", "0.25"),
+            ("recency_scale", "3d"),
+            ("sort_by[]", "weight:desc"),
+            ("sort_by[]", "id:asc"),
+        ]);
+        map.assign("offset", Value::U64(20)).unwrap();
+
+        let search = TalentSearchParams::from_map(&map).unwrap();
+        assert_eq!(search.keywords, Some("Rust".to_owned()));
+        assert_eq!(search.offset, 20);
+        assert_eq!(search.per_page, 5);
+        assert!(search.full_view);
+        assert!(search.debug_es_query);
+        assert!(search.track_min_score);
+        assert!(!search.track_total_hits);
+        assert_eq!(search.company_ids(), vec![7]);
+        assert_eq!(search.presented_talents, vec![3]);
+        assert_eq!(search.maximum_salary, Some(50000));
+        assert_eq!(search.work_locations, vec!["Berlin", "Amsterdam"]);
+        assert!(search.has_feature(Feature::KeywordsShould));
+        assert!(!search.has_feature(Feature::NoFulltextSearch));
+        assert_eq!(search.contacted_talents, vec![1, 2]);
+        assert_eq!(search.keywords_operator, Some(Operator::And));
+        assert_eq!(search.timeout_ms, Some(250));
+        assert_eq!(search.salary_sort, Some(SalarySort::Ascending));
+        assert_eq!(search.ranking.weight_factor, 0.25);
+        assert_eq!(search.ranking.recency_scale, "3d");
+        let sort_by: Vec<String> = search.sort_by.iter().map(|sort| sort.to_string()).collect();
+        assert_eq!(sort_by, vec!["weight:desc", "id:asc"]);
+    }
+
+    #[test]
+    fn test_sort_field() {
+        let sort: SortField = " id : ASC ".parse().unwrap();
+        assert_eq!(sort.field, "id");
+        assert!(!sort.descending);
+        assert_eq!(SortField::defaults()[0].to_string(), "batch_starts_at:desc");
+        assert!("weight".parse::<SortField>().is_err());
+        assert!("weight:up".parse::<SortField>().is_err());
+        assert!(":desc".parse::<SortField>().is_err());
+
+        let error = TalentSearchParams::from_map(&params(&[("sort_by[]", "weight")]));
+        assert_eq!(error.unwrap_err(), "sort_by must list field:asc or field:desc");
+    }
+
+    #[test]
+    fn test_keywords_sanitization() {
+        let search = TalentSearchParams::from_map(&params(&[("keywords", "\"C++")])).unwrap();
+        assert_eq!(search.keywords, Some("C\\+\\+".to_owned()));
+
+        let search = TalentSearchParams::from_map(&params(&[("keywords", "\"")])).unwrap();
+        assert_eq!(search.keywords, None);
+
+        let search = TalentSearchParams::from_map(&params(&[
+            ("keywords", "/jav.*/"),
+            ("features[]", "raw_query_syntax"),
+        ]))
+        .unwrap();
+        assert_eq!(search.keywords, Some("/jav.*/".to_owned()));
+    }
+
+    #[test]
+    fn test_validation() {
+        let error = TalentSearchParams::from_map(&params(&[("per_page", "ten")])).unwrap_err();
+        assert_eq!(error, "per_page must be a non-negative integer");
+
+        assert!(TalentSearchParams::from_map(&params(&[("offset", "-1")])).is_err());
+        assert!(TalentSearchParams::from_map(&params(&[("maximum_salary", "a lot")])).is_err());
+        assert!(TalentSearchParams::from_map(&params(&[("company_id", "honeypot")])).is_err());
+        assert!(TalentSearchParams::from_map(&params(&[("keywords_operator", "xor")])).is_err());
+        assert!(TalentSearchParams::from_map(&params(&[("timeout_ms", "soon")])).is_err());
+        assert!(TalentSearchParams::from_map(&params(&[("sort", "salary")])).is_err());
+        assert!(TalentSearchParams::from_map(&params(&[("weight_factor", "-1")])).is_err());
+        assert!(TalentSearchParams::from_map(&params(&[("recency_scale", "soon")])).is_err());
+
+        let error =
+            TalentSearchParams::from_map(&params(&[("features[]", "everything")])).unwrap_err();
+        assert_eq!(error, "Unknown feature: everything");
+    }
+
+    #[test]
+    fn test_check_limits() {
+        let limits = Limits {
+            max_keywords_length: 20,
+            max_boolean_operators: 1,
+            max_filter_values: 2,
+        };
+        let check = |pairs: &[(&str, &str)]| {
+            TalentSearchParams::from_map(&params(pairs))
+                .unwrap()
+                .check_limits(&limits)
+        };
+
+        assert!(check(&[("keywords", "rust AND go"), ("work_locations[]", "Berlin")]).is_ok());
+        assert_eq!(
+            check(&[("keywords", "rust AND go OR java")]).unwrap_err(),
+            "keywords can't contain more than 1 boolean operators"
+        );
+        assert!(check(&[("keywords", "a very long list of skills")]).is_err());
+        assert_eq!(
+            check(&[
+                ("work_locations[]", "Berlin"),
+                ("work_locations[]", "Munich"),
+                ("work_locations[]", "Hamburg"),
+            ])
+            .unwrap_err(),
+            "work_locations can't list more than 2 values"
+        );
+        assert!(check(&[("ignored_talents", "1,2,3")]).is_err());
+    }
+}
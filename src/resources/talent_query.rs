@@ -0,0 +1,296 @@
+use serde_json::Value as JsonValue;
+
+use features::Feature;
+use query::Query;
+use resources::talent::{Talent, FOUND_TALENT_FIELDS, KEYWORDS_MIN_SCORE, TOTAL_HITS_CAP};
+use resources::TalentSearchParams;
+use telemetry;
+
+/// The body of the search of the talents matching some `TalentSearchParams`,
+/// as sent to ElasticSearch (see `TalentQueryBuilder`).
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct TalentQuery {
+    pub query: JsonValue,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub highlight: Option<JsonValue>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sort: Option<JsonValue>,
+    pub from: u64,
+    pub size: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_score: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub track_scores: Option<bool>,
+    /// Only sent to cap the count of the hits. ElasticSearch 5.6, which rejects the key,
+    /// always counts every hit: only the embedded backends honour it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub track_total_hits: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<String>,
+    #[serde(rename = "_source", skip_serializing_if = "Option::is_none")]
+    pub source: Option<Vec<&'static str>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub post_filter: Option<JsonValue>,
+    /// Keeps a single hit per talent, where it ranks best, when more indexes are searched.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub collapse: Option<JsonValue>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aggs: Option<JsonValue>,
+}
+
+/// Build the `TalentQuery` of `params`: the filters, the highlight of the keywords,
+/// the sort, the pagination and the aggregations counting the excluded talents.
+/// The talents are searched as of `epoch`, which hides the ones of the batches
+/// that aren't running then.
+pub struct TalentQueryBuilder<'a> {
+    params: &'a TalentSearchParams,
+    epoch: String,
+    many_indexes: bool,
+}
+
+impl<'a> TalentQueryBuilder<'a> {
+    pub fn new(params: &'a TalentSearchParams, epoch: &str) -> TalentQueryBuilder<'a> {
+        TalentQueryBuilder {
+            params: params,
+            epoch: epoch.to_owned(),
+            many_indexes: false,
+        }
+    }
+
+    /// Count the talents found in each index, and the distinct ones across them,
+    /// when the search targets more indexes (see `Talent::hits_per_index`),
+    /// whose hits are collapsed by talent.
+    pub fn with_many_indexes(&mut self, many_indexes: bool) -> &mut TalentQueryBuilder<'a> {
+        self.many_indexes = many_indexes;
+        self
+    }
+
+    /// Return the filters of the candidates, without the exclusions.
+    pub fn filters(&self) -> Query {
+        telemetry::in_span("talent.build_query", vec![], || {
+            Talent::candidate_filters(self.params, &*self.epoch).build()
+        })
+    }
+
+    /// Return the `query`, which `Feature::WeightDecay` scores by recency
+    /// when there are no keywords.
+    pub fn query(&self, filters: &Query) -> JsonValue {
+        let params = self.params;
+        if params.keywords.is_none() && params.has_feature(Feature::WeightDecay) {
+            Talent::weight_decay(filters, &params.ranking, &*self.epoch)
+        } else {
+            json!(filters)
+        }
+    }
+
+    /// Return the `highlight` of the keywords, if any.
+    pub fn highlight(&self) -> Option<JsonValue> {
+        self.params.keywords.as_ref().map(|keywords| Talent::highlight(keywords))
+    }
+
+    /// Return the `sort`. The keywords sort by relevance, while the salaries,
+    /// when asked for, come first and keep the usual order among the talents
+    /// expecting the same.
+    pub fn sort(&self) -> Option<JsonValue> {
+        let params = self.params;
+        let sort = match params.keywords {
+            Some(_) => None,
+            None if params.has_feature(Feature::WeightDecay) => {
+                Some(Talent::decayed_sorting_criteria())
+            }
+            None => Some(Talent::sorting_criteria(&params.sort_by)),
+        };
+
+        match params.salary_sort {
+            Some(salary_sort) => {
+                let then = sort.unwrap_or(json!("_score"));
+                Some(Talent::salary_sorting_criteria(salary_sort, then))
+            }
+            None => sort,
+        }
+    }
+
+    /// Return the `post_filter` hiding the excluded talents (see `Talent::exclusion_filters`).
+    pub fn post_filter(&self) -> Option<JsonValue> {
+        let exclusions = Talent::exclusion_filters(self.params);
+        if exclusions.is_empty() {
+            return None;
+        }
+
+        let excluded: Vec<Query> = exclusions
+            .into_iter()
+            .flat_map(|(_, queries)| queries)
+            .collect();
+        Some(json!(Query::build_bool().with_must_not(excluded).build()))
+    }
+
+    /// Return the aggregations counting the excluded talents, by the `SearchResults`
+    /// field that reports them, and the `hits_per_index`.
+    pub fn aggregations(&self) -> Option<JsonValue> {
+        // the excluded talents are filtered out after the aggregations
+        // counting them, which see every talent matching the search
+        let mut aggs = json!({});
+        for (name, queries) in Talent::exclusion_filters(self.params).into_iter() {
+            let filter = Query::build_bool().with_should(queries).build();
+            aggs[name] = json!({ "filter": filter });
+        }
+
+        if self.many_indexes {
+            let visible = self.post_filter().unwrap_or(json!({ "match_all": {} }));
+            aggs["hits_per_index"] = json!({
+                "filter": visible,
+                "aggs":   {
+                    "indexes": { "terms": { "field": "_index", "size": 100 } },
+                    "talents": { "cardinality": { "field": "id" } }
+                }
+            });
+        }
+
+        if aggs.as_object().map_or(true, |aggs| aggs.is_empty()) {
+            None
+        } else {
+            Some(aggs)
+        }
+    }
+
+    pub fn build(&self) -> TalentQuery {
+        let params = self.params;
+        let keywords = params.keywords.is_some();
+
+        TalentQuery {
+            query: self.query(&self.filters()),
+            highlight: self.highlight(),
+            sort: self.sort(),
+            from: params.offset,
+            size: params.per_page,
+            min_score: if keywords { Some(KEYWORDS_MIN_SCORE) } else { None },
+            track_scores: if keywords { Some(true) } else { None },
+            track_total_hits: if params.track_total_hits { None } else { Some(TOTAL_HITS_CAP) },
+            timeout: params.timeout_ms.map(|timeout_ms| format!("{}ms", timeout_ms)),
+            // the documents are trimmed to `FoundTalent`s anyway
+            source: if params.full_view { None } else { Some(FOUND_TALENT_FIELDS.to_vec()) },
+            post_filter: self.post_filter(),
+            collapse: if self.many_indexes { Some(json!({ "field": "id" })) } else { None },
+            aggs: self.aggregations(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use resources::{TalentQueryBuilder, TalentSearchParams};
+
+    use params::{Map, Value};
+    use serde_json::{self, Value as JsonValue};
+
+    const EPOCH: &'static str = "2018-01-01T00:00:00Z";
+
+    fn params(pairs: &[(&str, &str)]) -> TalentSearchParams {
+        let mut params = Map::new();
+        for &(name, value) in pairs {
+            params.assign(name, Value::String(value.to_owned())).unwrap();
+        }
+        TalentSearchParams::from_map(&params).unwrap()
+    }
+
+    fn body(params: &TalentSearchParams) -> JsonValue {
+        serde_json::to_value(TalentQueryBuilder::new(params, EPOCH).build()).unwrap()
+    }
+
+    #[test]
+    fn test_default_query() {
+        let body = body(&params(&[]));
+        assert_eq!(body["from"], 0);
+        assert_eq!(body["size"], 10);
+        assert_eq!(
+            body["sort"],
+            json!([
+                { "batch_starts_at":   { "order": "desc", "unmapped_type": "date" } },
+                { "weight":            { "order": "desc", "unmapped_type": "integer" } },
+                { "added_to_batch_at": { "order": "desc", "unmapped_type": "date" } },
+            ])
+        );
+        assert!(body["query"]["bool"].is_object());
+        assert!(body["_source"].as_array().unwrap().contains(&json!("headline")));
+
+        // the exact count is the default, so `track_total_hits` isn't sent
+        let fields: Vec<&String> = body.as_object().unwrap().keys().collect();
+        assert_eq!(fields, vec!["_source", "from", "query", "size", "sort"]);
+    }
+
+    #[test]
+    fn test_keywords_query() {
+        let body = body(&params(&[("keywords", "rust"), ("offset", "20"), ("per_page", "5")]));
+        assert_eq!(body["from"], 20);
+        assert_eq!(body["size"], 5);
+        assert_eq!(body["min_score"], 0.56);
+        assert_eq!(body["track_scores"], true);
+        assert_eq!(body["highlight"]["encoder"], "html");
+        assert!(body["highlight"]["fields"]["skills"].is_object());
+        assert!(body.get("sort").is_none());
+
+        let params = params(&[("keywords", "rust"), ("sort", "salary_asc")]);
+        assert_eq!(
+            TalentQueryBuilder::new(&params, EPOCH).sort(),
+            Some(json!([
+                {
+                    "salary_expectations.minimum": {
+                        "order":       "asc",
+                        "mode":        "min",
+                        "nested_path": "salary_expectations",
+                    }
+                },
+                "_score",
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_weight_decay_query() {
+        let body = body(&params(&[("features[]", "weight_decay")]));
+        assert_eq!(body["query"]["function_score"]["boost_mode"], "replace");
+        assert_eq!(
+            body["query"]["function_score"]["functions"][0]["gauss"]["added_to_batch_at"]["origin"],
+            EPOCH
+        );
+        assert_eq!(
+            body["sort"],
+            json!([
+                { "batch_starts_at": { "order": "desc", "unmapped_type": "date" } },
+                { "_score":          { "order": "desc" } },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_exclusions() {
+        let params = params(&[
+            ("company_id", "6"),
+            ("contacted_talents", "1,2"),
+            ("track_total_hits", "false"),
+            ("timeout_ms", "250"),
+            ("view", "full"),
+        ]);
+        let body = body(&params);
+        assert_eq!(body["track_total_hits"], 10000);
+        assert_eq!(body["timeout"], "250ms");
+        assert!(body.get("_source").is_none());
+
+        let excluded = &body["post_filter"]["bool"]["must_not"];
+        assert_eq!(excluded.as_array().unwrap().len(), 3);
+        assert!(body["aggs"]["excluded_by_company"]["filter"]["bool"]["should"].is_array());
+        assert!(body["aggs"]["excluded_by_contacted"]["filter"].is_object());
+        assert!(body["aggs"].get("excluded_by_ignored").is_none());
+        assert!(body["aggs"].get("hits_per_index").is_none());
+        assert!(body.get("collapse").is_none());
+
+        let query = TalentQueryBuilder::new(&params, EPOCH).with_many_indexes(true).build();
+        assert_eq!(query.collapse, Some(json!({ "field": "id" })));
+        let aggs = query.aggs.unwrap();
+        assert_eq!(aggs["hits_per_index"]["filter"], body["post_filter"]);
+        assert_eq!(
+            aggs["hits_per_index"]["aggs"]["talents"],
+            json!({ "cardinality": { "field": "id" } })
+        );
+    }
+}
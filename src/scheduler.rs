@@ -0,0 +1,16 @@
+//! Runs the periodic tasks (i.e. the cleanup of the expired documents,
+//! the heartbeat and the alerts) in background threads.
+
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Run `task` every `interval`, starting after the first `interval`.
+pub fn every<F: FnMut() + Send + 'static>(name: &str, interval: Duration, mut task: F) -> JoinHandle<()> {
+    thread::Builder::new()
+        .name(name.to_owned())
+        .spawn(move || loop {
+            thread::sleep(interval);
+            task();
+        })
+        .unwrap()
+}
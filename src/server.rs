@@ -1,74 +1,704 @@
+use serde::ser::Serialize;
 use serde_json;
 
-use rs_es::Client;
+use chrono::Utc;
 
 use iron;
 use iron::headers;
 use iron::method::Method::{Delete, Get, Post, Put};
-use iron::middleware::AfterMiddleware;
-use iron::mime::Mime;
+use iron::middleware::{AfterMiddleware, BeforeMiddleware};
+use iron::mime::{Mime, SubLevel, TopLevel};
 use iron::prelude::*;
+use iron::response::WriteBody;
 use iron::typemap::Key;
 use iron::{status, Handler, Headers};
 use unicase::UniCase;
 
-use persistent::Write;
-
-use http_logger::Logger as HTTPLogger;
+use persistent::{State, Write};
 
 use router::Router;
 
-use params::Params;
+use params::{Map, Params, Value};
 
 use oath::{totp_raw_now, HashType};
 
+use reqwest::Url;
+
 use config::Auth as AuthConfig;
 use config::Config;
-
+use config::HttpAdmin;
+use config::{Ranking, Search};
+use config::HTTP as HttpConfig;
+use concurrency::{EndpointClass, Permit};
+use context::{self, RequestContext};
+use error::SearchspotError;
+use events::{self, Change};
+use experiments;
+use features;
+
+use backends::{self, Refresh, SearchBackend};
+use banner::Banner;
+use batches;
 use logger::start_logging;
-use resource::Resource;
-
-use std::collections::HashMap;
-use std::io::Read;
+use maintenance::{Maintenance, MaintenanceMiddleware, MaintenanceState};
+use metrics;
+use jsonapi::{self, Collection};
+use migrations::{self, Migratable, Switch};
+use openapi;
+use locations;
+use preprocessing;
+use presented_talents;
+use recommendations;
+use request_log::RequestLogger;
+use resource::{self, EsError, Hydratable, IndexSet, MappingResult, Resource, ResourceId};
+use resources::{Job, Score, ScoreIndexingResults, ScoreSearchBuilder, Talent, TalentId,
+                TalentSearchParams};
+use telemetry;
+
+use std::cmp;
+use std::convert::TryFrom;
+use std::num::IntErrorKind;
+use std::collections::HashSet;
+use std::io::{self, Read};
 use std::marker::PhantomData;
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock};
+use std::time::{Duration, Instant};
 
 #[derive(Copy, Clone)]
 pub struct SharedClient;
 
 impl Key for SharedClient {
-    type Value = Client;
+    type Value = Box<dyn SearchBackend>;
+}
+
+/// The version of the API served by a handler.
+///
+/// `V2` wraps the errors in `{ "error": { "status": ..., "message": ... } }`
+/// and the search results in `{ "data": ..., "meta": ... }`, where `meta`
+/// contains the pagination.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ApiVersion {
+    V1,
+    V2,
+}
+
+impl ApiVersion {
+    pub fn error_body(&self, status: status::Status, message: String) -> String {
+        self.error_value(status, message).to_string()
+    }
+
+    fn error_value(&self, status: status::Status, message: String) -> serde_json::Value {
+        match *self {
+            ApiVersion::V1 => json!({ "error": message }),
+            ApiVersion::V2 => json!({
+                "error": {
+                    "status":  status.to_u16(),
+                    "message": message,
+                }
+            }),
+        }
+    }
+
+    fn error_response(&self, status: status::Status, message: String) -> Response {
+        let content_type = "application/json".parse::<Mime>().unwrap();
+        Response::with((content_type, status, self.error_body(status, message)))
+    }
+
+    /// Like `error_response`, listing the `records` of the payload that caused the error.
+    fn records_error_response(
+        &self,
+        status: status::Status,
+        message: String,
+        records: Vec<serde_json::Value>,
+    ) -> Response {
+        let mut body = self.error_value(status, message);
+        match *self {
+            ApiVersion::V1 => body["records"] = json!(records),
+            ApiVersion::V2 => body["error"]["records"] = json!(records),
+        }
+
+        let content_type = "application/json".parse::<Mime>().unwrap();
+        Response::with((content_type, status, body.to_string()))
+    }
+
+    /// Answer `error` with its own status, listing the conflicting records of a
+    /// `MappingConflict`. `Unauthorized` keeps having no body.
+    fn failure_response(&self, error: SearchspotError) -> Response {
+        let status = status::Status::from_u16(error.status());
+        match error {
+            SearchspotError::Unauthorized => Response::with(status),
+            SearchspotError::MappingConflict(ref conflicts) => {
+                let records = conflicts.iter().map(|conflict| json!(conflict)).collect();
+                self.records_error_response(status, error.to_string(), records)
+            }
+            _ => self.error_response(status, error.to_string()),
+        }
+    }
+
+    /// Serialize `data`, which `V2` wraps in `{ "data": ... }`.
+    pub fn data_body<T: Serialize>(&self, data: &T) -> Result<String, serde_json::Error> {
+        match *self {
+            ApiVersion::V1 => serde_json::to_string(data),
+            ApiVersion::V2 => serde_json::to_string(&json!({ "data": data })),
+        }
+    }
+
+    /// Serialize the `results` of a search made with `params`.
+    pub fn search_body<T: Serialize>(
+        &self,
+        results: &T,
+        params: &Map,
+    ) -> Result<String, serde_json::Error> {
+        if *self == ApiVersion::V1 {
+            return serde_json::to_string(results);
+        }
+
+        let data = serde_json::to_value(results)?;
+        let offset = param_u64(params, "offset", 0);
+        let per_page = param_u64(params, "per_page", 10);
+        let total = data.get("total").and_then(serde_json::Value::as_u64);
+        let next_offset = match (total, offset.checked_add(per_page)) {
+            (Some(total), Some(next_offset)) if next_offset < total => Some(next_offset),
+            _ => None,
+        };
+
+        serde_json::to_string(&json!({
+            "data": data,
+            "meta": {
+                "offset":      offset,
+                "per_page":    per_page,
+                "total":       total,
+                "next_offset": next_offset,
+            }
+        }))
+    }
+}
+
+/// A search whose results are asked as a JSON:API document (see `jsonapi`).
+struct JsonApiRequest {
+    path: String,
+    query: Option<String>,
+}
+
+impl JsonApiRequest {
+    fn from_request(req: &Request) -> Option<JsonApiRequest> {
+        if !jsonapi::is_requested(&req.headers) {
+            return None;
+        }
+
+        Some(JsonApiRequest {
+            path: format!("/{}", req.url.path().join("/")),
+            query: req.url.query().map(str::to_owned),
+        })
+    }
+
+    fn response<T: Serialize>(
+        &self,
+        collection: &Collection,
+        results: &T,
+        params: &Map,
+    ) -> Result<Response, serde_json::Error> {
+        let document = jsonapi::document(
+            collection,
+            results,
+            &self.path,
+            self.query.as_ref().map(|query| &**query),
+            param_u64(params, "offset", 0),
+            param_u64(params, "per_page", 10),
+        )?;
+
+        let content_type = jsonapi::MEDIA_TYPE.parse::<Mime>().unwrap();
+        Ok(Response::with((content_type, status::Ok, document.to_string())))
+    }
+}
+
+fn param_u64(params: &Map, name: &str, default: u64) -> u64 {
+    match params.get(name) {
+        Some(&Value::String(ref value)) => value.parse().unwrap_or(default),
+        Some(&Value::U64(value)) => value,
+        _ => default,
+    }
+}
+
+/// Read the `interval` of the buckets of a histogram, `default` when it isn't given.
+/// The ones that aren't positive numbers are refused, as `resource::check_histogram` does.
+fn param_interval(params: &Map, default: f64) -> Result<f64, SearchspotError> {
+    let interval = match params.get("interval") {
+        Some(&Value::String(ref interval)) => interval.parse::<f64>().unwrap_or(0.0),
+        Some(&Value::U64(interval)) => interval as f64,
+        Some(&Value::F64(interval)) => interval,
+        None => default,
+        _ => 0.0,
+    };
+    resource::check_histogram(interval, None, None)?;
+
+    Ok(interval)
+}
+
+/// Check that the page of `size` results starting at `from` (named `names` in the request,
+/// i.e. `offset + per_page`) stays within the result window of ElasticSearch, which refuses
+/// to page past it and would silently end up in empty results.
+fn check_window(config: &Config, from: u64, size: u64, names: &str) -> Result<(), SearchspotError> {
+    let end = from
+        .checked_add(size)
+        .ok_or_else(|| SearchspotError::Unprocessable(format!("{} is too large", names)))?;
+
+    if end > config.es.max_result_window {
+        return Err(SearchspotError::BadRequest(format!(
+            "{} can't exceed {}: use cursor pagination to go deeper",
+            names, config.es.max_result_window
+        )));
+    }
+
+    Ok(())
+}
+
+/// Return the last value of the param `name` of `query` (empty when it has none).
+/// The query strings of the writes are read as they are, since their bodies aren't params.
+fn query_value<'a>(query: Option<&'a str>, name: &str) -> Option<&'a str> {
+    query
+        .into_iter()
+        .flat_map(|query| query.split('&'))
+        .filter_map(|pair| {
+            let mut pair = pair.splitn(2, '=');
+            match (pair.next(), pair.next()) {
+                (Some(param), value) if param == name => Some(value.unwrap_or("")),
+                _ => None,
+            }
+        })
+        .last()
+}
+
+/// Return when the documents written by a request become searchable, as told by
+/// the `refresh` param of its query string (`refresh` alone meaning `true`).
+fn refresh_param(query: Option<&str>) -> Result<Refresh, String> {
+    query_value(query, "refresh").map_or(Ok(Refresh::default()), str::parse)
+}
+
+/// Tell whether an indexing must fail on the ids taken already, as asked through
+/// `op_type=create`, instead of replacing their documents (`op_type=index`, the default).
+fn is_create_only(query: Option<&str>) -> Result<bool, String> {
+    match query_value(query, "op_type") {
+        None | Some("index") => Ok(false),
+        Some("create") => Ok(true),
+        Some(op_type) => Err(format!("Invalid op_type: {} (index or create)", op_type)),
+    }
+}
+
+/// Tell whether the scores must be matched by their job and talent instead of their
+/// request ids, as asked through `upsert=true` (`upsert` alone meaning `true`).
+fn is_upsert(query: Option<&str>) -> Result<bool, String> {
+    match query_value(query, "upsert") {
+        None | Some("false") => Ok(false),
+        Some("") | Some("true") => Ok(true),
+        Some(upsert) => Err(format!("Invalid upsert: {} (true or false)", upsert)),
+    }
+}
+
+/// The locked client of a write, which follows the refresh policy of the request
+/// until it's dropped, so that the policy doesn't apply to the following requests.
+struct RefreshingClient<'a> {
+    client: MutexGuard<'a, Box<dyn SearchBackend>>,
+}
+
+impl<'a> RefreshingClient<'a> {
+    fn new(mut client: MutexGuard<'a, Box<dyn SearchBackend>>, refresh: Refresh) -> Self {
+        client.set_refresh(refresh);
+        RefreshingClient { client: client }
+    }
+}
+
+impl<'a> Deref for RefreshingClient<'a> {
+    type Target = Box<dyn SearchBackend>;
+
+    fn deref(&self) -> &Box<dyn SearchBackend> {
+        &self.client
+    }
+}
+
+impl<'a> DerefMut for RefreshingClient<'a> {
+    fn deref_mut(&mut self) -> &mut Box<dyn SearchBackend> {
+        &mut self.client
+    }
+}
+
+impl<'a> Drop for RefreshingClient<'a> {
+    fn drop(&mut self) {
+        self.client.set_refresh(Refresh::default());
+    }
+}
+
+/// The shared client, locked for one request to the search backend at a time
+/// rather than for a whole operation, so that a long one (i.e. a migration,
+/// which copies the documents a page at a time) doesn't hold up the others.
+struct LockingClient {
+    client: Arc<Mutex<Box<dyn SearchBackend>>>,
+}
+
+impl LockingClient {
+    fn new(client: Arc<Mutex<Box<dyn SearchBackend>>>) -> Self {
+        LockingClient { client: client }
+    }
+
+    fn lock(&self) -> MutexGuard<Box<dyn SearchBackend>> {
+        self.client.lock().unwrap()
+    }
+}
+
+impl SearchBackend for LockingClient {
+    fn search_json(
+        &mut self,
+        indexes: &[&str],
+        body: &serde_json::Value,
+    ) -> Result<resource::SearchResult<serde_json::Value>, EsError> {
+        self.lock().search_json(indexes, body)
+    }
+
+    fn bulk_json(
+        &mut self,
+        index: &str,
+        doc_type: &str,
+        actions: Vec<resource::Action<serde_json::Value>>,
+    ) -> Result<resource::BulkResult, EsError> {
+        self.lock().bulk_json(index, doc_type, actions)
+    }
+
+    fn mget_json(
+        &mut self,
+        index: &str,
+        doc_type: &str,
+        ids: &[String],
+    ) -> Result<resource::MgetResult<serde_json::Value>, EsError> {
+        self.lock().mget_json(index, doc_type, ids)
+    }
+
+    fn delete(
+        &mut self,
+        index: &str,
+        doc_type: &str,
+        id: &str,
+    ) -> Result<resource::DeleteResult, EsError> {
+        self.lock().delete(index, doc_type, id)
+    }
+
+    fn delete_by_query(
+        &mut self,
+        indexes: &[&str],
+        query: &serde_json::Value,
+    ) -> Result<resource::DeleteByQueryResult, EsError> {
+        self.lock().delete_by_query(indexes, query)
+    }
+
+    fn create_index(
+        &mut self,
+        index: &str,
+        mappings: &serde_json::Value,
+        settings: &resource::Settings,
+    ) -> Result<MappingResult, EsError> {
+        self.lock().create_index(index, mappings, settings)
+    }
+
+    fn delete_index(&mut self, index: &str) -> Result<MappingResult, EsError> {
+        self.lock().delete_index(index)
+    }
+
+    fn refresh(&mut self, indexes: &[&str]) -> Result<(), EsError> {
+        self.lock().refresh(indexes)
+    }
+
+    fn scroll_json(
+        &mut self,
+        indexes: &[&str],
+        body: &serde_json::Value,
+        keep_alive: &str,
+    ) -> Result<resource::SearchResult<serde_json::Value>, EsError> {
+        self.lock().scroll_json(indexes, body, keep_alive)
+    }
+
+    fn next_scroll_json(
+        &mut self,
+        scroll_id: &str,
+        keep_alive: &str,
+    ) -> Result<resource::SearchResult<serde_json::Value>, EsError> {
+        self.lock().next_scroll_json(scroll_id, keep_alive)
+    }
+
+    fn clear_scroll(&mut self, scroll_id: &str) -> Result<(), EsError> {
+        self.lock().clear_scroll(scroll_id)
+    }
+
+    fn engine_version(&mut self) -> Result<String, EsError> {
+        self.lock().engine_version()
+    }
+
+    fn index_health(&mut self, index: &str) -> Result<String, EsError> {
+        self.lock().index_health(index)
+    }
+
+    fn switch_alias(&mut self, alias: &str, from: &str, to: &str) -> Result<(), EsError> {
+        self.lock().switch_alias(alias, from, to)
+    }
+
+    fn alias_targets(&mut self, alias: &str) -> Result<Vec<String>, EsError> {
+        self.lock().alias_targets(alias)
+    }
+
+    fn replace_with_alias(&mut self, index: &str, to: &str) -> Result<(), EsError> {
+        self.lock().replace_with_alias(index, to)
+    }
+}
+
+/// The header through which the callers tell by when (in milliseconds since
+/// the Unix epoch) they need the response, so that the search doesn't outlive them.
+const DEADLINE_HEADER: &'static str = "X-Request-Deadline";
+
+/// Give the search the time left until the `X-Request-Deadline` (if any) as its
+/// `timeout_ms`, unless one is given already. A deadline that passed already
+/// leaves 1 millisecond, in which ElasticSearch returns what it found so far.
+/// Deadlines that aren't numbers are ignored, while the ones too far from now
+/// to be counted in milliseconds are rejected.
+fn propagate_deadline(params: &mut Map, headers: &Headers, now: i64) -> Result<(), SearchspotError> {
+    if params.get("timeout_ms").is_some() {
+        return Ok(());
+    }
+
+    let deadline = headers
+        .get_raw(DEADLINE_HEADER)
+        .and_then(|values| values.first())
+        .and_then(|value| String::from_utf8(value.to_owned()).ok());
+    let out_of_range =
+        || SearchspotError::Unprocessable(format!("{} is out of range", DEADLINE_HEADER));
+
+    let deadline = match deadline.map(|deadline| deadline.trim().parse::<i64>()) {
+        Some(Ok(deadline)) => deadline,
+        Some(Err(ref error))
+            if *error.kind() == IntErrorKind::PosOverflow
+                || *error.kind() == IntErrorKind::NegOverflow =>
+        {
+            return Err(out_of_range())
+        }
+        _ => return Ok(()),
+    };
+
+    let left = deadline.checked_sub(now).ok_or_else(out_of_range)?;
+    params.insert("timeout_ms".to_owned(), Value::String(cmp::max(left, 1).to_string()));
+    Ok(())
+}
+
+/// Check the `params` of a search of `R` and complete them as configured: the signed
+/// `presented_talents`, the searchable indexes, the result window and the limits of `R`.
+/// Both `GET /talents` and the gRPC `Search` go through it, so that they're validated alike.
+pub fn search_params<R: Resource>(config: &Config, params: &Map) -> Result<Map, SearchspotError> {
+    if let Some(ref secret) = config.auth.presented_talents_secret {
+        presented_talents::verify(secret, params).map_err(SearchspotError::Forbidden)?;
+    }
+
+    if let Some(&Value::String(ref index)) = params.get("index") {
+        config.es.check_searchable(index)?;
+    }
+
+    check_window(
+        config,
+        param_u64(params, "offset", 0),
+        param_u64(params, "per_page", 10),
+        "offset + per_page",
+    )?;
+
+    let params = &preprocessing::apply(&config.preprocessing, params);
+    let params = &locations::apply(&config.locations, params);
+    R::validate_search(params, &config.limits)?;
+
+    let mut params = experiments::apply(&config.experiments, params);
+    if !config.es.track_total_hits && params.get("track_total_hits").is_none() {
+        params.insert("track_total_hits".to_owned(), Value::String("false".to_owned()));
+    }
+    propagate_ranking(&mut params, &config.ranking);
+    propagate_sort(&mut params, &config.search);
+    Ok(params)
+}
+
+/// Give the search the configured `ranking` of the `weight_decay` feature, unless it
+/// sets its own `weight_factor` and `recency_scale`. Only the settings other than the
+/// defaults are given, since the searches fall back to those anyway.
+fn propagate_ranking(params: &mut Map, ranking: &Ranking) {
+    let default = Ranking::default();
+    if ranking.weight_factor != default.weight_factor && params.get("weight_factor").is_none() {
+        let weight_factor = Value::String(ranking.weight_factor.to_string());
+        params.insert("weight_factor".to_owned(), weight_factor);
+    }
+    if ranking.recency_scale != default.recency_scale && params.get("recency_scale").is_none() {
+        let recency_scale = Value::String(ranking.recency_scale.to_owned());
+        params.insert("recency_scale".to_owned(), recency_scale);
+    }
+}
+
+/// Give the search the configured `sort` of the talents, unless it sets its own
+/// `sort_by[]` or the configuration keeps the default one.
+fn propagate_sort(params: &mut Map, search: &Search) {
+    if *search != Search::default() && params.get("sort_by").is_none() {
+        let sort_by = search.sort.iter().map(|sort| Value::String(sort.to_owned())).collect();
+        params.insert("sort_by".to_owned(), Value::Array(sort_by));
+    }
 }
 
 macro_rules! try_or_422 {
-    ($expr:expr) => {
+    ($version:expr, $expr:expr) => {
         match $expr {
             Ok(val) => val,
             Err(err) => {
                 let error_message = err.to_string();
                 error!("{}", error_message);
 
-                let mut error = HashMap::new();
-                error.insert("error", error_message);
+                return Ok($version.error_response(status::UnprocessableEntity, error_message));
+            }
+        }
+    };
+    ($expr:expr) => {
+        try_or_422!(ApiVersion::V1, $expr)
+    };
+}
 
-                let content_type = "application/json".parse::<Mime>().unwrap();
-                return Ok(Response::with((
-                    content_type,
-                    status::UnprocessableEntity,
-                    serde_json::to_string(&error).unwrap(),
-                )));
+/// Like `try_or_422`, answering the errors of the search backend and of the resources
+/// with the status of their `SearchspotError` (a missing index is `404`, and so on).
+macro_rules! try_or_fail {
+    ($version:expr, $expr:expr) => {
+        match $expr {
+            Ok(val) => val,
+            Err(err) => {
+                let error = SearchspotError::from(err);
+                error!("{}", error);
+
+                return Ok($version.failure_response(error));
+            }
+        }
+    };
+}
+
+macro_rules! bad_request {
+    ($version:expr, $message:expr) => {{
+        return Ok($version.failure_response(SearchspotError::BadRequest($message)));
+    }};
+}
+
+/// The formats the bodies of the requests can be written in.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum PayloadFormat {
+    Json,
+    /// Only accepted by the bulk indexing routes, when built with `--features msgpack`.
+    Msgpack,
+}
+
+impl PayloadFormat {
+    /// Tell the format of a body through its `Content-Type`, considering
+    /// JSON the bodies without any. `None` means that it's unsupported.
+    pub fn from_headers(headers: &Headers) -> Option<PayloadFormat> {
+        match headers.get::<headers::ContentType>() {
+            None => Some(PayloadFormat::Json),
+            Some(&headers::ContentType(Mime(TopLevel::Application, SubLevel::Json, _))) => {
+                Some(PayloadFormat::Json)
+            }
+            #[cfg(feature = "msgpack")]
+            Some(&headers::ContentType(Mime(TopLevel::Application, SubLevel::Ext(ref ext), _)))
+                if ext == "msgpack" || ext == "x-msgpack" =>
+            {
+                Some(PayloadFormat::Msgpack)
+            }
+            _ => None,
+        }
+    }
+}
+
+macro_rules! unsupported_media_type {
+    ($version:expr, $req:expr) => {{
+        let content_type = $req.headers.get::<headers::ContentType>().unwrap();
+        let message = format!("Unsupported Content-Type: {}", content_type);
+        return Ok($version.error_response(status::UnsupportedMediaType, message));
+    }};
+}
+
+/// Read the JSON body of `$req`, answering `415 Unsupported Media Type`
+/// when it's sent with any other `Content-Type`.
+macro_rules! read_json_payload {
+    ($version:expr, $req:expr) => {{
+        if PayloadFormat::from_headers(&$req.headers) != Some(PayloadFormat::Json) {
+            unsupported_media_type!($version, $req);
+        }
+
+        let mut payload = String::new();
+        try_or_422!($version, $req.body.read_to_string(&mut payload));
+        context::set_payload(&payload);
+        payload
+    }};
+}
+
+/// Answer `503 Service Unavailable` when the maintenance mode rejects the endpoints
+/// of `$class` or when the requests of `$class` being served already reached their
+/// limit, returning the permit to serve this one otherwise.
+macro_rules! limit_concurrency {
+    ($version:expr, $config:expr, $req:expr, $class:expr) => {{
+        if let Ok(maintenance) = $req.get::<State<MaintenanceState>>() {
+            let maintenance = maintenance.read().unwrap();
+            if maintenance.rejects_class($class) {
+                return Ok(Response::with(maintenance.rejection()));
+            }
+        }
+
+        match $class.try_acquire(&$config.concurrency) {
+            Some(permit) => permit,
+            None => {
+                let message = format!("Too many concurrent {} requests", $class.name());
+                warn!("{}", message);
+
+                let mut response = $version.error_response(status::ServiceUnavailable, message);
+                response.headers.set_raw("Retry-After", vec![b"1".to_vec()]);
+                return Ok(response);
             }
         }
+    }};
+}
+
+/// Read the `refresh` param of a write (`true`, `wait_for` or `false`), answering
+/// `400 Bad Request` when it's anything else.
+macro_rules! refresh_policy {
+    ($version:expr, $req:expr) => {
+        match refresh_param($req.url.query()) {
+            Ok(refresh) => refresh,
+            Err(message) => bad_request!($version, message),
+        }
     };
 }
 
 macro_rules! unauthorized {
     () => {{
-        return Ok(Response::with(status::Unauthorized));
+        return Ok(ApiVersion::V1.failure_response(SearchspotError::Unauthorized));
     }};
 }
 
+/// Return the token given in the `Authorization` header (i.e. `token 123456`).
+fn request_token(headers: &Headers) -> Option<u64> {
+    let header = headers.get_raw("Authorization")?;
+    let header = String::from_utf8(header[0].to_owned()).ok()?;
+    header.split("token ").last()?.parse::<u64>().ok()
+}
+
+/// Tell whether `token` is the current TOTP of `secret`.
+pub fn is_valid_token(secret: &str, token: u64, token_lifetime: u64) -> bool {
+    totp_raw_now(secret.as_bytes(), 6, 0, token_lifetime, &HashType::SHA1) == token
+}
+
+/// The secret whose tokens grant reads with anonymized responses.
+fn analytics_secret(auth_config: &AuthConfig) -> Option<&String> {
+    auth_config.analytics.as_ref()
+}
+
+fn no_secret(_: &AuthConfig) -> Option<&String> {
+    None
+}
+
 macro_rules! authorization {
-    ($trait_name:ident, $mode:ident) => {
+    ($trait_name:ident, $mode:ident, $anonymized_secret:ident) => {
         trait $trait_name {
             fn is_authorized(
                 &self,
@@ -76,208 +706,2025 @@ macro_rules! authorization {
                 headers: &Headers,
                 token_lifetime: u64,
             ) -> bool {
+                if auth_config.anonymized {
+                    context::set_anonymized();
+                }
+
                 if auth_config.enabled == false {
                     return true;
                 }
 
-                match headers.get_raw("Authorization") {
-                    Some(header) => match String::from_utf8(header[0].to_owned()) {
-                        Ok(header) => match header.split("token ").collect::<Vec<&str>>().last() {
-                            Some(token) => match token.parse::<u64>() {
-                                Ok(token) => {
-                                    totp_raw_now(
-                                        auth_config.$mode.as_bytes(),
-                                        6,
-                                        0,
-                                        token_lifetime as u64,
-                                        &HashType::SHA1,
-                                    ) == token
-                                }
-                                Err(_) => false,
-                            },
-                            None => false,
-                        },
-                        Err(_) => false,
-                    },
-                    None => false,
+                let token = match request_token(headers) {
+                    Some(token) => token,
+                    None => return false,
+                };
+                if is_valid_token(&auth_config.$mode, token, token_lifetime) {
+                    return true;
+                }
+
+                match $anonymized_secret(auth_config) {
+                    Some(secret) if is_valid_token(secret, token, token_lifetime) => {
+                        context::set_anonymized();
+                        true
+                    }
+                    _ => false,
                 }
             }
         }
     };
 }
 
-authorization!(ReadableEndpoint, read);
-authorization!(WritableEndpoint, write);
+authorization!(ReadableEndpoint, read, analytics_secret);
+authorization!(WritableEndpoint, write, no_secret);
+
+/// Tell whether a search asks for the complete documents through `view=full`.
+fn is_full_view(query: Option<&str>) -> bool {
+    query_value(query, "view") == Some("full")
+}
+
+/// Tell whether a search of the talents is authorized: the read (and analytics) tokens
+/// grant the usual results, while the complete documents (`full_view`) are meant for
+/// internal tools only, so they're granted by the write tokens alone and never anonymized.
+fn is_search_authorized<E>(endpoint: &E, config: &Config, headers: &Headers, full_view: bool) -> bool
+where
+    E: ReadableEndpoint + WritableEndpoint,
+{
+    let ref lifetimes = config.tokens.lifetime;
+    if !full_view {
+        return ReadableEndpoint::is_authorized(endpoint, &config.auth, headers, lifetimes.read);
+    }
+
+    !config.auth.anonymized
+        && WritableEndpoint::is_authorized(endpoint, &config.auth, headers, lifetimes.write)
+}
+
+/// Refuse the searches asking for `view=full` elsewhere than in the query string
+/// (i.e. in a JSON body), which `is_search_authorized` didn't authorize as such.
+macro_rules! check_full_view {
+    ($params:expr, $full_view:expr) => {
+        if !$full_view && $params.get("view") == Some(&Value::String("full".to_owned())) {
+            unauthorized!();
+        }
+    };
+}
 
 pub struct Server {
     config: Config,
+    maintenance: Arc<RwLock<Maintenance>>,
+}
+
+pub struct SearchableHandler<R> {
+    config: Config,
+    version: ApiVersion,
+    resource: PhantomData<R>,
+}
+
+impl<R: Resource> SearchableHandler<R> {
+    pub fn new(config: Config) -> Self {
+        SearchableHandler::<R> {
+            resource: PhantomData,
+            config: config,
+            version: ApiVersion::V1,
+        }
+    }
+
+    pub fn with_version(mut self, version: ApiVersion) -> Self {
+        self.version = version;
+        self
+    }
+}
+
+impl<R: Resource> ReadableEndpoint for SearchableHandler<R> {}
+impl<R: Resource> WritableEndpoint for SearchableHandler<R> {}
+
+impl<R: Resource> Handler for SearchableHandler<R> {
+    fn handle(&self, req: &mut Request) -> IronResult<Response> {
+        let _span = telemetry::request_span(req);
+        let _request = context::enter(RequestContext::new(req, &self.config.es.index));
+        let full_view = is_full_view(req.url.query());
+        if !is_search_authorized(self, &self.config, &req.headers, full_view) {
+            unauthorized!();
+        }
+
+        let _permit = limit_concurrency!(self.version, self.config, req, EndpointClass::Search);
+
+        let json_api = JsonApiRequest::from_request(req);
+        let client = req.get::<Write<SharedClient>>().unwrap();
+        let params = try_or_422!(self.version, req.get_ref::<Params>());
+        context::set_params(params);
+        check_full_view!(params, full_view);
+
+        let mut params = try_or_fail!(self.version, search_params::<R>(&self.config, params));
+        let now = Utc::now().timestamp_millis();
+        try_or_fail!(self.version, propagate_deadline(&mut params, &req.headers, now));
+
+        let params = &params;
+        let indexes = IndexSet::from_config(&self.config.es);
+        let response = try_or_fail!(
+            self.version,
+            R::search_in(&mut client.lock().unwrap(), &indexes, params)
+        );
+
+        if let (Some(json_api), Some(collection)) = (json_api, R::json_api()) {
+            return Ok(try_or_422!(self.version, json_api.response(&collection, &response, params)));
+        }
+
+        let content_type = "application/json".parse::<Mime>().unwrap();
+        Ok(Response::with((
+            content_type,
+            status::Ok,
+            try_or_422!(self.version, self.version.search_body(&response, params)),
+        )))
+    }
+}
+
+pub struct IndexableHandler<R> {
+    config: Config,
+    version: ApiVersion,
+    resource: PhantomData<R>,
+}
+
+impl<R: Resource> IndexableHandler<R> {
+    pub fn new(config: Config) -> Self {
+        IndexableHandler::<R> {
+            resource: PhantomData,
+            config: config,
+            version: ApiVersion::V1,
+        }
+    }
+
+    pub fn with_version(mut self, version: ApiVersion) -> Self {
+        self.version = version;
+        self
+    }
+}
+
+impl<R: Resource> WritableEndpoint for IndexableHandler<R> {}
+
+impl<R: Resource> Handler for IndexableHandler<R> {
+    fn handle(&self, req: &mut Request) -> IronResult<Response> {
+        let _span = telemetry::request_span(req);
+        let _request = context::enter(RequestContext::new(req, &self.config.es.index));
+        let ref lifetimes = self.config.tokens.lifetime;
+        if !self.is_authorized(&self.config.auth, &req.headers, lifetimes.write) {
+            unauthorized!();
+        }
+
+        let _permit = limit_concurrency!(self.version, self.config, req, EndpointClass::Index);
+        let refresh = refresh_policy!(self.version, req);
+        let create_only = match is_create_only(req.url.query()) {
+            Ok(create_only) => create_only,
+            Err(message) => bad_request!(self.version, message),
+        };
+
+        let documents: Vec<serde_json::Value> = match PayloadFormat::from_headers(&req.headers) {
+            Some(PayloadFormat::Json) => {
+                let payload = read_json_payload!(self.version, req);
+                try_or_422!(self.version, serde_json::from_str(&payload))
+            }
+            #[cfg(feature = "msgpack")]
+            Some(PayloadFormat::Msgpack) => {
+                let mut payload = vec![];
+                try_or_422!(self.version, req.body.read_to_end(&mut payload));
+                let documents: Vec<serde_json::Value> =
+                    try_or_422!(self.version, ::rmp_serde::from_slice(&payload));
+                context::set_payload(&serde_json::to_string(&documents).unwrap_or_default());
+                documents
+            }
+            _ => unsupported_media_type!(self.version, req),
+        };
+        let mut resources: Vec<R> = match resource::from_documents(&documents) {
+            Ok(resources) => resources,
+            Err(failures) => {
+                let message = format!("{} records can't be deserialized", failures.len());
+                return Ok(self.version.records_error_response(
+                    status::UnprocessableEntity,
+                    message,
+                    failures,
+                ));
+            }
+        };
+
+        // the fields unknown to `R` would be dropped, hiding the mistakes of the producers
+        if self.config.es.strict_payloads {
+            let offending: Vec<serde_json::Value> = documents
+                .iter()
+                .enumerate()
+                .filter_map(|(record, document)| {
+                    let unknown_fields = resource::unknown_fields::<R>(document);
+                    if unknown_fields.is_empty() {
+                        None
+                    } else {
+                        Some(json!({ "record": record, "unknown_fields": unknown_fields }))
+                    }
+                })
+                .collect();
+
+            if !offending.is_empty() {
+                let message = format!("{} records contain unknown fields", offending.len());
+                return Ok(self.version.records_error_response(
+                    status::UnprocessableEntity,
+                    message,
+                    offending,
+                ));
+            }
+        }
+
+        for resource in resources.iter_mut() {
+            resource.normalize(&self.config);
+        }
+
+        let client = req.get::<Write<SharedClient>>().unwrap();
+        let mut client = RefreshingClient::new(client.lock().unwrap(), refresh);
+        let indexes = IndexSet::from_config(&self.config.es);
+        let result = if create_only {
+            try_or_fail!(self.version, R::create_in(&mut client, &indexes, resources))
+        } else {
+            try_or_fail!(self.version, R::index_in(&mut client, &indexes, resources))
+        };
+
+        // the other documents are stored anyway
+        let mapping_conflicts = result.mapping_conflicts();
+        if !mapping_conflicts.is_empty() {
+            let error = SearchspotError::MappingConflict(mapping_conflicts);
+            return Ok(self.version.failure_response(error));
+        }
+
+        if !create_only {
+            return Ok(Response::with(status::Created));
+        }
+
+        let conflicts: Vec<serde_json::Value> = result
+            .items
+            .iter()
+            .flat_map(|item| item.values())
+            .filter(|item| item.status == 409)
+            .map(|item| json!({ "id": item.id, "error": item.error }))
+            .collect();
+
+        if !conflicts.is_empty() {
+            let message = format!("{} records exist already", conflicts.len());
+            return Ok(self.version.records_error_response(status::Conflict, message, conflicts));
+        }
+
+        Ok(Response::with(status::Created))
+    }
+}
+
+/// Delete the document identified by `:id`, or just mark it as deleted
+/// with `soft=true` when the resource supports it.
+pub struct DeletableHandler<R> {
+    config: Config,
+    version: ApiVersion,
+    resource: PhantomData<R>,
+}
+
+impl<R: Resource> DeletableHandler<R> {
+    pub fn new(config: Config) -> Self {
+        DeletableHandler::<R> {
+            resource: PhantomData,
+            config: config,
+            version: ApiVersion::V1,
+        }
+    }
+
+    pub fn with_version(mut self, version: ApiVersion) -> Self {
+        self.version = version;
+        self
+    }
+}
+
+impl<R: Resource> WritableEndpoint for DeletableHandler<R> {}
+
+impl<R: Resource> Handler for DeletableHandler<R> {
+    fn handle(&self, req: &mut Request) -> IronResult<Response> {
+        let _span = telemetry::request_span(req);
+        let _request = context::enter(RequestContext::new(req, &self.config.es.index));
+        let ref lifetimes = self.config.tokens.lifetime;
+        if !self.is_authorized(&self.config.auth, &req.headers, lifetimes.write) {
+            unauthorized!();
+        }
+
+        let _permit = limit_concurrency!(self.version, self.config, req, EndpointClass::Index);
+        let refresh = refresh_policy!(self.version, req);
+
+        let soft = match try_or_422!(self.version, req.get_ref::<Params>()).get("soft") {
+            Some(&Value::String(ref soft)) => soft == "true",
+            Some(&Value::Boolean(soft)) => soft,
+            _ => false,
+        };
+
+        let client = req.get::<Write<SharedClient>>().unwrap();
+        let mut client = RefreshingClient::new(client.lock().unwrap(), refresh);
+
+        let id = try_or_422!(
+            self.version,
+            req.extensions
+                .get::<Router>()
+                .unwrap()
+                .find("id")
+                .ok_or("DELETE#:id not found")
+        );
+        let ref id = match id.parse::<R::Id>() {
+            Ok(id) => id,
+            Err(_) => bad_request!(self.version, format!("Invalid id: {}", id)),
+        };
+
+        let result = if soft {
+            R::soft_delete_in(&mut client, id, &IndexSet::from_config(&self.config.es))
+        } else {
+            R::delete_in(&mut client, id, &IndexSet::from_config(&self.config.es))
+        };
+
+        try_or_fail!(self.version, result);
+        Ok(Response::with(status::NoContent))
+    }
+}
+
+/// Reset the whole index, along with the routed ones. Only served by the admin listener
+/// when `http.admin` is configured. A body is rejected rather than ignored, since the ids
+/// to delete are given to `BulkDeletionHandler`: a client sending them here by mistake
+/// mustn't wipe the index.
+pub struct ResettableHandler<R> {
+    config: Config,
+    version: ApiVersion,
+    resource: PhantomData<R>,
+}
+
+impl<R: Resource> ResettableHandler<R> {
+    pub fn new(config: Config) -> Self {
+        ResettableHandler::<R> {
+            resource: PhantomData,
+            config: config,
+            version: ApiVersion::V1,
+        }
+    }
+
+    pub fn with_version(mut self, version: ApiVersion) -> Self {
+        self.version = version;
+        self
+    }
+}
+
+impl<R: Resource> WritableEndpoint for ResettableHandler<R> {}
+
+impl<R: Resource> Handler for ResettableHandler<R> {
+    fn handle(&self, req: &mut Request) -> IronResult<Response> {
+        let _span = telemetry::request_span(req);
+        let _request = context::enter(RequestContext::new(req, &self.config.es.index));
+        let ref lifetimes = self.config.tokens.lifetime;
+        if !self.is_authorized(&self.config.auth, &req.headers, lifetimes.write) {
+            unauthorized!();
+        }
+
+        let payload = read_json_payload!(self.version, req);
+        if !payload.trim().is_empty() {
+            let message = "A reset takes no body: the ids are deleted through POST /talents/delete";
+            return Ok(self.version.error_response(status::UnprocessableEntity, message.to_owned()));
+        }
+
+        let _permit = limit_concurrency!(self.version, self.config, req, EndpointClass::Reset);
+        let definition = try_or_422!(self.version, self.config.es.index_definition());
+
+        // the routed indexes are created along with the primary one
+        let client = req.get::<Write<SharedClient>>().unwrap();
+        let mut client = client.lock().unwrap();
+        let indexes = IndexSet::from_config(&self.config.es);
+        let reset: Result<Vec<MappingResult>, EsError> = indexes
+            .writable()
+            .into_iter()
+            .map(|index| R::reset_index(&mut client, index, definition.as_ref()))
+            .collect();
+        try_or_fail!(self.version, reset);
+        Ok(Response::with(status::NoContent))
+    }
+}
+
+/// Delete the documents whose ids are given in the body (i.e. `[1, 2, 3]`)
+/// with a single bulk request per index. The ids that can't be deleted are
+/// reported along with the reason, while the missing ones are skipped.
+pub struct BulkDeletionHandler<R> {
+    config: Config,
+    version: ApiVersion,
+    resource: PhantomData<R>,
+}
+
+impl<R: Resource> BulkDeletionHandler<R> {
+    pub fn new(config: Config) -> Self {
+        BulkDeletionHandler::<R> {
+            resource: PhantomData,
+            config: config,
+            version: ApiVersion::V1,
+        }
+    }
+
+    pub fn with_version(mut self, version: ApiVersion) -> Self {
+        self.version = version;
+        self
+    }
+}
+
+impl<R: Resource> WritableEndpoint for BulkDeletionHandler<R> {}
+
+impl<R: Resource> Handler for BulkDeletionHandler<R> {
+    fn handle(&self, req: &mut Request) -> IronResult<Response> {
+        let _span = telemetry::request_span(req);
+        let _request = context::enter(RequestContext::new(req, &self.config.es.index));
+        let ref lifetimes = self.config.tokens.lifetime;
+        if !self.is_authorized(&self.config.auth, &req.headers, lifetimes.write) {
+            unauthorized!();
+        }
+
+        let _permit = limit_concurrency!(self.version, self.config, req, EndpointClass::Index);
+
+        let refresh = refresh_policy!(self.version, req);
+        let payload = read_json_payload!(self.version, req);
+        let ids: Vec<R::Id> = try_or_422!(self.version, parse_ids(&payload));
+        if ids.is_empty() {
+            let message = "The ids of the documents to delete must be given".to_owned();
+            return Ok(self.version.error_response(status::UnprocessableEntity, message));
+        }
+
+        let client = req.get::<Write<SharedClient>>().unwrap();
+        let result = try_or_fail!(self.version, R::delete_many_in(
+            &mut RefreshingClient::new(client.lock().unwrap(), refresh),
+            &ids,
+            &IndexSet::from_config(&self.config.es)
+        ));
+
+        let failures: Vec<serde_json::Value> = result
+            .failures()
+            .into_iter()
+            .map(|item| json!({ "id": item.id, "error": item.error }))
+            .collect();
+
+        if !failures.is_empty() {
+            let message = format!("{} records couldn't be deleted", failures.len());
+            return Ok(self.version.records_error_response(
+                status::UnprocessableEntity,
+                message,
+                failures,
+            ));
+        }
+
+        Ok(Response::with(status::NoContent))
+    }
+}
+
+/// Parse the params of the query string of a request whose body isn't
+/// made of params (i.e. a JSON array), leaving the body untouched.
+fn query_params(query: Option<&str>) -> Result<Map, String> {
+    let url = Url::parse(&format!("http://searchspot/?{}", query.unwrap_or("")))
+        .map_err(|error| error.to_string())?;
+
+    let mut params = Map::new();
+    for (key, value) in url.query_pairs() {
+        params
+            .assign(&key, Value::String(value.into_owned()))
+            .map_err(|error| format!("{:?}", error))?;
+    }
+
+    Ok(params)
+}
+
+/// Parse a JSON array of ids, given either as numbers or as strings.
+fn parse_ids<I: ResourceId>(payload: &str) -> Result<Vec<I>, String> {
+    let ids: Vec<serde_json::Value> = serde_json::from_str(payload).map_err(|e| e.to_string())?;
+
+    ids.into_iter()
+        .map(|id| {
+            let parsed = match id {
+                serde_json::Value::Number(ref id) => id.to_string().parse().ok(),
+                serde_json::Value::String(ref id) => id.parse().ok(),
+                _ => None,
+            };
+            parsed.ok_or_else(|| format!("Invalid id: {}", id))
+        })
+        .collect()
+}
+
+/// Return the documents with the ids given as a JSON array in the body, in the same
+/// order, as long as the searches would return them: the `epoch` and the (signed)
+/// `presented_talents` of the query string apply as they do to `GET /talents`,
+/// which is checked as the one of a search (see `search_params`).
+/// No more ids than `limits.max_filter_values` can be given.
+pub struct HydratableHandler<R> {
+    config: Config,
+    version: ApiVersion,
+    resource: PhantomData<R>,
+}
+
+impl<R: Hydratable> HydratableHandler<R> {
+    pub fn new(config: Config) -> Self {
+        HydratableHandler::<R> {
+            resource: PhantomData,
+            config: config,
+            version: ApiVersion::V1,
+        }
+    }
+
+    pub fn with_version(mut self, version: ApiVersion) -> Self {
+        self.version = version;
+        self
+    }
+}
+
+impl<R: Hydratable> ReadableEndpoint for HydratableHandler<R> {}
+
+impl<R: Hydratable> Handler for HydratableHandler<R> {
+    fn handle(&self, req: &mut Request) -> IronResult<Response> {
+        let _span = telemetry::request_span(req);
+        let _request = context::enter(RequestContext::new(req, &self.config.es.index));
+        let ref lifetimes = self.config.tokens.lifetime;
+        if !self.is_authorized(&self.config.auth, &req.headers, lifetimes.read) {
+            unauthorized!();
+        }
+
+        let _permit = limit_concurrency!(self.version, self.config, req, EndpointClass::Search);
+
+        // the body is the ids, so the params of the search are read from the query string alone
+        let params = try_or_422!(self.version, query_params(req.url.query()));
+        context::set_params(&params);
+        let params = try_or_fail!(self.version, search_params::<R>(&self.config, &params));
+
+        let payload = read_json_payload!(self.version, req);
+        let ids = try_or_422!(self.version, parse_ids(&payload));
+        if ids.len() > self.config.limits.max_filter_values {
+            let message = format!(
+                "No more than {} ids can be hydrated at once",
+                self.config.limits.max_filter_values
+            );
+            bad_request!(self.version, message);
+        }
+
+        let client = req.get::<Write<SharedClient>>().unwrap();
+        let hydrated = try_or_fail!(self.version, R::hydrate(
+            &mut client.lock().unwrap(),
+            &IndexSet::from_config(&self.config.es),
+            &ids,
+            &params
+        ));
+
+        let content_type = "application/json".parse::<Mime>().unwrap();
+        Ok(Response::with((
+            content_type,
+            status::Ok,
+            try_or_422!(self.version, self.version.data_body(&hydrated)),
+        )))
+    }
+}
+
+pub struct MigratableHandler<R> {
+    config: Config,
+    version: ApiVersion,
+    resource: PhantomData<R>,
+}
+
+impl<R: Migratable> MigratableHandler<R> {
+    pub fn new(config: Config) -> Self {
+        MigratableHandler::<R> {
+            resource: PhantomData,
+            config: config,
+            version: ApiVersion::V1,
+        }
+    }
+
+    pub fn with_version(mut self, version: ApiVersion) -> Self {
+        self.version = version;
+        self
+    }
+}
+
+impl<R: Migratable> WritableEndpoint for MigratableHandler<R> {}
+
+impl<R: Migratable> Handler for MigratableHandler<R> {
+    fn handle(&self, req: &mut Request) -> IronResult<Response> {
+        let _span = telemetry::request_span(req);
+        let _request = context::enter(RequestContext::new(req, &self.config.es.index));
+        let ref lifetimes = self.config.tokens.lifetime;
+        if !self.is_authorized(&self.config.auth, &req.headers, lifetimes.write) {
+            unauthorized!();
+        }
+
+        let _permit = limit_concurrency!(self.version, self.config, req, EndpointClass::Reset);
+
+        let definition = try_or_422!(self.version, self.config.es.index_definition());
+
+        // the other requests are served in between the pages of the copy
+        let mut client = LockingClient::new(req.get::<Write<SharedClient>>().unwrap());
+        let report = try_or_fail!(self.version, migrations::migrate::<R>(
+            &mut client,
+            &*self.config.es.index,
+            definition.as_ref()
+        ));
+
+        let content_type = "application/json".parse::<Mime>().unwrap();
+        Ok(Response::with((
+            content_type,
+            status::Ok,
+            try_or_422!(self.version, serde_json::to_string(&report)),
+        )))
+    }
+}
+
+/// Point the serving alias (`es.index` unless another `alias` is given) from the
+/// index `from` to the index `to` in a single step, for blue/green deployments.
+pub struct IndexSwitchHandler {
+    config: Config,
+    version: ApiVersion,
+}
+
+impl IndexSwitchHandler {
+    pub fn new(config: Config) -> Self {
+        IndexSwitchHandler {
+            config: config,
+            version: ApiVersion::V1,
+        }
+    }
+
+    pub fn with_version(mut self, version: ApiVersion) -> Self {
+        self.version = version;
+        self
+    }
+}
+
+impl WritableEndpoint for IndexSwitchHandler {}
+
+impl Handler for IndexSwitchHandler {
+    fn handle(&self, req: &mut Request) -> IronResult<Response> {
+        let _span = telemetry::request_span(req);
+        let _request = context::enter(RequestContext::new(req, &self.config.es.index));
+        let ref lifetimes = self.config.tokens.lifetime;
+        if !self.is_authorized(&self.config.auth, &req.headers, lifetimes.write) {
+            unauthorized!();
+        }
+
+        let _permit = limit_concurrency!(self.version, self.config, req, EndpointClass::Reset);
+
+        let payload = read_json_payload!(self.version, req);
+        let mut switch: Switch = try_or_422!(self.version, serde_json::from_str(&payload));
+        if let Err(message) = switch.validate() {
+            bad_request!(self.version, message);
+        }
+
+        let alias = switch.alias.take().unwrap_or_else(|| self.config.es.index.to_owned());
+        let client = req.get::<Write<SharedClient>>().unwrap();
+        try_or_fail!(
+            self.version,
+            client.lock().unwrap().switch_alias(&alias, &switch.from, &switch.to)
+        );
+        info!("{} switched from {} to {}", alias, switch.from, switch.to);
+
+        switch.alias = Some(alias);
+        let content_type = "application/json".parse::<Mime>().unwrap();
+        Ok(Response::with((
+            content_type,
+            status::Ok,
+            try_or_422!(self.version, self.version.data_body(&switch)),
+        )))
+    }
+}
+
+/// Return the talents matching the job identified by `:id`.
+pub struct JobTalentsHandler {
+    config: Config,
+    version: ApiVersion,
+}
+
+impl JobTalentsHandler {
+    pub fn new(config: Config) -> Self {
+        JobTalentsHandler {
+            config: config,
+            version: ApiVersion::V1,
+        }
+    }
+
+    pub fn with_version(mut self, version: ApiVersion) -> Self {
+        self.version = version;
+        self
+    }
+}
+
+impl ReadableEndpoint for JobTalentsHandler {}
+
+impl Handler for JobTalentsHandler {
+    fn handle(&self, req: &mut Request) -> IronResult<Response> {
+        let _span = telemetry::request_span(req);
+        let _request = context::enter(RequestContext::new(req, &self.config.es.index));
+        let ref lifetimes = self.config.tokens.lifetime;
+        if !self.is_authorized(&self.config.auth, &req.headers, lifetimes.read) {
+            unauthorized!();
+        }
+
+        let _permit = limit_concurrency!(self.version, self.config, req, EndpointClass::Search);
+
+        let id = try_or_422!(
+            self.version,
+            req.extensions
+                .get::<Router>()
+                .unwrap()
+                .find("id")
+                .map(str::to_owned)
+                .ok_or("GET#:id not found")
+        );
+
+        let json_api = JsonApiRequest::from_request(req);
+        let client = req.get::<Write<SharedClient>>().unwrap();
+        let params = try_or_422!(self.version, req.get_ref::<Params>());
+        context::set_params(params);
+
+        let mut client = client.lock().unwrap();
+        let job = try_or_fail!(self.version, Job::find(&mut client, &*self.config.es.index, &id));
+        let job = match job {
+            Some(job) => job,
+            None => {
+                let message = format!("Job not found: {}", id);
+                return Ok(self.version.error_response(status::NotFound, message));
+            }
+        };
+
+        // the job only adds the params of a talent search, which is checked as any other
+        let mut params = try_or_fail!(
+            self.version,
+            search_params::<Talent>(&self.config, &job.talent_params(params))
+        );
+        let now = Utc::now().timestamp_millis();
+        try_or_fail!(self.version, propagate_deadline(&mut params, &req.headers, now));
+
+        let params = &params;
+        let response = try_or_fail!(
+            self.version,
+            Talent::search_in(&mut client, &IndexSet::from_config(&self.config.es), params)
+        );
+
+        if let (Some(json_api), Some(collection)) = (json_api, Talent::json_api()) {
+            return Ok(try_or_422!(self.version, json_api.response(&collection, &response, params)));
+        }
+
+        let content_type = "application/json".parse::<Mime>().unwrap();
+        Ok(Response::with((
+            content_type,
+            status::Ok,
+            try_or_422!(self.version, self.version.search_body(&response, params)),
+        )))
+    }
+}
+
+/// Return the jobs and the companies recommended to the talent identified by `:id`.
+pub struct RecommendationsHandler {
+    config: Config,
+    version: ApiVersion,
+}
+
+impl RecommendationsHandler {
+    pub fn new(config: Config) -> Self {
+        RecommendationsHandler {
+            config: config,
+            version: ApiVersion::V1,
+        }
+    }
+
+    pub fn with_version(mut self, version: ApiVersion) -> Self {
+        self.version = version;
+        self
+    }
+}
+
+impl ReadableEndpoint for RecommendationsHandler {}
+
+impl Handler for RecommendationsHandler {
+    fn handle(&self, req: &mut Request) -> IronResult<Response> {
+        let _span = telemetry::request_span(req);
+        let _request = context::enter(RequestContext::new(req, &self.config.es.index));
+        let ref lifetimes = self.config.tokens.lifetime;
+        if !self.is_authorized(&self.config.auth, &req.headers, lifetimes.read) {
+            unauthorized!();
+        }
+
+        let _permit = limit_concurrency!(self.version, self.config, req, EndpointClass::Search);
+
+        let id = try_or_422!(
+            self.version,
+            req.extensions
+                .get::<Router>()
+                .unwrap()
+                .find("id")
+                .map(str::to_owned)
+                .ok_or("GET#:id not found")
+        );
+
+        let client = req.get::<Write<SharedClient>>().unwrap();
+        let params = try_or_422!(self.version, req.get_ref::<Params>());
+        context::set_params(params);
+
+        let per_page = param_u64(params, "per_page", 10);
+        try_or_fail!(self.version, check_window(&self.config, 0, per_page, "per_page"));
+
+        let recommendations = try_or_fail!(self.version, recommendations::recommend(
+            &mut client.lock().unwrap(),
+            &IndexSet::from_config(&self.config.es),
+            &id,
+            per_page
+        ));
+        let recommendations = match recommendations {
+            Some(recommendations) => recommendations,
+            None => {
+                let message = format!("Talent not found: {}", id);
+                return Ok(self.version.error_response(status::NotFound, message));
+            }
+        };
+
+        let content_type = "application/json".parse::<Mime>().unwrap();
+        Ok(Response::with((
+            content_type,
+            status::Ok,
+            try_or_422!(self.version, self.version.data_body(&recommendations)),
+        )))
+    }
+}
+
+/// Run the ElasticSearch query given in the body on the talents, so that they
+/// can be analyzed without accessing the cluster. Only searching is allowed
+/// and the talents are restricted to the fields returned by `GET /talents`.
+pub struct RawQueryHandler {
+    config: Config,
+    version: ApiVersion,
+}
+
+impl RawQueryHandler {
+    pub fn new(config: Config) -> Self {
+        RawQueryHandler {
+            config: config,
+            version: ApiVersion::V1,
+        }
+    }
+
+    pub fn with_version(mut self, version: ApiVersion) -> Self {
+        self.version = version;
+        self
+    }
+}
+
+impl WritableEndpoint for RawQueryHandler {}
+
+impl Handler for RawQueryHandler {
+    fn handle(&self, req: &mut Request) -> IronResult<Response> {
+        let _span = telemetry::request_span(req);
+        let _request = context::enter(RequestContext::new(req, &self.config.es.index));
+        let ref lifetimes = self.config.tokens.lifetime;
+        if !self.is_authorized(&self.config.auth, &req.headers, lifetimes.write) {
+            unauthorized!();
+        }
+
+        let _permit = limit_concurrency!(self.version, self.config, req, EndpointClass::Search);
+
+        let payload = read_json_payload!(self.version, req);
+
+        let body: serde_json::Value = try_or_422!(self.version, serde_json::from_str(&payload));
+        if let Err(message) = Talent::validate_raw_query(&body) {
+            bad_request!(self.version, message);
+        }
+
+        try_or_fail!(self.version, check_window(
+            &self.config,
+            body["from"].as_u64().unwrap_or(0),
+            body["size"].as_u64().unwrap_or(10),
+            "from + size"
+        ));
+
+        let client = req.get::<Write<SharedClient>>().unwrap();
+        let results = try_or_fail!(self.version, Talent::raw_query(
+            &mut client.lock().unwrap(),
+            &IndexSet::from_config(&self.config.es),
+            &body
+        ));
+
+        let content_type = "application/json".parse::<Mime>().unwrap();
+        Ok(Response::with((
+            content_type,
+            status::Ok,
+            try_or_422!(self.version, self.version.data_body(&results)),
+        )))
+    }
+}
+
+/// Return the distribution of the minimum salaries expected by the talents
+/// matching the search parameters of `GET /talents`, per city and currency,
+/// whose histograms have buckets `interval` (10000 by default) wide.
+pub struct SalaryDistributionHandler {
+    config: Config,
+    version: ApiVersion,
+}
+
+impl SalaryDistributionHandler {
+    pub fn new(config: Config) -> Self {
+        SalaryDistributionHandler {
+            config: config,
+            version: ApiVersion::V1,
+        }
+    }
+
+    pub fn with_version(mut self, version: ApiVersion) -> Self {
+        self.version = version;
+        self
+    }
+}
+
+impl ReadableEndpoint for SalaryDistributionHandler {}
+
+impl Handler for SalaryDistributionHandler {
+    fn handle(&self, req: &mut Request) -> IronResult<Response> {
+        let _span = telemetry::request_span(req);
+        let _request = context::enter(RequestContext::new(req, &self.config.es.index));
+        let ref lifetimes = self.config.tokens.lifetime;
+        if !self.is_authorized(&self.config.auth, &req.headers, lifetimes.read) {
+            unauthorized!();
+        }
+
+        let _permit = limit_concurrency!(self.version, self.config, req, EndpointClass::Search);
+
+        let client = req.get::<Write<SharedClient>>().unwrap();
+        let params = try_or_422!(self.version, req.get_ref::<Params>());
+        context::set_params(params);
+
+        let interval = try_or_fail!(self.version, param_interval(params, 10000.0));
+
+        // the distribution filters the talents as any other search
+        let params = try_or_fail!(self.version, search_params::<Talent>(&self.config, params));
+        let params = match TalentSearchParams::from_map(&params) {
+            Ok(params) => params,
+            Err(message) => bad_request!(self.version, message),
+        };
+
+        let distribution = try_or_fail!(self.version, Talent::salary_distribution(
+            &mut client.lock().unwrap(),
+            &IndexSet::from_config(&self.config.es),
+            &params,
+            interval
+        ));
+
+        let content_type = "application/json".parse::<Mime>().unwrap();
+        Ok(Response::with((
+            content_type,
+            status::Ok,
+            try_or_422!(self.version, self.version.data_body(&distribution)),
+        )))
+    }
+}
+
+/// How many talents are read from the search backend at a time by `TalentExportHandler`.
+const EXPORT_BATCH_SIZE: u64 = 500;
+
+/// How long the scroll of an export is kept alive between two batches.
+const EXPORT_KEEP_ALIVE: &'static str = "1m";
+
+fn export_error(error: EsError) -> io::Error {
+    error!("Export interrupted: {}", error);
+    io::Error::new(io::ErrorKind::Other, error.to_string())
+}
+
+/// Writes the talents found by `body` as a JSON array, one scroll batch at a time,
+/// so that the results are never buffered as a whole. The client is only locked
+/// while a batch is being read.
+struct TalentExport {
+    /// Held until the export is over, which happens after the handler returns.
+    _permit: Permit<'static>,
+    /// The request exported for, entered again while writing (i.e. to anonymize the talents).
+    context: Option<RequestContext>,
+    client: Arc<Mutex<Box<dyn SearchBackend>>>,
+    indexes: Vec<String>,
+    body: serde_json::Value,
+    params: TalentSearchParams,
+}
+
+impl TalentExport {
+    fn write_batches(
+        &mut self,
+        res: &mut dyn io::Write,
+        scroll_id: &mut Option<String>,
+    ) -> io::Result<()> {
+        let indexes: Vec<&str> = self.indexes.iter().map(String::as_str).collect();
+        let mut batch = self
+            .client
+            .lock()
+            .unwrap()
+            .scroll_json(&indexes, &self.body, EXPORT_KEEP_ALIVE);
+
+        // a talent stored in more of the exported indexes is written once
+        let mut written = HashSet::new();
+        loop {
+            let result = batch.map_err(export_error)?;
+            let empty = result.hits.hits.is_empty();
+            *scroll_id = result.scroll_id.clone();
+
+            for result in Talent::results_of(result, &self.params).map_err(export_error)? {
+                if written.insert(result.talent.id()) {
+                    if written.len() > 1 {
+                        res.write_all(b",")?;
+                    }
+                    serde_json::to_writer(&mut *res, &result)?;
+                }
+            }
+            res.flush()?;
+
+            batch = match *scroll_id {
+                Some(ref scroll_id) if !empty => self
+                    .client
+                    .lock()
+                    .unwrap()
+                    .next_scroll_json(scroll_id, EXPORT_KEEP_ALIVE),
+                _ => return Ok(()),
+            };
+        }
+    }
+}
+
+impl WriteBody for TalentExport {
+    fn write_body(&mut self, res: &mut dyn io::Write) -> io::Result<()> {
+        let _request = self.context.take().map(context::enter);
+        let mut scroll_id = None;
+
+        res.write_all(b"[")?;
+        let written = self.write_batches(res, &mut scroll_id);
+
+        if let Some(scroll_id) = scroll_id {
+            if let Err(error) = self.client.lock().unwrap().clear_scroll(&scroll_id) {
+                warn!("Scroll not cleared: {}", error);
+            }
+        }
+
+        written?;
+        res.write_all(b"]")
+    }
+}
+
+/// Export every talent matching the search parameters of `GET /talents`
+/// as a JSON array of search results, which is streamed while the talents
+/// are read instead of being paginated.
+pub struct TalentExportHandler {
+    config: Config,
+    version: ApiVersion,
+}
+
+impl TalentExportHandler {
+    pub fn new(config: Config) -> Self {
+        TalentExportHandler {
+            config: config,
+            version: ApiVersion::V1,
+        }
+    }
+
+    pub fn with_version(mut self, version: ApiVersion) -> Self {
+        self.version = version;
+        self
+    }
+}
+
+impl ReadableEndpoint for TalentExportHandler {}
+impl WritableEndpoint for TalentExportHandler {}
+
+impl Handler for TalentExportHandler {
+    fn handle(&self, req: &mut Request) -> IronResult<Response> {
+        let _span = telemetry::request_span(req);
+        let _request = context::enter(RequestContext::new(req, &self.config.es.index));
+        let full_view = is_full_view(req.url.query());
+        if !is_search_authorized(self, &self.config, &req.headers, full_view) {
+            unauthorized!();
+        }
+
+        let permit = limit_concurrency!(self.version, self.config, req, EndpointClass::Search);
+
+        let client = req.get::<Write<SharedClient>>().unwrap();
+        let params = try_or_422!(self.version, req.get_ref::<Params>());
+        context::set_params(params);
+        check_full_view!(params, full_view);
+
+        // the export is checked and completed as any other search
+        let params = try_or_fail!(self.version, search_params::<Talent>(&self.config, params));
+        let params = match TalentSearchParams::from_map(&params) {
+            Ok(params) => params,
+            Err(message) => bad_request!(self.version, message),
+        };
+
+        let epoch = match params.epoch {
+            Some(ref epoch) => epoch.to_owned(),
+            None => Utc::now().to_rfc3339(),
+        };
+        let routed = IndexSet::from_config(&self.config.es);
+        let indexes = match params.index {
+            Some(ref index) => resource::split_indexes(index),
+            None => routed.names(),
+        };
+
+        let export: Box<dyn WriteBody> = Box::new(TalentExport {
+            _permit: permit,
+            context: context::current(),
+            client: client,
+            indexes: indexes.into_iter().map(str::to_owned).collect(),
+            body: Talent::export_body(&params, &epoch, EXPORT_BATCH_SIZE),
+            params: params,
+        });
+        let content_type = "application/json".parse::<Mime>().unwrap();
+        Ok(Response::with((content_type, status::Ok, export)))
+    }
+}
+
+/// Writes the changes of `indexes` as server-sent events, with a comment every
+/// `KEEP_ALIVE_SECONDS` to notice the clients that went away. The stream ends
+/// once no change has been written for `IDLE_TIMEOUT_SECONDS`, or when the
+/// client falls too far behind the changes (see `events::publish`).
+struct EventStream {
+    /// Held until the stream ends, which happens after the handler returns.
+    _permit: Permit<'static>,
+    /// The request streamed to, entered again while writing.
+    context: Option<RequestContext>,
+    changes: Receiver<Change>,
+    indexes: Vec<String>,
+}
+
+const KEEP_ALIVE_SECONDS: u64 = 15;
+
+/// How long a stream without changes is kept open: the clients reconnect.
+const IDLE_TIMEOUT_SECONDS: u64 = 300;
+
+impl WriteBody for EventStream {
+    fn write_body(&mut self, res: &mut dyn io::Write) -> io::Result<()> {
+        let _request = self.context.take().map(context::enter);
+        res.write_all(b": connected\n\n")?;
+        res.flush()?;
+
+        let keep_alive = Duration::from_secs(KEEP_ALIVE_SECONDS);
+        let mut last_change = Instant::now();
+        let mut last_write = Instant::now();
+        loop {
+            if last_change.elapsed() >= Duration::from_secs(IDLE_TIMEOUT_SECONDS) {
+                return Ok(());
+            }
+
+            // the changes of the other indexes aren't written, so they mustn't
+            // put off the keep-alive that tells whether the client is gone
+            let wait = keep_alive.checked_sub(last_write.elapsed()).unwrap_or_default();
+            match self.changes.recv_timeout(wait) {
+                Ok(ref change) if self.indexes.contains(&change.index) => {
+                    res.write_all(change.to_event().as_bytes())?;
+                    last_change = Instant::now();
+                }
+                Ok(_) | Err(RecvTimeoutError::Timeout) => {
+                    if last_write.elapsed() < keep_alive {
+                        continue;
+                    }
+                    res.write_all(b": keep-alive\n\n")?;
+                }
+                Err(RecvTimeoutError::Disconnected) => return Ok(()),
+            }
+            res.flush()?;
+            last_write = Instant::now();
+        }
+    }
+}
+
+/// Stream the talents indexed, deleted or restored in the indexes given as
+/// `index` (the configured ones, routes included, by default) as server-sent
+/// events, until the client disconnects or the stream ends (see `EventStream`).
+/// Every stream takes a permit of `EndpointClass::Stream` for as long as it lasts.
+pub struct TalentStreamHandler {
+    config: Config,
+    version: ApiVersion,
+}
+
+impl TalentStreamHandler {
+    pub fn new(config: Config) -> Self {
+        TalentStreamHandler {
+            config: config,
+            version: ApiVersion::V1,
+        }
+    }
+
+    pub fn with_version(mut self, version: ApiVersion) -> Self {
+        self.version = version;
+        self
+    }
+}
+
+impl ReadableEndpoint for TalentStreamHandler {}
+
+impl Handler for TalentStreamHandler {
+    fn handle(&self, req: &mut Request) -> IronResult<Response> {
+        let _span = telemetry::request_span(req);
+        let _request = context::enter(RequestContext::new(req, &self.config.es.index));
+        let ref lifetimes = self.config.tokens.lifetime;
+        if !self.is_authorized(&self.config.auth, &req.headers, lifetimes.read) {
+            unauthorized!();
+        }
+
+        let permit = limit_concurrency!(self.version, self.config, req, EndpointClass::Stream);
+
+        let params = try_or_422!(self.version, req.get_ref::<Params>());
+        let routed = IndexSet::from_config(&self.config.es);
+        let indexes = match params.get("index") {
+            Some(&Value::String(ref index)) => {
+                try_or_fail!(self.version, self.config.es.check_searchable(index));
+                resource::split_indexes(index)
+            }
+            _ => routed.names(),
+        };
+
+        let stream: Box<dyn WriteBody> = Box::new(EventStream {
+            _permit: permit,
+            context: context::current(),
+            changes: events::subscribe(),
+            indexes: indexes.into_iter().map(str::to_owned).collect(),
+        });
+        let content_type = "text/event-stream".parse::<Mime>().unwrap();
+        let mut response = Response::with((content_type, status::Ok, stream));
+        response.headers.set(headers::CacheControl(vec![headers::CacheDirective::NoCache]));
+
+        Ok(response)
+    }
+}
+
+/// Return the distribution of the scores of the job identified by `job_id`,
+/// computed by `model_version` if given, whose histogram has buckets
+/// `interval` (0.1 by default) wide, up to `MAX_HISTOGRAM_BUCKETS` of them.
+pub struct ScoreStatsHandler {
+    config: Config,
+    version: ApiVersion,
+}
+
+impl ScoreStatsHandler {
+    pub fn new(config: Config) -> Self {
+        ScoreStatsHandler {
+            config: config,
+            version: ApiVersion::V1,
+        }
+    }
+
+    pub fn with_version(mut self, version: ApiVersion) -> Self {
+        self.version = version;
+        self
+    }
+}
+
+impl ReadableEndpoint for ScoreStatsHandler {}
+
+impl Handler for ScoreStatsHandler {
+    fn handle(&self, req: &mut Request) -> IronResult<Response> {
+        let _span = telemetry::request_span(req);
+        let _request = context::enter(RequestContext::new(req, &self.config.es.index));
+        let ref lifetimes = self.config.tokens.lifetime;
+        if !self.is_authorized(&self.config.auth, &req.headers, lifetimes.read) {
+            unauthorized!();
+        }
+
+        let _permit = limit_concurrency!(self.version, self.config, req, EndpointClass::Search);
+
+        let client = req.get::<Write<SharedClient>>().unwrap();
+        let params = try_or_422!(self.version, req.get_ref::<Params>());
+        context::set_params(params);
+
+        let job_id = match params.get("job_id") {
+            Some(&Value::String(ref job_id)) => job_id.parse::<u32>().ok(),
+            Some(&Value::U64(job_id)) => u32::try_from(job_id).ok(),
+            _ => None,
+        };
+        let job_id = match job_id {
+            Some(job_id) => job_id,
+            None => bad_request!(self.version, "A valid job_id must be given".to_owned()),
+        };
+
+        let interval = try_or_fail!(self.version, param_interval(params, 0.1));
+
+        let mut search = ScoreSearchBuilder::new();
+        search.with_job_id(job_id);
+        if let Some(&Value::String(ref model_version)) = params.get("model_version") {
+            search.with_model_version(model_version);
+        }
+        let search = search.build();
+        let stats = try_or_fail!(self.version, Score::stats(
+            &mut client.lock().unwrap(),
+            &*self.config.es.index,
+            &search,
+            interval
+        ));
+
+        let content_type = "application/json".parse::<Mime>().unwrap();
+        Ok(Response::with((
+            content_type,
+            status::Ok,
+            try_or_422!(self.version, self.version.data_body(&stats)),
+        )))
+    }
+}
+
+/// Index the scores given in the body, keyed by their job and talent
+/// (so that they replace the previous scores of the same pairs) if
+/// `upsert` is `true`, or by their request ids otherwise.
+/// The request ids of the accepted and of the rejected scores are returned.
+pub struct ScoresIndexingHandler {
+    config: Config,
+    version: ApiVersion,
+}
+
+impl ScoresIndexingHandler {
+    pub fn new(config: Config) -> Self {
+        ScoresIndexingHandler {
+            config: config,
+            version: ApiVersion::V1,
+        }
+    }
+
+    pub fn with_version(mut self, version: ApiVersion) -> Self {
+        self.version = version;
+        self
+    }
+}
+
+impl WritableEndpoint for ScoresIndexingHandler {}
+
+impl Handler for ScoresIndexingHandler {
+    fn handle(&self, req: &mut Request) -> IronResult<Response> {
+        let _span = telemetry::request_span(req);
+        let _request = context::enter(RequestContext::new(req, &self.config.es.index));
+        let ref lifetimes = self.config.tokens.lifetime;
+        if !self.is_authorized(&self.config.auth, &req.headers, lifetimes.write) {
+            unauthorized!();
+        }
+
+        let _permit = limit_concurrency!(self.version, self.config, req, EndpointClass::Index);
+
+        let upsert = match is_upsert(req.url.query()) {
+            Ok(upsert) => upsert,
+            Err(message) => bad_request!(self.version, message),
+        };
+        let refresh = refresh_policy!(self.version, req);
+
+        let payload = read_json_payload!(self.version, req);
+
+        let scores: Vec<Score> = try_or_422!(self.version, serde_json::from_str(&payload));
+        let request_ids = scores.iter().map(|score| score.request_id.to_owned()).collect();
+
+        let client = req.get::<Write<SharedClient>>().unwrap();
+        let mut client = RefreshingClient::new(client.lock().unwrap(), refresh);
+        let result = if upsert {
+            try_or_fail!(self.version, Score::upsert(&mut client, &*self.config.es.index, scores))
+        } else {
+            try_or_fail!(self.version, Score::index(&mut client, &*self.config.es.index, scores))
+        };
+
+        let results = ScoreIndexingResults::new(request_ids, &result);
+        let content_type = "application/json".parse::<Mime>().unwrap();
+        Ok(Response::with((
+            content_type,
+            status::Created,
+            try_or_422!(self.version, self.version.data_body(&results)),
+        )))
+    }
+}
+
+/// Delete the scores whose request ids are given in the body (i.e. `["a1", "b2"]`)
+/// with a single bulk request, or every score of the job identified by `job_id`
+/// through a delete by query if the body is empty.
+pub struct ScoresDeletionHandler {
+    config: Config,
+    version: ApiVersion,
+}
+
+impl ScoresDeletionHandler {
+    pub fn new(config: Config) -> Self {
+        ScoresDeletionHandler {
+            config: config,
+            version: ApiVersion::V1,
+        }
+    }
+
+    pub fn with_version(mut self, version: ApiVersion) -> Self {
+        self.version = version;
+        self
+    }
+}
+
+impl WritableEndpoint for ScoresDeletionHandler {}
+
+impl Handler for ScoresDeletionHandler {
+    fn handle(&self, req: &mut Request) -> IronResult<Response> {
+        let _span = telemetry::request_span(req);
+        let _request = context::enter(RequestContext::new(req, &self.config.es.index));
+        let ref lifetimes = self.config.tokens.lifetime;
+        if !self.is_authorized(&self.config.auth, &req.headers, lifetimes.write) {
+            unauthorized!();
+        }
+
+        let _permit = limit_concurrency!(self.version, self.config, req, EndpointClass::Index);
+        let refresh = refresh_policy!(self.version, req);
+        let payload = read_json_payload!(self.version, req);
+
+        if !payload.trim().is_empty() {
+            let ids = try_or_422!(self.version, parse_ids(&payload));
+            let client = req.get::<Write<SharedClient>>().unwrap();
+            let result = try_or_fail!(self.version, Score::delete_many(
+                &mut RefreshingClient::new(client.lock().unwrap(), refresh),
+                &ids,
+                &*self.config.es.index
+            ));
+
+            let failures: Vec<serde_json::Value> = result
+                .failures()
+                .into_iter()
+                .map(|item| json!({ "id": item.id, "error": item.error }))
+                .collect();
+
+            if !failures.is_empty() {
+                let message = format!("{} scores couldn't be deleted", failures.len());
+                return Ok(self.version.records_error_response(
+                    status::UnprocessableEntity,
+                    message,
+                    failures,
+                ));
+            }
+
+            return Ok(Response::with(status::NoContent));
+        }
+
+        let client = req.get::<Write<SharedClient>>().unwrap();
+        let params = try_or_422!(self.version, req.get_ref::<Params>());
+        context::set_params(params);
+
+        let job_id = match params.get("job_id") {
+            Some(&Value::String(ref job_id)) => job_id.parse::<u32>().ok(),
+            Some(&Value::U64(job_id)) => u32::try_from(job_id).ok(),
+            _ => None,
+        };
+        let job_id = match job_id {
+            Some(job_id) => job_id,
+            None => bad_request!(
+                self.version,
+                "Either a valid job_id or the request ids in the body must be given".to_owned()
+            ),
+        };
+
+        let search = ScoreSearchBuilder::new().with_job_id(job_id).build();
+        try_or_fail!(self.version, Score::delete_matching(
+            &mut RefreshingClient::new(client.lock().unwrap(), refresh),
+            &*self.config.es.index,
+            &search
+        ));
+
+        Ok(Response::with(status::NoContent))
+    }
+}
+
+/// Read the batch identified by the `starts_at` and `ends_at` parameters.
+fn batch_params(params: &Map) -> Result<(String, String), String> {
+    match (params.get("starts_at"), params.get("ends_at")) {
+        (Some(&Value::String(ref starts_at)), Some(&Value::String(ref ends_at)))
+            if !starts_at.is_empty() && !ends_at.is_empty() =>
+        {
+            Ok((starts_at.to_owned(), ends_at.to_owned()))
+        }
+        _ => Err("Both starts_at and ends_at must be given".to_owned()),
+    }
+}
+
+/// List the batches of talents, along with how many talents they contain.
+pub struct BatchesHandler {
+    config: Config,
+    version: ApiVersion,
+}
+
+impl BatchesHandler {
+    pub fn new(config: Config) -> Self {
+        BatchesHandler {
+            config: config,
+            version: ApiVersion::V1,
+        }
+    }
+
+    pub fn with_version(mut self, version: ApiVersion) -> Self {
+        self.version = version;
+        self
+    }
+}
+
+impl ReadableEndpoint for BatchesHandler {}
+
+impl Handler for BatchesHandler {
+    fn handle(&self, req: &mut Request) -> IronResult<Response> {
+        let _span = telemetry::request_span(req);
+        let _request = context::enter(RequestContext::new(req, &self.config.es.index));
+        let ref lifetimes = self.config.tokens.lifetime;
+        if !self.is_authorized(&self.config.auth, &req.headers, lifetimes.read) {
+            unauthorized!();
+        }
+
+        let _permit = limit_concurrency!(self.version, self.config, req, EndpointClass::Search);
+
+        let client = req.get::<Write<SharedClient>>().unwrap();
+        let batches = try_or_fail!(self.version, batches::list(
+            &mut client.lock().unwrap(),
+            &IndexSet::from_config(&self.config.es)
+        ));
+
+        let content_type = "application/json".parse::<Mime>().unwrap();
+        Ok(Response::with((
+            content_type,
+            status::Ok,
+            try_or_422!(self.version, self.version.data_body(&batches)),
+        )))
+    }
+}
+
+/// Delete every talent of the batch going from `starts_at` to `ends_at`.
+pub struct BatchDeletionHandler {
+    config: Config,
+    version: ApiVersion,
+}
+
+impl BatchDeletionHandler {
+    pub fn new(config: Config) -> Self {
+        BatchDeletionHandler {
+            config: config,
+            version: ApiVersion::V1,
+        }
+    }
+
+    pub fn with_version(mut self, version: ApiVersion) -> Self {
+        self.version = version;
+        self
+    }
+}
+
+impl WritableEndpoint for BatchDeletionHandler {}
+
+impl Handler for BatchDeletionHandler {
+    fn handle(&self, req: &mut Request) -> IronResult<Response> {
+        let _span = telemetry::request_span(req);
+        let _request = context::enter(RequestContext::new(req, &self.config.es.index));
+        let ref lifetimes = self.config.tokens.lifetime;
+        if !self.is_authorized(&self.config.auth, &req.headers, lifetimes.write) {
+            unauthorized!();
+        }
+
+        let _permit = limit_concurrency!(self.version, self.config, req, EndpointClass::Index);
+
+        let refresh = refresh_policy!(self.version, req);
+        let client = req.get::<Write<SharedClient>>().unwrap();
+        let params = try_or_422!(self.version, req.get_ref::<Params>());
+        context::set_params(params);
+
+        let (starts_at, ends_at) = match batch_params(params) {
+            Ok(batch) => batch,
+            Err(message) => bad_request!(self.version, message),
+        };
+
+        try_or_fail!(self.version, batches::delete(
+            &mut RefreshingClient::new(client.lock().unwrap(), refresh),
+            &IndexSet::from_config(&self.config.es),
+            &starts_at,
+            &ends_at
+        ));
+
+        Ok(Response::with(status::NoContent))
+    }
+}
+
+/// Hide every talent of the batch going from `starts_at` to `ends_at` from
+/// the searches, replying with how many talents were hidden.
+pub struct BatchHidingHandler {
+    config: Config,
+    version: ApiVersion,
+}
+
+impl BatchHidingHandler {
+    pub fn new(config: Config) -> Self {
+        BatchHidingHandler {
+            config: config,
+            version: ApiVersion::V1,
+        }
+    }
+
+    pub fn with_version(mut self, version: ApiVersion) -> Self {
+        self.version = version;
+        self
+    }
+}
+
+impl WritableEndpoint for BatchHidingHandler {}
+
+impl Handler for BatchHidingHandler {
+    fn handle(&self, req: &mut Request) -> IronResult<Response> {
+        let _span = telemetry::request_span(req);
+        let _request = context::enter(RequestContext::new(req, &self.config.es.index));
+        let ref lifetimes = self.config.tokens.lifetime;
+        if !self.is_authorized(&self.config.auth, &req.headers, lifetimes.write) {
+            unauthorized!();
+        }
+
+        let _permit = limit_concurrency!(self.version, self.config, req, EndpointClass::Index);
+
+        let refresh = refresh_policy!(self.version, req);
+        let client = req.get::<Write<SharedClient>>().unwrap();
+        let params = try_or_422!(self.version, req.get_ref::<Params>());
+        context::set_params(params);
+
+        let (starts_at, ends_at) = match batch_params(params) {
+            Ok(batch) => batch,
+            Err(message) => bad_request!(self.version, message),
+        };
+
+        let hidden = try_or_fail!(self.version, batches::hide(
+            &mut RefreshingClient::new(client.lock().unwrap(), refresh),
+            &IndexSet::from_config(&self.config.es),
+            &starts_at,
+            &ends_at
+        ));
+
+        let content_type = "application/json".parse::<Mime>().unwrap();
+        Ok(Response::with((
+            content_type,
+            status::Ok,
+            try_or_422!(self.version, self.version.data_body(&json!({ "hidden": hidden }))),
+        )))
+    }
+}
+
+/// Restore the talent identified by `:id` after a soft deletion.
+pub struct RestoreHandler {
+    config: Config,
+    version: ApiVersion,
+}
+
+impl RestoreHandler {
+    pub fn new(config: Config) -> Self {
+        RestoreHandler {
+            config: config,
+            version: ApiVersion::V1,
+        }
+    }
+
+    pub fn with_version(mut self, version: ApiVersion) -> Self {
+        self.version = version;
+        self
+    }
+}
+
+impl WritableEndpoint for RestoreHandler {}
+
+impl Handler for RestoreHandler {
+    fn handle(&self, req: &mut Request) -> IronResult<Response> {
+        let _span = telemetry::request_span(req);
+        let _request = context::enter(RequestContext::new(req, &self.config.es.index));
+        let ref lifetimes = self.config.tokens.lifetime;
+        if !self.is_authorized(&self.config.auth, &req.headers, lifetimes.write) {
+            unauthorized!();
+        }
+
+        let _permit = limit_concurrency!(self.version, self.config, req, EndpointClass::Index);
+
+        let id = try_or_422!(
+            self.version,
+            req.extensions
+                .get::<Router>()
+                .unwrap()
+                .find("id")
+                .map(str::to_owned)
+                .ok_or("POST#:id not found")
+        );
+        let id = match id.parse::<TalentId>() {
+            Ok(id) => id,
+            Err(message) => bad_request!(self.version, message),
+        };
+        let refresh = refresh_policy!(self.version, req);
+
+        let client = req.get::<Write<SharedClient>>().unwrap();
+        let mut client = RefreshingClient::new(client.lock().unwrap(), refresh);
+        let found = try_or_fail!(
+            self.version,
+            Talent::restore_in(&mut client, &IndexSet::from_config(&self.config.es), &id)
+        );
+
+        if !found {
+            let message = format!("Talent not found: {}", id);
+            return Ok(self.version.error_response(status::NotFound, message));
+        }
+
+        Ok(Response::with(status::NoContent))
+    }
+}
+
+/// Make every change done to the configured indexes searchable right away,
+/// for the environments that can't wait for the periodic refresh.
+pub struct RefreshHandler {
+    config: Config,
+    version: ApiVersion,
+}
+
+impl RefreshHandler {
+    pub fn new(config: Config) -> Self {
+        RefreshHandler {
+            config: config,
+            version: ApiVersion::V1,
+        }
+    }
+
+    pub fn with_version(mut self, version: ApiVersion) -> Self {
+        self.version = version;
+        self
+    }
+}
+
+impl WritableEndpoint for RefreshHandler {}
+
+impl Handler for RefreshHandler {
+    fn handle(&self, req: &mut Request) -> IronResult<Response> {
+        let _span = telemetry::request_span(req);
+        let _request = context::enter(RequestContext::new(req, &self.config.es.index));
+        let ref lifetimes = self.config.tokens.lifetime;
+        if !self.is_authorized(&self.config.auth, &req.headers, lifetimes.write) {
+            unauthorized!();
+        }
+
+        let _permit = limit_concurrency!(self.version, self.config, req, EndpointClass::Index);
+
+        let indexes = IndexSet::from_config(&self.config.es);
+        let client = req.get::<Write<SharedClient>>().unwrap();
+        try_or_fail!(self.version, client.lock().unwrap().refresh(&indexes.names()));
+
+        Ok(Response::with(status::NoContent))
+    }
 }
 
-pub struct SearchableHandler<R> {
+/// Return the soft deleted talents, which are paginated like `GET /talents`.
+pub struct DeletedTalentsHandler {
     config: Config,
-    resource: PhantomData<R>,
+    version: ApiVersion,
 }
 
-impl<R: Resource> SearchableHandler<R> {
+impl DeletedTalentsHandler {
     pub fn new(config: Config) -> Self {
-        SearchableHandler::<R> {
-            resource: PhantomData,
+        DeletedTalentsHandler {
             config: config,
+            version: ApiVersion::V1,
         }
     }
+
+    pub fn with_version(mut self, version: ApiVersion) -> Self {
+        self.version = version;
+        self
+    }
 }
 
-impl<R: Resource> ReadableEndpoint for SearchableHandler<R> {}
+impl WritableEndpoint for DeletedTalentsHandler {}
 
-impl<R: Resource> Handler for SearchableHandler<R> {
+impl Handler for DeletedTalentsHandler {
     fn handle(&self, req: &mut Request) -> IronResult<Response> {
+        let _span = telemetry::request_span(req);
+        let _request = context::enter(RequestContext::new(req, &self.config.es.index));
         let ref lifetimes = self.config.tokens.lifetime;
-        if !self.is_authorized(&self.config.auth, &req.headers, lifetimes.read) {
+        if !self.is_authorized(&self.config.auth, &req.headers, lifetimes.write) {
             unauthorized!();
         }
 
+        let _permit = limit_concurrency!(self.version, self.config, req, EndpointClass::Search);
+
         let client = req.get::<Write<SharedClient>>().unwrap();
-        let params = try_or_422!(req.get_ref::<Params>());
+        let params = try_or_422!(self.version, req.get_ref::<Params>());
+        context::set_params(params);
 
-        let response = R::search(&mut client.lock().unwrap(), &*self.config.es.index, params);
+        let offset = param_u64(params, "offset", 0);
+        let per_page = param_u64(params, "per_page", 10);
+        try_or_fail!(self.version, check_window(&self.config, offset, per_page, "offset + per_page"));
+
+        let deleted = try_or_fail!(self.version, Talent::deleted(
+            &mut client.lock().unwrap(),
+            &IndexSet::from_config(&self.config.es),
+            offset,
+            per_page
+        ));
 
         let content_type = "application/json".parse::<Mime>().unwrap();
         Ok(Response::with((
             content_type,
             status::Ok,
-            try_or_422!(serde_json::to_string(&response)),
+            try_or_422!(self.version, self.version.data_body(&deleted)),
         )))
     }
 }
 
-pub struct IndexableHandler<R> {
+/// Return how many distinct skills, locations and languages the talents of an
+/// index have, and the most frequent ones, for the checks following a reindex.
+pub struct DistinctCountsHandler {
     config: Config,
-    resource: PhantomData<R>,
+    version: ApiVersion,
 }
 
-impl<R: Resource> IndexableHandler<R> {
+impl DistinctCountsHandler {
     pub fn new(config: Config) -> Self {
-        IndexableHandler::<R> {
-            resource: PhantomData,
+        DistinctCountsHandler {
             config: config,
+            version: ApiVersion::V1,
         }
     }
+
+    pub fn with_version(mut self, version: ApiVersion) -> Self {
+        self.version = version;
+        self
+    }
 }
 
-impl<R: Resource> WritableEndpoint for IndexableHandler<R> {}
+impl WritableEndpoint for DistinctCountsHandler {}
 
-impl<R: Resource> Handler for IndexableHandler<R> {
+impl Handler for DistinctCountsHandler {
     fn handle(&self, req: &mut Request) -> IronResult<Response> {
+        let _span = telemetry::request_span(req);
+        let _request = context::enter(RequestContext::new(req, &self.config.es.index));
         let ref lifetimes = self.config.tokens.lifetime;
         if !self.is_authorized(&self.config.auth, &req.headers, lifetimes.write) {
             unauthorized!();
         }
 
-        let mut payload = String::new();
-        req.body.read_to_string(&mut payload).unwrap();
+        let _permit = limit_concurrency!(self.version, self.config, req, EndpointClass::Search);
 
-        let resources: Vec<R> = try_or_422!(serde_json::from_str(&payload));
         let client = req.get::<Write<SharedClient>>().unwrap();
-        try_or_422!(R::index(
+        let params = try_or_422!(self.version, req.get_ref::<Params>());
+        context::set_params(params);
+
+        let index = match params.get("index") {
+            Some(&Value::String(ref index)) => {
+                try_or_fail!(self.version, self.config.es.check_searchable(index));
+                index.to_owned()
+            }
+            _ => self.config.es.index.to_owned(),
+        };
+
+        let top = param_u64(params, "top", 10);
+        if top == 0 {
+            bad_request!(self.version, "top must be positive".to_owned());
+        }
+
+        let counts = try_or_fail!(self.version, Talent::distinct_counts(
             &mut client.lock().unwrap(),
-            &*self.config.es.index,
-            resources
+            &*index,
+            top
         ));
 
-        Ok(Response::with(status::Created))
+        let content_type = "application/json".parse::<Mime>().unwrap();
+        Ok(Response::with((
+            content_type,
+            status::Ok,
+            try_or_422!(self.version, self.version.data_body(&counts)),
+        )))
     }
 }
 
-pub struct DeletableHandler<R> {
+/// Return (`GET`) or change (`PUT`) the maintenance mode.
+pub struct MaintenanceHandler {
     config: Config,
-    resource: PhantomData<R>,
 }
 
-impl<R: Resource> DeletableHandler<R> {
+impl MaintenanceHandler {
     pub fn new(config: Config) -> Self {
-        DeletableHandler::<R> {
-            resource: PhantomData,
-            config: config,
-        }
+        MaintenanceHandler { config: config }
     }
 }
 
-impl<R: Resource> WritableEndpoint for DeletableHandler<R> {}
+impl ReadableEndpoint for MaintenanceHandler {}
+impl WritableEndpoint for MaintenanceHandler {}
 
-impl<R: Resource> Handler for DeletableHandler<R> {
+impl Handler for MaintenanceHandler {
     fn handle(&self, req: &mut Request) -> IronResult<Response> {
         let ref lifetimes = self.config.tokens.lifetime;
-        if !self.is_authorized(&self.config.auth, &req.headers, lifetimes.write) {
+        let authorized = if req.method == Get {
+            ReadableEndpoint::is_authorized(self, &self.config.auth, &req.headers, lifetimes.read)
+        } else {
+            WritableEndpoint::is_authorized(self, &self.config.auth, &req.headers, lifetimes.write)
+        };
+
+        if !authorized {
             unauthorized!();
         }
 
-        let client = req.get::<Write<SharedClient>>().unwrap();
-        let mut client = client.lock().unwrap();
-
-        let ref id = try_or_422!(
-            req.extensions
-                .get::<Router>()
-                .unwrap()
-                .find("id")
-                .ok_or("DELETE#:id not found")
-        );
+        let state = req.get::<State<MaintenanceState>>().unwrap();
 
-        match R::delete(&mut client, id, &*self.config.es.index) {
-            Ok(_) => Ok(Response::with(status::NoContent)),
-            Err(e) => {
-                let error_message = e.to_string();
-                error!("{}", error_message);
+        if req.method != Get {
+            let payload = read_json_payload!(ApiVersion::V1, req);
 
-                let content_type = "application/json".parse::<Mime>().unwrap();
-                Ok(Response::with((
-                    content_type,
-                    status::UnprocessableEntity,
-                    error_message,
-                )))
-            }
+            let maintenance: Maintenance = try_or_422!(serde_json::from_str(&payload));
+            warn!("{}", maintenance);
+            *state.write().unwrap() = maintenance;
         }
+
+        let maintenance = state.read().unwrap().to_owned();
+        let content_type = "application/json".parse::<Mime>().unwrap();
+        Ok(Response::with((
+            content_type,
+            status::Ok,
+            try_or_422!(serde_json::to_string(&maintenance)),
+        )))
     }
 }
 
-pub struct ResettableHandler<R> {
+/// List the search features supported by `GET /talents`.
+pub struct FeaturesHandler {
     config: Config,
-    resource: PhantomData<R>,
+    version: ApiVersion,
 }
 
-impl<R: Resource> ResettableHandler<R> {
+impl FeaturesHandler {
     pub fn new(config: Config) -> Self {
-        ResettableHandler::<R> {
-            resource: PhantomData,
+        FeaturesHandler {
             config: config,
+            version: ApiVersion::V1,
         }
     }
+
+    pub fn with_version(mut self, version: ApiVersion) -> Self {
+        self.version = version;
+        self
+    }
 }
 
-impl<R: Resource> WritableEndpoint for ResettableHandler<R> {}
+impl ReadableEndpoint for FeaturesHandler {}
 
-impl<R: Resource> Handler for ResettableHandler<R> {
+impl Handler for FeaturesHandler {
     fn handle(&self, req: &mut Request) -> IronResult<Response> {
         let ref lifetimes = self.config.tokens.lifetime;
-        if !self.is_authorized(&self.config.auth, &req.headers, lifetimes.write) {
+        if !self.is_authorized(&self.config.auth, &req.headers, lifetimes.read) {
             unauthorized!();
         }
 
-        let client = req.get::<Write<SharedClient>>().unwrap();
-        let mut client = client.lock().unwrap();
-        match R::reset_index(&mut client, &*self.config.es.index) {
-            Ok(_) => Ok(Response::with(status::NoContent)),
-            Err(e) => {
-                let error_message = e.to_string();
-                error!("{}", error_message);
+        let content_type = "application/json".parse::<Mime>().unwrap();
+        Ok(Response::with((
+            content_type,
+            status::Ok,
+            try_or_422!(self.version, self.version.data_body(&features::describe())),
+        )))
+    }
+}
 
-                let content_type = "application/json".parse::<Mime>().unwrap();
-                Ok(Response::with((
-                    content_type,
-                    status::UnprocessableEntity,
-                    error_message,
-                )))
-            }
+/// Describe the API as an OpenAPI 3 document (see `openapi`).
+pub struct OpenApiHandler;
+
+impl Handler for OpenApiHandler {
+    fn handle(&self, _: &mut Request) -> IronResult<Response> {
+        let content_type = "application/json".parse::<Mime>().unwrap();
+        let document = openapi::document(env!("CARGO_PKG_VERSION"));
+        Ok(Response::with((content_type, status::Ok, document.to_string())))
+    }
+}
+
+/// Expose the histograms collected by `metrics` to Prometheus.
+pub struct MetricsHandler;
+
+impl Handler for MetricsHandler {
+    fn handle(&self, _: &mut Request) -> IronResult<Response> {
+        let content_type = "text/plain; version=0.0.4".parse::<Mime>().unwrap();
+        Ok(Response::with((content_type, status::Ok, metrics::render())))
+    }
+}
+
+/// Rewrite the paths that differ from the routed ones only by their trailing slashes
+/// or by the case of their fixed segments (i.e. `/Talents/`) before they're routed.
+struct PathNormalizationMiddleware;
+
+impl BeforeMiddleware for PathNormalizationMiddleware {
+    fn before(&self, req: &mut Request) -> IronResult<()> {
+        if let Some(path) = openapi::normalize_path(&req.url.path()) {
+            req.url.as_mut().set_path(&path);
+        }
+
+        Ok(())
+    }
+}
+
+/// Turn the `404 Not Found` given by the router to the paths it serves with other
+/// methods into `405 Method Not Allowed`, listing those methods in `Allow`.
+/// `read_only` leaves out the writes, which the read-only replicas don't route.
+struct MethodNotAllowedMiddleware {
+    read_only: bool,
+}
+
+impl AfterMiddleware for MethodNotAllowedMiddleware {
+    fn catch(&self, req: &mut Request, err: IronError) -> IronResult<Response> {
+        if err.response.status != Some(status::NotFound) {
+            return Err(err);
+        }
+
+        let segments = req.url.path();
+        let allowed = openapi::allowed_methods(&segments, self.read_only);
+        if allowed.is_empty() || allowed.contains(&req.method.to_string()) {
+            return Err(err);
         }
+
+        let version = if segments.first() == Some(&"v2") {
+            ApiVersion::V2
+        } else {
+            ApiVersion::V1
+        };
+        let message = format!("{} isn't allowed on /{}", req.method, segments.join("/"));
+        let mut response = version.error_response(status::MethodNotAllowed, message);
+        response.headers.set_raw("Allow", vec![allowed.join(", ").into_bytes()]);
+
+        Ok(response)
     }
 }
 
@@ -299,57 +2746,130 @@ impl AfterMiddleware for CorsMiddleware {
     }
 }
 
+/// Return the timeouts of the listener configured in `http`,
+/// where 0 disables a timeout and the omitted ones keep Iron's default.
+fn timeouts(http: &HttpConfig) -> iron::Timeouts {
+    let timeout = |seconds: Option<u64>, default: Option<Duration>| match seconds {
+        Some(0) => None,
+        Some(seconds) => Some(Duration::from_secs(seconds)),
+        None => default,
+    };
+    let defaults = iron::Timeouts::default();
+
+    iron::Timeouts {
+        keep_alive: timeout(http.keep_alive_timeout, defaults.keep_alive),
+        read: timeout(http.read_timeout, defaults.read),
+        write: timeout(http.write_timeout, defaults.write),
+    }
+}
+
+/// The worker threads of the admin listener, which serves few requests.
+const ADMIN_THREADS: usize = 4;
+
 impl Server {
     pub fn new(config: Config) -> Self {
-        Server { config: config }
+        let maintenance = Arc::new(RwLock::new(config.maintenance.to_owned()));
+        Server {
+            config: config,
+            maintenance: maintenance,
+        }
+    }
+
+    /// Return the maintenance mode, shared with the interfaces served next to
+    /// the HTTP API (i.e. gRPC) so that `PUT /maintenance` reaches them too.
+    pub fn maintenance(&self) -> Arc<RwLock<Maintenance>> {
+        self.maintenance.to_owned()
     }
 
     pub fn start(&self, router: Router) {
+        self.start_with_admin(router, None)
+    }
+
+    /// Serve `router`, and `admin` on `http.admin` when both are given.
+    pub fn start_with_admin(&self, router: Router, admin: Option<Router>) {
         start_logging(&self.config).unwrap();
 
-        let host = format!("{}:{}", self.config.http.host, self.config.http.port);
+        if let Some(ref config) = self.config.telemetry {
+            if let Err(error) = telemetry::init(config) {
+                error!("Traces won't be exported: {}", error);
+            }
+        }
 
-        println!(
-            "Searchspot v{}\n{}\n",
-            env!("CARGO_PKG_VERSION"),
-            self.config
-        );
+        let host = format!("{}:{}", self.config.http.host, self.config.http.port);
 
-        let client = Client::new(&*self.config.to_owned().es.url).unwrap();
+        let mut client = backends::from_config(&self.config.es).unwrap();
+        Banner::new(&self.config, &mut *client).log();
 
         let mut chain = Chain::new(router);
         chain.link(Write::<SharedClient>::both(client));
-        chain.link(HTTPLogger::new(None));
+        chain.link(State::<MaintenanceState>::both(self.maintenance()));
+        if self.config.http.normalize_paths {
+            chain.link_before(PathNormalizationMiddleware);
+        }
+        chain.link_before(MaintenanceMiddleware);
+        chain.link(RequestLogger::new(self.config.request_log.to_owned()));
+        chain.link_after(MethodNotAllowedMiddleware {
+            read_only: self.config.read_only,
+        });
         chain.link_after(CorsMiddleware);
 
-        let thread_multiplier = self.config.server_threads_multiplier;
-        let mut threads = thread_multiplier * ::num_cpus::get();
+        let server = Iron {
+            handler: chain,
+            timeouts: timeouts(&self.config.http),
+            threads: self.config.server_threads(),
+        };
+
+        // kept for as long as the public listener, which never returns
+        let _admin = match (admin, self.config.http.admin.as_ref()) {
+            (Some(router), Some(config)) => Some(self.listen_admin(router, config)),
+            _ => None,
+        };
+
+        server.http(&*host).unwrap();
+    }
+
+    /// Serve the admin endpoints of `router` as configured in `http.admin`,
+    /// with a client of their own and the maintenance mode of the public listener.
+    fn listen_admin(&self, router: Router, config: &HttpAdmin) -> iron::Listening {
+        let client = backends::from_config(&self.config.es).unwrap();
 
-        if let Some(limit) = self.config.server_max_threads {
-            threads = ::std::cmp::min(threads, limit);
+        let mut chain = Chain::new(router);
+        chain.link(Write::<SharedClient>::both(client));
+        chain.link(State::<MaintenanceState>::both(self.maintenance()));
+        if self.config.http.normalize_paths {
+            chain.link_before(PathNormalizationMiddleware);
         }
+        chain.link_before(MaintenanceMiddleware);
+        chain.link(RequestLogger::new(self.config.request_log.to_owned()));
 
         let server = Iron {
             handler: chain,
-            timeouts: iron::Timeouts::default(),
-            threads: threads,
+            timeouts: timeouts(&self.config.http),
+            threads: ADMIN_THREADS,
         };
 
-        server.http(&*host).unwrap();
+        server.http(&*format!("{}:{}", config.host, config.port)).unwrap()
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use resource::Resource;
+    use backends::{Refresh, SearchBackend};
+    use error::SearchspotError;
+    use mapping::IndexDefinition;
+    use resource::{
+        self, Action, BulkResult, DeleteResult, EsError, MappingResult, Resource, Settings,
+    };
+    use resources::TalentId;
 
+    use config::Config;
+    use test_support;
+
+    use iron::Headers;
+    use oath::{totp_raw_now, HashType};
     use params::Map;
 
-    use rs_es::error::EsError;
-    use rs_es::operations::bulk::{Action, BulkResult};
-    use rs_es::operations::delete::DeleteResult;
-    use rs_es::operations::mapping::{MappingOperation, MappingResult};
-    use rs_es::Client;
+    use super::PayloadFormat;
 
     #[derive(Serialize, Deserialize, Clone, Debug)]
     pub struct TestResource {
@@ -358,36 +2878,498 @@ mod tests {
 
     const ES_TYPE: &'static str = "test_resource";
 
+    #[test]
+    fn test_error_body() {
+        use server::ApiVersion;
+        use iron::status;
+
+        assert_eq!(
+            ApiVersion::V1.error_body(status::BadRequest, "nope".to_owned()),
+            "{\"error\":\"nope\"}"
+        );
+        assert_eq!(
+            ApiVersion::V2.error_body(status::BadRequest, "nope".to_owned()),
+            "{\"error\":{\"message\":\"nope\",\"status\":400}}"
+        );
+    }
+
+    #[test]
+    fn test_failure_response() {
+        use server::ApiVersion;
+        use iron::status;
+        use resource::MappingConflict;
+
+        let status_of = |error| ApiVersion::V2.failure_response(error).status;
+        assert_eq!(
+            status_of(SearchspotError::BadRequest("nope".to_owned())),
+            Some(status::BadRequest)
+        );
+        assert_eq!(status_of(SearchspotError::NotFound("nope".to_owned())), Some(status::NotFound));
+        assert_eq!(
+            status_of(SearchspotError::EsUnavailable(EsError::EsServerError("down".to_owned()))),
+            Some(status::ServiceUnavailable)
+        );
+        assert_eq!(status_of(SearchspotError::Unauthorized), Some(status::Unauthorized));
+
+        let conflict = MappingConflict {
+            id: "42".to_owned(),
+            field: Some("weight".to_owned()),
+            value: Some("heavy".to_owned()),
+            expected: Some("integer".to_owned()),
+            reason: "failed to parse field [weight] of type [integer]".to_owned(),
+        };
+        assert_eq!(
+            status_of(SearchspotError::MappingConflict(vec![conflict])),
+            Some(status::UnprocessableEntity)
+        );
+    }
+
+    #[test]
+    fn test_search_body() {
+        use server::ApiVersion;
+        use params::Value;
+
+        let results = json!({ "total": 25, "talents": [] });
+        let mut params = Map::new();
+        params.assign("offset", Value::String("10".to_owned())).unwrap();
+
+        assert_eq!(
+            ApiVersion::V1.search_body(&results, &params).unwrap(),
+            results.to_string()
+        );
+
+        let body: ::serde_json::Value =
+            ::serde_json::from_str(&ApiVersion::V2.search_body(&results, &params).unwrap()).unwrap();
+        assert_eq!(body["data"], results);
+        assert_eq!(
+            body["meta"],
+            json!({ "offset": 10, "per_page": 10, "total": 25, "next_offset": 20 })
+        );
+
+        params.assign("per_page", Value::U64(15)).unwrap();
+        let body: ::serde_json::Value =
+            ::serde_json::from_str(&ApiVersion::V2.search_body(&results, &params).unwrap()).unwrap();
+        assert_eq!(body["meta"]["next_offset"], json!(null));
+
+        params.assign("offset", Value::U64(::std::u64::MAX)).unwrap();
+        let body: ::serde_json::Value =
+            ::serde_json::from_str(&ApiVersion::V2.search_body(&results, &params).unwrap()).unwrap();
+        assert_eq!(body["meta"]["next_offset"], json!(null));
+    }
+
+    #[test]
+    fn test_check_window() {
+        let mut config = test_support::CONFIG.to_owned();
+        config.es.max_result_window = 100;
+
+        assert!(super::check_window(&config, 90, 10, "offset + per_page").is_ok());
+        match super::check_window(&config, 91, 10, "offset + per_page") {
+            Err(SearchspotError::BadRequest(message)) => {
+                assert!(message.starts_with("offset + per_page can't exceed 100"))
+            }
+            result => panic!("Unexpected result: {:?}", result),
+        }
+
+        // adding them up would overflow, rather than exceed the window
+        match super::check_window(&config, ::std::u64::MAX, 10, "from + size") {
+            Err(SearchspotError::Unprocessable(message)) => {
+                assert_eq!(message, "from + size is too large")
+            }
+            result => panic!("Unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_param_interval() {
+        let interval = |query: &str| super::param_interval(&test_support::parse_query(query), 0.1);
+
+        assert_eq!(interval("job_id=1").unwrap(), 0.1);
+        assert_eq!(interval("interval=0.5").unwrap(), 0.5);
+        for query in ["interval=0", "interval=-1", "interval=wide"].iter() {
+            assert_eq!(interval(*query).unwrap_err().status(), 422);
+        }
+
+        assert!(resource::check_histogram(0.001, Some(0.0), Some(0.999)).is_ok());
+        assert!(resource::check_histogram(0.001, Some(0.0), Some(1.0)).is_err());
+        assert!(resource::check_histogram(0.0001, None, None).is_ok());
+    }
+
+    #[test]
+    fn test_parse_ids() {
+        assert_eq!(
+            super::parse_ids::<String>("[1, \"2\"]").unwrap(),
+            vec!["1".to_owned(), "2".to_owned()]
+        );
+        assert_eq!(super::parse_ids::<u32>("[1, \"2\"]").unwrap(), vec![1, 2]);
+        assert!(super::parse_ids::<u32>("[\"x\"]").is_err());
+        assert!(super::parse_ids::<String>("[null]").is_err());
+        assert!(super::parse_ids::<String>("{}").is_err());
+
+        let uuid = "0b6c3bb4-5c2a-4f5a-9a3e-3fdc1c9d4e21";
+        assert_eq!(
+            super::parse_ids::<TalentId>(&format!("[1, \"{}\"]", uuid)).unwrap(),
+            vec![TalentId::Numeric(1), TalentId::Uuid(uuid.to_owned())]
+        );
+    }
+
+    #[test]
+    fn test_query_params() {
+        use params::Value;
+
+        let params = super::query_params(Some("epoch=2018-01-01&presented_talents[]=1&presented_talents[]=2"))
+            .unwrap();
+        assert_eq!(params["epoch"], Value::String("2018-01-01".to_owned()));
+        assert_eq!(
+            params["presented_talents"],
+            Value::Array(vec![Value::String("1".to_owned()), Value::String("2".to_owned())])
+        );
+        assert!(super::query_params(None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_payload_format() {
+        let format = |content_type: Option<&str>| {
+            let mut headers = Headers::new();
+            if let Some(content_type) = content_type {
+                headers.set_raw("Content-Type", vec![content_type.as_bytes().to_vec()]);
+            }
+            PayloadFormat::from_headers(&headers)
+        };
+
+        assert_eq!(format(None), Some(PayloadFormat::Json));
+        assert_eq!(format(Some("application/json")), Some(PayloadFormat::Json));
+        assert_eq!(format(Some("application/json; charset=utf-8")), Some(PayloadFormat::Json));
+        assert_eq!(format(Some("text/plain")), None);
+        assert_eq!(format(Some("application/x-www-form-urlencoded")), None);
+
+        if cfg!(feature = "msgpack") {
+            assert_eq!(format(Some("application/msgpack")), Some(PayloadFormat::Msgpack));
+        } else {
+            assert_eq!(format(Some("application/msgpack")), None);
+        }
+    }
+
+    #[test]
+    fn test_timeouts() {
+        use config::HTTP;
+        use iron::Timeouts;
+        use std::time::Duration;
+
+        let mut http = HTTP {
+            host: "localhost".to_owned(),
+            port: 3000,
+            threads: None,
+            normalize_paths: true,
+            read_timeout: None,
+            write_timeout: None,
+            keep_alive_timeout: None,
+            admin: None,
+        };
+        let defaults = Timeouts::default();
+        let timeouts = super::timeouts(&http);
+        assert_eq!(timeouts.read, defaults.read);
+        assert_eq!(timeouts.write, defaults.write);
+        assert_eq!(timeouts.keep_alive, defaults.keep_alive);
+
+        http.read_timeout = Some(10);
+        http.keep_alive_timeout = Some(0);
+        let timeouts = super::timeouts(&http);
+        assert_eq!(timeouts.read, Some(Duration::from_secs(10)));
+        assert_eq!(timeouts.write, defaults.write);
+        assert_eq!(timeouts.keep_alive, None);
+    }
+
+    #[test]
+    fn test_propagate_deadline() {
+        use params::Value;
+
+        let deadline = |value: &str| {
+            let mut headers = Headers::new();
+            headers.set_raw("X-Request-Deadline", vec![value.as_bytes().to_vec()]);
+            headers
+        };
+        let timeout = |params: &Map| match params.get("timeout_ms") {
+            Some(&Value::String(ref timeout)) => Some(timeout.to_owned()),
+            _ => None,
+        };
+
+        let mut params = Map::new();
+        super::propagate_deadline(&mut params, &Headers::new(), 1000).unwrap();
+        assert_eq!(timeout(&params), None);
+
+        super::propagate_deadline(&mut params, &deadline("1250"), 1000).unwrap();
+        assert_eq!(timeout(&params), Some("250".to_owned()));
+
+        // the timeout given by the caller wins
+        super::propagate_deadline(&mut params, &deadline("5000"), 1000).unwrap();
+        assert_eq!(timeout(&params), Some("250".to_owned()));
+
+        let mut params = Map::new();
+        super::propagate_deadline(&mut params, &deadline("500"), 1000).unwrap();
+        assert_eq!(timeout(&params), Some("1".to_owned()));
+
+        let mut params = Map::new();
+        super::propagate_deadline(&mut params, &deadline("tomorrow"), 1000).unwrap();
+        assert_eq!(timeout(&params), None);
+
+        // the deadlines too far from now are rejected rather than overflowing
+        for value in &["-9223372036854775808", "99999999999999999999"] {
+            match super::propagate_deadline(&mut params, &deadline(value), 1000) {
+                Err(SearchspotError::Unprocessable(message)) => {
+                    assert_eq!(message, "X-Request-Deadline is out of range")
+                }
+                result => panic!("Unexpected result: {:?}", result),
+            }
+        }
+        assert_eq!(timeout(&params), None);
+    }
+
+    #[test]
+    fn test_propagate_ranking() {
+        use config::Ranking;
+        use params::Value;
+
+        let mut params = Map::new();
+        super::propagate_ranking(&mut params, &Ranking::default());
+        assert!(params.get("weight_factor").is_none());
+        assert!(params.get("recency_scale").is_none());
+
+        let ranking = Ranking {
+            weight_factor: 0.5,
+            recency_scale: "3d".to_owned(),
+        };
+        params.assign("recency_scale", Value::String("1d".to_owned())).unwrap();
+        super::propagate_ranking(&mut params, &ranking);
+        assert_eq!(params.get("weight_factor"), Some(&Value::String("0.5".to_owned())));
+        // the recency given by the caller wins
+        assert_eq!(params.get("recency_scale"), Some(&Value::String("1d".to_owned())));
+    }
+
+    #[test]
+    fn test_propagate_sort() {
+        use config::Search;
+        use params::Value;
+
+        let mut params = Map::new();
+        super::propagate_sort(&mut params, &Search::default());
+        assert!(params.get("sort_by").is_none());
+
+        let search = Search {
+            sort: vec!["weight:desc".to_owned(), "id:asc".to_owned()],
+        };
+        super::propagate_sort(&mut params, &search);
+        assert_eq!(
+            params.get("sort_by"),
+            Some(&Value::Array(vec![
+                Value::String("weight:desc".to_owned()),
+                Value::String("id:asc".to_owned()),
+            ]))
+        );
+
+        // the sort given by the caller wins
+        let mut params = Map::new();
+        params.assign("sort_by[]", Value::String("id:desc".to_owned())).unwrap();
+        super::propagate_sort(&mut params, &search);
+        assert_eq!(
+            params.get("sort_by"),
+            Some(&Value::Array(vec![Value::String("id:desc".to_owned())]))
+        );
+    }
+
+    #[test]
+    fn test_locking_client() {
+        use backends::memory::Memory;
+        use resources::Talent;
+        use std::sync::{Arc, Mutex};
+
+        let index = "server_locking_talents";
+        let memory: Box<dyn SearchBackend> = Box::new(Memory::new());
+        let shared = Arc::new(Mutex::new(memory));
+        let mut client = super::LockingClient::new(shared.clone());
+        Talent::reset_index(&mut client, index, None).unwrap();
+        Talent::index(&mut client, index, vec![test_support::talent(1, json!({}))]).unwrap();
+
+        // the shared client is only locked while a request is sent
+        assert!(shared.try_lock().is_ok());
+        let report = super::migrations::migrate::<Talent>(&mut client, index, None).unwrap();
+        assert_eq!(report.from, report.to);
+        assert!(shared.try_lock().is_ok());
+        let epoch = test_support::parse_query("epoch=2018-01-01T00:00:00Z");
+        assert_eq!(Talent::search(&mut **shared.lock().unwrap(), index, &epoch).total, 1);
+    }
+
+    #[test]
+    fn test_anonymized_export() {
+        use backends::memory::Memory;
+        use concurrency::EndpointClass;
+        use config::Concurrency;
+        use context::{self, RequestContext};
+        use iron::response::WriteBody;
+        use resources::{Talent, TalentSearchParams};
+        use std::sync::{Arc, Mutex};
+
+        let index = "server_export_talents";
+        let avatar_url = "https://example.com/avatar.png";
+        let mut memory: Box<dyn SearchBackend> = Box::new(Memory::new());
+        Talent::reset_index(&mut *memory, index, None).unwrap();
+        let talent = test_support::talent(1, json!({ "avatar_url": avatar_url }));
+        Talent::index(&mut *memory, index, vec![talent]).unwrap();
+        let client = Arc::new(Mutex::new(memory));
+
+        let export = |anonymized: bool| {
+            let query = test_support::parse_query("epoch=2018-01-01T00:00:00Z");
+            let params = TalentSearchParams::from_map(&query).unwrap();
+            let mut export = super::TalentExport {
+                _permit: EndpointClass::Search.try_acquire(&Concurrency::default()).unwrap(),
+                context: Some(RequestContext {
+                    anonymized: anonymized,
+                    ..RequestContext::default()
+                }),
+                client: client.clone(),
+                indexes: vec![index.to_owned()],
+                body: Talent::export_body(&params, "2018-01-01T00:00:00Z", 10),
+                params: params,
+            };
+
+            // written once the handler returned, outside of the context of the request
+            let mut body = vec![];
+            export.write_body(&mut body).unwrap();
+            assert!(context::current().is_none());
+
+            let results: ::serde_json::Value = ::serde_json::from_slice(&body).unwrap();
+            results[0]["talent"]["avatar_url"].as_str().unwrap().to_owned()
+        };
+
+        assert_eq!(export(false), avatar_url);
+        let anonymized = export(true);
+        assert!(anonymized != avatar_url);
+        assert_eq!(anonymized.len(), 64);
+    }
+
+    struct SearchEndpoint;
+    impl super::ReadableEndpoint for SearchEndpoint {}
+    impl super::WritableEndpoint for SearchEndpoint {}
+
+    fn authorization(secret: &str, lifetime: u64) -> Headers {
+        let token = totp_raw_now(secret.as_bytes(), 6, 0, lifetime, &HashType::SHA1);
+        let mut headers = Headers::new();
+        headers.set_raw("Authorization", vec![format!("token {}", token).into_bytes()]);
+        headers
+    }
+
+    #[test]
+    fn test_full_view_authorization() {
+        let mut config = test_support::CONFIG.to_owned();
+        config.auth.enabled = true;
+        config.auth.read = "readsecret234567".to_owned();
+        config.auth.write = "writesecret34567".to_owned();
+        let lifetimes = config.tokens.lifetime.to_owned();
+        let read = authorization(&config.auth.read, lifetimes.read);
+        let write = authorization(&config.auth.write, lifetimes.write);
+
+        let authorized = |config: &Config, headers: &Headers, query: &str| {
+            let full_view = super::is_full_view(Some(query));
+            super::is_search_authorized(&SearchEndpoint, config, headers, full_view)
+        };
+
+        assert!(authorized(&config, &read, "keywords=rust"));
+        assert!(!authorized(&config, &write, "keywords=rust"));
+        assert!(!authorized(&config, &Headers::new(), "keywords=rust"));
+
+        // the complete documents are granted by the write tokens alone
+        assert!(authorized(&config, &write, "keywords=rust&view=full"));
+        assert!(!authorized(&config, &read, "keywords=rust&view=full"));
+        assert!(authorized(&config, &read, "view=compact"));
+
+        // and never anonymized
+        config.auth.anonymized = true;
+        assert!(!authorized(&config, &write, "view=full"));
+    }
+
+    #[test]
+    fn test_refresh_param() {
+        assert_eq!(super::refresh_param(None), Ok(Refresh::Lazy));
+        assert_eq!(super::refresh_param(Some("upsert=true")), Ok(Refresh::Lazy));
+        assert_eq!(super::refresh_param(Some("upsert=true&refresh=true")), Ok(Refresh::Immediate));
+        assert_eq!(super::refresh_param(Some("refresh")), Ok(Refresh::Immediate));
+        assert_eq!(super::refresh_param(Some("refresh=wait_for")), Ok(Refresh::WaitFor));
+        assert_eq!(super::refresh_param(Some("refresh=false")), Ok(Refresh::Lazy));
+        assert!(super::refresh_param(Some("refresh=soon")).is_err());
+    }
+
+    #[test]
+    fn test_is_create_only() {
+        assert_eq!(super::is_create_only(None), Ok(false));
+        assert_eq!(super::is_create_only(Some("op_type=index&refresh=true")), Ok(false));
+        assert_eq!(super::is_create_only(Some("refresh=true&op_type=create")), Ok(true));
+        assert!(super::is_create_only(Some("op_type=upsert")).is_err());
+    }
+
+    #[test]
+    fn test_is_upsert() {
+        assert_eq!(super::is_upsert(None), Ok(false));
+        assert_eq!(super::is_upsert(Some("refresh=true&upsert=true")), Ok(true));
+        assert_eq!(super::is_upsert(Some("upsert")), Ok(true));
+        assert_eq!(super::is_upsert(Some("upsert=false")), Ok(false));
+        assert!(super::is_upsert(Some("upsert=yes")).is_err());
+    }
+
     impl Resource for TestResource {
         type Results = Vec<u32>;
-
-        fn search(_: &mut Client, _: &str, _: &Map) -> Self::Results {
-            vec![]
+        type Id = u32;
+
+        fn try_search(
+            _: &mut dyn SearchBackend,
+            _: &str,
+            _: &Map,
+        ) -> Result<Self::Results, SearchspotError> {
+            Ok(vec![])
         }
 
         fn index(
-            es: &mut Client,
+            es: &mut dyn SearchBackend,
             index: &str,
             resources: Vec<Self>,
         ) -> Result<BulkResult, EsError> {
-            es.bulk(&resources
-                .into_iter()
-                .map(|r| {
-                    let id = r.id.to_string();
-                    Action::index(r).with_id(id)
-                })
-                .collect::<Vec<Action<TestResource>>>())
-                .with_index(index)
-                .with_doc_type(ES_TYPE)
-                .send()
+            es.bulk(
+                index,
+                ES_TYPE,
+                resources
+                    .into_iter()
+                    .map(|r| {
+                        let id = r.id.to_string();
+                        Action::index(r).with_id(id)
+                    })
+                    .collect(),
+            )
+        }
+
+        fn delete(
+            es: &mut dyn SearchBackend,
+            id: &u32,
+            index: &str,
+        ) -> Result<DeleteResult, EsError> {
+            es.delete(index, ES_TYPE, &id.to_string())
         }
 
-        fn delete(es: &mut Client, id: &str, index: &str) -> Result<DeleteResult, EsError> {
-            es.delete(index, ES_TYPE, id).send()
+        fn delete_many(
+            es: &mut dyn SearchBackend,
+            ids: &[u32],
+            index: &str,
+        ) -> Result<BulkResult, EsError> {
+            let actions = ids
+                .iter()
+                .map(|id| Action::<TestResource>::delete(id.to_string()))
+                .collect();
+            es.bulk(index, ES_TYPE, actions)
         }
 
-        fn reset_index(mut es: &mut Client, index: &str) -> Result<MappingResult, EsError> {
-            MappingOperation::new(&mut es, index).send()
+        fn reset_index(
+            es: &mut dyn SearchBackend,
+            index: &str,
+            _: Option<&IndexDefinition>,
+        ) -> Result<MappingResult, EsError> {
+            es.create_index(index, &json!({}), &Settings::default())
         }
     }
 }
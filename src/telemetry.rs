@@ -0,0 +1,98 @@
+//! OpenTelemetry tracing: every request gets a span, child of the one described
+//! by the W3C `traceparent` header sent by the caller, and so do the building
+//! of the queries and the round trips to the search backend.
+//!
+//! The spans are exported over OTLP/HTTP when `[telemetry]` is configured,
+//! otherwise they're just dropped.
+
+use iron::{Headers, Request};
+
+use opentelemetry::global::{self, BoxedTracer};
+use opentelemetry::propagation::{Extractor, TextMapPropagator};
+use opentelemetry::sdk::propagation::TraceContextPropagator;
+use opentelemetry::sdk::trace;
+use opentelemetry::sdk::Resource;
+use opentelemetry::trace::{SpanKind, TraceContextExt, Tracer};
+use opentelemetry::{Context, ContextGuard, KeyValue};
+use opentelemetry_otlp::{self, WithExportConfig};
+
+use config::Telemetry;
+
+use std::str;
+
+/// The headers carrying the trace context, as defined by W3C.
+const TRACE_HEADERS: [&'static str; 2] = ["traceparent", "tracestate"];
+
+/// Install the exporter described by `config` as the global tracer provider.
+pub fn init(config: &Telemetry) -> Result<(), String> {
+    let exporter = opentelemetry_otlp::new_exporter()
+        .http()
+        .with_endpoint(config.endpoint.to_owned());
+    let resource = Resource::new(vec![KeyValue::new(
+        "service.name",
+        config.service_name.to_owned(),
+    )]);
+
+    opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(exporter)
+        .with_trace_config(trace::config().with_resource(resource))
+        .install_simple()
+        .map(|_| ())
+        .map_err(|error| error.to_string())
+}
+
+fn tracer() -> BoxedTracer {
+    global::tracer("searchspot")
+}
+
+/// Read the trace context from the headers of an incoming request.
+struct HeaderExtractor<'a>(&'a Headers);
+
+impl<'a> Extractor for HeaderExtractor<'a> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0
+            .get_raw(key)
+            .and_then(|values| values.first())
+            .and_then(|value| str::from_utf8(value).ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        TRACE_HEADERS
+            .iter()
+            .cloned()
+            .filter(|header| self.0.get_raw(header).is_some())
+            .collect()
+    }
+}
+
+/// Start the span of `req` and make it the current one until
+/// the returned guard is dropped.
+pub fn request_span(req: &Request) -> ContextGuard {
+    let parent = TraceContextPropagator::new().extract(&HeaderExtractor(&req.headers));
+    let target = format!("/{}", req.url.path().join("/"));
+
+    let tracer = tracer();
+    let span = tracer
+        .span_builder(format!("{} {}", req.method, target))
+        .with_kind(SpanKind::Server)
+        .with_attributes(vec![
+            KeyValue::new("http.method", req.method.to_string()),
+            KeyValue::new("http.target", target),
+        ])
+        .start_with_context(&tracer, &parent);
+
+    parent.with_span(span).attach()
+}
+
+/// Run `f` inside a new span, child of the current one.
+pub fn in_span<T, F: FnOnce() -> T>(name: &'static str, attributes: Vec<KeyValue>, f: F) -> T {
+    let tracer = tracer();
+    let span = tracer
+        .span_builder(name)
+        .with_attributes(attributes)
+        .start(&tracer);
+
+    let _guard = Context::current_with_span(span).attach();
+    f()
+}
@@ -1,4 +1,5 @@
-use rs_es::query::Query;
+use query::Query;
+use resources::TalentId;
 
 pub trait VectorOfTerms<T> {
     /// Extract the elements inside `Vec<T>` into `Vec<Filter>`, if present.
@@ -39,11 +40,12 @@ macro_rules! build_vector_of_terms_impl {
 }
 
 build_vector_of_terms_impl!(i32);
+build_vector_of_terms_impl!(TalentId);
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use rs_es::query::Query;
+    use query::Query;
     use serde_json;
 
     #[test]
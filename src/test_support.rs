@@ -0,0 +1,183 @@
+//! Helpers for the integration tests of Searchspot, and of the services
+//! embedding it, so that they can search the same talents it's tested with.
+//!
+//! The fixtures are the JSON talents in `tests/talents`, whose `$id` is replaced
+//! by the id they're loaded with. Only available in the unit tests and when
+//! Searchspot is built with the `test_support` feature.
+
+use params::{Map, Value};
+use serde_json::{self, Value as JsonValue};
+
+use backends::{self, SearchBackend};
+use config::Config;
+use resources::Talent;
+
+use std::collections::HashMap;
+use std::env;
+use std::fmt::Debug;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The configuration the tests connect with, unless `SEARCHSPOT_TEST_CONFIG` says otherwise.
+pub const CONFIG_FILE: &'static str = concat!(env!("CARGO_MANIFEST_DIR"), "/examples/tests.toml");
+
+/// The directory of the talents fixtures.
+pub const FIXTURES_DIR: &'static str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/talents");
+
+/// The talents fixtures, by name.
+pub const FIXTURES: &'static [&'static str] = &[
+    "backend_rust",
+    "senior_java",
+    "rejected",
+    "sysadmin_with_clojure",
+    "amsterdam_game_dev",
+    "frontend_ember",
+    "zurich_python_dev",
+    "munich_web_dev",
+];
+
+lazy_static! {
+    pub static ref CONFIG: Config = Config::from_file(
+        env::var("SEARCHSPOT_TEST_CONFIG").unwrap_or(CONFIG_FILE.to_owned())
+    );
+}
+
+/// Connect to the backend of `CONFIG`.
+pub fn make_client() -> Box<dyn SearchBackend> {
+    backends::from_config(&CONFIG.es).unwrap()
+}
+
+/// Make the documents indexed into `index` visible to the searches.
+pub fn refresh_index(client: &mut dyn SearchBackend, index: &str) {
+    client.refresh(&[index]).unwrap();
+}
+
+/// Return the path of the fixture called `name` (i.e. `backend_rust`).
+pub fn fixture_path(name: &str) -> PathBuf {
+    Path::new(FIXTURES_DIR).join(format!("{}.json", name))
+}
+
+/// Load the talent stored as JSON in `path`, identified by `id`.
+pub fn load_talent<P: AsRef<Path> + Debug>(path: P, id: usize) -> Talent {
+    let path = path.as_ref();
+    let raw = fs::read_to_string(path).expect(&format!("Failed to read {:?}", path));
+    let processed = raw.replace("$id", &id.to_string());
+    serde_json::from_str(&processed).expect(&format!("Failed to deserialize file: {:?}", path))
+}
+
+/// Load every fixture, by name, identified by its position in `FIXTURES` starting from 1.
+pub fn load_fixtures() -> HashMap<&'static str, Talent> {
+    FIXTURES
+        .iter()
+        .enumerate()
+        .map(|(idx, name)| (*name, load_talent(fixture_path(name), idx + 1)))
+        .collect()
+}
+
+/// Build the talent identified by `id`, accepted in a batch running through January 2018
+/// and weighing as much as its id, whose other fields are empty unless given in `fields`
+/// (i.e. `json!({ "skills": ["Rust"] })`).
+pub fn talent(id: u32, fields: JsonValue) -> Talent {
+    let mut talent = json!({
+        "id":                            id,
+        "accepted":                      true,
+        "desired_work_roles":            [],
+        "desired_work_roles_experience": [],
+        "professional_experience":       "2..6",
+        "work_locations":                [],
+        "educations":                    [],
+        "current_location":              "",
+        "work_authorization":            "yes",
+        "skills":                        [],
+        "summary":                       "",
+        "headline":                      "",
+        "contacted_company_ids":         [],
+        "batch_starts_at":               "2018-01-01T00:00:00Z",
+        "batch_ends_at":                 "2018-02-01T00:00:00Z",
+        "added_to_batch_at":             "2018-01-01T00:00:00Z",
+        "weight":                        id,
+        "blocked_companies":             [],
+        "work_experiences":              [],
+        "avatar_url":                    "",
+        "salary_expectations":           [],
+        "latest_position":               "",
+        "languages":                     [],
+    });
+
+    if let JsonValue::Object(fields) = fields {
+        for (name, value) in fields.into_iter() {
+            talent[name] = value;
+        }
+    }
+
+    serde_json::from_value(talent).expect(&format!("Failed to build the talent {}", id))
+}
+
+/// Return an index for the test written at `line` of `module_path`,
+/// so that the tests running at the same time don't share their indexes.
+pub fn index_name(module_path: &str, line: u32) -> String {
+    format!("tests_{}_line_{}", module_path.replace(":", "_"), line)
+}
+
+/// Parse `query` (i.e. `keywords=rust&work_locations[]=Berlin`) into the params
+/// of a search. The values are taken as they're written, without decoding them,
+/// so that the keywords can be given verbatim (i.e. `keywords=C++`).
+pub fn parse_query<S: AsRef<str>>(query: S) -> Map {
+    let mut params = Map::new();
+
+    for pair in query.as_ref().split('&').filter(|pair| !pair.is_empty()) {
+        let mut pair = pair.splitn(2, '=');
+        let key = pair.next().unwrap_or("");
+        let value = pair.next().unwrap_or("");
+        params
+            .assign(key, Value::String(value.to_owned()))
+            .expect(&format!("Failed to assign to {:?}", key));
+    }
+
+    params
+}
+
+#[cfg(test)]
+mod tests {
+    use resources::TalentId;
+    use test_support::{self, FIXTURES};
+
+    use params::Value;
+
+    #[test]
+    fn test_load_fixtures() {
+        let talents = test_support::load_fixtures();
+        assert_eq!(talents.len(), FIXTURES.len());
+        assert_eq!(talents["backend_rust"].id, TalentId::from(1));
+        assert_eq!(talents["munich_web_dev"].id, TalentId::from(8));
+    }
+
+    #[test]
+    fn test_talent() {
+        let talent = test_support::talent(3, json!({ "skills": ["Rust"] }));
+        assert_eq!(talent.id, TalentId::from(3));
+        assert_eq!(talent.weight, 3);
+        assert_eq!(talent.skills, vec!["Rust".to_owned()]);
+        assert!(talent.work_locations.is_empty());
+    }
+
+    #[test]
+    fn test_index_name() {
+        assert_eq!(
+            test_support::index_name("smoke::keywords", 42),
+            "tests_smoke__keywords_line_42"
+        );
+    }
+
+    #[test]
+    fn test_parse_query() {
+        let params = test_support::parse_query("keywords=C++&work_locations[]=Berlin&offset");
+        assert_eq!(params["keywords"], Value::String("C++".to_owned()));
+        assert_eq!(
+            params["work_locations"],
+            Value::Array(vec![Value::String("Berlin".to_owned())])
+        );
+        assert_eq!(params["offset"], Value::String("".to_owned()));
+        assert!(test_support::parse_query("").is_empty());
+    }
+}
@@ -1,40 +1,24 @@
 #![allow(non_upper_case_globals)]
 
 extern crate searchspot;
-extern crate rs_es;
 extern crate chrono;
 extern crate params;
-extern crate serde_json;
 #[macro_use]
 extern crate lazy_static;
-extern crate urlencoded;
-extern crate url;
 
-use helpers::{make_client, refresh_index, parse_query};
+use searchspot::test_support::{self, make_client, refresh_index, parse_query};
 
-use searchspot::resources::{Talent, FoundTalent, SearchResults};
-use searchspot::resource::Resource;
+use searchspot::resources::{Company, Job, Talent, TalentId, FoundTalent, SearchResults};
+use searchspot::backends::SearchBackend;
+use searchspot::migrations;
+use searchspot::recommendations;
+use searchspot::resource::{HighlightResult, IndexSet, Resource};
 
 use chrono::prelude::*;
-use rs_es::operations::search::highlight::HighlightResult;
-use rs_es::Client;
 use params::Value;
 
-use std::fs;
-use std::io::Read;
-use std::fmt::Debug;
-use std::path::Path;
 use std::collections::HashMap;
 
-pub fn load_talent<P: AsRef<Path> + Debug>(path: P, idx: usize) -> Talent {
-    let path = path.as_ref();
-    let mut file = fs::File::open(path).expect(&format!("Failed to open file: {:?}", path));
-    let mut raw = String::new();
-    file.read_to_string(&mut raw).expect(&format!("Failed to read {:?}", path));
-    let processed = raw.replace("$id", &idx.to_string());
-    serde_json::from_str(&processed).expect(&format!("Failed to deserialize file: {:?}", path))
-}
-
 macro_rules! get_talents {
     ($($talent_file:ident)*) => {{
         vec![$($talent_file.clone()),*]
@@ -44,19 +28,7 @@ macro_rules! get_talents {
 macro_rules! preload_talents {
     ($($talent_file:ident)*) => {
         lazy_static! {
-            pub static ref PRELOADED_TALENTS: HashMap<&'static str, Talent>  = {
-                let mut talents = vec![];
-                let filenames: Vec<&'static str> = vec![$(stringify!($talent_file)),*];
-
-                for (idx, filename) in filenames.into_iter().enumerate() {
-                    let path = format!("tests/talents/{}.json", filename);
-                    talents.push(load_talent(&path, idx + 1));
-                }
-
-                vec![$(stringify!($talent_file)),*].into_iter()
-                    .zip(talents.into_iter())
-                    .collect()
-            };
+            pub static ref PRELOADED_TALENTS: HashMap<&'static str, Talent> = test_support::load_fixtures();
 
             $(
                 pub static ref $talent_file: &'static Talent
@@ -73,65 +45,8 @@ preload_talents! {
     sysadmin_with_clojure
     amsterdam_game_dev
     frontend_ember
-}
-
-mod helpers {
-    use urlencoded;
-    use rs_es::Client;
-    use params::{Map, Value};
-    use url::form_urlencoded;
-
-    use searchspot::config::Config;
-
-    use std::collections::HashMap;
-
-    const CONFIG_FILE: &'static str = "examples/tests.toml";
-
-    lazy_static! {
-        pub static ref CONFIG: Config = Config::from_file(CONFIG_FILE.to_owned());
-    }
-
-    pub fn make_client() -> Client {
-        println!("Connecting client: {:?}", CONFIG.es.url);
-        Client::new(&*CONFIG.es.url).unwrap()
-    }
-
-    pub fn refresh_index(client: &mut Client, index: &str) {
-        client.refresh().with_indexes(&[&index]).send().unwrap();
-    }
-
-    // FIXME: this is relying a lot on implementation but I need a better api in order to fix
-    // Based on: https://github.com/iron/params/blob/ba3ebf8390bc60d8d54f05d7de45d3abe93f3459/src/lib.rs#L613-L623
-    pub fn parse_query<S: AsRef<str>>(query: S) -> Map {
-        let raw = query.as_ref();
-        let encoded = form_urlencoded::byte_serialize(raw.as_bytes())
-            .collect::<Vec<&str>>()
-            .concat()
-            // reverse the double encode of actual param seperators
-            .replace("%3D", "=").replace("%26", "&");
-
-        parse_query_url_encoded(&encoded)
-    }
-
-    pub fn parse_query_url_encoded(query: &str) -> Map {
-        let mut map = Map::new();
-
-        println!("query: {:?}", query);
-        let hash_map = match urlencoded::parse(query) {
-            Ok(hash_map) => hash_map,
-            Err(urlencoded::UrlDecodingError::EmptyQuery) => HashMap::new(),
-            err => err.expect(&format!("Failed to parse query: {:?}", query)),
-        };
-
-        for (path, vec) in hash_map {
-            for value in vec {
-                map.assign(&path, Value::String(value))
-                    .expect(&format!("Failed to assign to {:?}", path));
-            }
-        }
-
-        map
-    }
+    zurich_python_dev
+    munich_web_dev
 }
 
 macro_rules! epoch_from_year {
@@ -144,18 +59,18 @@ macro_rules! epoch_from_year {
 
 trait SearchResultsExt {
     fn talents(&self) -> Vec<&FoundTalent>;
-    fn ids(&self) -> Vec<u32>;
+    fn ids(&self) -> Vec<TalentId>;
     fn highlights(&self) -> Vec<Option<HighlightResult>>;
     fn is_empty(&self) -> bool;
 }
 
 impl SearchResultsExt for SearchResults {
     fn talents(&self) -> Vec<&FoundTalent> {
-        self.talents.iter().map(|r| &r.talent).collect()
+        self.talents.iter().filter_map(|r| r.talent.found()).collect()
     }
 
-    fn ids(&self) -> Vec<u32> {
-        self.talents.iter().map(|r| r.talent.id).collect()
+    fn ids(&self) -> Vec<TalentId> {
+        self.talents.iter().map(|r| r.talent.id()).collect()
     }
 
     fn highlights(&self) -> Vec<Option<HighlightResult>> {
@@ -167,7 +82,7 @@ impl SearchResultsExt for SearchResults {
     }
 }
 
-pub fn populate_index(mut client: &mut Client, index: &str) {
+pub fn populate_index(mut client: &mut dyn SearchBackend, index: &str) {
     let talents = get_talents!(
         backend_rust
         senior_java
@@ -182,15 +97,11 @@ pub fn populate_index(mut client: &mut Client, index: &str) {
 macro_rules! index_talents {
     ($($talent_file:ident)*) => {{
         let talents = get_talents!($($talent_file)*);
-        let index = format!(
-            "tests_{}_line_{}",
-            module_path!().replace(":", "_"),
-            line!(),
-        );
+        let index = test_support::index_name(module_path!(), line!());
         println!("index: {:?}", index);
         let mut client = make_client();
 
-        Talent::reset_index(&mut client, &*index).unwrap();
+        Talent::reset_index(&mut client, &*index, None).unwrap();
         refresh_index(&mut client, &*index);
 
         Talent::index(&mut client, &*index, talents.clone()).unwrap();
@@ -240,8 +151,8 @@ fn deletes_work() {
     let (mut client, index, _talents) = index_default_talents!();
     let empty_params = &parse_query("");
 
-    assert!(Talent::delete(&mut client, "1", &*index).is_ok());
-    assert!(Talent::delete(&mut client, "4", &*index).is_ok());
+    assert!(Talent::delete(&mut client, &TalentId::from(1), &*index).is_ok());
+    assert!(Talent::delete(&mut client, &TalentId::from(4), &*index).is_ok());
     refresh_index(&mut client, &*index);
 
     let results = Talent::search(&mut client, &*index, empty_params);
@@ -472,6 +383,10 @@ fn keyword_cplusplus() {
     let params = parse_query("keywords=C++ AND C#");
     let results = Talent::search(&mut client, &*index, &params);
     assert_eq!(vec![5], results.ids());
+
+    let params = parse_query("keywords=C++ C#&keywords_operator=and");
+    let results = Talent::search(&mut client, &*index, &params);
+    assert_eq!(vec![5], results.ids());
 }
 
 #[test]
@@ -788,7 +703,7 @@ fn ignored_talents_csv() {
     let params = parse_query("keywords=database admin\
         &ignored_talents=1, 4");
     let results = Talent::search(&mut client, &*index, &params);
-    assert_eq!(Vec::<u32>::new(), results.ids());
+    assert_eq!(Vec::<TalentId>::new(), results.ids());
 }
 
 #[test]
@@ -812,6 +727,10 @@ fn contacted_talents_by_company_id() {
     let params = parse_query("company_id=6");
     let results = Talent::search(&mut client, &*index, &params);
     assert_eq!(vec![2, 1], results.ids());
+
+    // the hidden talents are counted
+    assert_eq!(2, results.excluded_by_company);
+    assert_eq!(0, results.excluded_by_contacted);
 }
 
 #[test]
@@ -863,6 +782,45 @@ fn current_location() {
     assert_eq!(vec![5], results.ids());
 }
 
+#[test]
+fn accents_are_folded() {
+    let (mut client, index, _talents) = index_talents!(
+        backend_rust
+        amsterdam_game_dev
+        zurich_python_dev
+    );
+
+    let params = parse_query("current_location[]=Zurich");
+    let results = Talent::search(&mut client, &*index, &params);
+    assert_eq!(vec![*zurich_python_dev], results.ids());
+
+    let params = parse_query("work_locations[]=Munchen");
+    let results = Talent::search(&mut client, &*index, &params);
+    assert_eq!(vec![*zurich_python_dev], results.ids());
+
+    let params = parse_query("keywords=societe");
+    let results = Talent::search(&mut client, &*index, &params);
+    assert_eq!(vec![*zurich_python_dev], results.ids());
+
+    let params = parse_query("keywords=señor");
+    let results = Talent::search(&mut client, &*index, &params);
+    assert_eq!(vec![*zurich_python_dev], results.ids());
+}
+
+#[test]
+fn german_compounds() {
+    let (mut client, index, _talents) = index_talents!(
+        backend_rust
+        zurich_python_dev
+        munich_web_dev
+    );
+
+    // the plural isn't found among the trigrams of the singular
+    let params = parse_query("keywords=Softwareentwicklern");
+    let results = Talent::search(&mut client, &*index, &params);
+    assert_eq!(vec![*munich_web_dev], results.ids());
+}
+
 #[test]
 fn work_authorization() {
     let (mut client, index, _talents) = index_default_talents!();
@@ -936,4 +894,126 @@ fn maximum_salary_with_location_filters() {
 
     let results = Talent::search(&mut client, &*index, &params);
     assert_eq!(vec![5, 2], results.ids());
-}
\ No newline at end of file
+}
+
+#[test]
+fn companies_search() {
+    let (mut client, index, _talents) = index_talents!(backend_rust);
+
+    let company = |id: u32, industry: &str, location: &str, role: &str| Company {
+        id: id,
+        name: format!("Company {}", id),
+        industry: industry.to_owned(),
+        locations: vec![location.to_owned()],
+        hiring_roles: vec![role.to_owned()],
+    };
+    let companies = vec![
+        company(101, "Recruiting", "Berlin", "Backend developer"),
+        company(102, "Software", "Amsterdam", "Frontend developer"),
+    ];
+    Company::index(&mut client, &*index, companies).unwrap();
+    refresh_index(&mut client, &*index);
+
+    // the filters match whole values, the talents are never returned
+    let ids = |client: &mut Box<dyn SearchBackend>, query: &str| -> Vec<u32> {
+        let mut ids: Vec<u32> = Company::search(&mut **client, &*index, &parse_query(query))
+            .companies
+            .into_iter()
+            .map(|company| company.id)
+            .collect();
+        ids.sort();
+        ids
+    };
+    assert_eq!(vec![101, 102], ids(&mut client, ""));
+    assert_eq!(vec![101], ids(&mut client, "hiring_roles[]=Backend developer"));
+    assert_eq!(vec![101], ids(&mut client, "locations[]=Berlin&industries[]=Recruiting"));
+    assert_eq!(vec![102], ids(&mut client, "keywords=102"));
+    assert!(ids(&mut client, "hiring_roles[]=Backend").is_empty());
+}
+
+#[test]
+fn talent_recommendations() {
+    let (mut client, index, _talents) = index_talents!(backend_rust);
+
+    let job = |id: u32, location: &str, skill: &str, salary_budget: Option<u64>| Job {
+        id: id,
+        title: "Backend developer".to_owned(),
+        required_skills: vec![skill.to_owned()],
+        location: location.to_owned(),
+        salary_budget: salary_budget,
+        experience: None,
+    };
+    let jobs = vec![
+        job(201, "Berlin", "Rust", Some(50000)),
+        job(202, "Munich", "Java", None),
+        job(203, "Amsterdam", "Rust", None),
+    ];
+    Job::index(&mut client, &*index, jobs).unwrap();
+
+    let company = |id: u32, location: &str| Company {
+        id: id,
+        name: format!("Company {}", id),
+        industry: "Software".to_owned(),
+        locations: vec![location.to_owned()],
+        hiring_roles: vec![],
+    };
+    Company::index(&mut client, &*index, vec![company(301, "Berlin"), company(302, "Munich")])
+        .unwrap();
+    refresh_index(&mut client, &*index);
+
+    // the locations and the skills are matched as they're written
+    let talent_id = backend_rust.id.to_string();
+    let recommendations =
+        recommendations::recommend(&mut *client, &IndexSet::new(&*index), &talent_id, 10)
+            .unwrap()
+            .unwrap();
+    let jobs: Vec<u32> = recommendations.jobs.iter().map(|job| job.item.id).collect();
+    let companies: Vec<u32> =
+        recommendations.companies.iter().map(|company| company.item.id).collect();
+    assert_eq!(vec![201, 203], jobs);
+    assert_eq!(vec![301], companies);
+}
+
+#[test]
+fn migrations_from_the_baseline() {
+    let index = test_support::index_name(module_path!(), line!());
+    let mut client = make_client();
+
+    // left behind by a previous run
+    for target in client.alias_targets(&*index).unwrap_or_default() {
+        let _ = client.delete_index(&*target);
+    }
+    let _ = client.delete_index(&*index);
+    let _ = client.delete_index(&*migrations::migrations_index(&*index));
+
+    // an index predating every migration, which must be replaced by an alias
+    let mappings = migrations::mappings_at::<Talent>(0, None);
+    let settings = migrations::settings_at::<Talent>(0, None);
+    client.create_index(&*index, &mappings, &settings).unwrap();
+    Talent::index(&mut client, &*index, get_talents!(backend_rust senior_java)).unwrap();
+    refresh_index(&mut client, &*index);
+
+    let report = migrations::migrate::<Talent>(&mut *client, &*index, None).unwrap();
+    assert_eq!(0, report.from);
+    assert_eq!(migrations::latest_version::<Talent>(), report.to);
+    assert_eq!(2, report.documents);
+
+    let target = format!("{}_v{}", index, report.to);
+    assert_eq!(vec![target], client.alias_targets(&*index).unwrap());
+
+    // the jobs can be stored next to the talents once the migrations are applied
+    let job = Job {
+        id: 401,
+        title: "Backend developer".to_owned(),
+        required_skills: vec!["Rust".to_owned()],
+        location: "Berlin".to_owned(),
+        salary_budget: None,
+        experience: None,
+    };
+    assert!(!Job::index(&mut client, &*index, vec![job]).unwrap().errors);
+    refresh_index(&mut client, &*index);
+
+    let mut ids = Talent::search(&mut client, &*index, &parse_query("")).ids();
+    ids.sort();
+    assert_eq!(vec![backend_rust.id.clone(), senior_java.id.clone()], ids);
+}